@@ -12,6 +12,22 @@
 //! while providing controlled test cases for reproducible performance measurement.
 
 #![allow(missing_docs)]
+// Synthetic fixtures, held to the same standard as tests rather than library code
+#![allow(
+    clippy::unwrap_used,
+    clippy::missing_docs_in_private_items,
+    clippy::cast_precision_loss,
+    clippy::too_many_lines,
+    clippy::too_many_arguments,
+    clippy::type_complexity,
+    clippy::similar_names,
+    clippy::unreadable_literal,
+    clippy::inconsistent_digit_grouping,
+    clippy::needless_range_loop,
+    clippy::items_after_statements,
+    clippy::only_used_in_recursion,
+    clippy::needless_pass_by_value
+)]
 
 use alloy::primitives::{Address, U256};
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
@@ -33,7 +49,7 @@ fn generate_random_address() -> String {
     address_checksum.to_string()
 }
 
-/// Helper function to get log_rate from a pool
+/// Helper function to get `log_rate` from a pool
 fn get_log_rate(pool: &Pool) -> i64 {
     // Create a temporary swap to get the log_rate
     let swap_id = SwapId {
@@ -43,8 +59,8 @@ fn get_log_rate(pool: &Pool) -> i64 {
 
     match Swap::new(
         swap_id,
-        pool.token0.clone(),
-        pool.token1.clone(),
+        pool.token0,
+        pool.token1,
         pool.reserve0,
         pool.reserve1,
     ) {
@@ -93,8 +109,8 @@ fn generate_benchmark_pools(pool_count: usize, token_count: usize) -> (Vec<Pool>
 
         let pool = Pool::new(
             PoolId::try_from(generate_random_address()).unwrap(),
-            tokens[idx1].clone(),
-            tokens[idx2].clone(),
+            tokens[idx1],
+            tokens[idx2],
             Some(reserve0),
             Some(reserve1),
         );
@@ -103,9 +119,9 @@ fn generate_benchmark_pools(pool_count: usize, token_count: usize) -> (Vec<Pool>
     }
 
     // Create a few circular arbitrage opportunities and prepare an updated pool
-    let token_a = tokens[0].clone();
-    let token_b = tokens[1].clone();
-    let token_c = tokens[2].clone();
+    let token_a = tokens[0];
+    let token_b = tokens[1];
+    let token_c = tokens[2];
     let updated_pool;
 
     if pool_count >= 10 && token_count >= 4 {
@@ -114,24 +130,24 @@ fn generate_benchmark_pools(pool_count: usize, token_count: usize) -> (Vec<Pool>
             // Create three connected pools with initially balanced reserves
             pools[0] = Pool::new(
                 PoolId::try_from(generate_random_address()).unwrap(),
-                token_a.clone(),
-                token_b.clone(),
+                token_a,
+                token_b,
                 Some(U256::from(1_000_000_000_000_000_u128)),
                 Some(U256::from(3_000_000_000_000_000_000_u128)),
             );
 
             pools[1] = Pool::new(
                 PoolId::try_from(generate_random_address()).unwrap(),
-                token_b.clone(),
-                token_c.clone(),
+                token_b,
+                token_c,
                 Some(U256::from(1_000_000_000_000_000_u128)),
                 Some(U256::from(3_000_000_000_000_000_000_u128)),
             );
 
             pools[2] = Pool::new(
                 PoolId::try_from(generate_random_address()).unwrap(),
-                token_c.clone(),
-                token_a.clone(),
+                token_c,
+                token_a,
                 Some(U256::from(1_000_000_000_000_000_u128)),
                 Some(U256::from(3_000_000_000_000_000_000_u128)),
             );
@@ -139,8 +155,8 @@ fn generate_benchmark_pools(pool_count: usize, token_count: usize) -> (Vec<Pool>
             // Create an updated version of the C-A pool (pools[2]) with significant imbalance
             updated_pool = Pool::new(
                 pools[2].id.clone(),
-                token_c.clone(),
-                token_a.clone(),
+                token_c,
+                token_a,
                 Some(U256::from(1_000_000_000_000_000_u128)), // Imbalanced reserve
                 Some(U256::from(7_000_000_000_000_000_000_u128)), // Imbalanced reserve
             );
@@ -180,6 +196,7 @@ fn generate_benchmark_pools(pool_count: usize, token_count: usize) -> (Vec<Pool>
 ///
 /// # Returns
 /// A vector of all profitable cycles that contain the updated pool
+#[must_use]
 pub fn find_affected_cycles(pools: &[Pool], updated_pool: Pool) -> Vec<Cycle> {
     let _start_time = Instant::now();
 
@@ -192,40 +209,40 @@ pub fn find_affected_cycles(pools: &[Pool], updated_pool: Pool) -> Vec<Cycle> {
 
     // Build graph from all pools including the updated one
     for pool in pools.iter().chain(std::iter::once(&updated_pool)) {
-        let token0 = pool.token0.clone();
-        let token1 = pool.token1.clone();
+        let token0 = pool.token0;
+        let token1 = pool.token1;
 
         // Store pool information for later reserve lookup
         pool_lookup.insert(
             pool.id.clone(),
-            (token0.clone(), token1.clone(), pool.reserve0, pool.reserve1),
+            (token0, token1, pool.reserve0, pool.reserve1),
         );
 
         // Add edges in both directions
         token_graph
-            .entry(token0.clone())
-            .or_insert_with(Vec::new)
-            .push((token1.clone(), pool.id.clone(), true)); // token0 -> token1
+            .entry(token0)
+            .or_default()
+            .push((token1, pool.id.clone(), true)); // token0 -> token1
 
         token_graph
             .entry(token1)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push((token0, pool.id.clone(), false)); // token1 -> token0
     }
 
     // Try both methods and use the results from the one that finds cycles
     let dfs_cycles = find_cycles_dfs(&token_graph, &updated_pool, &pool_lookup);
     let bellman_ford_cycles = find_cycles_bellman_ford(&token_graph, &updated_pool, &pool_lookup);
-    
+
     // Print debug info for both methods
     // println!("DFS found {} cycles", dfs_cycles.len());
     // println!("Bellman-Ford found {} cycles", bellman_ford_cycles.len());
-    
+
     // If both find cycles, prefer Bellman-Ford as it's more likely to find all profitable cycles
-    if !bellman_ford_cycles.is_empty() {
-        bellman_ford_cycles
-    } else {
+    if bellman_ford_cycles.is_empty() {
         dfs_cycles
+    } else {
+        bellman_ford_cycles
     }
     // dfs_cycles
 }
@@ -241,7 +258,7 @@ fn find_cycles_dfs(
     let mut all_cycles = Vec::new();
 
     // The updated pool's tokens are our starting points for cycle detection
-    let start_tokens = vec![updated_pool.token0.clone(), updated_pool.token1.clone()];
+    let start_tokens = vec![updated_pool.token0, updated_pool.token1];
 
     for start_token in start_tokens {
         // Find cycles starting from this token
@@ -258,8 +275,8 @@ fn find_cycles_dfs(
             &mut unique_cycles,
             &mut all_cycles,
             0,
-            3,            // Maximum cycle length (3-hop)
-            pool_lookup, 
+            3, // Maximum cycle length (3-hop)
+            pool_lookup,
         );
     }
 
@@ -272,10 +289,10 @@ fn find_cycles_dfs(
 
     // Debug output for profitable cycles
     // println!("DFS found {} cycles, {} are profitable", all_cycles.len(), profitable_cycles.len());
-    
-    for (i, cycle) in profitable_cycles.iter().enumerate().take(3) {
+
+    for (_i, _cycle) in profitable_cycles.iter().enumerate().take(3) {
         // println!("DFS Profitable cycle {}: {} swaps, log_rate: {}",
-        //         i + 1, 
+        //         i + 1,
         //         cycle.swaps.len(),
         //         cycle.swaps.iter().map(|s| s.log_rate()).sum::<i64>());
     }
@@ -292,81 +309,84 @@ fn find_cycles_bellman_ford(
     // Map tokens to indices for the algorithm
     let mut token_to_index = HashMap::new();
     let mut index_to_token = HashMap::new();
-    
+
     // Collect all unique tokens
     let mut index = 0;
     for token in token_graph.keys() {
         if !token_to_index.contains_key(token) {
-            token_to_index.insert(token.clone(), index);
-            index_to_token.insert(index, token.clone());
+            token_to_index.insert(*token, index);
+            index_to_token.insert(index, *token);
             index += 1;
         }
     }
-    
+
     let num_tokens = token_to_index.len();
     if num_tokens == 0 {
         return Vec::new();
     }
-    
+
     // Store edges as (from_idx, to_idx, weight, swap)
     let mut edges = Vec::new();
     // For faster lookup: (from_idx, to_idx) -> edge_idx
     let mut edge_map: HashMap<(usize, usize), usize> = HashMap::new();
-    
+
     // Build edges with weights
     for (from_token, neighbors) in token_graph {
         let from_idx = *token_to_index.get(from_token).unwrap();
-        
+
         for (to_token, pool_id, is_forward) in neighbors {
             let to_idx = *token_to_index.get(to_token).unwrap();
-            
+
             // Get pool data
-            if let Some((token0, token1, reserve0, reserve1)) = pool_lookup.get(pool_id) {
+            if let Some((_token0, _token1, reserve0, reserve1)) = pool_lookup.get(pool_id) {
                 let swap_id = SwapId {
                     pool_id: pool_id.clone(),
-                    direction: if *is_forward { Direction::ZeroForOne } else { Direction::OneForZero },
+                    direction: if *is_forward {
+                        Direction::ZeroForOne
+                    } else {
+                        Direction::OneForZero
+                    },
                 };
-                
+
                 // Create swap with correct reserves
                 if let Ok(swap) = Swap::new(
                     swap_id,
-                    from_token.clone(),
-                    to_token.clone(),
+                    *from_token,
+                    *to_token,
                     if *is_forward { *reserve0 } else { *reserve1 },
                     if *is_forward { *reserve1 } else { *reserve0 },
                 ) {
                     // Get log_rate, use negative for Bellman-Ford
-                    if let log_rate = swap.log_rate() {
-                        let edge_idx = edges.len();
-                        edges.push((from_idx, to_idx, -log_rate, swap));
-                        edge_map.insert((from_idx, to_idx), edge_idx);
-                    }
+                    let log_rate = swap.log_rate();
+                    let edge_idx = edges.len();
+                    edges.push((from_idx, to_idx, -log_rate, swap));
+                    edge_map.insert((from_idx, to_idx), edge_idx);
                 }
             }
         }
     }
-    
+
     // Results storage
     let mut profitable_cycles = Vec::new();
-    
+
     // Only run from the tokens in the updated pool
     let start_tokens = vec![
         *token_to_index.get(&updated_pool.token0).unwrap(),
-        *token_to_index.get(&updated_pool.token1).unwrap()
+        *token_to_index.get(&updated_pool.token1).unwrap(),
     ];
 
     for source in start_tokens {
         // Distance and predecessor arrays
         let mut dist = vec![i64::MAX / 2; num_tokens]; // Avoid overflow
         let mut pred = vec![None; num_tokens];
-        
+
         // Initialize source distance
         dist[source] = 0;
-        
+
         // Relax edges |V|-1 times
         for _ in 0..num_tokens {
             let mut updated = false;
-            
+
             for (u, v, weight, _) in &edges {
                 // Try to relax edge
                 if dist[*v] > dist[*u] + weight {
@@ -375,25 +395,25 @@ fn find_cycles_bellman_ford(
                     updated = true;
                 }
             }
-            
+
             // If no updates occurred, we've converged
             if !updated {
                 break;
             }
         }
-        
+
         // Check for negative cycles affecting the updated pool
         for u in 0..num_tokens {
             // Skip if no path to this node
             if dist[u] == i64::MAX / 2 {
                 continue;
             }
-            
+
             // Check if this node is part of a negative cycle
             let mut visited = vec![false; num_tokens];
             let mut stack = Vec::new();
             let mut on_stack = vec![false; num_tokens];
-            
+
             // DFS to find cycles
             fn dfs_find_negative_cycle(
                 node: usize,
@@ -410,55 +430,63 @@ fn find_cycles_bellman_ford(
                 if visited[node] {
                     return;
                 }
-                
+
                 visited[node] = true;
                 stack.push(node);
                 on_stack[node] = true;
-                
+
                 // Check neighbors for cycles
                 if let Some(prev) = pred[node] {
                     if !visited[prev] {
                         dfs_find_negative_cycle(
-                            prev, edges, edge_map, dist, pred, visited, on_stack, stack,
-                            updated_pool_id, cycles,
+                            prev,
+                            edges,
+                            edge_map,
+                            dist,
+                            pred,
+                            visited,
+                            on_stack,
+                            stack,
+                            updated_pool_id,
+                            cycles,
                         );
                     } else if on_stack[prev] {
                         // We found a cycle - reconstruct it
                         let mut cycle_swaps = Vec::new();
                         let mut contains_updated_pool = false;
-                        
+
                         // Find position of prev in stack
                         let cycle_start = stack.iter().position(|&x| x == prev).unwrap();
-                        
+
                         // Reconstruct cycle
                         for i in cycle_start..stack.len() - 1 {
                             let from = stack[i];
                             let to = stack[i + 1];
-                            
+
                             if let Some(&edge_idx) = edge_map.get(&(from, to)) {
                                 let (_, _, _, swap) = &edges[edge_idx];
                                 cycle_swaps.push(swap.clone());
-                                
+
                                 // Check if this swap involves the updated pool
                                 if swap.id().pool_id == *updated_pool_id {
                                     contains_updated_pool = true;
                                 }
                             }
                         }
-                        
+
                         // Add the final edge to close the cycle
                         let from = stack[stack.len() - 1];
                         let to = prev;
-                        
+
                         if let Some(&edge_idx) = edge_map.get(&(from, to)) {
                             let (_, _, _, swap) = &edges[edge_idx];
                             cycle_swaps.push(swap.clone());
-                            
+
                             if swap.id().pool_id == *updated_pool_id {
                                 contains_updated_pool = true;
                             }
                         }
-                        
+
                         // If cycle contains updated pool and is profitable
                         if contains_updated_pool && !cycle_swaps.is_empty() {
                             // Create cycle and check profitability
@@ -470,49 +498,59 @@ fn find_cycles_bellman_ford(
                         }
                     }
                 }
-                
+
                 stack.pop();
                 on_stack[node] = false;
             }
-            
+
             // Run DFS from this node
             dfs_find_negative_cycle(
-                u, &edges, &edge_map, &dist, &pred, &mut visited, &mut on_stack, &mut stack,
-                &updated_pool.id, &mut profitable_cycles,
+                u,
+                &edges,
+                &edge_map,
+                &dist,
+                &pred,
+                &mut visited,
+                &mut on_stack,
+                &mut stack,
+                &updated_pool.id,
+                &mut profitable_cycles,
             );
         }
     }
-    
+
     // Deduplicate cycles
     let mut unique_cycles = HashSet::new();
     let mut unique_profitable_cycles = Vec::new();
-    
+
     for cycle in profitable_cycles {
         let mut swap_ids: Vec<SwapId> = cycle.swaps.iter().map(|s| s.id().clone()).collect();
-        
+
         // Normalize by rotating to smallest swap ID
-        if let Some(min_pos) = swap_ids.iter().enumerate()
+        if let Some(min_pos) = swap_ids
+            .iter()
+            .enumerate()
             .min_by(|(_, a), (_, b)| a.cmp(b))
-            .map(|(i, _)| i) 
+            .map(|(i, _)| i)
         {
             swap_ids.rotate_left(min_pos);
-            
+
             if unique_cycles.insert(swap_ids) {
                 unique_profitable_cycles.push(cycle);
             }
         }
     }
-    
+
     // Debug output for profitable cycles
     // println!("Bellman-Ford found {} profitable cycles", unique_profitable_cycles.len());
-    
-    for (i, cycle) in unique_profitable_cycles.iter().enumerate().take(3) {
+
+    for (_i, _cycle) in unique_profitable_cycles.iter().enumerate().take(3) {
         // println!("Bellman-Ford Profitable cycle {}: {} swaps, log_rate: {}",
-        //          i + 1, 
+        //          i + 1,
         //          cycle.swaps.len(),
         //          cycle.swaps.iter().map(|s| s.log_rate()).sum::<i64>());
     }
-    
+
     unique_profitable_cycles
 }
 
@@ -564,7 +602,7 @@ fn dfs_find_cycles(
 
             // Get the pool's reserve information from our lookup table
             let (reserve_in, reserve_out) =
-                if let Some((token0, token1, reserve0, reserve1)) = pool_lookup.get(pool_id) {
+                if let Some((_token0, _token1, reserve0, reserve1)) = pool_lookup.get(pool_id) {
                     if *is_forward {
                         // token0 -> token1 direction
                         (*reserve0, *reserve1)
@@ -579,8 +617,8 @@ fn dfs_find_cycles(
             // Create the swap with the reserve information
             if let Ok(swap) = Swap::new(
                 swap_id,
-                token_from.clone(),
-                token_to.clone(),
+                *token_from,
+                *token_to,
                 reserve_in,  // Use actual reserves
                 reserve_out, // Use actual reserves
             ) {
@@ -628,7 +666,7 @@ fn dfs_find_cycles(
             }
 
             visited.insert(pool_id.clone());
-            path.push((current_token.clone(), pool_id.clone(), *is_forward));
+            path.push((*current_token, pool_id.clone(), *is_forward));
 
             dfs_find_cycles(
                 graph,
@@ -685,16 +723,16 @@ fn bench_find_cycles(c: &mut Criterion) {
     let mut metrics_map: HashMap<usize, BenchmarkMetrics> = HashMap::new();
 
     // Benchmark with different pool counts to find our limits
-    for pool_count in [100, 500, 1000, 5000, 100000, 500000].iter() {
+    for pool_count in &[100, 500, 1000, 5000, 100000, 500000] {
         // Create a synthetic market with 20% of the pool count as tokens
         // This mimics real-world token-to-pool ratios
-        let token_count = ((pool_count / 5) as usize).max(10);
+        let token_count = (pool_count / 5).max(10);
 
         println!("\n========================================================");
         println!("BENCHMARK: {} pools, {} tokens", pool_count, token_count);
         println!("========================================================");
 
-        let (pools, updated_pool) = generate_benchmark_pools(*pool_count as usize, token_count);
+        let (pools, updated_pool) = generate_benchmark_pools(*pool_count, token_count);
 
         println!(
             "Using updated pool: ID={}, token0={}, token1={}, reserve0={}, reserve1={}, log_rate={:.8}",
@@ -744,7 +782,7 @@ fn bench_find_cycles(c: &mut Criterion) {
                     },
                     // Batch size
                     criterion::BatchSize::SmallInput,
-                )
+                );
             },
         );
 
@@ -812,10 +850,10 @@ fn bench_production_data(c: &mut Criterion) {
         // Make the pool even more imbalanced for high connectivity test
         let updated_high_connectivity_pool = Pool::new(
             base_updated_pool.id.clone(),
-            base_updated_pool.token0.clone(),
-            base_updated_pool.token1.clone(),
+            base_updated_pool.token0,
+            base_updated_pool.token1,
             Some(U256::from(1500)), // Extreme imbalance
-            Some(U256::from(500)),   // Extreme imbalance
+            Some(U256::from(500)),  // Extreme imbalance
         );
 
         println!(
@@ -836,7 +874,7 @@ fn bench_production_data(c: &mut Criterion) {
             let new_token_id = TokenId::try_from(generate_random_address()).unwrap();
             Pool::new(
                 PoolId::try_from(generate_random_address()).unwrap(),
-                base_updated_pool.token0.clone(),
+                base_updated_pool.token0,
                 new_token_id,
                 Some(U256::from(1000)),
                 Some(U256::from(1000)),
@@ -871,7 +909,7 @@ fn bench_production_data(c: &mut Criterion) {
                         cycles
                     },
                     criterion::BatchSize::SmallInput,
-                )
+                );
             },
         );
 
@@ -901,7 +939,7 @@ fn bench_production_data(c: &mut Criterion) {
                     cycles
                 },
                 criterion::BatchSize::SmallInput,
-            )
+            );
         });
     }
 
@@ -935,24 +973,24 @@ fn bench_specific_arbitrage_cycle(c: &mut Criterion) {
     // Create the triangular arbitrage pools
     let pool_ab = Pool::new(
         PoolId::try_from(generate_random_address()).unwrap(),
-        token_a.clone(),
-        token_b.clone(),
+        token_a,
+        token_b,
         Some(U256::from(1_000_000_000_000_000_u128)),
         Some(U256::from(7_000_000_000_000_000_000_u128)),
     );
 
     let pool_bc = Pool::new(
         PoolId::try_from(generate_random_address()).unwrap(),
-        token_b.clone(),
-        token_c.clone(),
+        token_b,
+        token_c,
         Some(U256::from(1_000_000_000_000_000_u128)),
         Some(U256::from(7_000_000_000_000_000_000_u128)),
     );
 
     let pool_ca = Pool::new(
         PoolId::try_from(generate_random_address()).unwrap(),
-        token_c.clone(),
-        token_a.clone(),
+        token_c,
+        token_a,
         Some(U256::from(1_000_000_000_000_000_u128)), // Initially balanced
         Some(U256::from(7_000_000_000_000_000_000_u128)),
     );
@@ -960,16 +998,16 @@ fn bench_specific_arbitrage_cycle(c: &mut Criterion) {
     // Create additional paths
     let pool_bd = Pool::new(
         PoolId::try_from(generate_random_address()).unwrap(),
-        token_b.clone(),
-        token_d.clone(),
+        token_b,
+        token_d,
         Some(U256::from(1_000_000_000_000_000_u128)),
         Some(U256::from(7_000_000_000_000_000_000_u128)),
     );
 
     let pool_cd = Pool::new(
         PoolId::try_from(generate_random_address()).unwrap(),
-        token_c.clone(),
-        token_d.clone(),
+        token_c,
+        token_d,
         Some(U256::from(1_000_000_000_000_000_u128)),
         Some(U256::from(7_000_000_000_000_000_000_u128)),
     );
@@ -997,8 +1035,8 @@ fn bench_specific_arbitrage_cycle(c: &mut Criterion) {
             "C-A (Imbalanced)",
             Pool::new(
                 pool_ca.id.clone(),
-                token_c.clone(),
-                token_a.clone(),
+                token_c,
+                token_a,
                 Some(U256::from(1_300_000_000_000_000_u128)), // Imbalanced reserve
                 Some(U256::from(6_500_000_000_000_000_000_u128)), // Imbalanced reserve
             ),
@@ -1008,8 +1046,8 @@ fn bench_specific_arbitrage_cycle(c: &mut Criterion) {
             "A-B (Imbalanced)",
             Pool::new(
                 pool_ab.id.clone(),
-                token_a.clone(),
-                token_b.clone(),
+                token_a,
+                token_b,
                 Some(U256::from(1_200_000_000_000_000_u128)), // Imbalanced reserve
                 Some(U256::from(8_000_000_000_000_000_000_u128)), // Imbalanced reserve
             ),
@@ -1019,15 +1057,15 @@ fn bench_specific_arbitrage_cycle(c: &mut Criterion) {
             "B-C (Imbalanced)",
             Pool::new(
                 pool_bc.id.clone(),
-                token_b.clone(),
-                token_c.clone(),
+                token_b,
+                token_c,
                 Some(U256::from(1_200_000_000_000_000_u128)), // Imbalanced reserve
                 Some(U256::from(7_500_000_000_000_000_000_u128)), // Imbalanced reserve
             ),
         ),
     ];
 
-    for (name, updated_pool) in updated_pools.iter() {
+    for (name, updated_pool) in &updated_pools {
         println!("\nTesting with {} as updated pool", name);
         println!("  Updated pool details: ID={}, token0={}, token1={}, reserve0={}, reserve1={}, log_rate={:.8}",
                 updated_pool.id,
@@ -1043,7 +1081,7 @@ fn bench_specific_arbitrage_cycle(c: &mut Criterion) {
                 |(p, up)| {
                     let start = Instant::now();
                     let cycles = black_box(find_affected_cycles(&p, up));
-                    let duration = start.elapsed();
+                    let _duration = start.elapsed();
 
                     // println!("Found {} cycles in {:?}", cycles.len(), duration);
 
@@ -1065,7 +1103,7 @@ fn bench_specific_arbitrage_cycle(c: &mut Criterion) {
                     cycles
                 },
                 criterion::BatchSize::SmallInput,
-            )
+            );
         });
     }
 
@@ -1073,29 +1111,13 @@ fn bench_specific_arbitrage_cycle(c: &mut Criterion) {
 }
 
 #[cfg(test)]
+// Without the libtest harness the `#[test]` functions are not compiled, only their imports
+#[allow(unused_imports)]
 mod tests {
     use super::*;
     use alloy::primitives::U256;
     use fly::arb::pool::Pool;
     use fly::arb::token::TokenId;
-    use std::str::FromStr;
-
-    // Create a helper function to generate simple test pools
-    fn create_test_pool(
-        id: &str,
-        token0: &str,
-        token1: &str,
-        reserve0: u64,
-        reserve1: u64,
-    ) -> Pool {
-        Pool::new(
-            PoolId::try_from(id).unwrap(),
-            TokenId::try_from(token0).unwrap(),
-            TokenId::try_from(token1).unwrap(),
-            Some(U256::from(reserve0)),
-            Some(U256::from(reserve1)),
-        )
-    }
 
     /// Test using the example graph provided in the requirements
     /// Nodes: 0, 1, 2, 3
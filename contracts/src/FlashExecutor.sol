@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.28;
+
+interface IUniV2Pair {
+    function swap(uint256, uint256, address, bytes calldata) external;
+}
+
+interface IERC20 {
+    function transfer(address, uint256) external returns (bool);
+    function balanceOf(address) external view returns (uint256);
+}
+
+/// @title FlashExecutor
+/// @notice Executes arbitrage trades across Uniswap V2 pairs without holding the start token
+/// @dev The first pair of the cycle lends its output through a flash swap. The remaining pairs
+///      are traded inside `uniswapV2Call` and the first pair is repaid with the start token
+///      received from the last one. Only the profit stays in the contract.
+contract FlashExecutor {
+    address public immutable owner;
+
+    /// @dev The pair we expect the callback from, only set while `run` executes
+    address private transient flashPair;
+
+    error NotOwner();
+    error WithdrawalFailed();
+    error ProfitTargetNotMet(uint256 minimumProfit, int256 actualProfit);
+    error InvalidPairCount();
+    error ERC20Failed();
+    error NoBalanceToWithdraw();
+    error UnauthorizedCallback();
+
+    // Argument for each pair
+    struct Pair {
+        address contractAddress; // 20 bytes
+        uint256 amountOut; // amount0Out or amount1Out to be passed to swap
+        bool isToken0; // 1 byte
+    }
+
+    // Payload passed through the flash swap to `uniswapV2Call`
+    struct Callback {
+        address tokenIn; // Start token, repaid to the flash pair
+        address borrowedToken; // Token lent by the flash pair
+        uint256 repayAmount; // Amount of tokenIn owed to the flash pair
+        uint256 minimumProfit; // Minimum profit in tokenIn
+        Pair[] pairs; // Remaining pairs, the last one pays out to this contract
+    }
+
+    constructor() {
+        owner = msg.sender;
+    }
+
+    modifier onlyOwner() {
+        if (msg.sender != owner) revert NotOwner();
+        _;
+    }
+
+    /// @notice Withdraws all ETH from the contract
+    /// @dev Uses low-level call intentionally to avoid gas limitations of transfer()
+    /// @custom:slither-disable-next-line low-level-calls
+    function withdraw() external onlyOwner {
+        (bool success,) = owner.call{value: address(this).balance}("");
+        if (!success) revert WithdrawalFailed();
+    }
+
+    /// @notice Withdraws ERC20 tokens from the contract
+    /// @param token Address of the ERC20 token
+    /// @param recipient Address to send the tokens to
+    /// @param amount Amount of tokens to withdraw
+    function withdrawERC20(address token, address recipient, uint256 amount) external onlyOwner {
+        uint256 contractBalance = IERC20(token).balanceOf(address(this));
+
+        if (contractBalance < amount) revert NoBalanceToWithdraw();
+
+        bool success = IERC20(token).transfer(recipient, amount);
+        if (!success) revert ERC20Failed();
+    }
+
+    /// @notice Executes a cycle, borrowing the output of the first pair
+    /// @param borrow The first pair of the cycle and the amount to borrow from it
+    /// @param data ABI-encoded `Callback`
+    /// @dev Reverts if profit target not met or the flash pair is not repaid
+    function run(Pair calldata borrow, bytes calldata data) external onlyOwner {
+        flashPair = borrow.contractAddress;
+
+        uint256 amount0Out = borrow.isToken0 ? borrow.amountOut : 0;
+        uint256 amount1Out = borrow.isToken0 ? 0 : borrow.amountOut;
+
+        IUniV2Pair(borrow.contractAddress).swap(amount0Out, amount1Out, address(this), data);
+
+        flashPair = address(0);
+    }
+
+    /// @notice Called by the flash pair after it sent us the borrowed tokens
+    /// @param sender The account that called `swap`, must be this contract
+    /// @param amount0 Amount of token0 borrowed
+    /// @param amount1 Amount of token1 borrowed
+    /// @param data ABI-encoded `Callback`
+    function uniswapV2Call(address sender, uint256 amount0, uint256 amount1, bytes calldata data) external {
+        if (msg.sender != flashPair || sender != address(this)) revert UnauthorizedCallback();
+
+        Callback memory callback = abi.decode(data, (Callback));
+        uint256 pairsLength = callback.pairs.length;
+        if (pairsLength == 0 || pairsLength > 4) revert InvalidPairCount();
+
+        address self = address(this);
+        IERC20 tokenIn = IERC20(callback.tokenIn);
+        uint256 tokenInBalanceBefore = tokenIn.balanceOf(self);
+
+        if (!IERC20(callback.borrowedToken).transfer(callback.pairs[0].contractAddress, amount0 + amount1)) {
+            revert ERC20Failed();
+        }
+
+        unchecked {
+            for (uint256 i; i < pairsLength;) {
+                Pair memory pair = callback.pairs[i];
+                address recipient = i == pairsLength - 1 ? self : callback.pairs[i + 1].contractAddress;
+
+                uint256 amount0Out = pair.isToken0 ? pair.amountOut : 0;
+                uint256 amount1Out = pair.isToken0 ? 0 : pair.amountOut;
+
+                IUniV2Pair(pair.contractAddress).swap(amount0Out, amount1Out, recipient, "");
+                ++i;
+            }
+        }
+
+        // Everything received from the last pair above the repayment is profit
+        int256 profit = int256(tokenIn.balanceOf(self)) - int256(tokenInBalanceBefore) - int256(callback.repayAmount);
+        if (profit < int256(callback.minimumProfit)) {
+            revert ProfitTargetNotMet(callback.minimumProfit, profit);
+        }
+
+        if (!tokenIn.transfer(msg.sender, callback.repayAmount)) revert ERC20Failed();
+    }
+}
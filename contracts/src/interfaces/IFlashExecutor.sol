@@ -0,0 +1,32 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.0;
+
+interface IFlashExecutor {
+    struct Pair {
+        address contractAddress;
+        uint256 amountOut;
+        bool isToken0;
+    }
+
+    struct Callback {
+        address tokenIn;
+        address borrowedToken;
+        uint256 repayAmount;
+        uint256 minimumProfit;
+        Pair[] pairs;
+    }
+
+    error NotOwner();
+    error WithdrawalFailed();
+    error ProfitTargetNotMet(uint256 minimumProfit, int256 actualProfit);
+    error InvalidPairCount();
+    error ERC20Failed();
+    error NoBalanceToWithdraw();
+    error UnauthorizedCallback();
+
+    function owner() external view returns (address);
+    function withdraw() external;
+    function withdrawERC20(address token, address recipient, uint256 amount) external;
+    function run(Pair calldata borrow, bytes calldata data) external;
+    function uniswapV2Call(address sender, uint256 amount0, uint256 amount1, bytes calldata data) external;
+}
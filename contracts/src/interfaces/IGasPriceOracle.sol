@@ -0,0 +1,15 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/// @notice OP Stack `GasPriceOracle` predeploy (0x420000000000000000000000000000000000000F)
+interface IGasPriceOracle {
+    function baseFee() external view returns (uint256);
+    function l1BaseFee() external view returns (uint256);
+    function blobBaseFee() external view returns (uint256);
+    function decimals() external pure returns (uint256);
+    function isEcotone() external view returns (bool);
+    function isFjord() external view returns (bool);
+    function getL1Fee(bytes memory _data) external view returns (uint256);
+    function getL1FeeUpperBound(uint256 _unsignedTxSize) external view returns (uint256);
+    function getL1GasUsed(bytes memory _data) external view returns (uint256);
+}
@@ -0,0 +1,34 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.0;
+
+interface ISimpleExecutor {
+    struct Pair {
+        address contractAddress;
+        uint256 amountOut;
+        bool isToken0;
+    }
+
+    error NotOwner();
+    error WithdrawalFailed();
+    error ProfitTargetNotMet(uint256 minimumProfit, int256 actualProfit);
+    error InvalidPairCount();
+    error CallFailed();
+    error InvalidAddress();
+    error ERC20Failed();
+    error NoBalanceToWithdraw();
+
+    function owner() external view returns (address);
+    function withdraw() external;
+    function withdrawERC20(address token, address recipient, uint256 amount) external;
+    function callContract(address payable _to, uint256 _value, bytes calldata _data)
+        external
+        payable
+        returns (bytes memory result);
+    function run(
+        address token0Address,
+        uint256 token0AmountIn,
+        uint256 minimumProfitInToken0,
+        Pair[] calldata pairs,
+        bool skipProfitCheck
+    ) external payable;
+}
@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.28;
+
+import {Test, console} from "forge-std/Test.sol";
+import {FlashExecutor} from "../src/FlashExecutor.sol";
+
+interface IERC20 {
+    function balanceOf(address account) external view returns (uint256);
+    function transfer(address to, uint256 amount) external returns (bool);
+}
+
+contract FlashExecutorTest is Test {
+    FlashExecutor public executor;
+
+    address public owner;
+    address public nonOwner;
+
+    // Mainnet addresses
+    address constant WETH = 0x4200000000000000000000000000000000000006;
+    address constant USDC = 0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913;
+    address constant UNIV2_USDC_WETH = 0x88A43bbDF9D098eEC7bCEda4e2494615dfD9bB9C; // Uniswap V2
+    address constant STANDARD_UNIV2_PAIR = 0xaEeB835f3Aa21d19ea5E33772DaA9E64f1b6982F; // Standard Uniswap V2 pair
+
+    function setUp() public {
+        // Fork mainnet using StdChains RPC URL
+        vm.createSelectFork(getChain("base").rpcUrl);
+
+        // Set the owner address to the current contract (the contract itself)
+        owner = address(this);
+
+        // Deploy a new FlashExecutor contract on the fork (the contract being tested)
+        executor = new FlashExecutor();
+
+        // Define a non-owner address to test access restrictions
+        nonOwner = address(0x1234567890123456789012345678901234567890);
+    }
+
+    function test_SuccessfulFlashArbitrage() public {
+        // Same reserves as SimpleExecutorTest.test_SuccessfulArbitrage
+        uint112 fixedWETHReserveUni = 1010.78 ether;
+        uint112 fixedUSDCReserveUni = 2_513_107e6;
+        uint112 fixedWETHReserveStandard = 0.86 ether;
+        uint112 fixedUSDCReserveStandard = 2_314e6;
+
+        setPair(UNIV2_USDC_WETH, fixedWETHReserveUni, fixedUSDCReserveUni);
+        setPair(STANDARD_UNIV2_PAIR, fixedWETHReserveStandard, fixedUSDCReserveStandard);
+
+        // The executor holds nothing, the first leg is borrowed
+        assertEq(IERC20(USDC).balanceOf(address(executor)), 0);
+        assertEq(IERC20(WETH).balanceOf(address(executor)), 0);
+
+        uint256 amountIn = 100e6;
+        uint256 amountOutUni = getAmountOut(amountIn, fixedUSDCReserveUni, fixedWETHReserveUni);
+        uint256 amountOutStandard = getAmountOut(amountOutUni, fixedWETHReserveStandard, fixedUSDCReserveStandard);
+
+        // Borrow WETH (token0) from Uniswap, owe it USDC
+        FlashExecutor.Pair memory borrow =
+            FlashExecutor.Pair({contractAddress: UNIV2_USDC_WETH, amountOut: amountOutUni, isToken0: true});
+
+        // Sell the WETH for USDC (token1) on the standard pair
+        FlashExecutor.Pair[] memory pairs = new FlashExecutor.Pair[](1);
+        pairs[0] =
+            FlashExecutor.Pair({contractAddress: STANDARD_UNIV2_PAIR, amountOut: amountOutStandard, isToken0: false});
+
+        executor.run(borrow, callback(amountIn, 0.01e6, pairs));
+
+        // Only the profit stays in the executor
+        assertEq(IERC20(USDC).balanceOf(address(executor)), amountOutStandard - amountIn, "Executor should keep profit");
+        assertEq(IERC20(WETH).balanceOf(address(executor)), 0, "Executor should not keep borrowed tokens");
+    }
+
+    function testRevert_IfProfitMarginNotMet() public {
+        // Same reserves as SimpleExecutorTest.testRevert_IfProfitMarginNotMet
+        uint112 fixedUSDCReserveUni = 100_000e6;
+        uint112 fixedWETHReserveUni = 0.5 ether;
+        uint112 fixedUSDCReserveStandard = 80_000e6;
+        uint112 fixedWETHReserveStandard = 0.45 ether;
+
+        setPair(UNIV2_USDC_WETH, fixedWETHReserveUni, fixedUSDCReserveUni);
+        setPair(STANDARD_UNIV2_PAIR, fixedWETHReserveStandard, fixedUSDCReserveStandard);
+
+        uint256 expectedWETHOutUni = getAmountOut(1000e6, fixedUSDCReserveUni, fixedWETHReserveUni);
+        uint256 expectedUSDCOutStandard =
+            getAmountOut(expectedWETHOutUni, fixedWETHReserveStandard, fixedUSDCReserveStandard);
+
+        FlashExecutor.Pair memory borrow =
+            FlashExecutor.Pair({contractAddress: UNIV2_USDC_WETH, amountOut: expectedWETHOutUni, isToken0: true});
+        FlashExecutor.Pair[] memory pairs = new FlashExecutor.Pair[](1);
+        pairs[0] = FlashExecutor.Pair({
+            contractAddress: STANDARD_UNIV2_PAIR,
+            amountOut: expectedUSDCOutStandard,
+            isToken0: false
+        });
+
+        vm.expectRevert(
+            abi.encodeWithSelector(
+                FlashExecutor.ProfitTargetNotMet.selector,
+                27e6, // Require 27 USDC profit
+                -134621970 // Get -134.621970 USDC
+            )
+        );
+        executor.run(borrow, callback(1000e6, 27e6, pairs));
+    }
+
+    function testRevert_IfInvalidPairCount() public {
+        setPair(UNIV2_USDC_WETH, 1010.78 ether, 2_513_107e6);
+
+        FlashExecutor.Pair memory borrow =
+            FlashExecutor.Pair({contractAddress: UNIV2_USDC_WETH, amountOut: 1e15, isToken0: true});
+        FlashExecutor.Pair[] memory pairs = new FlashExecutor.Pair[](0);
+
+        vm.expectRevert(FlashExecutor.InvalidPairCount.selector);
+        executor.run(borrow, callback(100e6, 0, pairs));
+    }
+
+    function testRevert_IfRunAsNonOwner() public {
+        FlashExecutor.Pair memory borrow =
+            FlashExecutor.Pair({contractAddress: UNIV2_USDC_WETH, amountOut: 1e15, isToken0: true});
+        FlashExecutor.Pair[] memory pairs = new FlashExecutor.Pair[](1);
+
+        vm.prank(nonOwner);
+        vm.expectRevert(FlashExecutor.NotOwner.selector);
+        executor.run(borrow, callback(100e6, 0, pairs));
+    }
+
+    function testRevert_IfCallbackNotFromFlashPair() public {
+        FlashExecutor.Pair[] memory pairs = new FlashExecutor.Pair[](1);
+        bytes memory data = callback(100e6, 0, pairs);
+
+        // Nobody can call the callback directly, not even the owner
+        vm.expectRevert(FlashExecutor.UnauthorizedCallback.selector);
+        executor.uniswapV2Call(address(executor), 1e15, 0, data);
+
+        // A pair swapping to the executor on behalf of someone else is rejected as well
+        vm.prank(UNIV2_USDC_WETH);
+        vm.expectRevert(FlashExecutor.UnauthorizedCallback.selector);
+        executor.uniswapV2Call(nonOwner, 1e15, 0, data);
+    }
+
+    function test_WithdrawERC20AsOwner() public {
+        deal(USDC, address(executor), 1000e6);
+
+        uint256 initialOwnerBalance = IERC20(USDC).balanceOf(address(this));
+        executor.withdrawERC20(USDC, owner, 1000e6);
+
+        assertEq(IERC20(USDC).balanceOf(address(this)), initialOwnerBalance + 1000e6);
+        assertEq(IERC20(USDC).balanceOf(address(executor)), 0);
+    }
+
+    // Allow this contract to receive ETH
+    receive() external payable {}
+
+    // ABI-encode the flash swap callback of a USDC -> WETH -> USDC cycle
+    function callback(uint256 repayAmount, uint256 minimumProfit, FlashExecutor.Pair[] memory pairs)
+        internal
+        pure
+        returns (bytes memory)
+    {
+        return abi.encode(
+            FlashExecutor.Callback({
+                tokenIn: USDC,
+                borrowedToken: WETH,
+                repayAmount: repayAmount,
+                minimumProfit: minimumProfit,
+                pairs: pairs
+            })
+        );
+    }
+
+    // Set reserves and matching balances of a pair (WETH is token0, USDC is token1)
+    function setPair(address pair, uint112 wethReserve, uint112 usdcReserve) internal {
+        mockPairReserves(pair, wethReserve, usdcReserve);
+        deal(WETH, pair, wethReserve);
+        deal(USDC, pair, usdcReserve);
+    }
+
+    // Helper function to mock pair reserves
+    // We are forking mainnet, so the balances are undefined and for tests we need to set them.
+    function mockPairReserves(address pair, uint112 reserve0, uint112 reserve1) internal {
+        uint32 blockTimestampLast = uint32(block.timestamp);
+        bytes32 value;
+        assembly {
+            // Pack reserve0 (112 bits) | reserve1 (112 bits) | blockTimestampLast (32 bits)
+            value := or(or(reserve0, shl(112, reserve1)), shl(224, blockTimestampLast))
+        }
+        vm.store(pair, bytes32(uint256(8)), value); // Slot 8 is where UniswapV2Pair stores reserves
+    }
+
+    // Helper function to calculate the expected output amount
+    function getAmountOut(uint256 amountIn, uint256 reserveIn, uint256 reserveOut)
+        internal
+        pure
+        returns (uint256 amountOut)
+    {
+        uint256 amountInWithFee = amountIn * 997;
+        uint256 numerator = amountInWithFee * reserveOut;
+        uint256 denominator = reserveIn * 1000 + amountInWithFee;
+        amountOut = numerator / denominator;
+    }
+}
@@ -0,0 +1,35 @@
+-- Executions we submitted on chain and what happened to them
+-- Pending - submitted, no receipt yet
+-- Included - mined and succeeded
+-- Reverted - mined and reverted
+-- Dropped - disappeared from the mempool without being mined
+-- Replaced - another transaction with the same nonce was mined instead
+
+CREATE TYPE execution_status AS ENUM ('Pending', 'Included', 'Reverted', 'Dropped', 'Replaced');
+
+CREATE TABLE executions (
+    id SERIAL PRIMARY KEY,
+    cycle VARCHAR NOT NULL,
+    start_token VARCHAR NOT NULL,
+    amount_in NUMERIC NOT NULL,
+    amount_out NUMERIC NOT NULL,
+    quoted_profit NUMERIC NOT NULL,
+    tx_hash VARCHAR NOT NULL UNIQUE,
+    nonce BIGINT NOT NULL,
+    gas_limit BIGINT NOT NULL,
+    max_fee_per_gas NUMERIC NOT NULL,
+    max_priority_fee_per_gas NUMERIC NOT NULL,
+    status execution_status NOT NULL DEFAULT 'Pending',
+    block_number BIGINT,
+    gas_used BIGINT,
+    effective_gas_price NUMERIC,
+    realized_profit NUMERIC,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_executions_status ON executions(status);
+CREATE INDEX idx_executions_cycle ON executions(cycle);
+
+COMMENT ON COLUMN executions.cycle IS 'Pair addresses of the cycle in execution order, comma separated';
+COMMENT ON COLUMN executions.realized_profit IS 'Profit in the start token computed from the receipt Transfer and Sync logs';
@@ -0,0 +1,8 @@
+-- Last nonce used by each sending address, so a restart does not reuse nonces
+-- of transactions that are still in the mempool
+
+CREATE TABLE nonces (
+    address VARCHAR PRIMARY KEY,
+    nonce BIGINT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
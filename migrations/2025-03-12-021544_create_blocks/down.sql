@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE pairs DROP COLUMN block_number;
+
+DROP TABLE blocks;
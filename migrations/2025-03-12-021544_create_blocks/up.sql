@@ -0,0 +1,9 @@
+-- Blocks whose Sync events have been ingested
+CREATE TABLE blocks (
+    number BIGINT PRIMARY KEY,
+    hash VARCHAR NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- The block of the last Sync event applied to the pair
+ALTER TABLE pairs ADD COLUMN block_number BIGINT;
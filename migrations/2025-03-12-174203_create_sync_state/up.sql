@@ -0,0 +1,8 @@
+-- Last block processed by each backfilled event stream
+-- sync_events - Sync events of Uniswap V2 pairs
+-- pair_created_events - PairCreated events of Uniswap V2 factories
+CREATE TABLE sync_state (
+    stream VARCHAR PRIMARY KEY,
+    last_block BIGINT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX pairs_block_number_idx;
+ALTER TABLE pairs DROP COLUMN block_hash;
+ALTER TABLE blocks DROP COLUMN parent_hash;
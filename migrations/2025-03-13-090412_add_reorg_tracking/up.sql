@@ -0,0 +1,8 @@
+-- The parent of each ingested block, used to detect reorgs
+ALTER TABLE blocks ADD COLUMN parent_hash VARCHAR;
+
+-- The hash of the block of the last Sync event applied to the pair
+ALTER TABLE pairs ADD COLUMN block_hash VARCHAR;
+
+-- Reorgs roll back pairs updated after the common ancestor
+CREATE INDEX pairs_block_number_idx ON pairs (block_number);
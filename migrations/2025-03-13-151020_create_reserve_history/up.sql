@@ -0,0 +1,19 @@
+-- Every Sync event applied to a pair, partitioned by block range
+-- Partitions are created ahead of the head and dropped past the retention by sync::reserve_history
+CREATE TABLE reserve_history (
+    pair_id INTEGER NOT NULL REFERENCES pairs(id),
+    block_number BIGINT NOT NULL,
+    log_index INTEGER NOT NULL,
+    tx_hash VARCHAR NOT NULL,
+    reserve0 NUMERIC NOT NULL,
+    reserve1 NUMERIC NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (block_number, log_index)
+) PARTITION BY RANGE (block_number);
+
+-- Catches rows written before their partition exists
+CREATE TABLE reserve_history_default PARTITION OF reserve_history DEFAULT;
+
+-- Reserves of a pair as of a block
+CREATE INDEX reserve_history_pair_id_block_number_idx
+    ON reserve_history (pair_id, block_number DESC, log_index DESC);
@@ -0,0 +1,6 @@
+-- Add Unvalidated to FactoryStatus enum
+-- Unvalidated - a factory we only know from a PairCreated event, its pairs are not trusted yet
+ALTER TYPE factory_status ADD VALUE 'Unvalidated';
+
+-- The block of the PairCreated event of the pair
+ALTER TABLE pairs ADD COLUMN created_block BIGINT;
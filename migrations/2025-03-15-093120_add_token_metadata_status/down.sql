@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tokens
+  DROP COLUMN name_status,
+  DROP COLUMN symbol_status,
+  DROP COLUMN decimals_status;
+
+DROP TYPE metadata_status;
@@ -0,0 +1,12 @@
+-- How a token metadata field was decoded
+-- Ok - the call returned a standard ABI value
+-- Bytes32 - the call returned a bytes32 instead of a string (e.g. MKR)
+-- Reverted - the call reverted, or the contract does not implement it
+-- Malformed - the call returned data that decodes as neither
+CREATE TYPE metadata_status AS ENUM ('Ok', 'Bytes32', 'Reverted', 'Malformed');
+
+-- NULL until the metadata is decoded with per-field status
+ALTER TABLE tokens
+  ADD COLUMN name_status metadata_status,
+  ADD COLUMN symbol_status metadata_status,
+  ADD COLUMN decimals_status metadata_status;
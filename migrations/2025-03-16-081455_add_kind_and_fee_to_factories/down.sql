@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE factories DROP COLUMN fee_bps;
+ALTER TABLE factories DROP COLUMN kind;
+
+DROP TYPE factory_kind;
@@ -0,0 +1,9 @@
+-- The kind of DEX a factory belongs to, detected from the functions it implements
+-- Unknown - the factory implements neither the Uniswap V2 nor the Solidly interface
+CREATE TYPE factory_kind AS ENUM ('UniswapV2', 'Solidly', 'Unknown');
+
+-- NULL until the factory is classified
+ALTER TABLE factories ADD COLUMN kind factory_kind;
+
+-- Swap fee of the factory's (volatile) pairs in basis points, NULL if it could not be detected
+ALTER TABLE factories ADD COLUMN fee_bps INTEGER;
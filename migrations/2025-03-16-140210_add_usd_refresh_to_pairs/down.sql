@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE pairs DROP COLUMN usd_block;
+ALTER TABLE pairs DROP COLUMN usd_updated_at;
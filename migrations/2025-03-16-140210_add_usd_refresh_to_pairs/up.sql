@@ -0,0 +1,3 @@
+-- When pairs.usd was last computed, and the block of the reserves it was computed from
+ALTER TABLE pairs ADD COLUMN usd_updated_at TIMESTAMP;
+ALTER TABLE pairs ADD COLUMN usd_block BIGINT;
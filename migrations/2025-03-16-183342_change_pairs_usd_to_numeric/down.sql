@@ -0,0 +1,26 @@
+-- This file should undo anything in `up.sql`
+DROP MATERIALIZED VIEW olap.pairs;
+
+-- Values that don't fit are lost
+ALTER TABLE pairs ALTER COLUMN usd TYPE INTEGER
+    USING CASE WHEN usd BETWEEN -2147483648 AND 2147483647 THEN usd::INTEGER END;
+
+CREATE MATERIALIZED VIEW olap.pairs AS
+    SELECT
+    pairs.address,
+    pairs.token0_id,
+    token0.symbol AS token0_symbol,
+    pairs.token1_id,
+    token1.symbol AS token1_symbol,
+    pairs.reserve0 / POWER(10, token0.decimals) AS reserve0,
+    pairs.reserve1 / POWER(10, token1.decimals) AS reserve1,
+    pairs.usd
+FROM public.pairs pairs
+INNER JOIN public.tokens token0 ON pairs.token0_id = token0.id
+INNER JOIN public.tokens token1 ON pairs.token1_id = token1.id
+INNER JOIN public.factories factory ON pairs.factory_id = factory.id;
+
+CREATE UNIQUE INDEX idx_olap_pairs_address ON olap.pairs(address);
+CREATE INDEX idx_olap_pairs_token0 ON olap.pairs(token0_id);
+CREATE INDEX idx_olap_pairs_token1 ON olap.pairs(token1_id);
+CREATE INDEX idx_olap_pairs_usd ON olap.pairs(usd);
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tokens
+  DROP COLUMN price_route,
+  DROP COLUMN price_depth,
+  DROP COLUMN price_confidence,
+  DROP COLUMN price_block,
+  DROP COLUMN price_status;
+
+DROP TYPE price_status;
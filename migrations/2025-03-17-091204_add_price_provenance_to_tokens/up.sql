@@ -0,0 +1,17 @@
+-- How a token price passed the manipulation guards
+-- Accepted - the price passed every guard
+-- Flagged - the price moved more than allowed or came through a new pool, and is stored with a lower confidence
+-- Rejected - the price did both, and the previous price was kept
+CREATE TYPE price_status AS ENUM ('Accepted', 'Flagged', 'Rejected');
+
+-- Where exchange_rate came from, as of its last update
+-- price_route - pair IDs from WETH to the token
+-- price_depth - USD liquidity of the shallowest pair of the route
+-- price_confidence - from 0 to 1, grows with depth and shrinks with hops and flags
+-- price_block - latest reserves block of the route
+ALTER TABLE tokens
+  ADD COLUMN price_route INTEGER[],
+  ADD COLUMN price_depth NUMERIC,
+  ADD COLUMN price_confidence DOUBLE PRECISION,
+  ADD COLUMN price_block BIGINT,
+  ADD COLUMN price_status price_status;
@@ -0,0 +1,27 @@
+-- Every profitable cycle the bot detected, executed or not
+-- A row is written per profitable cycle of a world update, with what was decided about it
+
+CREATE TABLE opportunities (
+    id BIGSERIAL PRIMARY KEY,
+    block_number BIGINT NOT NULL,
+    cycle VARCHAR NOT NULL,
+    start_token VARCHAR NOT NULL,
+    amount_in NUMERIC NOT NULL,
+    amount_out NUMERIC NOT NULL,
+    gross_profit NUMERIC NOT NULL,
+    net_profit NUMERIC,
+    profit_usd NUMERIC,
+    margin_bps INTEGER NOT NULL,
+    executed BOOLEAN NOT NULL DEFAULT FALSE,
+    skip_reason VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_opportunities_block_number ON opportunities(block_number);
+CREATE INDEX idx_opportunities_cycle ON opportunities(cycle);
+
+COMMENT ON COLUMN opportunities.cycle IS 'Pair addresses of the cycle in execution order, comma separated';
+COMMENT ON COLUMN opportunities.gross_profit IS 'Quoted profit in the start token, before gas';
+COMMENT ON COLUMN opportunities.net_profit IS 'Quoted profit in the start token after gas, if gas was estimated';
+COMMENT ON COLUMN opportunities.profit_usd IS 'Quoted profit in USD, if the start token has a USD rate';
+COMMENT ON COLUMN opportunities.skip_reason IS 'Why the opportunity was not executed';
@@ -0,0 +1,3 @@
+-- The index of the last Sync log applied to the pair in its block, so that a block committed in
+-- several parts only moves reserves forward
+ALTER TABLE pairs ADD COLUMN log_index INTEGER;
@@ -169,6 +169,14 @@ impl Cycle {
     /// Memoized for efficiency since this is an expensive calculation
     ///
     /// This is future functionality.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the search does not converge within 100 iterations
+    ///
+    /// # Panics
+    ///
+    /// If a swap of the cycle has no reserves
     #[allow(dead_code)]
     pub fn best_quote(&self) -> Result<CycleQuote, Error> {
         // Check if we already have a cached result
@@ -320,6 +328,10 @@ impl Cycle {
     /// This is based merely on pool price. Gas and slippage are not considered.
     ///
     /// This is future functionality.
+    ///
+    /// # Panics
+    ///
+    /// If a swap of the cycle has no reserves
     #[allow(dead_code)]
     pub fn is_positive(&self) -> bool {
         assert!(
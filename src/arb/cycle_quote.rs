@@ -1,7 +1,11 @@
 use alloy::primitives::{I256, U256};
+use bigdecimal::{BigDecimal, Zero};
 
 use crate::arb::cycle::Cycle;
 use crate::arb::swap_quote::SwapQuote;
+use crate::arb::token::{Token, TokenId};
+use crate::pricing::amount;
+use crate::utils::numeric::i256_to_big_decimal;
 
 /// Represents a quote for a complete trading cycle, containing quotes for each swap in the cycle.
 ///
@@ -27,6 +31,10 @@ impl CycleQuote {
     /// # Returns
     ///
     /// A new `CycleQuote` containing quotes for each swap in the cycle
+    ///
+    /// # Panics
+    ///
+    /// If the cycle has no swaps
     pub fn new(cycle: &Cycle, amount_in: U256) -> Self {
         let mut swap_quotes = Vec::with_capacity(cycle.swaps.len() + 1);
         cycle.swaps.iter().fold(amount_in, |amount, swap_side| {
@@ -47,19 +55,81 @@ impl CycleQuote {
     ///
     /// This is future functionality.
     #[allow(dead_code)]
+    #[must_use]
     pub fn swap_quotes(&self) -> Vec<SwapQuote> {
         self.swap_quotes.clone()
     }
 
+    /// Returns the token the cycle starts and ends with.
+    ///
+    /// # Returns
+    ///
+    /// The `TokenId` of the first swap's input token
+    ///
+    /// # Panics
+    ///
+    /// Never, quotes are built from cycles of at least two swaps
+    #[must_use]
+    pub fn start_token(&self) -> TokenId {
+        // SAFETY: we know the cycle has at least one swap because it is created from
+        // a Cycle struct which enforces a minimum of 2 swaps
+        #[allow(clippy::unwrap_used)]
+        self.swap_quotes.first().unwrap().swap().token_in()
+    }
+
     /// Calculates the profit for this cycle quote.
     ///
     /// # Returns
     ///
     /// The profit as an I256 value (can be negative if the cycle is not profitable)
+    #[must_use]
     pub fn profit(&self) -> I256 {
         I256::from_raw(self.amount_out()).saturating_sub(I256::from_raw(self.amount_in()))
     }
 
+    /// Calculates the profit in USD, so cycles starting in different tokens can be compared.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start token, with its decimals and USD rate
+    ///
+    /// # Returns
+    ///
+    /// The profit in USD, or `None` if `start` is not the start token or has no USD rate
+    #[must_use]
+    pub fn profit_usd(&self, start: &Token) -> Option<BigDecimal> {
+        if start.id() != self.start_token() {
+            return None;
+        }
+        Some(amount::usd(
+            start.usd()?,
+            &i256_to_big_decimal(self.profit()),
+            start.decimals(),
+        ))
+    }
+
+    /// Calculates the profit in whole WETH.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start token, with its decimals and USD rate
+    /// * `weth` - WETH, with its USD rate
+    ///
+    /// # Returns
+    ///
+    /// The profit in WETH, or `None` if either token has no USD rate
+    #[must_use]
+    pub fn profit_weth(&self, start: &Token, weth: &Token) -> Option<BigDecimal> {
+        if start.id() == weth.id() && start.id() == self.start_token() {
+            return Some(amount::whole(
+                &i256_to_big_decimal(self.profit()),
+                weth.decimals(),
+            ));
+        }
+        let weth_usd = weth.usd().filter(|usd| !usd.is_zero())?;
+        Some(self.profit_usd(start)? / weth_usd)
+    }
+
     /// Calculates the profit margin for this cycle quote in basis points (10,000 = 100%).
     ///
     /// # Returns
@@ -69,6 +139,7 @@ impl CycleQuote {
     /// This is future functionality.
     #[allow(clippy::cast_possible_truncation)]
     #[allow(dead_code)]
+    #[must_use]
     pub fn profit_margin(&self) -> i32 {
         let profit = self.profit();
         let amount_in = self.amount_in();
@@ -105,6 +176,7 @@ impl CycleQuote {
     ///
     /// This is future functionality.
     #[allow(dead_code)]
+    #[must_use]
     pub fn is_profitable(&self) -> bool {
         self.profit().is_positive()
     }
@@ -116,7 +188,12 @@ impl CycleQuote {
     /// The amount input as a U256 value
     ///
     /// This is future functionality.
+    ///
+    /// # Panics
+    ///
+    /// Never, quotes are built from cycles of at least two swaps
     #[allow(dead_code)]
+    #[must_use]
     pub fn amount_in(&self) -> U256 {
         // SAFETY: we know the cycle has at least one swap because it is created from
         // a Cycle struct which enforces a minimum of 2 swaps
@@ -131,7 +208,12 @@ impl CycleQuote {
     /// The amount output as a U256 value
     ///
     /// This is future functionality.
+    ///
+    /// # Panics
+    ///
+    /// Never, quotes are built from cycles of at least two swaps
     #[allow(dead_code)]
+    #[must_use]
     pub fn amount_out(&self) -> U256 {
         // SAFETY: we know the cycle has at least one swap because it is created from
         // a Cycle struct which enforces a minimum of 2 swaps
@@ -145,6 +227,7 @@ impl CycleQuote {
 mod tests {
     use super::*;
     use crate::arb::test_helpers::*;
+    use std::str::FromStr;
 
     #[test]
     fn test_quotes_not_exploitable() {
@@ -218,4 +301,40 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_profit_in_usd_and_weth() {
+        let cycle = cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 300, 300)]).unwrap();
+        // 9 units of A
+        let quote = CycleQuote::new(&cycle, U256::from(25));
+        let weth = Token::priced(token("E").id(), 18, Some(BigDecimal::from(2000)));
+
+        // A has 2 decimals and is worth 1,000 USD
+        let a = Token::priced(token("A").id(), 2, Some(BigDecimal::from(1000)));
+        assert_eq!(quote.profit_usd(&a), Some(BigDecimal::from(90)));
+        assert_eq!(
+            quote.profit_weth(&a, &weth),
+            Some(BigDecimal::from_str("0.045").unwrap())
+        );
+
+        // Unpriced, or not the start token
+        let unpriced = Token::priced(token("A").id(), 2, None);
+        assert_eq!(quote.profit_usd(&unpriced), None);
+        assert_eq!(quote.profit_weth(&unpriced, &weth), None);
+        let b = Token::priced(token("B").id(), 2, Some(BigDecimal::from(1000)));
+        assert_eq!(quote.profit_usd(&b), None);
+    }
+
+    #[test]
+    fn test_profit_in_weth_without_rates() {
+        let cycle = cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 300, 300)]).unwrap();
+        let quote = CycleQuote::new(&cycle, U256::from(25));
+
+        // A cycle from WETH is worth its profit in WETH, priced or not
+        let weth = Token::priced(token("A").id(), 1, None);
+        assert_eq!(
+            quote.profit_weth(&weth, &weth),
+            Some(BigDecimal::from_str("0.9").unwrap())
+        );
+    }
 }
@@ -0,0 +1,233 @@
+//! Detection of opportunities on every committed block.
+//!
+//! The world is loaded from the database on startup. Every block `sync::events` commits updates
+//! the reserves of the world's pools and quotes the cycles through them, and the opportunities of
+//! the update are handed to a `Recorder`. Execution is not part of the binary yet, so every
+//! opportunity is recorded as skipped.
+//!
+//! A subscriber that lagged behind the blocks, or a reorg, leaves the world with reserves the
+//! database no longer has, so the world is loaded again. It is also loaded again every
+//! `RELOAD_INTERVAL` to pick up the pairs discovered since. The USD rates of the world's tokens are
+//! loaded again whenever exchange rates change, or at least every `CATCH_UP_INTERVAL`, so profits
+//! are ranked and priced on current rates. Failed loads are logged and retried, a failed load of
+//! the rates keeps the previous ones.
+
+use std::collections::HashSet;
+
+use eyre::Result;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::cycle_quote::CycleQuote;
+use super::pool::{Pool, PoolId};
+use super::recorder::{Outcome, Recorder};
+use super::swap::{Direction, SwapId};
+use super::world::World;
+use crate::bootstrap::world::{self, LoadOptions};
+use crate::models::block::BlockUpdate;
+use crate::models::opportunity::SkipReason;
+use crate::repo::{PairRepo, TokenRepo};
+use crate::sync::bus::Topic;
+use crate::utils::app_context::AppContext;
+
+/// World updates waiting to be recorded before new ones are dropped
+const RECORDER_CAPACITY: usize = 64;
+
+/// How often the world is loaded again, to add the pairs discovered since
+const RELOAD_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_mins(10);
+
+/// How long to wait before retrying a failed load of the world
+const RETRY_DELAY: tokio::time::Duration = tokio::time::Duration::from_secs(5);
+
+/// Quote the cycles of every committed block and record their opportunities
+///
+/// # Errors
+/// Returns an error if the recorder's writer panicked
+pub async fn detect(ctx: &AppContext) -> Result<()> {
+    let options = LoadOptions::from_env();
+    let (recorder, writer) = Recorder::spawn(ctx.repo.clone(), RECORDER_CAPACITY);
+    let mut blocks = ctx.blocks.subscribe();
+    let mut reorgs = ctx.reorgs.subscribe();
+    let mut rates = ctx.bus.subscribe(&[Topic::ExchangeRateUpdated]);
+
+    let mut world = load(&ctx.repo, &options).await;
+    let mut reload = tokio::time::interval_at(
+        tokio::time::Instant::now() + RELOAD_INTERVAL,
+        RELOAD_INTERVAL,
+    );
+    reload.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            block = blocks.recv() => match block {
+                Ok(block) => {
+                    let update = world.update(&pools(&world, &block));
+                    let _ = recorder.record(block.number, &update, |quote| outcome(&world, quote));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("arb::detector: Missed {skipped} blocks, reloading the world");
+                    world = load(&ctx.repo, &options).await;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            reorg = reorgs.recv() => {
+                if matches!(reorg, Err(RecvError::Closed)) {
+                    break;
+                }
+                log::warn!("arb::detector: Reserves were rolled back, reloading the world");
+                world = load(&ctx.repo, &options).await;
+            }
+            _ = reload.tick() => {
+                world = load(&ctx.repo, &options).await;
+            }
+            () = rates.wait() => match world::tokens(&ctx.repo, &options).await {
+                Ok(tokens) => world.update_tokens(tokens),
+                Err(e) => log::error!("arb::detector: Failed to load token rates: {e}"),
+            },
+        }
+    }
+
+    drop(recorder);
+    writer.await?;
+
+    Ok(())
+}
+
+/// Load the world, retrying until it succeeds
+async fn load(repo: &(impl PairRepo + TokenRepo), options: &LoadOptions) -> World {
+    loop {
+        match world::load(repo, options).await {
+            Ok((world, _)) => return world,
+            Err(e) => {
+                log::error!("arb::detector: Failed to load the world: {e}");
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// The pools of the world updated by a block, with their new reserves
+///
+/// Pools that are not in the world are left out.
+fn pools(world: &World, block: &BlockUpdate) -> HashSet<Pool> {
+    block
+        .pools
+        .iter()
+        .filter_map(|update| {
+            let pool_id = PoolId::from(update.address);
+            let index = world.swap_map.get(&SwapId {
+                pool_id: pool_id.clone(),
+                direction: Direction::ZeroForOne,
+            })?;
+            let swap = &world.swap_vec[*index];
+
+            Some(Pool::new(
+                pool_id,
+                swap.token_in(),
+                swap.token_out(),
+                Some(update.reserve0),
+                Some(update.reserve1),
+            ))
+        })
+        .collect()
+}
+
+/// What was decided about a quote: nothing is executed, unpriced quotes could not even be ranked
+fn outcome(world: &World, quote: &CycleQuote) -> Outcome {
+    let priced = world
+        .token(quote.start_token())
+        .is_some_and(|token| token.usd().is_some());
+
+    if priced {
+        Outcome::skipped(SkipReason::ExecutionDisabled, None)
+    } else {
+        Outcome::skipped(SkipReason::Unpriced, None)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloy::primitives::{Address, B256, U256};
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::arb::token::{Token, TokenId};
+    use crate::models::block::PoolUpdate;
+
+    /// A world of a cycle A -> B -> A through two pools, with A priced at 1 USD
+    fn world() -> World {
+        let (a, b) = (
+            TokenId::from(Address::repeat_byte(0xa)),
+            TokenId::from(Address::repeat_byte(0xb)),
+        );
+        let pools = [1, 2]
+            .map(|byte| {
+                Pool::new(
+                    PoolId::from(Address::repeat_byte(byte)),
+                    a,
+                    b,
+                    Some(U256::from(1_000_000_000u64)),
+                    Some(U256::from(1_000_000_000u64)),
+                )
+            })
+            .into_iter()
+            .collect();
+
+        let mut world = World::new(&pools);
+        world.update_tokens([
+            Token::priced(a, 6, Some(BigDecimal::from(1))),
+            Token::priced(b, 6, None),
+        ]);
+        world
+    }
+
+    fn block(pools: Vec<PoolUpdate>) -> BlockUpdate {
+        BlockUpdate {
+            number: 7,
+            hash: B256::repeat_byte(7),
+            parent_hash: None,
+            pools,
+            syncs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_pools_of_a_block() {
+        let world = world();
+        let update = |byte, reserve: u64| PoolUpdate {
+            address: Address::repeat_byte(byte),
+            reserve0: U256::from(reserve),
+            reserve1: U256::from(2 * reserve),
+            log_index: 0,
+        };
+
+        // The pool of 3 is not in the world
+        let pools = pools(&world, &block(vec![update(1, 10), update(3, 30)]));
+
+        assert_eq!(pools.len(), 1);
+        let pool = pools.iter().next().unwrap();
+        assert_eq!(pool.id, PoolId::from(Address::repeat_byte(1)));
+        assert_eq!(pool.token0, TokenId::from(Address::repeat_byte(0xa)));
+        assert_eq!(pool.reserve1, Some(U256::from(20)));
+    }
+
+    #[test]
+    fn test_block_updates_yield_opportunities() {
+        let mut world = world();
+        // Pool 2 pays 10% more B for A than pool 1 takes back
+        let block = block(vec![PoolUpdate {
+            address: Address::repeat_byte(2),
+            reserve0: U256::from(1_000_000_000u64),
+            reserve1: U256::from(1_100_000_000u64),
+            log_index: 0,
+        }]);
+
+        let update = world.update(&pools(&world, &block));
+
+        let best = update.best().unwrap();
+        assert_eq!(
+            outcome(&world, &best.quote),
+            Outcome::skipped(SkipReason::ExecutionDisabled, None)
+        );
+    }
+}
@@ -9,8 +9,10 @@
  *
  * - `cycle`: Defines the `Cycle` struct representing a sequence of swaps forming a trading cycle
  * - `cycle_quote`: Provides quote calculation for cycles to determine profitability
+ * - `detector`: Quotes the cycles of every committed block and records their opportunities
  * - `pool`: Represents liquidity pools where tokens can be exchanged
  * - `portfolio`: Manages token holdings and balances
+ * - `recorder`: Durable record of every detected opportunity
  * - `swap`: Defines individual swap operations between tokens
  * - `swap_quote`: Calculates expected outputs for individual swaps
  * - `token`: Token identification and metadata
@@ -19,22 +21,29 @@
  */
 
 /// Core cycle detection and representation
-mod cycle;
+pub mod cycle;
 /// Cycle profitability calculation
-mod cycle_quote;
+pub mod cycle_quote;
+/// Opportunity detection on committed blocks
+pub mod detector;
 /// Liquidity pool representation and operations
 pub mod pool;
 /// Token portfolio management
-mod portfolio;
+pub mod portfolio;
+/// Recording of detected opportunities
+pub mod recorder;
 /// Individual swap operations
-mod swap;
+pub mod swap;
 /// Swap quote calculation
 pub mod swap_quote;
 /// Helpers for testing
-mod test_helpers;
+pub(crate) mod test_helpers;
 /// Token identification and metadata
 pub mod token;
 /// Common types used across the arbitrage module
+///
+/// This is future functionality.
+#[allow(dead_code)]
 mod types;
 /// Graph representation of the trading environment
 pub mod world;
@@ -26,6 +26,17 @@ impl Debug for PoolId {
     }
 }
 
+impl PoolId {
+    /// The on-chain address of the pool
+    ///
+    /// This is future functionality.
+    #[must_use]
+    #[allow(dead_code)]
+    pub const fn address(&self) -> Address {
+        self.0
+    }
+}
+
 impl TryFrom<&str> for PoolId {
     type Error = eyre::Error;
 
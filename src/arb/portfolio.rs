@@ -24,6 +24,7 @@ impl Portfolio {
     /// # Returns
     ///
     /// A new Portfolio instance
+    #[must_use]
     pub const fn new(holdings: HashMap<TokenId, U256>) -> Self {
         Self { holdings }
     }
@@ -38,7 +39,68 @@ impl Portfolio {
     ///
     /// The token balance as a U256 value if the token exists in the portfolio,
     /// or None if the token is not in the portfolio
+    #[must_use]
     pub fn balance(&self, token_id: &TokenId) -> Option<U256> {
         self.holdings.get(token_id).copied()
     }
+
+    /// Adds `amount` to the balance of a token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_id` - The ID of the token received
+    /// * `amount` - The amount received
+    pub fn credit(&mut self, token_id: TokenId, amount: U256) {
+        let balance = self.holdings.entry(token_id).or_default();
+        *balance = balance.saturating_add(amount);
+    }
+
+    /// Subtracts `amount` from the balance of a token, down to zero.
+    /// Tokens with a zero balance are removed from the portfolio.
+    ///
+    /// # Arguments
+    ///
+    /// * `token_id` - The ID of the token sent
+    /// * `amount` - The amount sent
+    pub fn debit(&mut self, token_id: TokenId, amount: U256) {
+        if let Some(balance) = self.holdings.get_mut(&token_id) {
+            *balance = balance.saturating_sub(amount);
+            if balance.is_zero() {
+                self.holdings.remove(&token_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arb::test_helpers::address_from_str;
+
+    #[test]
+    fn test_credit_and_debit() {
+        let token = TokenId::from(address_from_str("A"));
+        let mut portfolio = Portfolio::default();
+
+        portfolio.credit(token, U256::from(100));
+        portfolio.credit(token, U256::from(50));
+        assert_eq!(portfolio.balance(&token), Some(U256::from(150)));
+
+        portfolio.debit(token, U256::from(40));
+        assert_eq!(portfolio.balance(&token), Some(U256::from(110)));
+    }
+
+    #[test]
+    fn test_debit_removes_empty_balances() {
+        let token = TokenId::from(address_from_str("A"));
+        let mut portfolio = Portfolio::default();
+
+        portfolio.credit(token, U256::from(100));
+        portfolio.debit(token, U256::from(200));
+        assert_eq!(portfolio.balance(&token), None);
+
+        // Debiting an unknown token is a no-op
+        portfolio.debit(TokenId::from(address_from_str("B")), U256::from(1));
+        assert!(portfolio.holdings.is_empty());
+    }
 }
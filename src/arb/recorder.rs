@@ -0,0 +1,282 @@
+//! Durable record of every opportunity the bot detected.
+//!
+//! Executions only tell what the bot traded. To study thresholds and missed trades, every
+//! profitable cycle of a `WorldUpdate` is written to `opportunities` with what was decided about
+//! it. Writes happen on a task of their own, so detection never waits on the database: when the
+//! writer falls behind, updates are dropped rather than queued without bound.
+
+use alloy::primitives::I256;
+use bigdecimal::BigDecimal;
+use itertools::Itertools;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::task::JoinHandle;
+
+use super::cycle_quote::CycleQuote;
+use super::world_update::WorldUpdate;
+use crate::models::opportunity::{NewOpportunity, SkipReason};
+use crate::models::pair::DBAddress;
+use crate::repo::OpportunityRepo;
+use crate::utils::numeric::{i256_to_big_decimal, u256_to_big_decimal};
+
+/// What was decided about an opportunity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    /// Quoted profit in the start token after gas, if gas was estimated
+    pub net_profit: Option<I256>,
+    /// Why the opportunity was not executed, `None` if it was
+    pub skip_reason: Option<SkipReason>,
+}
+
+impl Outcome {
+    /// The opportunity was executed
+    ///
+    /// Execution is not part of the binary yet.
+    #[must_use]
+    #[allow(dead_code)]
+    pub const fn executed(net_profit: Option<I256>) -> Self {
+        Self {
+            net_profit,
+            skip_reason: None,
+        }
+    }
+
+    /// The opportunity was not executed
+    #[must_use]
+    pub const fn skipped(reason: SkipReason, net_profit: Option<I256>) -> Self {
+        Self {
+            net_profit,
+            skip_reason: Some(reason),
+        }
+    }
+}
+
+/// Sends the opportunities of world updates to a writer task
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    /// Opportunities of one update per message
+    sender: mpsc::Sender<Vec<NewOpportunity>>,
+}
+
+impl Recorder {
+    /// Spawn the writer task
+    ///
+    /// # Arguments
+    ///
+    /// * `repo` - Where the opportunities are written
+    /// * `capacity` - Updates waiting to be written before new ones are dropped
+    ///
+    /// The task ends once every `Recorder` is dropped and the waiting updates are written.
+    #[must_use]
+    pub fn spawn(repo: impl OpportunityRepo + 'static, capacity: usize) -> (Self, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let writer = tokio::spawn(async move { write(&repo, receiver).await });
+
+        (Self { sender }, writer)
+    }
+
+    /// Record the opportunities of a world update without waiting for them to be written
+    ///
+    /// # Arguments
+    ///
+    /// * `block` - The block of the reserves the update was quoted on
+    /// * `update` - The world update
+    /// * `outcome` - What was decided about the best quote of a cycle
+    ///
+    /// # Returns
+    ///
+    /// Whether the opportunities were queued, `false` if the writer fell behind or stopped, or the
+    /// block does not fit in `opportunities.block_number`
+    #[must_use]
+    pub fn record(
+        &self,
+        block: u64,
+        update: &WorldUpdate,
+        outcome: impl Fn(&CycleQuote) -> Outcome,
+    ) -> bool {
+        let Ok(block_number) = i64::try_from(block) else {
+            log::error!("arb::recorder: Block {block} is out of range, opportunities are dropped");
+            return false;
+        };
+
+        let rows = opportunities(block_number, update, outcome);
+        if rows.is_empty() {
+            return true;
+        }
+
+        match self.sender.try_send(rows) {
+            Ok(()) => true,
+            Err(TrySendError::Full(rows)) => {
+                log::warn!(
+                    "arb::recorder: Writer is behind, dropped {} opportunities of block {block}",
+                    rows.len()
+                );
+                false
+            }
+            Err(TrySendError::Closed(_)) => {
+                log::error!("arb::recorder: Writer stopped, opportunities are not recorded");
+                false
+            }
+        }
+    }
+}
+
+/// Write opportunities as they come, until every sender is dropped
+///
+/// A failed write is logged and its opportunities are lost, later ones are still written.
+async fn write(repo: &impl OpportunityRepo, mut receiver: mpsc::Receiver<Vec<NewOpportunity>>) {
+    while let Some(rows) = receiver.recv().await {
+        if let Err(e) = repo.record(&rows).await {
+            log::error!(
+                "arb::recorder: Failed to record {} opportunities: {e}",
+                rows.len()
+            );
+        }
+    }
+}
+
+/// Rows of the profitable cycles of a world update, ranked ones first
+fn opportunities(
+    block_number: i64,
+    update: &WorldUpdate,
+    outcome: impl Fn(&CycleQuote) -> Outcome,
+) -> Vec<NewOpportunity> {
+    let ranked = update
+        .opportunities()
+        .iter()
+        .map(|opportunity| (&opportunity.quote, Some(opportunity.profit_usd.clone())));
+    let unpriced = update.unpriced().iter().map(|quote| (quote, None));
+
+    ranked
+        .chain(unpriced)
+        .map(|(quote, profit_usd)| row(block_number, quote, profit_usd, outcome(quote)))
+        .collect()
+}
+
+/// The row of a quote
+fn row(
+    block_number: i64,
+    quote: &CycleQuote,
+    profit_usd: Option<BigDecimal>,
+    outcome: Outcome,
+) -> NewOpportunity {
+    NewOpportunity {
+        block_number,
+        cycle: quote
+            .swap_quotes()
+            .iter()
+            .map(|swap_quote| swap_quote.swap().id().pool_id.address())
+            .join(","),
+        start_token: DBAddress::new(quote.start_token().0),
+        amount_in: u256_to_big_decimal(quote.amount_in()),
+        amount_out: u256_to_big_decimal(quote.amount_out()),
+        gross_profit: i256_to_big_decimal(quote.profit()),
+        net_profit: outcome.net_profit.map(i256_to_big_decimal),
+        profit_usd,
+        margin_bps: quote.profit_margin(),
+        executed: outcome.skip_reason.is_none(),
+        skip_reason: outcome
+            .skip_reason
+            .map(|reason| reason.as_str().to_string()),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloy::primitives::U256;
+
+    use super::*;
+    use crate::arb::cycle::Cycle;
+    use crate::arb::test_helpers::{cycle, token};
+    use crate::arb::token::Token;
+    use crate::repo::memory::MemoryRepo;
+
+    /// A profitable cycle starting in A, and one starting in C
+    fn cycles() -> Vec<Cycle> {
+        vec![
+            cycle(&[
+                ("F1", "A", "B", 100_000_000, 200_000_000),
+                ("F2", "B", "A", 200_000_000, 101_000_000),
+            ])
+            .unwrap(),
+            cycle(&[
+                ("F3", "C", "D", 1_000_000_000, 2_000_000_000),
+                ("F4", "D", "C", 2_000_000_000, 1_010_000_000),
+            ])
+            .unwrap(),
+        ]
+    }
+
+    /// An update where A is priced at 1 USD and C has no USD rate
+    fn update() -> WorldUpdate {
+        let dollar = Token::priced(token("A").id(), 6, Some(BigDecimal::from(1)));
+        WorldUpdate::new(cycles(), |id| (id == dollar.id()).then_some(&dollar), None)
+    }
+
+    #[test]
+    fn test_opportunities_keep_unpriced_quotes() {
+        let update = update();
+        let best = update.best().unwrap().quote.clone();
+        let net_profit = I256::from_raw(U256::from(9));
+
+        let rows = opportunities(42, &update, |quote| {
+            if quote.start_token() == best.start_token() {
+                Outcome::executed(Some(net_profit))
+            } else {
+                Outcome::skipped(SkipReason::Unpriced, None)
+            }
+        });
+
+        assert_eq!(rows.len(), 2);
+        let (executed, skipped) = (&rows[0], &rows[1]);
+        assert_eq!(executed.block_number, 42);
+        assert_eq!(
+            executed.cycle,
+            format!(
+                "{},{}",
+                best.swap_quotes()[0].swap().id().pool_id.address(),
+                best.swap_quotes()[1].swap().id().pool_id.address()
+            )
+        );
+        assert_eq!(executed.start_token.value, token("A").id().0);
+        assert_eq!(executed.gross_profit, BigDecimal::from(49));
+        assert_eq!(executed.net_profit, Some(BigDecimal::from(9)));
+        assert_eq!(
+            executed.profit_usd,
+            Some(update.best().unwrap().profit_usd.clone())
+        );
+        assert_eq!(executed.margin_bps, best.profit_margin());
+        assert!(executed.executed);
+        assert_eq!(executed.skip_reason, None);
+
+        assert_eq!(skipped.start_token.value, token("C").id().0);
+        assert_eq!(skipped.profit_usd, None);
+        assert!(!skipped.executed);
+        assert_eq!(skipped.skip_reason.as_deref(), Some("Unpriced"));
+    }
+
+    #[tokio::test]
+    async fn test_recorder_queues_updates_for_the_writer() {
+        let repo = MemoryRepo::new();
+        let (sender, receiver) = mpsc::channel(1);
+        let recorder = Recorder { sender };
+
+        // The block does not fit in the column, nothing is queued
+        assert!(!recorder.record(u64::MAX, &update(), |_| Outcome::executed(None)));
+        assert!(recorder.record(1, &update(), |_| Outcome::skipped(
+            SkipReason::BelowThreshold,
+            None
+        )));
+        assert!(recorder.record(2, &WorldUpdate::default(), |_| Outcome::executed(None)));
+        // The writer is behind, the second update is dropped
+        assert!(!recorder.record(3, &update(), |_| Outcome::executed(None)));
+        drop(recorder);
+        write(&repo, receiver).await;
+
+        let rows = repo.opportunities();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(
+            |row| row.block_number == 1 && row.skip_reason.as_deref() == Some("BelowThreshold")
+        ));
+    }
+}
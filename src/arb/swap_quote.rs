@@ -8,6 +8,9 @@ use super::swap::Swap;
 /// optimizer. We need complete quotes for each swap in a cycle (both amount in and amount out).
 #[derive(Debug, Clone)]
 pub struct SwapQuote {
+    /// The swap being quoted
+    #[allow(dead_code)]
+    swap: Swap,
     /// The amount of tokens input into the swap
     amount_in: U256,
     /// The amount of tokens output from the swap
@@ -22,11 +25,21 @@ impl SwapQuote {
         let amount_out = Self::calculated_amount_out(swap, amount_in);
 
         Self {
+            swap: swap.clone(),
             amount_in,
             amount_out,
         }
     }
 
+    /// Returns the swap being quoted
+    ///
+    /// This is future functionality.
+    #[must_use]
+    #[allow(dead_code)]
+    pub const fn swap(&self) -> &Swap {
+        &self.swap
+    }
+
     /// f64 is a lot, also this function is used in logs only
     #[allow(clippy::cast_precision_loss)]
     /// This is future functionality.
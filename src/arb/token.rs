@@ -1,9 +1,12 @@
 /// A token is what we are trading
 /// Here, mostly for type safety.
 use alloy::primitives::Address;
+use bigdecimal::BigDecimal;
 use core::fmt::{self, Debug};
 use eyre::Result;
+use std::cmp::Ordering;
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
 
 /// Globally unique identifier for a token to distinguish between different chains
 #[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Ord, PartialOrd)]
@@ -60,11 +63,53 @@ impl Display for TokenId {
     }
 }
 
+/// Decimals of tokens we know nothing about
+pub const DEFAULT_DECIMALS: u8 = 18;
+
 /// A token is what we are trading
-#[derive(Clone, Default, PartialEq, Eq, Hash, Ord, PartialOrd)]
+#[derive(Clone)]
 pub struct Token {
     /// The ID of the token
     id: TokenId,
+    /// The decimals of the token
+    decimals: u8,
+    /// USD per whole token, if the token is priced
+    usd: Option<BigDecimal>,
+}
+
+impl Default for Token {
+    fn default() -> Self {
+        Self::new(TokenId::default())
+    }
+}
+
+/// Two tokens are equal if they have the same ID, whatever their price
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Token {}
+
+/// Hash the token by its ID
+impl Hash for Token {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl PartialOrd for Token {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Tokens are ordered by ID
+impl Ord for Token {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
 }
 
 impl Debug for Token {
@@ -74,7 +119,7 @@ impl Debug for Token {
 }
 
 impl Token {
-    /// Creates a new token with the given ID.
+    /// Creates a new token with the given ID, `DEFAULT_DECIMALS` and no USD rate.
     ///
     /// # Arguments
     ///
@@ -82,7 +127,23 @@ impl Token {
     ///
     #[must_use]
     pub const fn new(id: TokenId) -> Self {
-        Self { id }
+        Self {
+            id,
+            decimals: DEFAULT_DECIMALS,
+            usd: None,
+        }
+    }
+
+    /// Creates a token with its decimals and USD rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the token
+    /// * `decimals` - The decimals of the token
+    /// * `usd` - USD per whole token, if the token is priced
+    #[must_use]
+    pub const fn priced(id: TokenId, decimals: u8, usd: Option<BigDecimal>) -> Self {
+        Self { id, decimals, usd }
     }
 
     /// Returns the ID of the token.
@@ -94,4 +155,16 @@ impl Token {
     pub const fn id(&self) -> TokenId {
         self.id
     }
+
+    /// Returns the decimals of the token.
+    #[must_use]
+    pub const fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    /// Returns USD per whole token, if the token is priced.
+    #[must_use]
+    pub const fn usd(&self) -> Option<&BigDecimal> {
+        self.usd.as_ref()
+    }
 }
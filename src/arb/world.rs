@@ -11,6 +11,8 @@
 /// one of supported tokens in our balances.
 use std::collections::{HashMap, HashSet};
 
+use crate::utils::constants::WETH;
+
 use super::{
     cycle::Cycle,
     pool::Pool,
@@ -37,9 +39,6 @@ pub struct World {
     pub swap_vec: Vec<Swap>,
 
     /// `SwapId` to `SwapIndex` mapping
-    ///
-    /// This is future functionality.
-    #[allow(dead_code)]
     pub swap_map: HashMap<SwapId, SwapIndex>,
 
     /// Adjacency list of `TokenId` (Vertex) to a list of `SwapId` (outgoing edges)
@@ -112,16 +111,36 @@ impl World {
         market
     }
 
-    /// Update the market with new pool reserves and balances and return affected cycles
-    /// Call this once per block with new pools and balances
+    /// Update the market with new pool reserves and return affected cycles
+    /// Call this once per block with new pools, opportunities are ranked by profit in USD
+    pub fn update(&mut self, pools: &HashSet<Pool>) -> WorldUpdate {
+        let updated_swaps = self.update_swaps(pools.clone());
+        let updated_cycles = self.update_cycles(&updated_swaps);
+        WorldUpdate::new(
+            updated_cycles,
+            |id| self.token(id),
+            self.token(TokenId::from(WETH)),
+        )
+    }
+
+    /// Returns the token with the given ID, with its decimals and USD rate.
+    #[must_use]
+    pub fn token(&self, id: TokenId) -> Option<&Token> {
+        self.token_map.get(&id).map(|&index| &self.token_vec[index])
+    }
+
+    /// Sets the decimals and USD rates of tokens in the world.
+    /// Tokens that are not in the world are ignored.
     ///
-    /// This is future functionality.
-    #[allow(dead_code)]
-    pub fn update(&mut self, _pools: &HashSet<Pool>) -> WorldUpdate {
-        todo!()
-        // let updated_swaps = self.update_swaps(pools.clone());
-        // let updated_cycles = self.update_cycles(&updated_swaps);
-        // WorldUpdate::new(updated_cycles)
+    /// # Arguments
+    ///
+    /// * `tokens` - Tokens with their decimals and USD rates
+    pub fn update_tokens(&mut self, tokens: impl IntoIterator<Item = Token>) {
+        for token in tokens {
+            if let Some(&index) = self.token_map.get(&token.id()) {
+                self.token_vec[index] = token;
+            }
+        }
     }
 
     /// Updates the swaps in the world based on the updated pools.
@@ -136,9 +155,6 @@ impl World {
     /// # Returns
     ///
     /// A vector of swaps that were updated
-    ///
-    /// This is future functionality.
-    #[allow(dead_code)]
     fn update_swaps(&mut self, updated_pools: HashSet<Pool>) -> Vec<Swap> {
         let mut updated_swaps = Vec::with_capacity(updated_pools.len() * 2);
 
@@ -161,8 +177,8 @@ impl World {
 
     /// Updates the cycles in the world based on the updated swaps.
     ///
-    /// This method filters the existing cycles to include only those
-    /// that contain at least one of the updated swaps.
+    /// This method rebuilds the existing cycles that contain at least one of the updated
+    /// swaps from the current swaps, so their quotes use the new reserves.
     ///
     /// # Arguments
     ///
@@ -171,24 +187,36 @@ impl World {
     /// # Returns
     ///
     /// A vector of cycles that were affected by the updated swaps
-    #[allow(dead_code)]
-    fn update_cycles(&self, updated_swaps: &[Swap]) -> Vec<Cycle> {
-        // Filter all_cycles to only include cycles with at least one updated swap
-        let updated_set: HashSet<Swap> = updated_swaps.iter().cloned().collect();
-
-        self.cycle_vec
-            .iter()
-            .filter(|cycle| {
-                cycle.swaps.iter().any(|swap| {
-                    if let Some(&swap_id) = self.swap_map.get(&swap.id()) {
-                        updated_set.contains(&self.swap_vec[swap_id])
-                    } else {
-                        false
-                    }
+    fn update_cycles(&mut self, updated_swaps: &[Swap]) -> Vec<Cycle> {
+        let updated_ids: HashSet<SwapId> = updated_swaps.iter().map(Swap::id).collect();
+        let mut updated_cycles = Vec::new();
+
+        for cycle in &mut self.cycle_vec {
+            if !cycle
+                .swaps
+                .iter()
+                .any(|swap| updated_ids.contains(&swap.id()))
+            {
+                continue;
+            }
+
+            let swaps = cycle
+                .swaps
+                .iter()
+                .map(|swap| {
+                    self.swap_map
+                        .get(&swap.id())
+                        .map_or_else(|| swap.clone(), |&index| self.swap_vec[index].clone())
                 })
-            })
-            .cloned()
-            .collect()
+                .collect();
+            // A new cycle also drops the best quote cached for the old reserves
+            if let Ok(updated) = Cycle::new(swaps) {
+                *cycle = updated;
+            }
+            updated_cycles.push(cycle.clone());
+        }
+
+        updated_cycles
     }
 
     /// Returns a vector of all cycles in the world.
@@ -292,6 +320,9 @@ impl World {
 mod tests {
     use super::*;
     use alloy::primitives::map::HashMap;
+    use alloy::primitives::{I256, U256};
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
 
     use crate::arb::pool::PoolId;
     use crate::arb::swap::Direction;
@@ -457,6 +488,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_update_ranks_affected_cycles() {
+        let mut world = world(&[
+            ("F1", "A", "B", 100_000_000, 200_000_000),
+            ("F2", "A", "B", 100_000_000, 200_000_000),
+            ("F3", "C", "D", 100_000_000, 200_000_000),
+        ]);
+        world.update_tokens([
+            Token::priced(token("A").id(), 6, Some(BigDecimal::from(1))),
+            Token::priced(token("B").id(), 6, Some(BigDecimal::from(1))),
+            Token::priced(token("E").id(), 18, Some(BigDecimal::from(2000))),
+        ]);
+        assert_eq!(world.token(token("A").id()).unwrap().decimals(), 6);
+        assert!(world.token(token("E").id()).is_none());
+
+        let update = world.update(&HashSet::from([pool(
+            "F2",
+            "A",
+            "B",
+            101_000_000,
+            200_000_000,
+        )]));
+
+        assert_eq!(update.cycles().len(), 2);
+        let best = update.best().unwrap();
+        assert_eq!(best.quote.profit(), I256::from_raw(U256::from(49)));
+        assert_eq!(best.profit_usd, BigDecimal::from_str("0.000049").unwrap());
+        // WETH is not in this world
+        assert_eq!(best.profit_weth, None);
+    }
+
     #[test]
     fn test_find_cycles() {
         let world = world(&[("F1", "A", "B", 100, 200), ("F2", "A", "B", 100, 300)]);
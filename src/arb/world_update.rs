@@ -1,188 +1,259 @@
-/// WIP The cycles that were affected by the update
+use bigdecimal::BigDecimal;
+
+use super::cycle::Cycle;
+use super::cycle_quote::CycleQuote;
+use super::swap::Swap;
+use super::token::{Token, TokenId};
+
+/// A profitable cycle with its profit in units every cycle can be compared in
+#[derive(Debug, Clone)]
+pub struct Opportunity {
+    /// The best quote of the cycle
+    pub quote: CycleQuote,
+    /// The profit in USD
+    pub profit_usd: BigDecimal,
+    /// The profit in WETH, if WETH is priced
+    #[allow(dead_code)]
+    pub profit_weth: Option<BigDecimal>,
+}
+
+/// The cycles that were affected by an update, and the opportunities among them
+///
+/// Cycles start in different tokens, so their raw profits can't be compared. Opportunities are
+/// ranked on their profit in USD instead. Profitable cycles starting in a token without a USD
+/// rate can't be ranked and are kept apart.
+#[derive(Debug, Clone, Default)]
 pub struct WorldUpdate {
-    // The cycles that were affected by the update
-    // cycles: Vec<Cycle>,
+    /// The cycles that were affected by the update
+    #[allow(dead_code)]
+    cycles: Vec<Cycle>,
+    /// Profitable cycles, most profitable in USD first
+    opportunities: Vec<Opportunity>,
+    /// Best quotes of profitable cycles starting in a token without a USD rate
+    unpriced: Vec<CycleQuote>,
+}
+
+impl WorldUpdate {
+    /// Creates a new `WorldUpdate` from the affected cycles, quoting and ranking them.
+    ///
+    /// # Arguments
+    ///
+    /// * `cycles` - The cycles that were affected by the update
+    /// * `token` - Looks up a token with its decimals and USD rate
+    /// * `weth` - WETH, to report profits in WETH
+    #[must_use]
+    pub fn new<'a>(
+        cycles: Vec<Cycle>,
+        token: impl Fn(TokenId) -> Option<&'a Token>,
+        weth: Option<&Token>,
+    ) -> Self {
+        let mut opportunities = Vec::new();
+        let mut unpriced = Vec::new();
+
+        for cycle in cycles.iter().filter(|cycle| cycle.has_all_reserves()) {
+            let Ok(quote) = cycle.best_quote() else {
+                continue;
+            };
+            if !quote.is_profitable() {
+                continue;
+            }
+
+            let start = token(quote.start_token());
+            match start.and_then(|start| quote.profit_usd(start)) {
+                Some(profit_usd) => opportunities.push(Opportunity {
+                    profit_weth: start
+                        .zip(weth)
+                        .and_then(|(start, weth)| quote.profit_weth(start, weth)),
+                    profit_usd,
+                    quote,
+                }),
+                None => unpriced.push(quote),
+            }
+        }
+
+        opportunities.sort_by(|a, b| b.profit_usd.cmp(&a.profit_usd));
+
+        Self {
+            cycles,
+            opportunities,
+            unpriced,
+        }
+    }
+
+    /// Returns a reference to the cycles in this update.
+    #[must_use]
+    #[allow(dead_code)]
+    pub const fn cycles(&self) -> &Vec<Cycle> {
+        &self.cycles
+    }
+
+    /// Checks if all cycles in this update have reserves.
+    #[must_use]
+    #[allow(dead_code)]
+    pub fn has_all_reserves(&self) -> bool {
+        self.cycles.iter().all(Cycle::has_all_reserves)
+    }
+
+    /// Returns a list of swaps in the cycles that have no reserves.
+    #[must_use]
+    #[allow(dead_code)]
+    pub fn swaps_with_no_reserves(&self) -> Vec<Swap> {
+        self.cycles
+            .iter()
+            .flat_map(Cycle::swaps_with_no_reserves)
+            .collect()
+    }
+
+    /// Profitable cycles, most profitable in USD first
+    #[must_use]
+    pub fn opportunities(&self) -> &[Opportunity] {
+        &self.opportunities
+    }
+
+    /// The most profitable cycle in USD
+    #[must_use]
+    #[allow(dead_code)]
+    pub fn best(&self) -> Option<&Opportunity> {
+        self.opportunities.first()
+    }
+
+    /// Best quotes of profitable cycles that can't be ranked, because their start token has no
+    /// USD rate
+    #[must_use]
+    pub fn unpriced(&self) -> &[CycleQuote] {
+        &self.unpriced
+    }
 }
 
-// impl WorldUpdate {
-//     /// Creates a new `WorldUpdate` with the given cycles.
-//     pub const fn new(cycles: Vec<Cycle>) -> Self {
-//         Self { cycles }
-//     }
-
-//     /// Returns a reference to the cycles in this update.
-//     pub const fn cycles(&self) -> &Vec<Cycle> {
-//         &self.cycles
-//     }
-
-//     /// Checks if all cycles in this update have reserves.
-//     pub fn has_all_reserves(&self) -> bool {
-//         self.cycles
-//             .iter()
-//             .all(super::cycle::Cycle::has_all_reserves)
-//     }
-
-//     /// Returns a list of swaps in the cycles that have no reserves.
-//     pub fn swaps_with_no_reserves(&self) -> Vec<Swap> {
-//         self.cycles
-//             .iter()
-//             .flat_map(super::cycle::Cycle::swaps_with_no_reserves)
-//             .collect()
-//     }
-
-//     /// Best cycle quotes - the best quote for each cycle. Not necessarily exploitable.
-//     pub fn best_cycle_quotes(&self) -> Vec<Result<CycleQuote, Error>> {
-//         assert!(self.has_all_reserves(), "All cycles must have reserves");
-
-//         self.cycles
-//             .iter()
-//             .map(super::cycle::Cycle::best_quote)
-//             .collect()
-//     }
-
-//     /// Positive rate cycles - the cycles that have a positive rate.
-//     fn positive_cycles(&self) -> Vec<Cycle> {
-//         assert!(self.has_all_reserves(), "All cycles must have reserves");
-
-//         self.cycles
-//             .iter()
-//             .filter(|cycle| cycle.is_positive())
-//             .cloned()
-//             .collect()
-//     }
-
-//     /// Profitable cycles - the cycles that have a positive rate and are exploitable
-//     fn profitable_cycles(&self) -> Vec<Cycle> {
-//         assert!(self.has_all_reserves(), "All cycles must have reserves");
-
-//         // SAFETY: we know the cycles have reserves because we checked it
-//         #[allow(clippy::unwrap_used)]
-//         self.positive_cycles()
-//             .iter()
-//             .filter(|cycle| cycle.best_quote().is_ok() && cycle.best_quote().unwrap().is_profitable())
-//             .cloned()
-//             .collect()
-//     }
-
-//     /// Unprofitable cycles - the cycles that have a negative rate or are not exploitable
-//     pub fn unprofitable_cycles(&self) -> Vec<Cycle> {
-//         assert!(self.has_all_reserves(), "All cycles must have reserves");
-
-//         // SAFETY: we know the cycles have reserves because we checked it
-//         #[allow(clippy::unwrap_used)]
-//         self.cycles()
-//             .iter()
-//             .filter(|cycle| {
-//                 cycle.best_quote().is_ok() && !cycle.best_quote().unwrap().is_profitable()
-//             })
-//             .cloned()
-//             .collect()
-//     }
-
-//     /// Profitable cycle quotes - the best quote for each profitable cycle.
-//     pub fn profitable_cycle_quotes(&self) -> Vec<Result<CycleQuote, Error>> {
-//         self.profitable_cycles()
-//             .iter()
-//             .map(super::cycle::Cycle::best_quote)
-//             .collect()
-//     }
-// }
-
-// #[cfg(test)]
-// #[allow(clippy::unwrap_used)]
-// mod tests {
-//     use alloy::primitives::{I256, U256};
-
-//     use crate::arb::test_helpers::{bare_swap, cycle, swap};
-
-//     use super::*;
-
-//     #[test]
-//     fn test_has_all_reserves_is_true() {
-//         let world_update = WorldUpdate::new(vec![
-//             cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 200, 100)]).unwrap(),
-//             cycle(&[("F2", "A", "B", 100, 200), ("F1", "B", "A", 200, 100)]).unwrap(),
-//         ]);
-//         assert!(world_update.has_all_reserves());
-//     }
-
-//     #[test]
-//     fn test_has_all_reserves_is_false() {
-//         let world_update = WorldUpdate::new(vec![Cycle::new(Vec::from([
-//             bare_swap("F1", "A", "B"),
-//             bare_swap("F2", "B", "A"),
-//         ]))
-//         .unwrap()]);
-//         assert!(!world_update.has_all_reserves());
-//     }
-
-//     #[test]
-//     fn test_swaps_with_no_reserves() {
-//         let world_update = WorldUpdate::new(vec![Cycle::new(Vec::from([
-//             bare_swap("F1", "A", "B"),
-//             bare_swap("F2", "B", "A"),
-//         ]))
-//         .unwrap()]);
-//         assert_eq!(
-//             world_update.swaps_with_no_reserves(),
-//             vec![bare_swap("F1", "A", "B"), bare_swap("F2", "B", "A")]
-//         );
-
-//         let world_update = WorldUpdate::new(vec![Cycle::new(Vec::from([
-//             swap("F1", "A", "B", 100, 200),
-//             swap("F2", "B", "A", 200, 100),
-//         ]))
-//         .unwrap()]);
-//         assert_eq!(world_update.swaps_with_no_reserves(), vec![]);
-//     }
-
-//     #[test]
-//     fn test_profitable_cycles() {
-//         // Unprofitable update
-//         let world_update = WorldUpdate::new(vec![cycle(&[
-//             ("F1", "A", "B", 100_000_000, 200_000_000),
-//             ("F2", "B", "A", 200_000_000, 100_000_000),
-//         ])
-//         .unwrap()]);
-//         assert!(world_update.profitable_cycles().is_empty());
-
-//         // Slightly profitable update
-//         let world_update = WorldUpdate::new(vec![
-//             cycle(&[
-//                 ("F1", "A", "B", 100_000_000, 200_000_000),
-//                 ("F2", "B", "A", 200_000_000, 101_000_000),
-//             ])
-//             .unwrap(),
-//             cycle(&[
-//                 ("F1", "B", "A", 200_000_000, 100_000_000),
-//                 ("F2", "A", "B", 101_000_000, 200_000_000),
-//             ])
-//             .unwrap(),
-//         ]);
-//         assert_eq!(world_update.cycles().len(), 2);
-
-//         // Profitable cycle
-//         assert_eq!(world_update.profitable_cycles().len(), 1);
-//         let profitable_cycles = world_update.profitable_cycles();
-//         let cycle = profitable_cycles.first().unwrap();
-//         assert!(cycle.is_positive());
-
-//         let best_quote = cycle.best_quote().unwrap();
-//         assert!(best_quote.is_profitable());
-//         assert_eq!(best_quote.amount_in(), U256::from(13354));
-//         assert_eq!(best_quote.amount_out(), U256::from(13403));
-//         assert_eq!(best_quote.profit(), I256::from_raw(U256::from(49)));
-
-//         // Unprofitable cycle
-//         assert_eq!(world_update.unprofitable_cycles().len(), 1);
-//         let unprofitable_cycles = world_update.unprofitable_cycles();
-//         let cycle = unprofitable_cycles.first().unwrap();
-//         assert!(!cycle.is_positive());
-
-//         let best_quote = cycle.best_quote().unwrap();
-//         assert!(!best_quote.is_profitable());
-//         assert_eq!(best_quote.amount_in(), U256::from(0));
-//         assert_eq!(best_quote.amount_out(), U256::from(0));
-//         assert_eq!(best_quote.profit(), I256::from_raw(U256::from(0)));
-//     }
-// }
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use alloy::primitives::{I256, U256};
+
+    use crate::arb::test_helpers::{bare_swap, cycle, swap, token};
+
+    use super::*;
+
+    /// A token priced at 1 USD
+    fn dollar(id: &str, decimals: u8) -> Token {
+        Token::priced(token(id).id(), decimals, Some(BigDecimal::from(1)))
+    }
+
+    fn update(cycles: Vec<Cycle>, tokens: &[Token]) -> WorldUpdate {
+        let tokens = tokens
+            .iter()
+            .map(|token| (token.id(), token.clone()))
+            .collect::<HashMap<_, _>>();
+        WorldUpdate::new(cycles, |id| tokens.get(&id), tokens.get(&token("E").id()))
+    }
+
+    #[test]
+    fn test_has_all_reserves_is_true() {
+        let world_update = update(
+            vec![
+                cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 200, 100)]).unwrap(),
+                cycle(&[("F2", "A", "B", 100, 200), ("F1", "B", "A", 200, 100)]).unwrap(),
+            ],
+            &[],
+        );
+        assert!(world_update.has_all_reserves());
+    }
+
+    #[test]
+    fn test_swaps_with_no_reserves() {
+        let world_update = update(
+            vec![Cycle::new(Vec::from([
+                bare_swap("F1", "A", "B"),
+                bare_swap("F2", "B", "A"),
+            ]))
+            .unwrap()],
+            &[],
+        );
+        assert!(!world_update.has_all_reserves());
+        assert_eq!(
+            world_update.swaps_with_no_reserves(),
+            vec![bare_swap("F1", "A", "B"), bare_swap("F2", "B", "A")]
+        );
+        assert!(world_update.opportunities().is_empty());
+
+        let world_update = update(
+            vec![Cycle::new(Vec::from([
+                swap("F1", "A", "B", 100, 200),
+                swap("F2", "B", "A", 200, 100),
+            ]))
+            .unwrap()],
+            &[],
+        );
+        assert_eq!(world_update.swaps_with_no_reserves(), vec![]);
+    }
+
+    #[test]
+    fn test_ranks_opportunities_in_usd() {
+        let cycles = vec![
+            // Unprofitable
+            cycle(&[
+                ("F1", "A", "B", 100_000_000, 200_000_000),
+                ("F2", "B", "A", 200_000_000, 100_000_000),
+            ])
+            .unwrap(),
+            // Profitable in A, a 6 decimals token
+            cycle(&[
+                ("F1", "A", "B", 100_000_000, 200_000_000),
+                ("F2", "B", "A", 200_000_000, 101_000_000),
+            ])
+            .unwrap(),
+            // Ten times the raw profit in C, an 18 decimals token
+            cycle(&[
+                ("F3", "C", "D", 1_000_000_000, 2_000_000_000),
+                ("F4", "D", "C", 2_000_000_000, 1_010_000_000),
+            ])
+            .unwrap(),
+        ];
+        let weth = Token::priced(token("E").id(), 18, Some(BigDecimal::from(2000)));
+        let tokens = [
+            dollar("A", 6),
+            dollar("B", 6),
+            dollar("C", 18),
+            dollar("D", 18),
+            weth,
+        ];
+
+        let world_update = update(cycles, &tokens);
+
+        assert_eq!(world_update.opportunities().len(), 2);
+        let (best, second) = (
+            &world_update.opportunities()[0],
+            &world_update.opportunities()[1],
+        );
+        assert_eq!(best.quote.start_token(), token("A").id());
+        assert_eq!(second.quote.start_token(), token("C").id());
+        assert!(second.quote.profit() > best.quote.profit());
+        assert!(best.profit_usd > second.profit_usd);
+
+        assert_eq!(best.quote.profit(), I256::from_raw(U256::from(49)));
+        assert_eq!(best.profit_usd, BigDecimal::from_str("0.000049").unwrap());
+        assert_eq!(
+            best.profit_weth,
+            Some(BigDecimal::from_str("0.0000000245").unwrap())
+        );
+        assert!(world_update.unpriced().is_empty());
+    }
+
+    #[test]
+    fn test_keeps_unpriced_opportunities_apart() {
+        let cycles = vec![cycle(&[
+            ("F1", "A", "B", 100_000_000, 200_000_000),
+            ("F2", "B", "A", 200_000_000, 101_000_000),
+        ])
+        .unwrap()];
+
+        let world_update = update(cycles, &[token("A")]);
+
+        assert!(world_update.best().is_none());
+        assert_eq!(world_update.unpriced().len(), 1);
+        assert_eq!(world_update.cycles().len(), 1);
+    }
+}
@@ -1,14 +1,11 @@
 /// Types for the bootstrap module
 pub mod types;
+/// Building the world from the database
+pub mod world;
 
 use crate::bootstrap::types::{PairInfo, Reserves};
-use crate::utils::app_context::AppContext;
-use crate::utils::constants::UNISWAP_V2_BATCH_QUERY_ADDRESS;
 
-use alloy::{
-    primitives::{Address, U256},
-    sol,
-};
+use alloy::{primitives::U256, sol};
 use bigdecimal::BigDecimal;
 
 use std::str::FromStr;
@@ -114,33 +111,3 @@ fn calculate_reserves_and_usd(
 
     (token0_reserve, token1_reserve, usd_value)
 }
-
-/// Retrieves reserves for a list of pairs
-///
-/// # Arguments
-/// * `pairs` - Vector of pair addresses
-///
-/// # Returns
-/// Vector of `Reserves` containing reserve information for each pair
-///
-/// # Errors
-/// * If contract call to get reserves fails
-/// * If batch request initialization fails
-/// * If the RPC connection fails
-pub async fn fetch_reserves_by_range(
-    ctx: &AppContext,
-    pairs: Vec<Address>,
-) -> Result<Vec<Reserves>, eyre::Report> {
-    let uniswap_v2_batch_request =
-        UniswapQuery::new(UNISWAP_V2_BATCH_QUERY_ADDRESS, &ctx.base_provider);
-
-    Ok(uniswap_v2_batch_request
-        .getReservesByPairs(pairs)
-        .gas(3_000_000_000)
-        .call()
-        .await?
-        ._0
-        .into_iter()
-        .map(Into::into)
-        .collect())
-}
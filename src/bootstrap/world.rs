@@ -0,0 +1,509 @@
+//! Building the `World` from the pairs and tokens in the database.
+//!
+//! Valid pairs with both tokens and reserves are paged through in ID order, so hundreds of
+//! thousands of rows never sit in memory at once: each page is turned into pools and dropped.
+//! Rows that can't become a pool, or whose tokens the options exclude, are counted in a
+//! `LoadReport`.
+
+use std::collections::HashSet;
+use std::env;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use alloy::primitives::{Address, U256};
+use bigdecimal::BigDecimal;
+use eyre::Result;
+use log::{debug, info, warn};
+
+use crate::arb::pool::{Pool, PoolId};
+use crate::arb::token::{Token, TokenId};
+use crate::arb::world::World;
+use crate::repo::{PairRepo, PoolFilter, TokenRepo, TradablePair};
+use crate::utils::numeric::big_decimal_to_u256;
+
+/// Number of pairs loaded per query
+const BATCH_SIZE: i64 = 10_000;
+
+/// Number of skipped pairs kept as examples in a `LoadReport`
+const MAX_EXAMPLES: usize = 10;
+
+/// Token prices with a lower confidence don't get a USD rate
+const MIN_PRICE_CONFIDENCE: f64 = 0.1;
+
+/// Which pairs make it into the world
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadOptions {
+    /// Pairs the database leaves out
+    pub filter: PoolFilter,
+    /// Pairs with one of these tokens are left out
+    pub exclude_tokens: HashSet<Address>,
+    /// Number of pairs loaded per query
+    pub batch_size: i64,
+    /// Tokens whose price has a lower confidence get no USD rate
+    pub min_price_confidence: f64,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            filter: PoolFilter::default(),
+            exclude_tokens: HashSet::new(),
+            batch_size: BATCH_SIZE,
+            min_price_confidence: MIN_PRICE_CONFIDENCE,
+        }
+    }
+}
+
+impl LoadOptions {
+    /// Read the options from the environment, falling back to the defaults
+    ///
+    /// - `FLY_WORLD_MIN_USD`: Pairs worth less than this in USD are left out
+    /// - `FLY_WORLD_FACTORIES`: Comma separated factory IDs, pairs of other factories are left out
+    /// - `FLY_WORLD_EXCLUDE_TOKENS`: Comma separated token addresses, their pairs are left out
+    /// - `FLY_WORLD_MIN_PRICE_CONFIDENCE`: Tokens whose price has a lower confidence get no USD
+    ///   rate
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self {
+            filter: PoolFilter {
+                min_usd: env::var("FLY_WORLD_MIN_USD")
+                    .ok()
+                    .and_then(|value| BigDecimal::from_str(&value).ok()),
+                factories: env::var("FLY_WORLD_FACTORIES").ok().map(|value| {
+                    value
+                        .split(',')
+                        .filter_map(|id| id.trim().parse().ok())
+                        .collect()
+                }),
+            },
+            exclude_tokens: env::var("FLY_WORLD_EXCLUDE_TOKENS")
+                .map(|value| {
+                    value
+                        .split(',')
+                        .filter_map(|address| Address::from_str(address.trim()).ok())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            min_price_confidence: env::var("FLY_WORLD_MIN_PRICE_CONFIDENCE")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|value: &f64| (0.0..=1.0).contains(value))
+                .unwrap_or(MIN_PRICE_CONFIDENCE),
+            ..Self::default()
+        }
+    }
+}
+
+/// Why a pair was left out of the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Skip {
+    /// A reserve is zero, nothing can be swapped through the pair
+    EmptyReserve,
+    /// A reserve is negative, fractional or too large for a `U256`
+    InvalidReserve,
+    /// Both tokens are the same
+    SameToken,
+    /// One of the tokens is excluded by the options
+    ExcludedToken,
+}
+
+/// What a load went through
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// Rows returned by the database
+    pub rows: usize,
+    /// Pools loaded
+    pub loaded: usize,
+    /// Pairs with an empty reserve
+    pub empty_reserve: usize,
+    /// Pairs with a reserve that is not a `U256`
+    pub invalid_reserve: usize,
+    /// Pairs of a token with itself
+    pub same_token: usize,
+    /// Pairs of an excluded token
+    pub excluded_token: usize,
+    /// The first skipped pairs, for debugging
+    pub examples: Vec<(Address, Skip)>,
+}
+
+impl LoadReport {
+    /// Number of rows that did not become a pool
+    #[must_use]
+    pub const fn skipped(&self) -> usize {
+        self.empty_reserve + self.invalid_reserve + self.same_token + self.excluded_token
+    }
+
+    /// Count a skipped pair
+    fn skip(&mut self, pair: Address, skip: Skip) {
+        match skip {
+            Skip::EmptyReserve => self.empty_reserve += 1,
+            Skip::InvalidReserve => self.invalid_reserve += 1,
+            Skip::SameToken => self.same_token += 1,
+            Skip::ExcludedToken => self.excluded_token += 1,
+        }
+
+        if self.examples.len() < MAX_EXAMPLES {
+            self.examples.push((pair, skip));
+        }
+    }
+}
+
+impl Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} pairs loaded, {} skipped: {} with an empty reserve, {} with an invalid \
+             reserve, {} of a token with itself and {} of an excluded token",
+            self.loaded,
+            self.rows,
+            self.skipped(),
+            self.empty_reserve,
+            self.invalid_reserve,
+            self.same_token,
+            self.excluded_token
+        )
+    }
+}
+
+/// Load the world from the database, with the decimals and USD rates of its tokens
+///
+/// # Errors
+/// Returns an error if a database query fails
+pub async fn load(
+    repo: &(impl PairRepo + TokenRepo),
+    options: &LoadOptions,
+) -> Result<(World, LoadReport)> {
+    let (pools, report) = load_pools(repo, options).await?;
+    let mut world = World::new(&pools);
+
+    world.update_tokens(tokens(repo, options).await?);
+
+    info!(
+        "bootstrap::world: Loaded {} tokens, {} swaps and {} cycles, {report}",
+        world.token_vec.len(),
+        world.swap_vec.len(),
+        world.cycle_vec.len()
+    );
+
+    Ok((world, report))
+}
+
+/// Load the decimals and USD rates of tokens, to set them in the world
+///
+/// Prices flagged by `pricing::guard`, or with a confidence below the options' floor, are left
+/// out, so a manipulated pool can't inflate the USD profit of cycles through its tokens.
+///
+/// # Errors
+/// Returns an error if a database query fails
+pub async fn tokens(repo: &impl TokenRepo, options: &LoadOptions) -> Result<Vec<Token>> {
+    Ok(repo
+        .prices()
+        .await?
+        .into_iter()
+        .filter_map(|token| {
+            let id = TokenId::try_from(token.address.as_str()).ok()?;
+            let decimals = u8::try_from(token.decimals?).ok()?;
+            let usd = token.trusted_rate(options.min_price_confidence).cloned();
+            if usd.is_none() && token.exchange_rate.is_some() {
+                debug!(
+                    "bootstrap::world: Not trusting the {:?} price of {} with a confidence of {:?}",
+                    token.price_status, token.address, token.price_confidence
+                );
+            }
+            Some(Token::priced(id, decimals, usd))
+        })
+        .collect())
+}
+
+/// Load the pools of the world, walking the pairs in ID order
+///
+/// # Errors
+/// Returns an error if a database query fails
+pub async fn load_pools(
+    repo: &impl PairRepo,
+    options: &LoadOptions,
+) -> Result<(HashSet<Pool>, LoadReport)> {
+    let mut pools = HashSet::new();
+    let mut report = LoadReport::default();
+    let mut after = 0;
+
+    loop {
+        let pairs = repo
+            .tradable(&options.filter, after, options.batch_size)
+            .await?;
+        let Some(last) = pairs.last() else {
+            break;
+        };
+        after = last.id;
+        report.rows += pairs.len();
+
+        for pair in &pairs {
+            match pool(pair, &options.exclude_tokens) {
+                Ok(pool) => {
+                    pools.insert(pool);
+                }
+                Err(skip) => {
+                    if skip == Skip::InvalidReserve {
+                        warn!(
+                            "bootstrap::world: Pair {} has reserves {} and {} that are not U256",
+                            pair.address.value, pair.reserve0, pair.reserve1
+                        );
+                    }
+                    report.skip(pair.address.value, skip);
+                }
+            }
+        }
+        debug!(
+            "bootstrap::world: Loaded {} pools after {} rows",
+            pools.len(),
+            report.rows
+        );
+
+        if pairs.len() < usize::try_from(options.batch_size)? {
+            break;
+        }
+    }
+
+    report.loaded = pools.len();
+    if !report.examples.is_empty() {
+        debug!(
+            "bootstrap::world: First skipped pairs: {:?}",
+            report.examples
+        );
+    }
+
+    Ok((pools, report))
+}
+
+/// Turn a pair into a pool, or say why it is left out
+fn pool(pair: &TradablePair, exclude_tokens: &HashSet<Address>) -> Result<Pool, Skip> {
+    let (token0, token1) = (pair.token0.value, pair.token1.value);
+    if exclude_tokens.contains(&token0) || exclude_tokens.contains(&token1) {
+        return Err(Skip::ExcludedToken);
+    }
+    if token0 == token1 {
+        return Err(Skip::SameToken);
+    }
+
+    let (Some(reserve0), Some(reserve1)) = (
+        big_decimal_to_u256(&pair.reserve0),
+        big_decimal_to_u256(&pair.reserve1),
+    ) else {
+        return Err(Skip::InvalidReserve);
+    };
+    if reserve0 == U256::ZERO || reserve1 == U256::ZERO {
+        return Err(Skip::EmptyReserve);
+    }
+
+    Ok(Pool::new(
+        PoolId::from(pair.address.value),
+        TokenId::from(token0),
+        TokenId::from(token1),
+        Some(reserve0),
+        Some(reserve1),
+    ))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use crate::models::token::PriceStatus;
+    use crate::repo::memory::{MemoryRepo, PairRow, TokenRow};
+
+    use super::*;
+
+    /// A pair with tokens and reserves
+    fn row(byte: u8, tokens: (i32, i32), reserves: (i64, i64)) -> PairRow {
+        let mut pair = PairRow::new(Address::repeat_byte(byte));
+        pair.token0_id = Some(tokens.0);
+        pair.token1_id = Some(tokens.1);
+        pair.reserve0 = Some(BigDecimal::from(reserves.0));
+        pair.reserve1 = Some(BigDecimal::from(reserves.1));
+        pair
+    }
+
+    /// Insert a pair with tokens and reserves, returning its address
+    fn pair(repo: &MemoryRepo, byte: u8, tokens: (i32, i32), reserves: (i64, i64)) -> Address {
+        repo.insert_pair(row(byte, tokens, reserves));
+        Address::repeat_byte(byte)
+    }
+
+    #[tokio::test]
+    async fn test_load_pools_skips_and_reports_unusable_pairs() {
+        let repo = MemoryRepo::new();
+        let [a, b, c] = [0xa, 0xb, 0xc].map(|byte| {
+            let mut token = TokenRow::new(Address::repeat_byte(byte));
+            token.decimals = Some(18);
+            repo.insert_token(token)
+        });
+
+        let loaded = [
+            pair(&repo, 1, (a, b), (100, 200)),
+            pair(&repo, 2, (b, a), (300, 400)),
+            pair(&repo, 3, (a, b), (500, 600)),
+        ];
+        let empty = pair(&repo, 4, (a, b), (0, 100));
+        let negative = pair(&repo, 5, (a, b), (-1, 100));
+        pair(&repo, 6, (a, a), (100, 100));
+        pair(&repo, 7, (a, c), (100, 100));
+        let mut invalid = PairRow::new(Address::repeat_byte(8));
+        invalid.is_valid = false;
+        repo.insert_pair(invalid);
+
+        let options = LoadOptions {
+            exclude_tokens: HashSet::from([Address::repeat_byte(0xc)]),
+            // Pages of two walk every row
+            batch_size: 2,
+            ..LoadOptions::default()
+        };
+        let (pools, report) = load_pools(&repo, &options).await.unwrap();
+
+        assert_eq!(
+            pools
+                .iter()
+                .map(|pool| pool.id.address())
+                .collect::<HashSet<_>>(),
+            HashSet::from(loaded)
+        );
+        let pool = pools
+            .iter()
+            .find(|pool| pool.id.address() == loaded[1])
+            .unwrap();
+        assert_eq!(pool.token0, TokenId::from(Address::repeat_byte(0xb)));
+        assert_eq!(pool.reserve1, Some(U256::from(400)));
+
+        assert_eq!(report.rows, 7);
+        assert_eq!(report.loaded, 3);
+        assert_eq!(report.skipped(), 4);
+        assert_eq!(
+            (
+                report.empty_reserve,
+                report.invalid_reserve,
+                report.same_token,
+                report.excluded_token
+            ),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(report.examples[0], (empty, Skip::EmptyReserve));
+        assert_eq!(report.examples[1], (negative, Skip::InvalidReserve));
+    }
+
+    #[tokio::test]
+    async fn test_load_pools_applies_the_filter() {
+        let repo = MemoryRepo::new();
+        let [a, b] =
+            [0xa, 0xb].map(|byte| repo.insert_token(TokenRow::new(Address::repeat_byte(byte))));
+        for (byte, usd, factory_id) in [(1, 5000, 1), (2, 50, 1), (3, 5000, 2)] {
+            let mut pair = row(byte, (a, b), (100, 200));
+            pair.usd = Some(BigDecimal::from(usd));
+            pair.factory_id = Some(factory_id);
+            repo.insert_pair(pair);
+        }
+        // Not valued yet
+        pair(&repo, 4, (a, b), (100, 200));
+
+        let options = LoadOptions {
+            filter: PoolFilter {
+                min_usd: Some(BigDecimal::from(1000)),
+                factories: Some(vec![1]),
+            },
+            ..LoadOptions::default()
+        };
+        let (pools, report) = load_pools(&repo, &options).await.unwrap();
+
+        assert_eq!(
+            pools
+                .iter()
+                .map(|pool| pool.id.address())
+                .collect::<Vec<_>>(),
+            vec![Address::repeat_byte(1)]
+        );
+        assert_eq!(report.rows, 1);
+    }
+
+    #[tokio::test]
+    async fn test_load_prices_tokens() {
+        let repo = MemoryRepo::new();
+        let [a, b] = [0xa, 0xb].map(|byte| {
+            let mut token = TokenRow::new(Address::repeat_byte(byte));
+            token.decimals = Some(6);
+            token.exchange_rate = Some(BigDecimal::from(1));
+            repo.insert_token(token)
+        });
+        pair(&repo, 1, (a, b), (100, 200));
+        pair(&repo, 2, (b, a), (300, 400));
+
+        let (world, report) = load(&repo, &LoadOptions::default()).await.unwrap();
+
+        assert_eq!(report.loaded, 2);
+        assert_eq!(world.token_vec.len(), 2);
+        assert_eq!(world.swap_vec.len(), 4);
+        let token = world
+            .token(TokenId::from(Address::repeat_byte(0xa)))
+            .unwrap();
+        assert_eq!(token.decimals(), 6);
+        assert_eq!(token.usd(), Some(&BigDecimal::from(1)));
+    }
+
+    #[tokio::test]
+    async fn test_load_drops_untrusted_prices() {
+        let repo = MemoryRepo::new();
+        let [a, b, c] = [
+            (0xa, PriceStatus::Accepted, 0.9),
+            // Moved 60% through an old pool
+            (0xb, PriceStatus::Flagged, 0.45),
+            // Through a shallow route
+            (0xc, PriceStatus::Accepted, 0.05),
+        ]
+        .map(|(byte, status, confidence)| {
+            let mut token = TokenRow::new(Address::repeat_byte(byte));
+            token.decimals = Some(18);
+            token.exchange_rate = Some(BigDecimal::from(1000));
+            token.price_status = Some(status);
+            token.price_confidence = Some(confidence);
+            repo.insert_token(token)
+        });
+        pair(&repo, 1, (a, b), (100, 200));
+        pair(&repo, 2, (b, c), (300, 400));
+        pair(&repo, 3, (c, a), (500, 600));
+
+        let (world, _) = load(&repo, &LoadOptions::default()).await.unwrap();
+
+        let usd = |byte| {
+            world
+                .token(TokenId::from(Address::repeat_byte(byte)))
+                .unwrap()
+                .usd()
+                .cloned()
+        };
+        assert_eq!(usd(0xa), Some(BigDecimal::from(1000)));
+        assert_eq!(usd(0xb), None);
+        assert_eq!(usd(0xc), None);
+    }
+
+    #[tokio::test]
+    async fn test_tokens_follow_stored_prices() {
+        let repo = MemoryRepo::new();
+        let [a, b] = [0xa, 0xb].map(|byte| {
+            let mut token = TokenRow::new(Address::repeat_byte(byte));
+            token.decimals = Some(6);
+            repo.insert_token(token)
+        });
+        pair(&repo, 1, (a, b), (100, 200));
+        let options = LoadOptions::default();
+        let (mut world, _) = load(&repo, &options).await.unwrap();
+        let id = TokenId::from(Address::repeat_byte(0xa));
+        assert_eq!(world.token(id).unwrap().usd(), None);
+
+        repo.set_rate(
+            Address::repeat_byte(0xa),
+            &BigDecimal::from(2),
+            chrono::Utc::now().naive_utc(),
+        )
+        .await
+        .unwrap();
+        world.update_tokens(tokens(&repo, &options).await.unwrap());
+
+        assert_eq!(world.token(id).unwrap().usd(), Some(&BigDecimal::from(2)));
+    }
+}
@@ -1,7 +1,9 @@
+use std::future::Future;
 use std::sync::Arc;
 
 use eyre::Result;
 
+use crate::arb::detector;
 use crate::sync;
 use crate::utils::app_context::AppContext;
 
@@ -10,72 +12,46 @@ use crate::utils::app_context::AppContext;
 /// # Arguments
 ///
 /// * `ctx` - The application context
-///
 pub async fn start(ctx: AppContext) -> Result<()> {
     let ctx = Arc::new(ctx);
 
-    // Spawn events sync task
-    let ctx1 = Arc::clone(&ctx);
-    tokio::spawn(async move {
-        if let Err(e) = sync::events(&ctx1).await {
-            log::error!("{}", e);
-        }
+    spawn_worker("sync::events", Arc::clone(&ctx), |ctx| async move {
+        sync::events(&ctx).await
     });
-
-    // Spawn reserve sync task
-    let ctx2 = Arc::clone(&ctx);
-    tokio::spawn(async move {
-        if let Err(e) = sync::reserves(&ctx2).await {
-            log::error!("{}", e);
-        }
+    spawn_worker("sync::reserves", Arc::clone(&ctx), |ctx| async move {
+        sync::reserves(&ctx).await
     });
-
-    // Spawn pair tokens sync task
-    let ctx3 = Arc::clone(&ctx);
-    tokio::spawn(async move {
-        if let Err(e) = sync::pair_tokens(&ctx3).await {
-            log::error!("{}", e);
-        }
+    spawn_worker("sync::pair_tokens", Arc::clone(&ctx), |ctx| async move {
+        sync::pair_tokens(&ctx).await
     });
-
-    // Spawn factories sync task
-    let ctx4 = Arc::clone(&ctx);
-    tokio::spawn(async move {
-        if let Err(e) = sync::factories(&ctx4).await {
-            log::error!("{}", e);
-        }
+    spawn_worker("sync::factories", Arc::clone(&ctx), |ctx| async move {
+        sync::factories(&ctx).await
     });
-
-    // Spawn USD value sync task
-    let ctx5 = Arc::clone(&ctx);
-    tokio::spawn(async move {
-        if let Err(e) = sync::usd(&ctx5).await {
-            log::error!("{}", e);
-        }
+    spawn_worker("sync::usd", Arc::clone(&ctx), |ctx| async move {
+        sync::usd(&ctx).await
     });
-
-    // Spawn exchange rates sync task
-    let ctx6 = Arc::clone(&ctx);
-    tokio::spawn(async move {
-        if let Err(e) = sync::exchange_rates(&ctx6).await {
-            log::error!("{}", e);
-        }
+    spawn_worker("sync::exchange_rates", Arc::clone(&ctx), |ctx| async move {
+        sync::exchange_rates(&ctx).await
     });
-
-    // Spawn factory pairs sync task
-    let ctx7 = Arc::clone(&ctx);
-    tokio::spawn(async move {
-        if let Err(e) = sync::factory_pairs(&ctx7).await {
-            log::error!("{}", e);
-        }
+    spawn_worker("sync::factory_pairs", Arc::clone(&ctx), |ctx| async move {
+        sync::factory_pairs(&ctx).await
     });
-
-    // Spawn WETH price sync task
-    let ctx8 = Arc::clone(&ctx);
-    tokio::spawn(async move {
-        if let Err(e) = sync::weth(&ctx8).await {
-            log::error!("{}", e);
-        }
+    spawn_worker("sync::weth", Arc::clone(&ctx), |ctx| async move {
+        sync::weth(&ctx).await
+    });
+    spawn_worker("sync::balances", Arc::clone(&ctx), |ctx| async move {
+        sync::balances(&ctx).await
+    });
+    spawn_worker("sync::backfill", Arc::clone(&ctx), |ctx| async move {
+        sync::backfill(&ctx).await
+    });
+    spawn_worker(
+        "sync::reserve_history",
+        Arc::clone(&ctx),
+        |ctx| async move { sync::reserve_history(&ctx).await },
+    );
+    spawn_worker("arb::detector", Arc::clone(&ctx), |ctx| async move {
+        detector::detect(&ctx).await
     });
 
     // Wait for all spawned tasks to complete
@@ -83,3 +59,23 @@ pub async fn start(ctx: AppContext) -> Result<()> {
     log::info!("Received shutdown signal, waiting for tasks to complete...");
     Ok(())
 }
+
+/// Spawn a worker task on its own handle of the context, logging the error it stops with
+///
+/// # Arguments
+///
+/// * `name` - The name of the worker, for logs
+/// * `ctx` - The application context
+/// * `worker` - Runs the worker on the context
+fn spawn_worker<F, Fut>(name: &'static str, ctx: Arc<AppContext>, worker: F)
+where
+    F: FnOnce(Arc<AppContext>) -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let task = worker(ctx);
+    tokio::spawn(async move {
+        if let Err(e) = task.await {
+            log::error!("{}: {}", name, e);
+        }
+    });
+}
@@ -0,0 +1,469 @@
+//! A scriptable in-memory chain.
+//!
+//! Tests emit logs, mine blocks, reorganize the chain and register what contracts return, then
+//! hand the `FakeChain` to the code under test in place of the RPC provider.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use alloy::primitives::{self, keccak256, Address, Bytes, LogData, B256, U256};
+use alloy::rpc::types::{Filter, Log};
+use alloy::sol;
+use alloy::sol_types::{SolCall, SolValue};
+use eyre::{bail, Result};
+use futures::channel::mpsc;
+
+use super::{BlockHeader, Call, CallResult, Chain, LogStream};
+use crate::bootstrap::types::Reserves;
+
+sol! {
+    #[sol(rpc)]
+    "contracts/src/interfaces/IERC20.sol"
+}
+
+sol! {
+    #[sol(rpc)]
+    "contracts/src/interfaces/IUniswapV2Pair.sol"
+}
+
+/// Mutable state of a `FakeChain`
+#[derive(Debug, Default)]
+struct State {
+    /// Canonical blocks, indexed by number
+    blocks: Vec<BlockHeader>,
+    /// Logs of canonical blocks
+    logs: Vec<Log>,
+    /// Logs of the block being built
+    pending: Vec<primitives::Log>,
+    /// Return data by target and calldata
+    calls: HashMap<(Address, Bytes), Bytes>,
+    /// Reserves by pair
+    reserves: HashMap<Address, (U256, U256)>,
+    /// Live subscriptions
+    subscribers: Vec<(Filter, mpsc::UnboundedSender<Log>)>,
+    /// Subscriptions that stay open but no longer receive logs
+    stalled: Vec<mpsc::UnboundedSender<Log>>,
+    /// Most logs `eth_getLogs` returns before refusing the range
+    max_logs: Option<usize>,
+    /// How long `eth_blockNumber` and `eth_getLogs` take to answer
+    latency: Duration,
+    /// Bumped on every reorg so replacement blocks get new hashes
+    fork: u64,
+}
+
+impl State {
+    /// Send a log to every subscription it matches
+    fn notify(&mut self, log: &Log) {
+        self.subscribers.retain(|(filter, sender)| {
+            !matches(filter, log) || sender.unbounded_send(log.clone()).is_ok()
+        });
+    }
+}
+
+/// An in-memory chain for tests
+#[derive(Debug)]
+pub struct FakeChain {
+    /// The chain state
+    state: Mutex<State>,
+}
+
+impl Default for FakeChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeChain {
+    /// Create a chain with only a genesis block
+    #[must_use]
+    pub fn new() -> Self {
+        let genesis = BlockHeader {
+            number: 0,
+            hash: block_hash(0, 0),
+            parent_hash: B256::ZERO,
+        };
+
+        Self {
+            state: Mutex::new(State {
+                blocks: vec![genesis],
+                ..State::default()
+            }),
+        }
+    }
+
+    /// Lock the state, ignoring poisoning by a failed test
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// The latest block
+    #[must_use]
+    pub fn head(&self) -> BlockHeader {
+        let state = self.state();
+        state.blocks[state.blocks.len() - 1]
+    }
+
+    /// Emit a log in the block being built
+    pub fn emit(&self, address: Address, data: LogData) {
+        self.state().pending.push(primitives::Log { address, data });
+    }
+
+    /// Seal the block being built and deliver its logs to subscribers
+    pub fn mine(&self) -> BlockHeader {
+        let mut state = self.state();
+        let parent = state.blocks[state.blocks.len() - 1];
+        let number = parent.number + 1;
+        let header = BlockHeader {
+            number,
+            hash: block_hash(number, state.fork),
+            parent_hash: parent.hash,
+        };
+        state.blocks.push(header);
+
+        let pending = std::mem::take(&mut state.pending);
+        for (index, inner) in pending.into_iter().enumerate() {
+            let log = Log {
+                inner,
+                block_hash: Some(header.hash),
+                block_number: Some(number),
+                transaction_hash: Some(keccak256([header.hash.as_slice(), &[0]].concat())),
+                transaction_index: Some(0),
+                log_index: Some(index as u64),
+                ..Log::default()
+            };
+            state.notify(&log);
+            state.logs.push(log);
+        }
+
+        header
+    }
+
+    /// Mine empty blocks up to `number`
+    pub fn mine_to(&self, number: u64) {
+        while self.head().number < number {
+            self.mine();
+        }
+    }
+
+    /// Orphan the last `depth` blocks
+    ///
+    /// Subscribers receive the logs of orphaned blocks again with `removed: true`. The next
+    /// mined blocks get hashes different from the orphaned ones.
+    pub fn reorg(&self, depth: u64) {
+        let mut state = self.state();
+        let head = state.blocks[state.blocks.len() - 1].number;
+        let ancestor = head.saturating_sub(depth);
+
+        state
+            .blocks
+            .truncate(usize::try_from(ancestor).unwrap_or(usize::MAX) + 1);
+        state.fork += 1;
+
+        let (orphaned, kept) = std::mem::take(&mut state.logs)
+            .into_iter()
+            .partition::<Vec<_>, _>(|log| log.block_number.is_some_and(|n| n > ancestor));
+        state.logs = kept;
+
+        for mut log in orphaned {
+            log.removed = true;
+            state.notify(&log);
+        }
+    }
+
+    /// End every live subscription, like a closed WebSocket
+    pub fn close_subscriptions(&self) {
+        self.state().subscribers.clear();
+    }
+
+    /// Stop delivering logs to live subscriptions without ending them, like a dead WebSocket
+    pub fn stall_subscriptions(&self) {
+        let mut state = self.state();
+        let stalled = std::mem::take(&mut state.subscribers);
+        state
+            .stalled
+            .extend(stalled.into_iter().map(|(_, sender)| sender));
+    }
+
+    /// Make `eth_getLogs` refuse ranges with more than `max` logs, like hosted providers do
+    pub fn limit_logs(&self, max: usize) {
+        self.state().max_logs = Some(max);
+    }
+
+    /// Make `eth_blockNumber` and `eth_getLogs` take `latency` to answer, like a slow provider
+    pub fn delay_reads(&self, latency: Duration) {
+        self.state().latency = latency;
+    }
+
+    /// Wait for the latency of a read, without holding the state
+    async fn read_latency(&self) {
+        let latency = self.state().latency;
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+    }
+
+    /// Register what a contract returns for a call
+    pub fn respond<C: SolCall>(&self, target: Address, call: &C, return_data: impl Into<Bytes>) {
+        self.state()
+            .calls
+            .insert((target, Bytes::from(call.abi_encode())), return_data.into());
+    }
+
+    /// Deploy an ERC20 token with metadata
+    pub fn add_token(&self, token: Address, name: &str, symbol: &str, decimals: u8) {
+        self.respond(token, &IERC20::nameCall {}, name.to_string().abi_encode());
+        self.respond(
+            token,
+            &IERC20::symbolCall {},
+            symbol.to_string().abi_encode(),
+        );
+        self.respond(
+            token,
+            &IERC20::decimalsCall {},
+            U256::from(decimals).abi_encode(),
+        );
+    }
+
+    /// Set the balance of an owner
+    pub fn set_balance(&self, token: Address, owner: Address, balance: U256) {
+        self.respond(
+            token,
+            &IERC20::balanceOfCall { account: owner },
+            balance.abi_encode(),
+        );
+    }
+
+    /// Deploy a Uniswap V2 pair
+    pub fn add_pair(&self, pair: Address, factory: Address, token0: Address, token1: Address) {
+        self.respond(pair, &IUniswapV2Pair::token0Call {}, token0.abi_encode());
+        self.respond(pair, &IUniswapV2Pair::token1Call {}, token1.abi_encode());
+        self.respond(pair, &IUniswapV2Pair::factoryCall {}, factory.abi_encode());
+        self.set_reserves(pair, U256::ZERO, U256::ZERO);
+    }
+
+    /// Set the reserves `UniswapQuery` reports for a pair
+    pub fn set_reserves(&self, pair: Address, reserve0: U256, reserve1: U256) {
+        self.state().reserves.insert(pair, (reserve0, reserve1));
+    }
+}
+
+impl Chain for FakeChain {
+    async fn block_number(&self) -> Result<u64> {
+        self.read_latency().await;
+        Ok(self.head().number)
+    }
+
+    async fn header_by_number(&self, number: u64) -> Result<Option<BlockHeader>> {
+        let state = self.state();
+
+        Ok(usize::try_from(number)
+            .ok()
+            .and_then(|index| state.blocks.get(index))
+            .copied())
+    }
+
+    async fn header_by_hash(&self, hash: B256) -> Result<Option<BlockHeader>> {
+        Ok(self
+            .state()
+            .blocks
+            .iter()
+            .find(|header| header.hash == hash)
+            .copied())
+    }
+
+    async fn logs(&self, filter: &Filter) -> Result<Vec<Log>> {
+        self.read_latency().await;
+        let state = self.state();
+        let logs = state
+            .logs
+            .iter()
+            .filter(|log| matches(filter, log))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if let Some(max) = state.max_logs {
+            if logs.len() > max {
+                bail!("query returned more than {max} results");
+            }
+        }
+
+        Ok(logs)
+    }
+
+    async fn subscribe_logs(&self, filter: &Filter) -> Result<LogStream> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.state().subscribers.push((filter.clone(), sender));
+
+        Ok(Box::pin(receiver))
+    }
+
+    async fn aggregate3(&self, calls: Vec<Call>, _block: Option<u64>) -> Result<Vec<CallResult>> {
+        let state = self.state();
+
+        Ok(calls
+            .into_iter()
+            .map(
+                |call| match state.calls.get(&(call.target, call.call_data)) {
+                    Some(return_data) => CallResult {
+                        success: true,
+                        return_data: return_data.clone(),
+                    },
+                    None => CallResult {
+                        success: false,
+                        return_data: Bytes::new(),
+                    },
+                },
+            )
+            .collect())
+    }
+
+    async fn reserves(&self, pairs: Vec<Address>, _block: Option<u64>) -> Result<Vec<Reserves>> {
+        let state = self.state();
+
+        Ok(pairs
+            .iter()
+            .map(|pair| {
+                let (reserve0, reserve1) = state.reserves.get(pair).copied().unwrap_or_default();
+                Reserves {
+                    reserve0,
+                    reserve1,
+                    block_timestamp_last: U256::ZERO,
+                }
+            })
+            .collect())
+    }
+}
+
+/// The hash of a fake block
+fn block_hash(number: u64, fork: u64) -> B256 {
+    keccak256([number.to_be_bytes(), fork.to_be_bytes()].concat())
+}
+
+/// Whether a log matches the addresses, topics and block range of a filter
+fn matches(filter: &Filter, log: &Log) -> bool {
+    let number = log.block_number.unwrap_or_default();
+    let topics = log.topics();
+
+    filter.get_from_block().is_none_or(|from| number >= from)
+        && filter.get_to_block().is_none_or(|to| number <= to)
+        && filter.address.matches(&log.address())
+        && filter.topics.iter().enumerate().all(|(index, topic)| {
+            topic.is_empty() || topics.get(index).is_some_and(|value| topic.matches(value))
+        })
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn address(byte: u8) -> Address {
+        Address::repeat_byte(byte)
+    }
+
+    fn data(topic: u8) -> LogData {
+        LogData::new_unchecked(vec![B256::repeat_byte(topic)], Bytes::new())
+    }
+
+    #[tokio::test]
+    async fn test_mine_and_get_logs() {
+        let chain = FakeChain::new();
+        chain.emit(address(1), data(1));
+        chain.emit(address(2), data(2));
+        let block = chain.mine();
+
+        assert_eq!(block.number, 1);
+        assert_eq!(
+            block.parent_hash,
+            chain.header_by_number(0).await.unwrap().unwrap().hash
+        );
+
+        let logs = chain
+            .logs(&Filter::new().address(address(2)))
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].block_number, Some(1));
+        assert_eq!(logs[0].log_index, Some(1));
+
+        let logs = chain
+            .logs(
+                &Filter::new()
+                    .event_signature(B256::repeat_byte(1))
+                    .from_block(2),
+            )
+            .await
+            .unwrap();
+        assert!(logs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reorg_notifies_removed_logs() {
+        let chain = FakeChain::new();
+        let mut stream = chain.subscribe_logs(&Filter::new()).await.unwrap();
+
+        chain.emit(address(1), data(1));
+        let orphaned = chain.mine();
+        chain.reorg(1);
+        let replacement = chain.mine();
+
+        assert_eq!(orphaned.number, replacement.number);
+        assert_ne!(orphaned.hash, replacement.hash);
+        assert!(chain.header_by_hash(orphaned.hash).await.unwrap().is_none());
+
+        assert!(!stream.next().await.unwrap().removed);
+        assert!(stream.next().await.unwrap().removed);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate3() {
+        let chain = FakeChain::new();
+        chain.add_token(address(1), "Wrapped Ether", "WETH", 18);
+
+        let results = chain
+            .aggregate3(
+                vec![
+                    Call::new(address(1), IERC20::symbolCall {}.abi_encode()),
+                    Call::new(address(2), IERC20::symbolCall {}.abi_encode()),
+                ],
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(results[0].success);
+        assert_eq!(
+            String::abi_decode(&results[0].return_data, true).unwrap(),
+            "WETH"
+        );
+        assert!(!results[1].success);
+    }
+
+    #[tokio::test]
+    async fn test_pair() {
+        let chain = FakeChain::new();
+        let pair = address(3);
+        chain.add_pair(pair, address(4), address(1), address(2));
+        chain.set_reserves(pair, U256::from(10), U256::from(20));
+
+        let results = chain
+            .aggregate3(
+                vec![Call::new(pair, IUniswapV2Pair::token1Call {}.abi_encode())],
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            Address::abi_decode(&results[0].return_data, true).unwrap(),
+            address(2)
+        );
+
+        let reserves = chain.reserves(vec![pair, address(5)], None).await.unwrap();
+        assert_eq!(reserves[0].reserve1, U256::from(20));
+        assert_eq!(reserves[1].reserve0, U256::ZERO);
+    }
+}
@@ -0,0 +1,110 @@
+//! The chain calls sync workers make, behind a trait.
+//!
+//! Workers used to call the alloy provider in `AppContext` directly, so none of them could run
+//! without a live node. `Chain` covers the calls they need: block headers, `eth_getLogs`, log
+//! subscriptions, Multicall3 `aggregate3` and `UniswapQuery` reserves. The provider implements
+//! it for production, and `fake::FakeChain` is a scriptable in-memory chain for tests.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use alloy::primitives::{Address, Bytes, B256};
+use alloy::rpc::types::{Filter, Log};
+use eyre::Result;
+use futures::Stream;
+
+pub use subscription::Subscription;
+
+use crate::bootstrap::types::Reserves;
+
+/// Fake chain for tests
+#[cfg(test)]
+pub mod fake;
+/// Chain backed by the RPC provider
+pub mod rpc;
+/// Log subscriptions that reconnect and fill gaps
+pub mod subscription;
+
+/// Logs received from a subscription
+pub type LogStream = Pin<Box<dyn Stream<Item = Log> + Send>>;
+
+/// The parts of a block header workers care about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    /// The block number
+    pub number: u64,
+    /// The block hash
+    pub hash: B256,
+    /// The parent block hash
+    pub parent_hash: B256,
+}
+
+/// A call batched through Multicall3 `aggregate3`, allowed to fail
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    /// The contract to call
+    pub target: Address,
+    /// ABI-encoded calldata
+    pub call_data: Bytes,
+}
+
+impl Call {
+    /// Create a call
+    #[must_use]
+    pub fn new(target: Address, call_data: impl Into<Bytes>) -> Self {
+        Self {
+            target,
+            call_data: call_data.into(),
+        }
+    }
+}
+
+/// The outcome of a `Call`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallResult {
+    /// Whether the call succeeded
+    pub success: bool,
+    /// ABI-encoded return data, or revert data if the call failed
+    pub return_data: Bytes,
+}
+
+/// Chain calls made by sync workers
+pub trait Chain: Send + Sync {
+    /// The number of the latest block
+    fn block_number(&self) -> impl Future<Output = Result<u64>> + Send;
+
+    /// The header of a canonical block by number
+    fn header_by_number(
+        &self,
+        number: u64,
+    ) -> impl Future<Output = Result<Option<BlockHeader>>> + Send;
+
+    /// The header of a block by hash, `None` if the chain doesn't know it (anymore)
+    fn header_by_hash(
+        &self,
+        hash: B256,
+    ) -> impl Future<Output = Result<Option<BlockHeader>>> + Send;
+
+    /// Logs matching a filter (`eth_getLogs`)
+    fn logs(&self, filter: &Filter) -> impl Future<Output = Result<Vec<Log>>> + Send;
+
+    /// Subscribe to logs matching a filter from now on
+    fn subscribe_logs(&self, filter: &Filter) -> impl Future<Output = Result<LogStream>> + Send;
+
+    /// Run calls through Multicall3 `aggregate3`, at `block` or the latest block
+    ///
+    /// Results are in the order of the calls.
+    fn aggregate3(
+        &self,
+        calls: Vec<Call>,
+        block: Option<u64>,
+    ) -> impl Future<Output = Result<Vec<CallResult>>> + Send;
+
+    /// Reserves of pairs through `UniswapQuery.getReservesByPairs`, at `block` or the latest
+    /// block, in the order of the pairs
+    fn reserves(
+        &self,
+        pairs: Vec<Address>,
+        block: Option<u64>,
+    ) -> impl Future<Output = Result<Vec<Reserves>>> + Send;
+}
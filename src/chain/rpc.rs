@@ -0,0 +1,105 @@
+use alloy::eips::{BlockId, BlockNumberOrTag};
+use alloy::primitives::{Address, B256};
+use alloy::providers::{Provider, MULTICALL3_ADDRESS};
+use alloy::rpc::types::{Block, BlockTransactionsKind, Filter, Log};
+use alloy::sol;
+use eyre::Result;
+
+use super::{BlockHeader, Call, CallResult, Chain, LogStream};
+use crate::bootstrap::types::Reserves;
+use crate::bootstrap::UniswapQuery;
+use crate::utils::app_context::EthereumProvider;
+use crate::utils::constants::UNISWAP_V2_BATCH_QUERY_ADDRESS;
+
+sol! {
+    #[sol(rpc)]
+    "contracts/src/interfaces/IMulticall3.sol"
+}
+
+/// Gas limit of `UniswapQuery` calls, which read thousands of pairs at once
+const UNISWAP_QUERY_GAS: u64 = 3_000_000_000;
+
+impl From<&Block> for BlockHeader {
+    fn from(block: &Block) -> Self {
+        Self {
+            number: block.header.number,
+            hash: block.header.hash,
+            parent_hash: block.header.parent_hash,
+        }
+    }
+}
+
+impl Chain for EthereumProvider {
+    async fn block_number(&self) -> Result<u64> {
+        Ok(self.get_block_number().await?)
+    }
+
+    async fn header_by_number(&self, number: u64) -> Result<Option<BlockHeader>> {
+        let block = self
+            .get_block_by_number(
+                BlockNumberOrTag::Number(number),
+                BlockTransactionsKind::Hashes,
+            )
+            .await?;
+
+        Ok(block.as_ref().map(BlockHeader::from))
+    }
+
+    async fn header_by_hash(&self, hash: B256) -> Result<Option<BlockHeader>> {
+        let block = self
+            .get_block_by_hash(hash, BlockTransactionsKind::Hashes)
+            .await?;
+
+        Ok(block.as_ref().map(BlockHeader::from))
+    }
+
+    async fn logs(&self, filter: &Filter) -> Result<Vec<Log>> {
+        Ok(self.get_logs(filter).await?)
+    }
+
+    async fn subscribe_logs(&self, filter: &Filter) -> Result<LogStream> {
+        let subscription = Provider::subscribe_logs(self, filter).await?;
+
+        Ok(Box::pin(subscription.into_stream()))
+    }
+
+    async fn aggregate3(&self, calls: Vec<Call>, block: Option<u64>) -> Result<Vec<CallResult>> {
+        let multicall = IMulticall3::new(MULTICALL3_ADDRESS, self);
+        let calls = calls
+            .into_iter()
+            .map(|call| IMulticall3::Call3 {
+                target: call.target,
+                allowFailure: true,
+                callData: call.call_data,
+            })
+            .collect();
+
+        let block = block.map_or(BlockId::latest(), BlockId::number);
+        let results = multicall.aggregate3(calls).block(block).call().await?;
+
+        Ok(results
+            .returnData
+            .into_iter()
+            .map(|result| CallResult {
+                success: result.success,
+                return_data: result.returnData,
+            })
+            .collect())
+    }
+
+    async fn reserves(&self, pairs: Vec<Address>, block: Option<u64>) -> Result<Vec<Reserves>> {
+        let query = UniswapQuery::new(UNISWAP_V2_BATCH_QUERY_ADDRESS, self);
+
+        let block = block.map_or(BlockId::latest(), BlockId::number);
+        Ok(query
+            .getReservesByPairs(pairs)
+            .gas(UNISWAP_QUERY_GAS)
+            .block(block)
+            .call()
+            .await?
+            ._0
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+}
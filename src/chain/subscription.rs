@@ -0,0 +1,560 @@
+//! Log subscriptions that survive dropped and stalled WebSocket streams.
+//!
+//! A raw subscription ends when the WebSocket closes, and can also go quiet without closing
+//! while the node keeps producing blocks. `Subscription` never ends: it reconnects with backoff
+//! when the stream ends, checks the head whenever the stream has been quiet for
+//! `STALL_TIMEOUT`, and replays the logs of the blocks it may have missed with `eth_getLogs`.
+//! Replayed logs that the stream delivers again are dropped.
+
+use std::collections::{HashMap, VecDeque};
+
+use alloy::primitives::B256;
+use alloy::rpc::types::{Filter, Log};
+use futures::future::BoxFuture;
+use futures::{FutureExt, StreamExt};
+use log::{info, warn};
+use tokio::time::{sleep_until, timeout_at, Duration, Instant};
+
+use super::{Chain, LogStream};
+
+/// How long a stream may stay quiet before the head is checked
+const STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Delay before the second reconnect attempt in a row
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest delay between two reconnect attempts
+const MAX_BACKOFF: Duration = Duration::from_mins(1);
+
+/// Most blocks replayed after a disconnect, older gaps are left to `sync::backfill`
+pub(crate) const MAX_GAP_BLOCKS: u64 = 1_000;
+
+/// Blocks whose delivered logs are remembered, to drop logs that are both replayed and streamed
+const DEDUP_BLOCKS: u64 = 32;
+
+/// Delay between reconnect attempts, doubling after each failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Backoff {
+    /// Delay before the next attempt
+    delay: Duration,
+    /// Delay after the first failure
+    min: Duration,
+    /// Largest delay
+    max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            delay: Duration::ZERO,
+            min: MIN_BACKOFF,
+            max: MAX_BACKOFF,
+        }
+    }
+}
+
+impl Backoff {
+    /// The delay before the next attempt, doubling the one after it
+    fn next(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (self.delay * 2).clamp(self.min, self.max);
+        delay
+    }
+
+    /// Attempt immediately next time
+    fn reset(&mut self) {
+        self.delay = Duration::ZERO;
+    }
+}
+
+/// The outcome of a reconnect or of a head check, applied once it completed
+enum Step {
+    /// Subscribed, with the head at that time and the logs missed up to it
+    Connected {
+        /// The new stream
+        stream: LogStream,
+        /// The head right after subscribing
+        head: u64,
+        /// Logs up to the head, `None` if they could not be fetched
+        missed: Option<Vec<Log>>,
+    },
+    /// The head moved while the stream stayed quiet
+    Checked {
+        /// The new head
+        head: u64,
+        /// Logs up to the head, `None` if they could not be fetched
+        missed: Option<Vec<Log>>,
+    },
+    /// The head did not move while the stream stayed quiet
+    Stalled,
+    /// Subscribing or reading the head failed
+    Failed,
+}
+
+/// A log subscription that reconnects and fills gaps on its own
+///
+/// Logs are delivered in stream order. Replayed logs come before the logs the new stream
+/// delivers, and removed logs of a reorg are passed through as is.
+pub struct Subscription<'a, C> {
+    /// The chain to subscribe to
+    chain: &'a C,
+    /// The name of the subscription, for logs
+    name: &'static str,
+    /// The filters of the subscription, without block range
+    filters: Vec<Filter>,
+    /// The live stream, `None` until (re)connected
+    stream: Option<LogStream>,
+    /// A reconnect or head check in progress, kept when `next` is dropped so it resumes
+    task: Option<BoxFuture<'a, Step>>,
+    /// Replayed logs waiting to be delivered
+    pending: VecDeque<Log>,
+    /// Block number of each delivered log of the last `DEDUP_BLOCKS` blocks
+    delivered: HashMap<(B256, u64), u64>,
+    /// Last block whose logs are all delivered
+    last_block: Option<u64>,
+    /// Head at the last check
+    head: Option<u64>,
+    /// Delay between reconnect attempts
+    backoff: Backoff,
+    /// Earliest time of the next connection attempt
+    retry_at: Instant,
+    /// When the stream last delivered a log or was last checked
+    quiet_since: Instant,
+    /// How long the stream may stay quiet before the head is checked
+    stall_timeout: Duration,
+}
+
+impl<'a, C: Chain> Subscription<'a, C> {
+    /// Subscribe to logs matching any of `filters`, starting at the latest block
+    #[must_use]
+    pub fn new(chain: &'a C, name: &'static str, filters: Vec<Filter>) -> Self {
+        Self {
+            chain,
+            name,
+            filters,
+            stream: None,
+            task: None,
+            pending: VecDeque::new(),
+            delivered: HashMap::new(),
+            last_block: None,
+            head: None,
+            backoff: Backoff::default(),
+            retry_at: Instant::now(),
+            quiet_since: Instant::now(),
+            stall_timeout: STALL_TIMEOUT,
+        }
+    }
+
+    /// Check the head after the stream stayed quiet for `stall_timeout`
+    #[must_use]
+    #[allow(dead_code)]
+    pub const fn with_stall_timeout(mut self, stall_timeout: Duration) -> Self {
+        self.stall_timeout = stall_timeout;
+        self
+    }
+
+    /// Replay the logs from `block` on the first connection instead of starting at the head
+    #[must_use]
+    pub const fn since(mut self, block: u64) -> Self {
+        self.last_block = Some(block);
+        self
+    }
+
+    /// The next log, waiting as long as it takes
+    ///
+    /// Cancel safe: a reconnect or head check in progress is kept in the subscription and its
+    /// result is only applied once it completed, so dropping the future neither loses logs nor
+    /// restarts timers, and it can be polled under a timeout.
+    pub async fn next(&mut self) -> Log {
+        loop {
+            if let Some(task) = self.task.as_mut() {
+                let step = task.await;
+                self.task = None;
+                self.apply(step);
+                continue;
+            }
+
+            if let Some(log) = self.pending.pop_front() {
+                if self.is_new(&log) {
+                    return log;
+                }
+                continue;
+            }
+
+            let Some(stream) = self.stream.as_mut() else {
+                self.task = Some(self.connect());
+                continue;
+            };
+
+            let deadline = self.quiet_since + self.stall_timeout;
+            match timeout_at(deadline, stream.next()).await {
+                Ok(Some(log)) => {
+                    if self.is_new(&log) {
+                        self.backoff.reset();
+                        self.quiet_since = Instant::now();
+                        return log;
+                    }
+                }
+                Ok(None) => {
+                    warn!("chain::subscription: {} stream ended", self.name);
+                    self.stream = None;
+                }
+                Err(_) => self.task = Some(self.check()),
+            }
+        }
+    }
+
+    /// Subscribe after the backoff delay, then fetch the logs missed while disconnected
+    fn connect(&mut self) -> BoxFuture<'a, Step> {
+        let retry_at = self.retry_at;
+        self.retry_at = retry_at.max(Instant::now()) + self.backoff.next();
+
+        let (chain, name, filters, last_block) =
+            (self.chain, self.name, self.filters.clone(), self.last_block);
+
+        async move {
+            sleep_until(retry_at).await;
+
+            let mut streams = Vec::with_capacity(filters.len());
+            for filter in &filters {
+                match chain.subscribe_logs(filter).await {
+                    Ok(stream) => streams.push(stream),
+                    Err(e) => {
+                        warn!("chain::subscription: Failed to subscribe to {name}: {e}");
+                        return Step::Failed;
+                    }
+                }
+            }
+
+            // Subscribed before the head is read, so no block falls between replay and stream
+            let head = match chain.block_number().await {
+                Ok(head) => head,
+                Err(e) => {
+                    warn!("chain::subscription: Failed to get head of {name}: {e}");
+                    return Step::Failed;
+                }
+            };
+
+            Step::Connected {
+                stream: Box::pin(futures::stream::select_all(streams)),
+                head,
+                missed: replay(chain, name, &filters, last_block, head).await,
+            }
+        }
+        .boxed()
+    }
+
+    /// Look for a stalled stream after it stayed quiet
+    fn check(&self) -> BoxFuture<'a, Step> {
+        let (chain, name, filters, last_block, previous) = (
+            self.chain,
+            self.name,
+            self.filters.clone(),
+            self.last_block,
+            self.head,
+        );
+
+        async move {
+            let head = match chain.block_number().await {
+                Ok(head) => head,
+                Err(e) => {
+                    warn!("chain::subscription: Failed to get head of {name}: {e}");
+                    return Step::Failed;
+                }
+            };
+
+            if previous.is_some_and(|previous| head <= previous) {
+                return Step::Stalled;
+            }
+
+            Step::Checked {
+                head,
+                missed: replay(chain, name, &filters, last_block, head).await,
+            }
+        }
+        .boxed()
+    }
+
+    /// Apply a completed reconnect or head check
+    ///
+    /// After a check, the stream is dropped, to be reconnected, if the head did not move since
+    /// the last check or if `eth_getLogs` found logs the stream did not deliver.
+    fn apply(&mut self, step: Step) {
+        match step {
+            Step::Connected {
+                stream,
+                head,
+                missed,
+            } => {
+                self.stream = Some(stream);
+                self.head = Some(head);
+                self.quiet_since = Instant::now();
+                let missed = self.queue(head, missed);
+                info!(
+                    "chain::subscription: Subscribed to {} at block {head}, replaying {missed} logs",
+                    self.name
+                );
+            }
+            Step::Checked { head, missed } => {
+                self.head = Some(head);
+                self.quiet_since = Instant::now();
+                match self.queue(head, missed) {
+                    0 => self.backoff.reset(),
+                    missed => {
+                        warn!(
+                            "chain::subscription: {} stream missed {missed} logs, reconnecting",
+                            self.name
+                        );
+                        self.stream = None;
+                    }
+                }
+            }
+            Step::Stalled => {
+                warn!(
+                    "chain::subscription: No new heads for {:?}, reconnecting {}",
+                    self.stall_timeout, self.name
+                );
+                self.quiet_since = Instant::now();
+                self.stream = None;
+            }
+            Step::Failed => self.stream = None,
+        }
+    }
+
+    /// Queue the undelivered logs from the last delivered block up to `head`
+    ///
+    /// Returns the number of queued logs. On the first connection there is nothing to replay
+    /// and the subscription starts at `head`.
+    fn queue(&mut self, head: u64, missed: Option<Vec<Log>>) -> usize {
+        if self.last_block.is_none() {
+            self.last_block = Some(head);
+            return 0;
+        }
+        let Some(mut logs) = missed else {
+            return 0;
+        };
+
+        logs.retain(|log| key(log).is_none_or(|key| !self.delivered.contains_key(&key)));
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+        logs.dedup_by_key(|log| key(log));
+
+        let missed = logs.len();
+        self.pending.extend(logs);
+        self.advance(head);
+
+        missed
+    }
+
+    /// Whether a log was not delivered yet, remembering it if so
+    fn is_new(&mut self, log: &Log) -> bool {
+        if log.removed {
+            return true;
+        }
+
+        let (Some(key), Some(number)) = (key(log), log.block_number) else {
+            return true;
+        };
+        if self.delivered.insert(key, number).is_some() {
+            return false;
+        }
+
+        self.advance(number);
+        true
+    }
+
+    /// Move the last delivered block forward, forgetting logs of old blocks
+    fn advance(&mut self, number: u64) {
+        if self
+            .last_block
+            .is_some_and(|last_block| number <= last_block)
+        {
+            return;
+        }
+
+        self.last_block = Some(number);
+        let oldest = number.saturating_sub(DEDUP_BLOCKS);
+        self.delivered.retain(|_, block| *block >= oldest);
+    }
+}
+
+/// The logs of the blocks from `last_block` up to `head`
+///
+/// Returns `None` if `eth_getLogs` failed. Nothing is replayed on the first connection, when
+/// there is no last block.
+async fn replay<C: Chain>(
+    chain: &C,
+    name: &str,
+    filters: &[Filter],
+    last_block: Option<u64>,
+    head: u64,
+) -> Option<Vec<Log>> {
+    let Some(last_block) = last_block.filter(|last_block| *last_block < head) else {
+        return Some(Vec::new());
+    };
+
+    // The last block may have been cut off in the middle
+    let from = last_block.max(head.saturating_sub(MAX_GAP_BLOCKS));
+    if from > last_block {
+        warn!("chain::subscription: Skipping blocks {last_block}..{from} of {name}");
+    }
+
+    let mut logs = Vec::new();
+    for filter in filters {
+        let filter = filter.clone().from_block(from).to_block(head);
+        match chain.logs(&filter).await {
+            Ok(page) => logs.extend(page),
+            Err(e) => {
+                warn!(
+                    "chain::subscription: Failed to replay blocks {from}..={head} of {name}: {e}"
+                );
+                return None;
+            }
+        }
+    }
+
+    Some(logs)
+}
+
+/// The identity of a mined log
+fn key(log: &Log) -> Option<(B256, u64)> {
+    Some((log.block_hash?, log.log_index?))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::chain::fake::FakeChain;
+    use alloy::primitives::{Address, Bytes, LogData};
+    use tokio::time::timeout;
+
+    const STALL_TIMEOUT: Duration = Duration::from_millis(50);
+
+    fn subscribe(chain: &FakeChain, filters: Vec<Filter>) -> Subscription<'_, FakeChain> {
+        Subscription::new(chain, "test", filters).with_stall_timeout(STALL_TIMEOUT)
+    }
+
+    /// Poll the subscription for `duration`, asserting it delivers nothing
+    async fn assert_quiet(subscription: &mut Subscription<'_, FakeChain>, duration: Duration) {
+        assert!(timeout(duration, subscription.next()).await.is_err());
+    }
+
+    fn emit(chain: &FakeChain, byte: u8) {
+        let data = LogData::new_unchecked(vec![B256::repeat_byte(1)], Bytes::from(vec![byte]));
+        chain.emit(Address::repeat_byte(1), data);
+        chain.mine();
+    }
+
+    fn byte(log: &Log) -> u8 {
+        log.data().data[0]
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff {
+            delay: Duration::ZERO,
+            min: Duration::from_secs(1),
+            max: Duration::from_secs(3),
+        };
+
+        assert_eq!(backoff.next(), Duration::ZERO);
+        assert_eq!(backoff.next(), Duration::from_secs(1));
+        assert_eq!(backoff.next(), Duration::from_secs(2));
+        assert_eq!(backoff.next(), Duration::from_secs(3));
+        assert_eq!(backoff.next(), Duration::from_secs(3));
+        backoff.reset();
+        assert_eq!(backoff.next(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_when_stream_ends() {
+        let chain = FakeChain::new();
+        let mut subscription = subscribe(&chain, vec![Filter::new()]);
+
+        // Connects on the first poll
+        assert_quiet(&mut subscription, Duration::from_millis(5)).await;
+        emit(&chain, 1);
+        assert_eq!(byte(&subscription.next().await), 1);
+
+        chain.close_subscriptions();
+        emit(&chain, 2);
+        emit(&chain, 3);
+
+        assert_eq!(byte(&subscription.next().await), 2);
+        assert_eq!(byte(&subscription.next().await), 3);
+
+        emit(&chain, 4);
+        assert_eq!(byte(&subscription.next().await), 4);
+    }
+
+    #[tokio::test]
+    async fn test_replays_logs_of_stalled_stream() {
+        let chain = FakeChain::new();
+        let mut subscription = subscribe(&chain, vec![Filter::new()]);
+
+        assert_quiet(&mut subscription, Duration::from_millis(5)).await;
+        chain.stall_subscriptions();
+        emit(&chain, 1);
+
+        assert_eq!(byte(&subscription.next().await), 1);
+
+        // The replacement stream works, and the replayed log is not delivered twice
+        emit(&chain, 2);
+        assert_eq!(byte(&subscription.next().await), 2);
+        assert_quiet(&mut subscription, STALL_TIMEOUT * 3).await;
+    }
+
+    #[tokio::test]
+    async fn test_since_replays_from_block() {
+        let chain = FakeChain::new();
+        emit(&chain, 1);
+        let block = chain.head().number;
+        emit(&chain, 2);
+
+        let mut subscription = subscribe(&chain, vec![Filter::new()]).since(block);
+
+        // The log of `block` itself is replayed too
+        assert_eq!(byte(&subscription.next().await), 1);
+        assert_eq!(byte(&subscription.next().await), 2);
+    }
+
+    #[tokio::test]
+    async fn test_merges_filters_without_duplicates() {
+        let chain = FakeChain::new();
+        let filters = vec![
+            Filter::new().address(Address::repeat_byte(1)),
+            Filter::new().event_signature(B256::repeat_byte(1)),
+        ];
+        let mut subscription = subscribe(&chain, filters);
+
+        assert_quiet(&mut subscription, Duration::from_millis(5)).await;
+        emit(&chain, 1);
+
+        assert_eq!(byte(&subscription.next().await), 1);
+        assert_quiet(&mut subscription, STALL_TIMEOUT * 3).await;
+    }
+
+    #[tokio::test]
+    async fn test_resumes_reconnect_after_dropped_next() {
+        let chain = FakeChain::new();
+        let mut subscription = subscribe(&chain, vec![Filter::new()]);
+
+        assert_quiet(&mut subscription, Duration::from_millis(5)).await;
+        emit(&chain, 1);
+        assert_eq!(byte(&subscription.next().await), 1);
+
+        chain.close_subscriptions();
+        emit(&chain, 2);
+        chain.delay_reads(Duration::from_millis(20));
+
+        // Every call is dropped in the middle of the reconnect
+        for _ in 0..3 {
+            assert_quiet(&mut subscription, Duration::from_millis(5)).await;
+        }
+        emit(&chain, 3);
+
+        // The log missed while disconnected is still replayed
+        assert_eq!(byte(&subscription.next().await), 2);
+        assert_eq!(byte(&subscription.next().await), 3);
+    }
+}
@@ -0,0 +1,176 @@
+//! Turns a `CycleQuote` into a `FlashExecutor.run` call.
+//!
+//! `SimpleExecutor` pays the first pair from its own balance, so it can only trade cycles whose
+//! start token it holds. `FlashExecutor` instead borrows the output of the first pair through a
+//! Uniswap V2 flash swap, trades it through the remaining pairs inside `uniswapV2Call` and
+//! repays the first pair with the start token received from the last one.
+
+use alloy::primitives::{Address, Bytes, U256};
+use alloy::sol;
+use alloy::sol_types::{SolCall, SolValue};
+use eyre::{bail, Result};
+
+use super::plan::{ExecutionPlan, ISimpleExecutor};
+use crate::arb::cycle_quote::CycleQuote;
+use crate::arb::pool::PoolId;
+
+sol! {
+    #[sol(rpc, all_derives)]
+    "contracts/src/interfaces/IFlashExecutor.sol"
+}
+
+/// A `FlashExecutor.run` call prepared from a `CycleQuote`
+#[derive(Debug, Clone)]
+pub struct FlashPlan {
+    /// Pools of the cycle in execution order
+    pools: Vec<PoolId>,
+    /// The first pair and the amount borrowed from it
+    borrow: IFlashExecutor::Pair,
+    /// The payload `uniswapV2Call` receives
+    callback: IFlashExecutor::Callback,
+}
+
+impl FlashPlan {
+    /// Create a plan from a quote.
+    /// The pairs are the ones of `ExecutionPlan::from_quote`, the first one is borrowed from.
+    ///
+    /// # Arguments
+    ///
+    /// * `quote` - The quote to execute
+    /// * `minimum_profit` - The profit (in the start token) the contract must realize
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the quote has more swaps than the executor supports or
+    /// if the quoted amount in is zero
+    pub fn from_quote(quote: &CycleQuote, minimum_profit: U256) -> Result<Self> {
+        let plan = ExecutionPlan::from_quote(quote, minimum_profit)?;
+        let swap_quotes = quote.swap_quotes();
+
+        let (Some(first), Some((borrow, rest))) = (swap_quotes.first(), plan.pairs().split_first())
+        else {
+            bail!("Cycle quote has no swaps");
+        };
+
+        let callback = IFlashExecutor::Callback {
+            tokenIn: plan.token_in(),
+            borrowedToken: first.swap().token_out().0,
+            repayAmount: plan.amount_in(),
+            minimumProfit: plan.minimum_profit(),
+            pairs: rest.iter().map(pair).collect(),
+        };
+
+        Ok(Self {
+            pools: plan.pools().to_vec(),
+            borrow: pair(borrow),
+            callback,
+        })
+    }
+
+    /// The token the cycle starts and ends with
+    #[must_use]
+    pub const fn token_in(&self) -> Address {
+        self.callback.tokenIn
+    }
+
+    /// The amount of the start token owed to the first pair
+    #[must_use]
+    pub const fn repay_amount(&self) -> U256 {
+        self.callback.repayAmount
+    }
+
+    /// Pools of the cycle in execution order
+    #[must_use]
+    pub fn pools(&self) -> &[PoolId] {
+        &self.pools
+    }
+
+    /// The first pair and the amount borrowed from it
+    #[must_use]
+    pub const fn borrow(&self) -> &IFlashExecutor::Pair {
+        &self.borrow
+    }
+
+    /// The payload `uniswapV2Call` receives
+    #[must_use]
+    pub const fn callback(&self) -> &IFlashExecutor::Callback {
+        &self.callback
+    }
+
+    /// ABI-encoded `FlashExecutor.run` calldata
+    #[must_use]
+    pub fn calldata(&self) -> Bytes {
+        Bytes::from(
+            IFlashExecutor::runCall {
+                borrow: self.borrow.clone(),
+                data: Bytes::from(self.callback.abi_encode()),
+            }
+            .abi_encode(),
+        )
+    }
+}
+
+/// `FlashExecutor.Pair` argument of a `SimpleExecutor.Pair`, both executors take pairs alike
+fn pair(pair: &ISimpleExecutor::Pair) -> IFlashExecutor::Pair {
+    IFlashExecutor::Pair {
+        contractAddress: pair.contractAddress,
+        amountOut: pair.amountOut,
+        isToken0: pair.isToken0,
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::arb::test_helpers::*;
+
+    #[test]
+    fn test_from_quote() {
+        let cycle = cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 300, 300)]).unwrap();
+        let quote = cycle.quote(U256::from(25));
+
+        let plan = FlashPlan::from_quote(&quote, U256::from(1)).unwrap();
+
+        assert_eq!(plan.token_in(), address_from_str("A"));
+        assert_eq!(plan.repay_amount(), U256::from(25));
+        assert_eq!(plan.pools().len(), 2);
+
+        // Borrow B (token1) from F1
+        assert_eq!(plan.borrow().contractAddress, address_from_str("F1"));
+        assert_eq!(plan.borrow().amountOut, U256::from(39));
+        assert!(!plan.borrow().isToken0);
+
+        // Sell B for A (token0) on F2
+        let callback = plan.callback();
+        assert_eq!(callback.borrowedToken, address_from_str("B"));
+        assert_eq!(callback.minimumProfit, U256::from(1));
+        assert_eq!(callback.pairs.len(), 1);
+        assert_eq!(callback.pairs[0].contractAddress, address_from_str("F2"));
+        assert_eq!(callback.pairs[0].amountOut, U256::from(34));
+        assert!(callback.pairs[0].isToken0);
+    }
+
+    #[test]
+    fn test_calldata_roundtrip() {
+        let cycle = cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 300, 300)]).unwrap();
+        let plan = FlashPlan::from_quote(&cycle.quote(U256::from(25)), U256::ZERO).unwrap();
+
+        let call = IFlashExecutor::runCall::abi_decode(&plan.calldata(), true).unwrap();
+        assert_eq!(call.borrow, *plan.borrow());
+
+        let callback = IFlashExecutor::Callback::abi_decode(&call.data, true).unwrap();
+        assert_eq!(callback, *plan.callback());
+    }
+
+    #[test]
+    fn test_zero_amount_in() {
+        let cycle = cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 300, 300)]).unwrap();
+        let plan = FlashPlan::from_quote(&cycle.quote(U256::ZERO), U256::ZERO);
+
+        assert_eq!(
+            plan.err().unwrap().to_string(),
+            "Cycle quote has zero amount in"
+        );
+    }
+}
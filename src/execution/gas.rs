@@ -0,0 +1,403 @@
+//! EIP-1559 gas bidding.
+//!
+//! On Base a transaction pays the L2 execution fee (`gas_used * (base_fee + priority_fee)`) plus
+//! an L1 data fee charged for posting its calldata to Ethereum. The strategy reads the base fee
+//! of the next block and the tips paid in recent blocks from `eth_feeHistory`, asks the
+//! `GasPriceOracle` predeploy for the L1 fee and bids a configurable share of the remaining
+//! profit as priority fee, within hard caps. Opportunities that are not profitable after fees
+//! get no bid at all.
+
+use std::env;
+
+use alloy::eips::BlockNumberOrTag;
+use alloy::primitives::{Bytes, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::FeeHistory;
+use alloy::sol;
+use eyre::Result;
+
+use crate::utils::constants::GAS_PRICE_ORACLE;
+
+sol! {
+    #[sol(rpc)]
+    "contracts/src/interfaces/IGasPriceOracle.sol"
+}
+
+/// Number of recent blocks the fee history covers
+const FEE_HISTORY_BLOCKS: u64 = 10;
+/// Percentile of the tips paid in recent blocks we consider competitive
+const PRIORITY_FEE_PERCENTILE: f64 = 50.0;
+
+/// Gas bidding configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasConfig {
+    /// Share of the net profit (after base and L1 fees) paid as priority fee, in basis points
+    pub profit_share_bps: u128,
+    /// Lowest priority fee per gas we bid
+    pub min_priority_fee_per_gas: u128,
+    /// Highest priority fee per gas we bid
+    pub max_priority_fee_per_gas: u128,
+    /// Highest max fee per gas we bid
+    pub max_fee_per_gas: u128,
+    /// Max fee headroom over the next base fee in basis points, covers base fee increases
+    /// while the transaction waits
+    pub base_fee_headroom_bps: u128,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            profit_share_bps: 3_000,
+            min_priority_fee_per_gas: 1_000_000,
+            max_priority_fee_per_gas: 5_000_000_000,
+            max_fee_per_gas: 20_000_000_000,
+            base_fee_headroom_bps: 12_500,
+        }
+    }
+}
+
+impl GasConfig {
+    /// Load the configuration from environment variables, falling back to the defaults
+    ///
+    /// # Environment Variables:
+    /// - `FLY_GAS_PROFIT_SHARE_BPS`: Share of net profit paid as priority fee
+    /// - `FLY_GAS_MIN_PRIORITY_FEE`: Lowest priority fee per gas in wei
+    /// - `FLY_GAS_MAX_PRIORITY_FEE`: Highest priority fee per gas in wei
+    /// - `FLY_GAS_MAX_FEE`: Highest max fee per gas in wei
+    /// - `FLY_GAS_BASE_FEE_HEADROOM_BPS`: Max fee headroom over the next base fee
+    #[must_use]
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str, default: u128| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            profit_share_bps: var("FLY_GAS_PROFIT_SHARE_BPS", defaults.profit_share_bps),
+            min_priority_fee_per_gas: var(
+                "FLY_GAS_MIN_PRIORITY_FEE",
+                defaults.min_priority_fee_per_gas,
+            ),
+            max_priority_fee_per_gas: var(
+                "FLY_GAS_MAX_PRIORITY_FEE",
+                defaults.max_priority_fee_per_gas,
+            ),
+            max_fee_per_gas: var("FLY_GAS_MAX_FEE", defaults.max_fee_per_gas),
+            base_fee_headroom_bps: var(
+                "FLY_GAS_BASE_FEE_HEADROOM_BPS",
+                defaults.base_fee_headroom_bps,
+            ),
+        }
+    }
+}
+
+/// Fee market state derived from recent blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSnapshot {
+    /// Base fee per gas of the next block
+    pub next_base_fee: u128,
+    /// Median of the tips paid in recent blocks, if the node reported rewards
+    pub priority_fee: Option<u128>,
+}
+
+impl FeeSnapshot {
+    /// Build a snapshot from an `eth_feeHistory` response
+    ///
+    /// # Returns
+    ///
+    /// `None` if the response has no base fees
+    #[must_use]
+    pub fn from_fee_history(history: &FeeHistory) -> Option<Self> {
+        // `base_fee_per_gas` has one entry more than the number of blocks: the next block
+        let next_base_fee = *history.base_fee_per_gas.last()?;
+
+        let mut rewards = history
+            .reward
+            .iter()
+            .flatten()
+            .filter_map(|percentiles| percentiles.first().copied())
+            .collect::<Vec<_>>();
+        rewards.sort_unstable();
+        let priority_fee = rewards.get(rewards.len() / 2).copied();
+
+        Some(Self {
+            next_base_fee,
+            priority_fee,
+        })
+    }
+}
+
+/// Fees to send an execution with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasBid {
+    /// Gas limit of the transaction
+    pub gas_limit: u64,
+    /// Max fee per gas
+    pub max_fee_per_gas: u128,
+    /// Max priority fee per gas
+    pub max_priority_fee_per_gas: u128,
+    /// L1 data fee in wei
+    pub l1_fee: U256,
+    /// Expected total cost in wei if the transaction is included at the next base fee
+    pub expected_cost: U256,
+}
+
+/// Prices executions
+#[derive(Debug, Clone, Default)]
+pub struct GasStrategy {
+    /// Bidding configuration
+    config: GasConfig,
+}
+
+impl GasStrategy {
+    /// Create a strategy
+    #[must_use]
+    pub const fn new(config: GasConfig) -> Self {
+        Self { config }
+    }
+
+    /// The bidding configuration
+    #[must_use]
+    pub const fn config(&self) -> &GasConfig {
+        &self.config
+    }
+
+    /// Read the fee market state from the latest blocks
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RPC call fails or the node returns no base fees
+    pub async fn snapshot<P: Provider>(&self, provider: &P) -> Result<FeeSnapshot> {
+        let history = provider
+            .get_fee_history(
+                FEE_HISTORY_BLOCKS,
+                BlockNumberOrTag::Latest,
+                &[PRIORITY_FEE_PERCENTILE],
+            )
+            .await?;
+
+        FeeSnapshot::from_fee_history(&history)
+            .ok_or_else(|| eyre::eyre!("Fee history has no base fees"))
+    }
+
+    /// Quote the L1 data fee of a transaction through the `GasPriceOracle` predeploy
+    ///
+    /// The oracle expects the signed transaction. Passing the calldata slightly underestimates
+    /// the fee, which is negligible next to the calldata of an execution.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the call to the oracle fails
+    pub async fn l1_fee<P: Provider>(&self, provider: &P, calldata: &Bytes) -> Result<U256> {
+        let oracle = IGasPriceOracle::new(GAS_PRICE_ORACLE, provider);
+        Ok(oracle.getL1Fee(calldata.clone()).call().await?._0)
+    }
+
+    /// Bid for an execution
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - The fee market state
+    /// * `gas_limit` - Gas limit of the transaction
+    /// * `l1_fee` - L1 data fee in wei
+    /// * `gross_profit` - Expected profit of the execution in wei, before fees
+    ///
+    /// # Returns
+    ///
+    /// `None` if the execution is not profitable after fees
+    #[must_use]
+    pub fn bid(
+        &self,
+        snapshot: &FeeSnapshot,
+        gas_limit: u64,
+        l1_fee: U256,
+        gross_profit: U256,
+    ) -> Option<GasBid> {
+        let config = &self.config;
+        let gas = U256::from(gas_limit);
+        if gas.is_zero() || snapshot.next_base_fee > config.max_fee_per_gas {
+            return None;
+        }
+
+        let base_cost = gas
+            .saturating_mul(U256::from(snapshot.next_base_fee))
+            .saturating_add(l1_fee);
+        let net_profit = gross_profit.checked_sub(base_cost)?;
+
+        // Our share of the profit, per gas, floored at what recent blocks paid and capped
+        let share = net_profit.saturating_mul(U256::from(config.profit_share_bps))
+            / U256::from(10_000)
+            / gas;
+        let floor = snapshot
+            .priority_fee
+            .unwrap_or(0)
+            .max(config.min_priority_fee_per_gas);
+        let max_priority_fee_per_gas = share
+            .saturating_to::<u128>()
+            .max(floor)
+            .min(config.max_priority_fee_per_gas)
+            .min(config.max_fee_per_gas - snapshot.next_base_fee);
+
+        let expected_cost =
+            base_cost.saturating_add(gas.saturating_mul(U256::from(max_priority_fee_per_gas)));
+        if expected_cost >= gross_profit {
+            return None;
+        }
+
+        let headroom = snapshot
+            .next_base_fee
+            .saturating_mul(config.base_fee_headroom_bps)
+            / 10_000;
+        let max_fee_per_gas = headroom
+            .max(snapshot.next_base_fee)
+            .saturating_add(max_priority_fee_per_gas)
+            .min(config.max_fee_per_gas);
+
+        Some(GasBid {
+            gas_limit,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            l1_fee,
+            expected_cost,
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn fee_history(base_fees: &[u128], rewards: &[u128]) -> FeeHistory {
+        FeeHistory {
+            base_fee_per_gas: base_fees.to_vec(),
+            reward: Some(rewards.iter().map(|reward| vec![*reward]).collect()),
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(next_base_fee: u128, priority_fee: Option<u128>) -> FeeSnapshot {
+        FeeSnapshot {
+            next_base_fee,
+            priority_fee,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_from_fee_history() {
+        let history = fee_history(&[10, 11, 12, 13], &[5, 1, 3]);
+        let snapshot = FeeSnapshot::from_fee_history(&history).unwrap();
+
+        assert_eq!(snapshot.next_base_fee, 13);
+        assert_eq!(snapshot.priority_fee, Some(3));
+    }
+
+    #[test]
+    fn test_snapshot_without_rewards() {
+        let history = FeeHistory {
+            base_fee_per_gas: vec![10, 11],
+            ..Default::default()
+        };
+        let snapshot = FeeSnapshot::from_fee_history(&history).unwrap();
+
+        assert_eq!(snapshot.next_base_fee, 11);
+        assert_eq!(snapshot.priority_fee, None);
+
+        assert!(FeeSnapshot::from_fee_history(&FeeHistory::default()).is_none());
+    }
+
+    #[test]
+    fn test_bid_shares_net_profit() {
+        let strategy = GasStrategy::new(GasConfig {
+            profit_share_bps: 5_000,
+            min_priority_fee_per_gas: 1,
+            max_priority_fee_per_gas: 10 * GWEI,
+            max_fee_per_gas: 100 * GWEI,
+            base_fee_headroom_bps: 12_500,
+        });
+        let snapshot = snapshot(GWEI, None);
+
+        // 200k gas at 1 gwei = 0.0002 ETH, L1 fee 0.0001 ETH, gross 0.0013 ETH
+        // net 0.001 ETH, half of it over 200k gas = 2.5 gwei
+        let bid = strategy
+            .bid(
+                &snapshot,
+                200_000,
+                U256::from(100_000 * GWEI),
+                U256::from(1_300_000 * GWEI),
+            )
+            .unwrap();
+
+        assert_eq!(bid.max_priority_fee_per_gas, 2_500_000_000);
+        assert_eq!(bid.max_fee_per_gas, 1_250_000_000 + 2_500_000_000);
+        assert_eq!(bid.expected_cost, U256::from(800_000 * GWEI));
+    }
+
+    #[test]
+    fn test_bid_caps() {
+        let strategy = GasStrategy::new(GasConfig {
+            profit_share_bps: 10_000,
+            min_priority_fee_per_gas: 1,
+            max_priority_fee_per_gas: GWEI,
+            max_fee_per_gas: 2 * GWEI,
+            base_fee_headroom_bps: 20_000,
+        });
+
+        let bid = strategy
+            .bid(&snapshot(GWEI, None), 100_000, U256::ZERO, U256::MAX)
+            .unwrap();
+        assert_eq!(bid.max_priority_fee_per_gas, GWEI);
+        assert_eq!(bid.max_fee_per_gas, 2 * GWEI);
+
+        // Base fee above the cap
+        assert!(strategy
+            .bid(&snapshot(3 * GWEI, None), 100_000, U256::ZERO, U256::MAX)
+            .is_none());
+    }
+
+    #[test]
+    fn test_bid_floor_from_recent_blocks() {
+        let strategy = GasStrategy::default();
+
+        let bid = strategy
+            .bid(
+                &snapshot(GWEI, Some(GWEI / 10)),
+                100_000,
+                U256::ZERO,
+                U256::from(120_000 * GWEI),
+            )
+            .unwrap();
+        // 30% of the net profit is 0.06 gwei per gas, below what recent blocks paid
+        assert_eq!(bid.max_priority_fee_per_gas, GWEI / 10);
+    }
+
+    #[test]
+    fn test_no_bid_when_unprofitable() {
+        let strategy = GasStrategy::default();
+
+        // Fees exceed the profit
+        assert!(strategy
+            .bid(
+                &snapshot(GWEI, None),
+                200_000,
+                U256::from(100_000 * GWEI),
+                U256::from(250_000 * GWEI)
+            )
+            .is_none());
+
+        // Net profit does not cover the minimum priority fee
+        assert!(strategy
+            .bid(
+                &snapshot(GWEI, Some(GWEI)),
+                100_000,
+                U256::ZERO,
+                U256::from(150_000 * GWEI)
+            )
+            .is_none());
+    }
+}
@@ -0,0 +1,18 @@
+//! Execution of arbitrage opportunities.
+//!
+//! Everything between a profitable `CycleQuote` and a transaction on chain lives here.
+
+/// Calldata planning for the `FlashExecutor` contract, trades without inventory
+pub mod flash;
+/// EIP-1559 and L1 data fee bidding
+pub mod gas;
+/// Local nonce reservation, replacement and cancellation
+pub mod nonce;
+/// Calldata planning for the `SimpleExecutor` contract
+pub mod plan;
+/// Per-pool risk controls fed by failed simulations and executions
+pub mod risk;
+/// Pre-flight `eth_call` simulation with decoded reverts
+pub mod simulation;
+/// Persistence of submitted executions and resolution of their outcome
+pub mod tracker;
@@ -0,0 +1,491 @@
+//! Nonce management for executions.
+//!
+//! The provider in `AppContext` fills nonces with alloy's `NonceFiller`, which asks the node for
+//! every transaction. Two executions sent in the same block (or by two processes) get the same
+//! nonce and one of them is lost. Executions therefore reserve nonces from a `NonceManager` and
+//! set them on the transaction request, in which case `NonceFiller` leaves them alone.
+//!
+//! Nonces are reserved in the `nonces` table, which holds the last used nonce of each address.
+//! The reservation is a single upsert, so several processes sending from the same address never
+//! get the same nonce, and a restart continues after the transactions that may still be in the
+//! mempool instead of colliding with them.
+
+use std::collections::BTreeSet;
+
+use alloy::eips::BlockId;
+use alloy::primitives::{Address, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use diesel_async::AsyncPgConnection;
+use eyre::Result;
+use tokio::sync::Mutex;
+
+use crate::models::nonce;
+
+/// Default fee increase of replacement transactions in basis points.
+/// Nodes reject replacements that do not pay at least 10% more.
+pub const DEFAULT_REPLACEMENT_BUMP_BPS: u128 = 1_250;
+
+/// Gas used by a plain ETH transfer, i.e. a cancellation
+const CANCELLATION_GAS_LIMIT: u64 = 21_000;
+
+/// What a resync changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resync {
+    /// The local state matches the chain
+    InSync,
+    /// Nonces were used outside of this manager, the next nonce moved forward
+    Advanced,
+    /// A nonce never reached the chain, nothing was reserved after it and it will be handed out
+    /// again
+    Rewound,
+    /// A nonce never reached the chain while later nonces are reserved, a cancellation was sent
+    /// with it
+    GapFilled(u64),
+}
+
+/// Local nonce bookkeeping, without any I/O
+#[derive(Debug, Clone, Default)]
+pub struct NonceState {
+    /// The next nonce to hand out
+    next: u64,
+    /// Nonces handed out that the chain has not caught up with yet
+    in_flight: BTreeSet<u64>,
+    /// Every nonce from this one up to `next` was handed out by this state and not reserved
+    /// elsewhere
+    owned_from: u64,
+}
+
+impl NonceState {
+    /// Create a state that hands out `next` first
+    #[must_use]
+    pub fn new(next: u64) -> Self {
+        Self {
+            next,
+            in_flight: BTreeSet::new(),
+            owned_from: next,
+        }
+    }
+
+    /// The next nonce to hand out
+    #[must_use]
+    pub const fn next(&self) -> u64 {
+        self.next
+    }
+
+    /// Nonces handed out that the chain has not caught up with yet
+    #[must_use]
+    pub const fn in_flight(&self) -> &BTreeSet<u64> {
+        &self.in_flight
+    }
+
+    /// Reserve the next nonce
+    pub fn reserve(&mut self) -> u64 {
+        let nonce = self.next;
+        self.claim(nonce);
+        nonce
+    }
+
+    /// Take a nonce reserved elsewhere, e.g. in the database, moving past it
+    pub fn claim(&mut self, nonce: u64) {
+        // The nonces skipped over belong to someone else
+        if nonce > self.next {
+            self.owned_from = nonce;
+        }
+        self.in_flight.insert(nonce);
+        self.next = self.next.max(nonce.saturating_add(1));
+    }
+
+    /// Give back a nonce whose transaction was never broadcast.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the nonce will be handed out again. `false` if later nonces are already
+    /// reserved, in which case the gap has to be filled by a resync or a cancellation.
+    pub fn release(&mut self, nonce: u64) -> bool {
+        if !self.in_flight.remove(&nonce) {
+            return false;
+        }
+
+        if nonce.saturating_add(1) == self.next {
+            self.next = nonce;
+            return true;
+        }
+
+        false
+    }
+
+    /// Reconcile with the chain
+    ///
+    /// # Arguments
+    ///
+    /// * `chain_next` - The pending transaction count of the address, i.e. the next nonce the
+    ///   chain will accept
+    ///
+    /// # Returns
+    ///
+    /// `GapFilled(chain_next)` if the chain is waiting for a nonce nobody is going to send. The
+    /// state is left alone, the gap has to be filled with `rewind` or a cancellation.
+    pub fn resync(&mut self, chain_next: u64) -> Resync {
+        // Everything below the chain nonce is mined or in the mempool
+        self.in_flight.retain(|nonce| *nonce >= chain_next);
+
+        if chain_next > self.next {
+            self.next = chain_next;
+            self.owned_from = chain_next;
+            return Resync::Advanced;
+        }
+
+        if chain_next < self.next && !self.in_flight.contains(&chain_next) {
+            return Resync::GapFilled(chain_next);
+        }
+
+        Resync::InSync
+    }
+
+    /// Whether the next nonce can move back to `nonce`, i.e. every nonce from it on was handed
+    /// out here and none of them is in flight
+    #[must_use]
+    pub fn can_rewind(&self, nonce: u64) -> bool {
+        self.in_flight.is_empty() && self.owned_from <= nonce
+    }
+
+    /// Hand out `nonce` again next, see `can_rewind`
+    pub fn rewind(&mut self, nonce: u64) {
+        self.next = nonce;
+        self.owned_from = nonce;
+    }
+}
+
+/// Fee of a replacement transaction, at least one wei more than `fee`
+#[must_use]
+pub fn bump_fee(fee: u128, bump_bps: u128) -> u128 {
+    let bump = fee.saturating_mul(bump_bps) / 10_000;
+    fee.saturating_add(bump.max(1))
+}
+
+/// Hands out nonces for one sending address
+#[derive(Debug)]
+pub struct NonceManager {
+    /// The sending address
+    address: Address,
+    /// Fee increase of replacement transactions in basis points
+    replacement_bump_bps: u128,
+    /// Local bookkeeping
+    state: Mutex<NonceState>,
+}
+
+impl NonceManager {
+    /// Create a manager starting after both the chain and the persisted nonce
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RPC call or the database query fails
+    pub async fn load<P: Provider>(
+        provider: &P,
+        conn: &mut AsyncPgConnection,
+        address: Address,
+    ) -> Result<Self> {
+        let chain_next = provider
+            .get_transaction_count(address)
+            .block_id(BlockId::pending())
+            .await?;
+        let persisted_next = nonce::last_nonce(conn, address)
+            .await?
+            .map(|nonce| u64::try_from(nonce.saturating_add(1)))
+            .transpose()?
+            .unwrap_or(0);
+
+        let next = chain_next.max(persisted_next);
+        log::info!(
+            "execution::nonce: {address} starts at nonce {next} (chain: {chain_next}, persisted: {persisted_next})"
+        );
+
+        Ok(Self {
+            address,
+            replacement_bump_bps: DEFAULT_REPLACEMENT_BUMP_BPS,
+            state: Mutex::new(NonceState::new(next)),
+        })
+    }
+
+    /// Use a different fee increase for replacements
+    #[must_use]
+    pub const fn with_replacement_bump_bps(mut self, bps: u128) -> Self {
+        self.replacement_bump_bps = bps;
+        self
+    }
+
+    /// The sending address
+    #[must_use]
+    pub const fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Reserve a nonce in the database, at least the next one known locally
+    ///
+    /// Nothing is reserved locally when the database reservation fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database reservation fails
+    pub async fn reserve(&self, conn: &mut AsyncPgConnection) -> Result<u64> {
+        let mut state = self.state.lock().await;
+        let floor = i64::try_from(state.next())?;
+        let nonce = u64::try_from(nonce::reserve(conn, self.address, floor).await?)?;
+        state.claim(nonce);
+        Ok(nonce)
+    }
+
+    /// Give back a nonce whose transaction was never broadcast or was dropped
+    ///
+    /// # Returns
+    ///
+    /// `true` if the nonce will be handed out again, `false` if it left a gap, also when
+    /// another process reserved a later nonce in the meantime
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails
+    pub async fn release(&self, conn: &mut AsyncPgConnection, nonce: u64) -> Result<bool> {
+        let mut state = self.state.lock().await;
+        if !state.release(nonce) {
+            return Ok(false);
+        }
+
+        Ok(nonce::release(conn, self.address, i64::try_from(nonce)?).await?)
+    }
+
+    /// Reconcile with the chain, e.g. after a released nonce left a gap or a dropped
+    /// transaction was released
+    ///
+    /// A gap is filled by handing its nonce out again if nothing was reserved after it, here or
+    /// by another process. Otherwise a cancellation is sent with the missing nonce, so that
+    /// reserved nonces above it, possibly already in the mempool, are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an RPC call or the database update fails
+    pub async fn resync<P: Provider>(
+        &self,
+        provider: &P,
+        conn: &mut AsyncPgConnection,
+    ) -> Result<Resync> {
+        let mut state = self.state.lock().await;
+        let chain_next = provider
+            .get_transaction_count(self.address)
+            .block_id(BlockId::pending())
+            .await?;
+
+        let mut resync = state.resync(chain_next);
+        if let Resync::GapFilled(gap) = resync {
+            // The database only moves back if nobody reserved a nonce after ours
+            let last = i64::try_from(state.next().saturating_sub(1))?;
+            if state.can_rewind(gap)
+                && nonce::rewind(conn, self.address, i64::try_from(gap)?, last).await?
+            {
+                state.rewind(gap);
+                resync = Resync::Rewound;
+            } else {
+                // Later nonces may be in the mempool already, only the gap is filled
+                let fees = provider.estimate_eip1559_fees(None).await?;
+                let pending = provider
+                    .send_transaction(self.cancellation(
+                        gap,
+                        fees.max_fee_per_gas,
+                        fees.max_priority_fee_per_gas,
+                    ))
+                    .await?;
+                log::warn!(
+                    "execution::nonce: {} filled nonce {gap} with cancellation {}",
+                    self.address,
+                    pending.tx_hash()
+                );
+                state.claim(gap);
+            }
+        }
+        if resync != Resync::InSync {
+            log::warn!(
+                "execution::nonce: {} resynced from chain ({resync:?}), next nonce is {}",
+                self.address,
+                state.next()
+            );
+        }
+        Ok(resync)
+    }
+
+    /// Turn `tx` into a replacement of the transaction sent with `nonce`
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - The new transaction
+    /// * `nonce` - The nonce of the transaction to replace
+    /// * `max_fee_per_gas` - The max fee per gas of the transaction to replace
+    /// * `max_priority_fee_per_gas` - The max priority fee per gas of the transaction to replace
+    #[must_use]
+    pub fn replacement(
+        &self,
+        tx: TransactionRequest,
+        nonce: u64,
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    ) -> TransactionRequest {
+        tx.from(self.address)
+            .nonce(nonce)
+            .max_fee_per_gas(bump_fee(max_fee_per_gas, self.replacement_bump_bps))
+            .max_priority_fee_per_gas(bump_fee(
+                max_priority_fee_per_gas,
+                self.replacement_bump_bps,
+            ))
+    }
+
+    /// A zero value transfer to ourselves replacing the transaction sent with `nonce`
+    ///
+    /// # Arguments
+    ///
+    /// * `nonce` - The nonce of the transaction to cancel
+    /// * `max_fee_per_gas` - The max fee per gas of the transaction to cancel
+    /// * `max_priority_fee_per_gas` - The max priority fee per gas of the transaction to cancel
+    #[must_use]
+    pub fn cancellation(
+        &self,
+        nonce: u64,
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    ) -> TransactionRequest {
+        let tx = TransactionRequest::default()
+            .to(self.address)
+            .value(U256::ZERO)
+            .gas_limit(CANCELLATION_GAS_LIMIT);
+
+        self.replacement(tx, nonce, max_fee_per_gas, max_priority_fee_per_gas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_and_release() {
+        let mut state = NonceState::new(7);
+
+        assert_eq!(state.reserve(), 7);
+        assert_eq!(state.reserve(), 8);
+
+        // The last nonce is handed out again
+        assert!(state.release(8));
+        assert_eq!(state.reserve(), 8);
+
+        // Releasing a nonce below another reserved one leaves a gap
+        assert!(!state.release(7));
+        assert_eq!(state.next(), 9);
+        assert!(!state.release(42));
+    }
+
+    #[test]
+    fn test_claim_moves_past_nonces_reserved_elsewhere() {
+        let mut state = NonceState::new(3);
+
+        // Another process reserved 3 and 4
+        state.claim(5);
+        assert_eq!(state.next(), 6);
+        assert_eq!(
+            state.in_flight().iter().copied().collect::<Vec<_>>(),
+            vec![5]
+        );
+
+        // An older nonce does not move the next one back
+        state.claim(4);
+        assert_eq!(state.next(), 6);
+        assert_eq!(state.reserve(), 6);
+    }
+
+    #[test]
+    fn test_resync_advances() {
+        let mut state = NonceState::new(3);
+        state.reserve();
+
+        assert_eq!(state.resync(10), Resync::Advanced);
+        assert_eq!(state.next(), 10);
+        assert!(state.in_flight().is_empty());
+    }
+
+    #[test]
+    fn test_resync_fills_gap() {
+        let mut state = NonceState::new(3);
+        state.reserve();
+        state.reserve();
+        state.reserve();
+
+        // 3 was broadcast, 4 failed to broadcast, 5 is stuck behind it
+        assert!(!state.release(4));
+        assert_eq!(state.resync(4), Resync::GapFilled(4));
+
+        // 5 may be in the mempool, it is kept and only 4 is filled
+        assert!(!state.can_rewind(4));
+        state.claim(4);
+        assert_eq!(state.next(), 6);
+        assert_eq!(
+            state.in_flight().iter().copied().collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+        assert_eq!(state.resync(4), Resync::InSync);
+    }
+
+    #[test]
+    fn test_resync_rewinds_own_nonces() {
+        let mut state = NonceState::new(3);
+        state.reserve();
+        state.reserve();
+
+        // Neither reached the chain and both were released out of order
+        assert!(!state.release(3));
+        assert!(state.release(4));
+        assert_eq!(state.resync(3), Resync::GapFilled(3));
+
+        assert!(state.can_rewind(3));
+        state.rewind(3);
+        assert_eq!(state.reserve(), 3);
+    }
+
+    #[test]
+    fn test_resync_keeps_nonces_reserved_elsewhere() {
+        let mut state = NonceState::new(3);
+        state.reserve();
+
+        // Another process reserved 4 and has not broadcast it yet
+        state.claim(5);
+        assert!(!state.release(3));
+        assert!(state.release(5));
+        assert_eq!(state.resync(3), Resync::GapFilled(3));
+
+        assert!(!state.can_rewind(3));
+    }
+
+    #[test]
+    fn test_resync_in_sync() {
+        let mut state = NonceState::new(3);
+        state.reserve();
+        state.reserve();
+
+        // 3 is not in the mempool yet
+        assert_eq!(state.resync(3), Resync::InSync);
+        assert_eq!(state.next(), 5);
+
+        // 3 is in the mempool
+        assert_eq!(state.resync(4), Resync::InSync);
+        assert_eq!(state.in_flight().len(), 1);
+
+        // Both are in the mempool
+        assert_eq!(state.resync(5), Resync::InSync);
+        assert!(state.in_flight().is_empty());
+    }
+
+    #[test]
+    fn test_bump_fee() {
+        assert_eq!(bump_fee(1_000_000, 1_250), 1_125_000);
+        assert_eq!(bump_fee(0, 1_250), 1);
+        assert_eq!(bump_fee(5, 1_250), 6);
+        assert_eq!(bump_fee(u128::MAX, 1_250), u128::MAX);
+    }
+}
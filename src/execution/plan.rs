@@ -0,0 +1,196 @@
+//! Turns a `CycleQuote` into a `SimpleExecutor.run` call.
+//!
+//! The executor contract takes the start token, the amount to send to the first pair and,
+//! for every hop, the pair address, the exact amount to take out and which side of the pair
+//! that amount is on. All of this is already known by the quote.
+
+use alloy::primitives::{Address, Bytes, U256};
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use eyre::{bail, Result};
+
+use crate::arb::cycle_quote::CycleQuote;
+use crate::arb::pool::PoolId;
+use crate::arb::swap::Direction;
+
+sol! {
+    #[sol(rpc, all_derives)]
+    "contracts/src/interfaces/ISimpleExecutor.sol"
+}
+
+/// Maximum number of pairs `SimpleExecutor.run` accepts (see `InvalidPairCount`)
+pub const MAX_PAIRS: usize = 5;
+
+/// A `SimpleExecutor.run` call prepared from a `CycleQuote`
+#[derive(Debug, Clone)]
+pub struct ExecutionPlan {
+    /// The token the cycle starts and ends with
+    token_in: Address,
+    /// The amount of `token_in` sent to the first pair
+    amount_in: U256,
+    /// The profit (in `token_in`) below which the contract reverts
+    minimum_profit: U256,
+    /// Pools of the cycle in execution order
+    pools: Vec<PoolId>,
+    /// `SimpleExecutor.Pair` arguments in execution order
+    pairs: Vec<ISimpleExecutor::Pair>,
+}
+
+impl ExecutionPlan {
+    /// Create a plan from a quote.
+    ///
+    /// # Arguments
+    ///
+    /// * `quote` - The quote to execute
+    /// * `minimum_profit` - The profit (in the start token) the contract must realize
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the quote has more swaps than the executor supports or
+    /// if the quoted amount in is zero
+    pub fn from_quote(quote: &CycleQuote, minimum_profit: U256) -> Result<Self> {
+        let swap_quotes = quote.swap_quotes();
+
+        if swap_quotes.len() > MAX_PAIRS {
+            bail!(
+                "Cycle has {} swaps, executor supports at most {}",
+                swap_quotes.len(),
+                MAX_PAIRS
+            );
+        }
+
+        if quote.amount_in().is_zero() {
+            bail!("Cycle quote has zero amount in");
+        }
+
+        let pools = swap_quotes
+            .iter()
+            .map(|swap_quote| swap_quote.swap().id().pool_id)
+            .collect();
+
+        let pairs = swap_quotes
+            .iter()
+            .map(|swap_quote| {
+                let swap_id = swap_quote.swap().id();
+                ISimpleExecutor::Pair {
+                    contractAddress: swap_id.pool_id.address(),
+                    amountOut: swap_quote.amount_out(),
+                    // Taking token0 out of the pair means we are swapping token1 for token0
+                    isToken0: swap_id.direction == Direction::OneForZero,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            token_in: quote.start_token().0,
+            amount_in: quote.amount_in(),
+            minimum_profit,
+            pools,
+            pairs,
+        })
+    }
+
+    /// The token the cycle starts and ends with
+    #[must_use]
+    pub const fn token_in(&self) -> Address {
+        self.token_in
+    }
+
+    /// The amount of the start token sent to the first pair
+    #[must_use]
+    pub const fn amount_in(&self) -> U256 {
+        self.amount_in
+    }
+
+    /// The profit the contract must realize, in the start token
+    #[must_use]
+    pub const fn minimum_profit(&self) -> U256 {
+        self.minimum_profit
+    }
+
+    /// Pools of the cycle in execution order
+    #[must_use]
+    pub fn pools(&self) -> &[PoolId] {
+        &self.pools
+    }
+
+    /// `SimpleExecutor.Pair` arguments in execution order
+    #[must_use]
+    pub fn pairs(&self) -> &[ISimpleExecutor::Pair] {
+        &self.pairs
+    }
+
+    /// ABI-encoded `SimpleExecutor.run` calldata
+    #[must_use]
+    pub fn calldata(&self) -> Bytes {
+        Bytes::from(
+            ISimpleExecutor::runCall {
+                token0Address: self.token_in,
+                token0AmountIn: self.amount_in,
+                minimumProfitInToken0: self.minimum_profit,
+                pairs: self.pairs.clone(),
+                skipProfitCheck: false,
+            }
+            .abi_encode(),
+        )
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::arb::test_helpers::*;
+
+    #[test]
+    fn test_from_quote() {
+        let cycle = cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 300, 300)]).unwrap();
+        let quote = cycle.quote(U256::from(25));
+
+        let plan = ExecutionPlan::from_quote(&quote, U256::from(1)).unwrap();
+
+        assert_eq!(plan.token_in(), address_from_str("A"));
+        assert_eq!(plan.amount_in(), U256::from(25));
+        assert_eq!(plan.minimum_profit(), U256::from(1));
+        assert_eq!(
+            plan.pools(),
+            &[
+                PoolId::from(address_from_str("F1")),
+                PoolId::from(address_from_str("F2"))
+            ]
+        );
+
+        // A -> B takes token1 out of F1
+        assert_eq!(plan.pairs()[0].contractAddress, address_from_str("F1"));
+        assert_eq!(plan.pairs()[0].amountOut, U256::from(39));
+        assert!(!plan.pairs()[0].isToken0);
+
+        // B -> A takes token0 out of F2
+        assert_eq!(plan.pairs()[1].contractAddress, address_from_str("F2"));
+        assert_eq!(plan.pairs()[1].amountOut, U256::from(34));
+        assert!(plan.pairs()[1].isToken0);
+    }
+
+    #[test]
+    fn test_calldata_roundtrip() {
+        let cycle = cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 300, 300)]).unwrap();
+        let plan = ExecutionPlan::from_quote(&cycle.quote(U256::from(25)), U256::ZERO).unwrap();
+
+        let call = ISimpleExecutor::runCall::abi_decode(&plan.calldata(), true).unwrap();
+        assert_eq!(call.token0Address, address_from_str("A"));
+        assert_eq!(call.token0AmountIn, U256::from(25));
+        assert_eq!(call.pairs.len(), 2);
+        assert!(!call.skipProfitCheck);
+    }
+
+    #[test]
+    fn test_zero_amount_in() {
+        let cycle = cycle(&[("F1", "A", "B", 100, 200), ("F2", "B", "A", 300, 300)]).unwrap();
+        let plan = ExecutionPlan::from_quote(&cycle.quote(U256::ZERO), U256::ZERO);
+
+        assert_eq!(
+            plan.err().unwrap().to_string(),
+            "Cycle quote has zero amount in"
+        );
+    }
+}
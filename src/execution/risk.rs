@@ -0,0 +1,146 @@
+//! Per-pool risk controls.
+//!
+//! Pools that keep failing simulation or execution (stale reserves, fee-on-transfer tokens,
+//! paused pairs, etc.) are put on cooldown so we stop burning RPC calls and gas on them.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::arb::pool::PoolId;
+
+/// Default number of failures after which a pool is put on cooldown
+const DEFAULT_MAX_FAILURES: u32 = 3;
+/// Default cooldown period
+const DEFAULT_COOLDOWN: Duration = Duration::from_mins(10);
+
+/// Failure record of a single pool
+#[derive(Debug, Clone, Copy)]
+struct PoolRisk {
+    /// Consecutive failures
+    failures: u32,
+    /// When the pool failed last
+    last_failure: Instant,
+}
+
+/// Tracks failing pools and decides whether cycles through them may be executed
+#[derive(Debug, Clone)]
+pub struct RiskControls {
+    /// Failure records by pool
+    pools: HashMap<PoolId, PoolRisk>,
+    /// Number of consecutive failures after which a pool is blocked
+    max_failures: u32,
+    /// How long a blocked pool stays blocked after its last failure
+    cooldown: Duration,
+}
+
+impl Default for RiskControls {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FAILURES, DEFAULT_COOLDOWN)
+    }
+}
+
+impl RiskControls {
+    /// Create new risk controls
+    ///
+    /// # Arguments
+    ///
+    /// * `max_failures` - Consecutive failures after which a pool is blocked
+    /// * `cooldown` - How long a pool stays blocked after its last failure
+    #[must_use]
+    pub fn new(max_failures: u32, cooldown: Duration) -> Self {
+        Self {
+            pools: HashMap::new(),
+            max_failures,
+            cooldown,
+        }
+    }
+
+    /// Record a failed simulation or execution involving `pools`
+    pub fn record_failure(&mut self, pools: &[PoolId], now: Instant) {
+        for pool in pools {
+            let risk = self.pools.entry(pool.clone()).or_insert(PoolRisk {
+                failures: 0,
+                last_failure: now,
+            });
+            risk.failures = risk.failures.saturating_add(1);
+            risk.last_failure = now;
+        }
+    }
+
+    /// Record a successful simulation or execution involving `pools`, clearing their failures
+    pub fn record_success(&mut self, pools: &[PoolId]) {
+        for pool in pools {
+            self.pools.remove(pool);
+        }
+    }
+
+    /// Number of consecutive failures recorded for a pool
+    #[must_use]
+    pub fn failures(&self, pool: &PoolId) -> u32 {
+        self.pools.get(pool).map_or(0, |risk| risk.failures)
+    }
+
+    /// Whether a pool is currently blocked
+    #[must_use]
+    pub fn is_blocked(&self, pool: &PoolId, now: Instant) -> bool {
+        self.pools.get(pool).is_some_and(|risk| {
+            risk.failures >= self.max_failures
+                && now.saturating_duration_since(risk.last_failure) < self.cooldown
+        })
+    }
+
+    /// Whether none of the pools is blocked
+    #[must_use]
+    pub fn allows(&self, pools: &[PoolId], now: Instant) -> bool {
+        pools.iter().all(|pool| !self.is_blocked(pool, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arb::test_helpers::address_from_str;
+
+    #[test]
+    fn test_blocked_after_max_failures() {
+        let mut risk = RiskControls::new(2, Duration::from_mins(1));
+        let pool = PoolId::from(address_from_str("F1"));
+        let now = Instant::now();
+
+        risk.record_failure(std::slice::from_ref(&pool), now);
+        assert!(!risk.is_blocked(&pool, now));
+
+        risk.record_failure(std::slice::from_ref(&pool), now);
+        assert!(risk.is_blocked(&pool, now));
+        assert!(!risk.allows(std::slice::from_ref(&pool), now));
+        assert_eq!(risk.failures(&pool), 2);
+    }
+
+    #[test]
+    fn test_cooldown_expires() {
+        let mut risk = RiskControls::new(1, Duration::from_mins(1));
+        let pool = PoolId::from(address_from_str("F1"));
+        let now = Instant::now();
+
+        risk.record_failure(std::slice::from_ref(&pool), now);
+        assert!(risk.is_blocked(&pool, now));
+        assert!(!risk.is_blocked(&pool, now + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn test_success_clears_failures() {
+        let mut risk = RiskControls::new(1, Duration::from_mins(1));
+        let pools = [
+            PoolId::from(address_from_str("F1")),
+            PoolId::from(address_from_str("F2")),
+        ];
+        let now = Instant::now();
+
+        risk.record_failure(&pools, now);
+        assert!(!risk.allows(&pools, now));
+
+        risk.record_success(&pools[..1]);
+        assert!(!risk.is_blocked(&pools[0], now));
+        assert!(risk.is_blocked(&pools[1], now));
+    }
+}
@@ -0,0 +1,342 @@
+//! Pre-flight simulation of executions.
+//!
+//! Every execution is run through `eth_call` against the latest (or pending) state before it is
+//! broadcast. Reverts are decoded into `SimulationError` so we know whether the executor, a pair
+//! or the node is to blame. Failures are logged with the cycle and the pools involved are fed
+//! back into `RiskControls`.
+
+use std::fmt::{self, Display};
+use std::time::Instant;
+
+use alloy::eips::BlockId;
+use alloy::primitives::{Address, Bytes, I256, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::{TransactionInput, TransactionRequest};
+use alloy::sol_types::{Panic, Revert, SolError, SolInterface};
+use alloy::transports::TransportError;
+
+use super::plan::{ExecutionPlan, ISimpleExecutor::ISimpleExecutorErrors};
+use super::risk::RiskControls;
+use crate::arb::cycle_quote::CycleQuote;
+
+/// Reasons an execution reverts (or fails to be simulated)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimulationError {
+    /// `SimpleExecutor` was not called by its owner
+    NotOwner,
+    /// `SimpleExecutor` failed to send ETH to the owner
+    WithdrawalFailed,
+    /// The cycle did not realize the minimum profit
+    ProfitTargetNotMet {
+        /// The minimum profit we asked for, in the start token
+        minimum_profit: U256,
+        /// The profit the cycle would have made, in the start token
+        actual_profit: I256,
+    },
+    /// The cycle has no pairs or more than the executor supports
+    InvalidPairCount,
+    /// `SimpleExecutor.callContract` target reverted
+    CallFailed,
+    /// `SimpleExecutor.callContract` target is the zero address
+    InvalidAddress,
+    /// An ERC20 transfer returned `false`
+    Erc20Failed,
+    /// `SimpleExecutor` holds less than the requested withdrawal
+    NoBalanceToWithdraw,
+    /// A pair reverted with its own reason, e.g. `UniswapV2: K`
+    PairReverted(String),
+    /// Any other `Error(string)` revert, e.g. from a token contract
+    Reverted(String),
+    /// A Solidity panic (overflow, division by zero, etc.) with its code
+    Panic(U256),
+    /// Revert data we could not decode
+    Unknown(Bytes),
+    /// The node failed to run the call at all
+    Rpc(String),
+}
+
+impl Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotOwner => write!(f, "NotOwner"),
+            Self::WithdrawalFailed => write!(f, "WithdrawalFailed"),
+            Self::ProfitTargetNotMet {
+                minimum_profit,
+                actual_profit,
+            } => write!(
+                f,
+                "ProfitTargetNotMet(minimumProfit: {minimum_profit}, actualProfit: {actual_profit})"
+            ),
+            Self::InvalidPairCount => write!(f, "InvalidPairCount"),
+            Self::CallFailed => write!(f, "CallFailed"),
+            Self::InvalidAddress => write!(f, "InvalidAddress"),
+            Self::Erc20Failed => write!(f, "ERC20Failed"),
+            Self::NoBalanceToWithdraw => write!(f, "NoBalanceToWithdraw"),
+            Self::PairReverted(reason) => write!(f, "Pair reverted: {reason}"),
+            Self::Reverted(reason) => write!(f, "Reverted: {reason}"),
+            Self::Panic(code) => write!(f, "Panic({code:#x})"),
+            Self::Unknown(data) => write!(f, "Unknown revert: {data}"),
+            Self::Rpc(message) => write!(f, "RPC error: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+impl From<ISimpleExecutorErrors> for SimulationError {
+    fn from(error: ISimpleExecutorErrors) -> Self {
+        match error {
+            ISimpleExecutorErrors::NotOwner(_) => Self::NotOwner,
+            ISimpleExecutorErrors::WithdrawalFailed(_) => Self::WithdrawalFailed,
+            ISimpleExecutorErrors::ProfitTargetNotMet(e) => Self::ProfitTargetNotMet {
+                minimum_profit: e.minimumProfit,
+                actual_profit: e.actualProfit,
+            },
+            ISimpleExecutorErrors::InvalidPairCount(_) => Self::InvalidPairCount,
+            ISimpleExecutorErrors::CallFailed(_) => Self::CallFailed,
+            ISimpleExecutorErrors::InvalidAddress(_) => Self::InvalidAddress,
+            ISimpleExecutorErrors::ERC20Failed(_) => Self::Erc20Failed,
+            ISimpleExecutorErrors::NoBalanceToWithdraw(_) => Self::NoBalanceToWithdraw,
+        }
+    }
+}
+
+impl SimulationError {
+    /// Decode revert data returned by `eth_call`
+    ///
+    /// Tries, in order: `SimpleExecutor` custom errors, `Error(string)` and `Panic(uint256)`.
+    #[must_use]
+    pub fn decode(data: &[u8]) -> Self {
+        if let Ok(error) = ISimpleExecutorErrors::abi_decode(data, true) {
+            return error.into();
+        }
+
+        if let Ok(revert) = Revert::abi_decode(data, true) {
+            return if is_pair_reason(&revert.reason) {
+                Self::PairReverted(revert.reason)
+            } else {
+                Self::Reverted(revert.reason)
+            };
+        }
+
+        if let Ok(panic) = Panic::abi_decode(data, true) {
+            return Self::Panic(panic.code);
+        }
+
+        Self::Unknown(Bytes::copy_from_slice(data))
+    }
+
+    /// Convert a transport error into a `SimulationError`, decoding revert data if present
+    #[must_use]
+    pub fn from_transport(error: &TransportError) -> Self {
+        let Some(payload) = error.as_error_resp() else {
+            return Self::Rpc(error.to_string());
+        };
+
+        payload
+            .as_revert_data()
+            .map_or_else(|| Self::Rpc(error.to_string()), |data| Self::decode(&data))
+    }
+
+    /// Whether the failure is caused by the pools of the cycle rather than by us or the node.
+    /// Only these failures are fed into `RiskControls`.
+    #[must_use]
+    pub const fn is_pool_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::ProfitTargetNotMet { .. }
+                | Self::PairReverted(_)
+                | Self::Reverted(_)
+                | Self::Panic(_)
+                | Self::Unknown(_)
+        )
+    }
+}
+
+/// Uniswap V2 style pairs (and their forks) revert with `<Name>: <CODE>`,
+/// e.g. `UniswapV2: K` or `UniswapV2: INSUFFICIENT_OUTPUT_AMOUNT`.
+fn is_pair_reason(reason: &str) -> bool {
+    reason.split_once(": ").is_some_and(|(name, code)| {
+        !name.is_empty()
+            && !name.contains(' ')
+            && !code.is_empty()
+            && code
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+    })
+}
+
+/// Runs executions through `eth_call` before they are broadcast
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    /// The `SimpleExecutor` contract
+    executor: Address,
+    /// The executor's owner, i.e. our wallet
+    from: Address,
+    /// The state to simulate against
+    block: BlockId,
+}
+
+impl Simulator {
+    /// Create a simulator that runs against the pending state
+    ///
+    /// # Arguments
+    ///
+    /// * `executor` - The `SimpleExecutor` contract address
+    /// * `from` - The executor's owner
+    #[must_use]
+    pub fn new(executor: Address, from: Address) -> Self {
+        Self {
+            executor,
+            from,
+            block: BlockId::pending(),
+        }
+    }
+
+    /// Simulate against a specific block instead, e.g. `BlockId::latest()`
+    #[must_use]
+    pub const fn at(mut self, block: BlockId) -> Self {
+        self.block = block;
+        self
+    }
+
+    /// Simulate a plan
+    ///
+    /// # Returns
+    ///
+    /// The raw return data of the call
+    ///
+    /// # Errors
+    ///
+    /// Returns the decoded revert reason if the call reverts, or `SimulationError::Rpc`
+    /// if the node fails to run it
+    pub async fn simulate<P: Provider>(
+        &self,
+        provider: &P,
+        plan: &ExecutionPlan,
+    ) -> Result<Bytes, SimulationError> {
+        let tx = TransactionRequest::default()
+            .from(self.from)
+            .to(self.executor)
+            .input(TransactionInput::new(plan.calldata()));
+
+        provider
+            .call(&tx)
+            .block(self.block)
+            .await
+            .map_err(|e| SimulationError::from_transport(&e))
+    }
+
+    /// Simulate a plan before broadcasting it.
+    /// Failures are logged with the cycle and pool failures are recorded in `risk`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the decoded revert reason if the simulation fails
+    pub async fn preflight<P: Provider>(
+        &self,
+        provider: &P,
+        quote: &CycleQuote,
+        plan: &ExecutionPlan,
+        risk: &mut RiskControls,
+    ) -> Result<(), SimulationError> {
+        match self.simulate(provider, plan).await {
+            Ok(_) => {
+                risk.record_success(plan.pools());
+                Ok(())
+            }
+            Err(e) => {
+                log::warn!(
+                    "execution::simulation: Cycle {:?} (in: {}, profit: {}) failed simulation: {e}",
+                    plan.pools(),
+                    quote.amount_in(),
+                    quote.profit()
+                );
+                if e.is_pool_failure() {
+                    risk.record_failure(plan.pools(), Instant::now());
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::execution::plan::ISimpleExecutor;
+
+    #[test]
+    fn test_decode_executor_errors() {
+        let data = ISimpleExecutor::ProfitTargetNotMet {
+            minimumProfit: U256::from(27_000_000),
+            actualProfit: I256::try_from(-134_621_970).unwrap(),
+        }
+        .abi_encode();
+        assert_eq!(
+            SimulationError::decode(&data),
+            SimulationError::ProfitTargetNotMet {
+                minimum_profit: U256::from(27_000_000),
+                actual_profit: I256::try_from(-134_621_970).unwrap(),
+            }
+        );
+
+        let data = ISimpleExecutor::InvalidPairCount {}.abi_encode();
+        assert_eq!(
+            SimulationError::decode(&data),
+            SimulationError::InvalidPairCount
+        );
+
+        let data = ISimpleExecutor::CallFailed {}.abi_encode();
+        assert_eq!(SimulationError::decode(&data), SimulationError::CallFailed);
+    }
+
+    #[test]
+    fn test_decode_pair_reverts() {
+        for reason in ["UniswapV2: K", "UniswapV2: INSUFFICIENT_OUTPUT_AMOUNT"] {
+            let data = Revert {
+                reason: reason.to_string(),
+            }
+            .abi_encode();
+            assert_eq!(
+                SimulationError::decode(&data),
+                SimulationError::PairReverted(reason.to_string())
+            );
+        }
+
+        let data = Revert {
+            reason: "ERC20: transfer amount exceeds balance".to_string(),
+        }
+        .abi_encode();
+        assert_eq!(
+            SimulationError::decode(&data),
+            SimulationError::Reverted("ERC20: transfer amount exceeds balance".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_panic_and_unknown() {
+        let data = Panic {
+            code: U256::from(0x11),
+        }
+        .abi_encode();
+        assert_eq!(
+            SimulationError::decode(&data),
+            SimulationError::Panic(U256::from(0x11))
+        );
+
+        let data = [0xde, 0xad, 0xbe, 0xef];
+        assert_eq!(
+            SimulationError::decode(&data),
+            SimulationError::Unknown(Bytes::copy_from_slice(&data))
+        );
+    }
+
+    #[test]
+    fn test_is_pool_failure() {
+        assert!(SimulationError::PairReverted("UniswapV2: K".to_string()).is_pool_failure());
+        assert!(!SimulationError::NotOwner.is_pool_failure());
+        assert!(!SimulationError::Rpc("timeout".to_string()).is_pool_failure());
+    }
+}
@@ -0,0 +1,461 @@
+//! Transaction lifecycle tracking.
+//!
+//! Every execution we broadcast is recorded in the `executions` table as `Pending`. The tracker
+//! worker then polls for receipts and resolves each execution to `Included`, `Reverted`,
+//! `Replaced` (another transaction with the same nonce was mined) or `Dropped` (the node
+//! forgot about it). For mined executions the realized profit is computed from the receipt's
+//! Transfer and Sync logs so it can be compared against the quoted profit per cycle.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use alloy::primitives::{Address, Log, B256, I256, U256};
+use alloy::providers::Provider;
+use alloy::sol;
+use alloy::sol_types::SolEvent;
+use bigdecimal::BigDecimal;
+use diesel_async::AsyncPgConnection;
+use eyre::Result;
+use itertools::Itertools;
+
+use super::plan::ExecutionPlan;
+use crate::arb::cycle_quote::CycleQuote;
+use crate::arb::pool::PoolId;
+use crate::models::execution::{self, Execution, ExecutionOutcome, ExecutionStatus, NewExecution};
+use crate::models::pair::DBAddress;
+use crate::utils::app_context::AppContext;
+use crate::utils::numeric::{big_decimal_to_u256, i256_to_big_decimal, u256_to_big_decimal};
+
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 value);
+    event Sync(uint112 reserve0, uint112 reserve1);
+}
+
+/// How long a transaction may be unknown to the node before it is considered dropped
+const DROP_TIMEOUT: Duration = Duration::from_mins(2);
+
+/// Number of pending executions resolved per iteration
+const BATCH_SIZE: i64 = 50;
+
+/// Transaction parameters of a broadcast execution
+#[derive(Debug, Clone)]
+pub struct Submission {
+    /// The transaction hash
+    pub tx_hash: B256,
+    /// The transaction nonce
+    pub nonce: u64,
+    /// The transaction gas limit
+    pub gas_limit: u64,
+    /// The transaction max fee per gas
+    pub max_fee_per_gas: u128,
+    /// The transaction max priority fee per gas
+    pub max_priority_fee_per_gas: u128,
+}
+
+/// Record a broadcast execution as `Pending`
+///
+/// # Returns
+///
+/// The ID of the new execution
+///
+/// # Errors
+///
+/// Returns an error if the database insert fails
+pub async fn record(
+    conn: &mut AsyncPgConnection,
+    quote: &CycleQuote,
+    plan: &ExecutionPlan,
+    submission: &Submission,
+) -> Result<i32> {
+    let execution = NewExecution {
+        cycle: cycle_key(plan),
+        start_token: DBAddress::new(plan.token_in()),
+        amount_in: u256_to_big_decimal(quote.amount_in()),
+        amount_out: u256_to_big_decimal(quote.amount_out()),
+        quoted_profit: i256_to_big_decimal(quote.profit()),
+        tx_hash: submission.tx_hash.to_string(),
+        nonce: i64::try_from(submission.nonce)?,
+        gas_limit: i64::try_from(submission.gas_limit)?,
+        max_fee_per_gas: BigDecimal::from(submission.max_fee_per_gas),
+        max_priority_fee_per_gas: BigDecimal::from(submission.max_priority_fee_per_gas),
+    };
+
+    let id = execution.insert(conn).await?;
+
+    log::info!(
+        "execution::tracker: Recorded execution {id} ({}) for cycle {}",
+        execution.tx_hash,
+        execution.cycle
+    );
+
+    Ok(id)
+}
+
+/// Resolve pending executions.
+/// This runs as a worker thread to continuously track submitted transactions.
+///
+/// # Arguments
+///
+/// * `ctx` - Application context
+/// * `wallet` - The address sending the transactions
+/// * `executor` - The executor contract, which holds the profit
+///
+/// # Errors
+///
+/// * If RPC calls fail
+/// * If database operations fail
+pub async fn track(ctx: &AppContext, wallet: Address, executor: Address) -> Result<()> {
+    loop {
+        let resolved = sync(ctx, wallet, executor).await?;
+
+        if resolved == 0 {
+            tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        }
+    }
+}
+
+/// Resolve a batch of pending executions
+///
+/// # Returns
+///
+/// The number of executions resolved
+async fn sync(ctx: &AppContext, wallet: Address, executor: Address) -> Result<usize> {
+    let mut conn = ctx.db.get().await?;
+    let pending = execution::pending(&mut conn, BATCH_SIZE).await?;
+
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    // The nonce of the next transaction that will be mined from the wallet.
+    // Anything below it is final: either our transaction or a replacement was mined.
+    let mined_nonce = ctx.base_provider.get_transaction_count(wallet).await?;
+
+    let mut resolved = 0;
+    for execution in &pending {
+        match resolve(ctx, execution, executor, mined_nonce).await {
+            Ok(Some(outcome)) => {
+                log::info!(
+                    "execution::tracker: Execution {} ({}) is {:?}, realized profit: {:?}, quoted: {}",
+                    execution.id,
+                    execution.tx_hash,
+                    outcome.status,
+                    outcome.realized_profit,
+                    execution.quoted_profit
+                );
+                execution.update_outcome(&mut conn, &outcome).await?;
+                resolved += 1;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                log::error!(
+                    "execution::tracker: Failed to resolve execution {} ({}): {e}",
+                    execution.id,
+                    execution.tx_hash
+                );
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Work out what happened to a pending execution
+///
+/// # Returns
+///
+/// The outcome, or `None` if the execution is still pending
+async fn resolve(
+    ctx: &AppContext,
+    execution: &Execution,
+    executor: Address,
+    mined_nonce: u64,
+) -> Result<Option<ExecutionOutcome>> {
+    let provider = &ctx.base_provider;
+    let tx_hash = B256::from_str(&execution.tx_hash)?;
+    let now = chrono::Utc::now().naive_utc();
+
+    if let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? {
+        let status = if receipt.status() {
+            ExecutionStatus::Included
+        } else {
+            ExecutionStatus::Reverted
+        };
+
+        let logs = receipt
+            .inner
+            .logs()
+            .iter()
+            .map(|log| log.inner.clone())
+            .collect::<Vec<_>>();
+        let pairs = execution
+            .cycle
+            .split(',')
+            .map(Address::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        let amount_in = big_decimal_to_u256(&execution.amount_in).unwrap_or_default();
+
+        let outputs = leg_outputs(&logs, &pairs);
+        log::debug!(
+            "execution::tracker: Execution {} ({}) legs sent out {:?}",
+            execution.id,
+            execution.tx_hash,
+            outputs
+        );
+        let profit = realized_profit(
+            &logs,
+            execution.start_token.value,
+            executor,
+            &outputs,
+            amount_in,
+        );
+
+        return Ok(Some(ExecutionOutcome {
+            status,
+            block_number: receipt.block_number.map(i64::try_from).transpose()?,
+            gas_used: Some(i64::try_from(receipt.gas_used)?),
+            effective_gas_price: Some(BigDecimal::from(receipt.effective_gas_price)),
+            realized_profit: Some(i256_to_big_decimal(profit)),
+            updated_at: now,
+        }));
+    }
+
+    let nonce = u64::try_from(execution.nonce)?;
+    if nonce < mined_nonce {
+        // The nonce is used but our transaction has no receipt
+        return Ok(Some(unmined(ExecutionStatus::Replaced, now)));
+    }
+
+    let known = provider.get_transaction_by_hash(tx_hash).await?.is_some();
+    let age = now
+        .signed_duration_since(execution.created_at)
+        .to_std()
+        .unwrap_or_default();
+
+    if !known && age > DROP_TIMEOUT {
+        return Ok(Some(unmined(ExecutionStatus::Dropped, now)));
+    }
+
+    Ok(None)
+}
+
+/// Outcome of an execution that was never mined
+fn unmined(status: ExecutionStatus, now: chrono::NaiveDateTime) -> ExecutionOutcome {
+    ExecutionOutcome {
+        status,
+        block_number: None,
+        gas_used: None,
+        effective_gas_price: None,
+        realized_profit: None,
+        updated_at: now,
+    }
+}
+
+/// Amount of tokens every pair of a cycle sent out, in cycle order
+///
+/// A pair transfers the output of a swap before it emits Sync with its new reserves, so the
+/// Transfers out of a pair up to its Sync are the output of its leg. `None` for pairs that did
+/// not Sync, e.g. because the transaction reverted.
+#[must_use]
+pub fn leg_outputs(logs: &[Log], pairs: &[Address]) -> Vec<Option<U256>> {
+    let mut sent = HashMap::<Address, U256>::new();
+    let mut outputs = HashMap::new();
+
+    for log in logs {
+        if let Ok(transfer) = Transfer::decode_log(log, true) {
+            if pairs.contains(&transfer.from) {
+                let amount = sent.entry(transfer.from).or_default();
+                *amount = amount.saturating_add(transfer.value);
+            }
+        } else if pairs.contains(&log.address) && Sync::decode_log(log, true).is_ok() {
+            outputs.insert(log.address, sent.remove(&log.address).unwrap_or_default());
+        }
+    }
+
+    pairs
+        .iter()
+        .map(|pair| outputs.get(pair).copied())
+        .collect()
+}
+
+/// Profit in `token` realized by `owner`, from the `leg_outputs` of the cycle
+///
+/// The cycle sends `amount_in` of `token` to its first pair and gets the output of its last leg
+/// back, so once every leg synced the profit is that output minus `amount_in`. Transfers that
+/// are not part of the cycle are left out this way.
+///
+/// When a leg did not sync, the profit falls back to the net amount of `token` the Transfer logs
+/// send to `owner`: incoming minus outgoing transfers. Transfers of other tokens and logs that
+/// are not Transfers are ignored.
+#[must_use]
+pub fn realized_profit(
+    logs: &[Log],
+    token: Address,
+    owner: Address,
+    outputs: &[Option<U256>],
+    amount_in: U256,
+) -> I256 {
+    let synced: Option<Vec<U256>> = outputs.iter().copied().collect();
+    if let Some(amount_out) = synced.and_then(|synced| synced.last().copied()) {
+        return I256::from_raw(amount_out).saturating_sub(I256::from_raw(amount_in));
+    }
+
+    logs.iter()
+        .filter(|log| log.address == token)
+        .filter_map(|log| Transfer::decode_log(log, true).ok())
+        .fold(I256::ZERO, |profit, transfer| {
+            let value = I256::from_raw(transfer.value);
+            match (transfer.from == owner, transfer.to == owner) {
+                (false, true) => profit.saturating_add(value),
+                (true, false) => profit.saturating_sub(value),
+                _ => profit,
+            }
+        })
+}
+
+/// Pair addresses of the cycle in execution order, comma separated
+fn cycle_key(plan: &ExecutionPlan) -> String {
+    plan.pools().iter().map(PoolId::address).join(",")
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloy::primitives::aliases::U112;
+
+    use super::*;
+    use crate::arb::test_helpers::address_from_str;
+
+    fn transfer(token: &str, from: &str, to: &str, value: u64) -> Log {
+        let event = Transfer {
+            from: address_from_str(from),
+            to: address_from_str(to),
+            value: U256::from(value),
+        };
+        Log {
+            address: address_from_str(token),
+            data: event.encode_log_data(),
+        }
+    }
+
+    fn sync(pair: &str) -> Log {
+        let event = Sync {
+            reserve0: U112::from(1_000),
+            reserve1: U112::from(1_000),
+        };
+        Log {
+            address: address_from_str(pair),
+            data: event.encode_log_data(),
+        }
+    }
+
+    /// The logs of a cycle A -> B -> A through F1 and F2, sending 25 A and getting 34 A back
+    fn cycle_logs() -> Vec<Log> {
+        vec![
+            transfer("A", "E", "F1", 25),
+            transfer("B", "F1", "F2", 39),
+            sync("F1"),
+            transfer("A", "F2", "E", 34),
+            sync("F2"),
+        ]
+    }
+
+    fn pairs() -> Vec<Address> {
+        vec![address_from_str("F1"), address_from_str("F2")]
+    }
+
+    #[test]
+    fn test_leg_outputs() {
+        assert_eq!(
+            leg_outputs(&cycle_logs(), &pairs()),
+            [Some(U256::from(39)), Some(U256::from(34))]
+        );
+    }
+
+    #[test]
+    fn test_leg_outputs_without_sync() {
+        let logs = &cycle_logs()[..4];
+
+        assert_eq!(leg_outputs(logs, &pairs()), [Some(U256::from(39)), None]);
+    }
+
+    #[test]
+    fn test_realized_profit_from_legs() {
+        let mut logs = cycle_logs();
+        // A transfer of the start token to the executor that is not part of the cycle
+        logs.push(transfer("A", "F3", "E", 100));
+        let outputs = leg_outputs(&logs, &pairs());
+
+        assert_eq!(
+            realized_profit(
+                &logs,
+                address_from_str("A"),
+                address_from_str("E"),
+                &outputs,
+                U256::from(25)
+            ),
+            I256::try_from(9).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_realized_profit() {
+        let logs = [
+            transfer("A", "E", "F1", 25),
+            transfer("B", "F1", "F2", 39),
+            transfer("A", "F2", "E", 34),
+        ];
+        let outputs = leg_outputs(&logs, &pairs());
+
+        assert_eq!(outputs, [None, None]);
+        assert_eq!(
+            realized_profit(
+                &logs,
+                address_from_str("A"),
+                address_from_str("E"),
+                &outputs,
+                U256::from(25)
+            ),
+            I256::try_from(9).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_realized_loss() {
+        let logs = [transfer("A", "E", "F1", 25), transfer("A", "F2", "E", 20)];
+
+        assert_eq!(
+            realized_profit(
+                &logs,
+                address_from_str("A"),
+                address_from_str("E"),
+                &[],
+                U256::from(25)
+            ),
+            I256::try_from(-5).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_realized_profit_ignores_other_logs() {
+        let mut logs = vec![transfer("A", "F2", "E", 34)];
+        logs.push(Log {
+            address: address_from_str("A"),
+            data: alloy::primitives::LogData::new_unchecked(vec![], vec![0u8; 64].into()),
+        });
+        // A transfer of another token to the executor
+        logs.push(transfer("B", "F1", "E", 100));
+
+        assert_eq!(
+            realized_profit(
+                &logs,
+                address_from_str("A"),
+                address_from_str("E"),
+                &[],
+                U256::ZERO
+            ),
+            I256::try_from(34).unwrap()
+        );
+    }
+}
@@ -15,9 +15,13 @@
  *
  * - `arb`: Core arbitrage detection and execution logic
  * - `bootstrap`: System initialization and startup procedures
+ * - `chain`: Chain calls behind a mockable trait
  * - `config`: Configuration management for the system
  * - `db_service`: Database interaction for persistent storage
+ * - `execution`: Simulation and execution of arbitrage opportunities
  * - `models`: Data models for the application
+ * - `pricing`: Token pricing through the deepest liquidity
+ * - `repo`: Database reads and writes behind mockable traits
  * - `schemas`: Database schema definitions
  * - `sync`: Blockchain synchronization components
  * - `utils`: Utility functions and helpers
@@ -27,10 +31,18 @@
 pub mod arb;
 /// System initialization and startup procedures
 pub mod bootstrap;
+/// Chain calls behind a mockable trait
+pub mod chain;
 /// Configuration management for the system
 pub mod config;
+/// Simulation and execution of arbitrage opportunities
+pub mod execution;
 /// Data models for the application
 pub mod models;
+/// Token pricing through the deepest liquidity
+pub mod pricing;
+/// Database reads and writes behind mockable traits
+pub mod repo;
 /// Database schema definitions
 pub mod schemas;
 /// Blockchain synchronization components
@@ -1,6 +1,7 @@
 #![doc = "Fly - Blockchain Arbitrage Detection and Execution CLI"]
 
 /// Main entry point for the application
+use crate::bootstrap::world::LoadOptions;
 use crate::utils::app_context::AppContext;
 use crate::utils::logger::setup_logger;
 use clap::{Parser, Subcommand};
@@ -11,12 +12,18 @@ mod arb;
 mod bootstrap;
 /// Arbitrage detection and execution logic
 mod bot;
+/// Chain calls behind a mockable trait
+mod chain;
 /// Configuration management for the system
 mod config;
 /// Data models for the application
 mod models;
 /// Notification system
 mod notify;
+/// Token pricing through the deepest liquidity
+mod pricing;
+/// Database reads and writes behind mockable traits
+mod repo;
 /// Database schema definitions
 mod schemas;
 /// Blockchain synchronization components
@@ -33,6 +40,7 @@ struct Cli {
     command: Option<Commands>,
 }
 
+/// Available subcommands
 #[derive(Subcommand)]
 enum Commands {
     /// [DEBUG] Sync `Sync` events
@@ -51,8 +59,16 @@ enum Commands {
     SyncPairCreatedEvents,
     /// [DEBUG] Sync exchange rates
     SyncExchangeRates,
-    /// [DEBUG] Sync WETH price from Moralis API
+    /// [DEBUG] Sync WETH price from the configured price sources
     SyncWeth,
+    /// [DEBUG] Sync wallet and executor token balances
+    SyncBalances,
+    /// [DEBUG] Backfill missed Sync and `PairCreated` events
+    SyncBackfill,
+    /// [DEBUG] Maintain reserve history partitions
+    SyncReserveHistory,
+    /// [DEBUG] Load the world from the database and report the pairs it skipped
+    LoadWorld,
     /// Start the bot
     Start,
 }
@@ -92,6 +108,18 @@ async fn main() -> Result<()> {
         Some(Commands::SyncWeth) => {
             sync::weth(&ctx).await?;
         }
+        Some(Commands::SyncBalances) => {
+            sync::balances(&ctx).await?;
+        }
+        Some(Commands::SyncBackfill) => {
+            sync::backfill(&ctx).await?;
+        }
+        Some(Commands::SyncReserveHistory) => {
+            sync::reserve_history(&ctx).await?;
+        }
+        Some(Commands::LoadWorld) => {
+            bootstrap::world::load(&ctx.repo, &LoadOptions::from_env()).await?;
+        }
         Some(Commands::Start) => {
             bot::start(ctx).await?;
         }
@@ -0,0 +1,44 @@
+use alloy::primitives::{Address, B256, U256};
+
+/// Reserves of a pair after a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolUpdate {
+    /// The address of the pair
+    pub address: Address,
+    /// The reserve of the token0
+    pub reserve0: U256,
+    /// The reserve of the token1
+    pub reserve1: U256,
+    /// The index of the Sync log the reserves are from
+    pub log_index: u64,
+}
+
+/// A Sync log, as recorded in `reserve_history`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncLog {
+    /// The address of the pair
+    pub address: Address,
+    /// The index of the log in its block
+    pub log_index: u64,
+    /// The transaction that emitted the log
+    pub tx_hash: B256,
+    /// The reserve of the token0 after the log
+    pub reserve0: U256,
+    /// The reserve of the token1 after the log
+    pub reserve1: U256,
+}
+
+/// Pools whose reserves changed in a committed block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockUpdate {
+    /// The block number
+    pub number: u64,
+    /// The block hash
+    pub hash: B256,
+    /// The parent block hash, unknown for replayed blocks
+    pub parent_hash: Option<B256>,
+    /// The updated pools, sorted by address
+    pub pools: Vec<PoolUpdate>,
+    /// Every Sync log of the block, in log order
+    pub syncs: Vec<SyncLog>,
+}
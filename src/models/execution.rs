@@ -0,0 +1,251 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::dsl::{self, count_star};
+use diesel::expression::AsExpression;
+use diesel::pg::Pg;
+use diesel::result::Error;
+use diesel::serialize::ToSql;
+use diesel::sql_types::Text;
+use diesel::{
+    AsChangeset, ExpressionMethods, Insertable, QueryDsl, Queryable, Selectable, SelectableHelper,
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use super::pair::DBAddress;
+use crate::schemas::executions;
+
+/// The status of a submitted execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = crate::schemas::sql_types::ExecutionStatus)]
+pub enum ExecutionStatus {
+    /// Submitted, no receipt yet
+    Pending,
+    /// Mined and succeeded
+    Included,
+    /// Mined and reverted
+    Reverted,
+    /// Disappeared from the mempool without being mined
+    Dropped,
+    /// Another transaction with the same nonce was mined instead
+    Replaced,
+}
+
+impl FromStr for ExecutionStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Pending" => Ok(ExecutionStatus::Pending),
+            "Included" => Ok(ExecutionStatus::Included),
+            "Reverted" => Ok(ExecutionStatus::Reverted),
+            "Dropped" => Ok(ExecutionStatus::Dropped),
+            "Replaced" => Ok(ExecutionStatus::Replaced),
+            _ => Err("Invalid execution status".to_string()),
+        }
+    }
+}
+
+impl ToSql<crate::schemas::sql_types::ExecutionStatus, diesel::pg::Pg> for ExecutionStatus {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+    ) -> diesel::serialize::Result {
+        let s = match self {
+            ExecutionStatus::Pending => "Pending",
+            ExecutionStatus::Included => "Included",
+            ExecutionStatus::Reverted => "Reverted",
+            ExecutionStatus::Dropped => "Dropped",
+            ExecutionStatus::Replaced => "Replaced",
+        };
+        <str as ToSql<diesel::sql_types::Text, diesel::pg::Pg>>::to_sql(s, out)
+    }
+}
+
+impl FromSql<crate::schemas::sql_types::ExecutionStatus, Pg> for ExecutionStatus {
+    fn from_sql(
+        bytes: <Pg as diesel::backend::Backend>::RawValue<'_>,
+    ) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        match ExecutionStatus::from_str(&s) {
+            Ok(status) => Ok(status),
+            Err(e) => Err(Box::new(Error::DeserializationError(e.into()))
+                as Box<dyn std::error::Error + Send + Sync>),
+        }
+    }
+}
+
+/// An execution we submitted on chain
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::schemas::executions)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Execution {
+    /// The ID of the execution
+    pub id: i32,
+    /// Pair addresses of the cycle in execution order, comma separated
+    pub cycle: String,
+    /// The token the cycle starts and ends with
+    pub start_token: DBAddress,
+    /// The quoted amount in
+    pub amount_in: BigDecimal,
+    /// The quoted amount out
+    pub amount_out: BigDecimal,
+    /// The quoted profit in the start token
+    pub quoted_profit: BigDecimal,
+    /// The transaction hash
+    pub tx_hash: String,
+    /// The transaction nonce
+    pub nonce: i64,
+    /// The transaction gas limit
+    pub gas_limit: i64,
+    /// The transaction max fee per gas
+    pub max_fee_per_gas: BigDecimal,
+    /// The transaction max priority fee per gas
+    pub max_priority_fee_per_gas: BigDecimal,
+    /// The status of the execution
+    pub status: ExecutionStatus,
+    /// The block the transaction was mined in
+    pub block_number: Option<i64>,
+    /// Gas used by the transaction
+    pub gas_used: Option<i64>,
+    /// The gas price actually paid
+    pub effective_gas_price: Option<BigDecimal>,
+    /// Profit in the start token computed from the receipt Transfer and Sync logs
+    pub realized_profit: Option<BigDecimal>,
+    /// When the execution was submitted
+    pub created_at: NaiveDateTime,
+    /// When the execution was last updated
+    pub updated_at: NaiveDateTime,
+}
+
+impl Execution {
+    /// Update the execution with its outcome
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails
+    pub async fn update_outcome(
+        &self,
+        conn: &mut AsyncPgConnection,
+        outcome: &ExecutionOutcome,
+    ) -> Result<(), Error> {
+        diesel::update(executions::table.find(self.id))
+            .set(outcome)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// A new execution, recorded right after the transaction is sent
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schemas::executions)]
+pub struct NewExecution {
+    /// Pair addresses of the cycle in execution order, comma separated
+    pub cycle: String,
+    /// The token the cycle starts and ends with
+    pub start_token: DBAddress,
+    /// The quoted amount in
+    pub amount_in: BigDecimal,
+    /// The quoted amount out
+    pub amount_out: BigDecimal,
+    /// The quoted profit in the start token
+    pub quoted_profit: BigDecimal,
+    /// The transaction hash
+    pub tx_hash: String,
+    /// The transaction nonce
+    pub nonce: i64,
+    /// The transaction gas limit
+    pub gas_limit: i64,
+    /// The transaction max fee per gas
+    pub max_fee_per_gas: BigDecimal,
+    /// The transaction max priority fee per gas
+    pub max_priority_fee_per_gas: BigDecimal,
+}
+
+impl NewExecution {
+    /// Insert the execution
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new execution
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database insert fails
+    pub async fn insert(&self, conn: &mut AsyncPgConnection) -> Result<i32, Error> {
+        diesel::insert_into(executions::table)
+            .values(self)
+            .returning(executions::id)
+            .get_result(conn)
+            .await
+    }
+}
+
+/// What happened to an execution
+#[derive(AsChangeset, Debug)]
+#[diesel(table_name = crate::schemas::executions)]
+pub struct ExecutionOutcome {
+    /// The new status
+    pub status: ExecutionStatus,
+    /// The block the transaction was mined in
+    pub block_number: Option<i64>,
+    /// Gas used by the transaction
+    pub gas_used: Option<i64>,
+    /// The gas price actually paid
+    pub effective_gas_price: Option<BigDecimal>,
+    /// Profit in the start token computed from the receipt Transfer and Sync logs
+    pub realized_profit: Option<BigDecimal>,
+    /// When the outcome was observed
+    pub updated_at: NaiveDateTime,
+}
+
+/// Realized against quoted profit of a cycle
+#[derive(Queryable, Debug)]
+pub struct CycleProfit {
+    /// Pair addresses of the cycle in execution order, comma separated
+    pub cycle: String,
+    /// Number of mined executions, included or reverted
+    pub executions: i64,
+    /// Sum of quoted profits in the start token
+    pub quoted_profit: Option<BigDecimal>,
+    /// Sum of realized profits in the start token
+    pub realized_profit: Option<BigDecimal>,
+}
+
+/// Load executions that have not been resolved yet
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+pub async fn pending(conn: &mut AsyncPgConnection, limit: i64) -> Result<Vec<Execution>, Error> {
+    executions::table
+        .filter(executions::status.eq(ExecutionStatus::Pending))
+        .order(executions::nonce.asc())
+        .limit(limit)
+        .select(Execution::as_select())
+        .load(conn)
+        .await
+}
+
+/// Realized against quoted profit per cycle, for mined executions
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+pub async fn profit_by_cycle(conn: &mut AsyncPgConnection) -> Result<Vec<CycleProfit>, Error> {
+    executions::table
+        .filter(executions::status.eq_any([ExecutionStatus::Included, ExecutionStatus::Reverted]))
+        .group_by(executions::cycle)
+        .select((
+            executions::cycle,
+            count_star(),
+            dsl::sum(executions::quoted_profit),
+            dsl::sum(executions::realized_profit),
+        ))
+        .load::<CycleProfit>(conn)
+        .await
+}
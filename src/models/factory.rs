@@ -7,11 +7,9 @@ use diesel::pg::Pg;
 use diesel::result::Error;
 use diesel::serialize::ToSql;
 use diesel::sql_types::Text;
+use diesel::Insertable;
 use diesel::Queryable;
 use diesel::Selectable;
-use diesel::{ExpressionMethods, Insertable};
-use diesel_async::AsyncPgConnection;
-use diesel_async::RunQueryDsl;
 
 /// The status of a factory
 #[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
@@ -25,6 +23,17 @@ pub enum FactoryStatus {
     Synced,
     /// The factory is broken - workers have failed to sync it due to reverts
     Broken,
+    /// The factory is only known from a `PairCreated` event - its pairs are not trusted until
+    /// it is validated
+    Unvalidated,
+}
+
+impl FactoryStatus {
+    /// Whether pairs of the factory can be trusted
+    #[must_use]
+    pub const fn is_trusted(self) -> bool {
+        !matches!(self, Self::Unvalidated)
+    }
 }
 
 impl FromStr for FactoryStatus {
@@ -36,6 +45,7 @@ impl FromStr for FactoryStatus {
             "Syncing" => Ok(FactoryStatus::Syncing),
             "Synced" => Ok(FactoryStatus::Synced),
             "Broken" => Ok(FactoryStatus::Broken),
+            "Unvalidated" => Ok(FactoryStatus::Unvalidated),
             _ => Err("Invalid factory status".to_string()),
         }
     }
@@ -51,6 +61,7 @@ impl ToSql<crate::schemas::sql_types::FactoryStatus, diesel::pg::Pg> for Factory
             FactoryStatus::Syncing => "Syncing",
             FactoryStatus::Synced => "Synced",
             FactoryStatus::Broken => "Broken",
+            FactoryStatus::Unvalidated => "Unvalidated",
         };
         <str as ToSql<diesel::sql_types::Text, diesel::pg::Pg>>::to_sql(s, out)
     }
@@ -69,7 +80,57 @@ impl FromSql<crate::schemas::sql_types::FactoryStatus, Pg> for FactoryStatus {
     }
 }
 
-use crate::schemas::factories;
+/// The kind of DEX a factory belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = crate::schemas::sql_types::FactoryKind)]
+pub enum FactoryKind {
+    /// A Uniswap V2 fork - `getPair` returns its pairs
+    UniswapV2,
+    /// A Solidly fork (Velodrome, Aerodrome) - `isPair` returns true for its pairs
+    Solidly,
+    /// The factory implements none of the interfaces we know
+    Unknown,
+}
+
+impl FromStr for FactoryKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "UniswapV2" => Ok(FactoryKind::UniswapV2),
+            "Solidly" => Ok(FactoryKind::Solidly),
+            "Unknown" => Ok(FactoryKind::Unknown),
+            _ => Err("Invalid factory kind".to_string()),
+        }
+    }
+}
+
+impl ToSql<crate::schemas::sql_types::FactoryKind, diesel::pg::Pg> for FactoryKind {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+    ) -> diesel::serialize::Result {
+        let s = match self {
+            FactoryKind::UniswapV2 => "UniswapV2",
+            FactoryKind::Solidly => "Solidly",
+            FactoryKind::Unknown => "Unknown",
+        };
+        <str as ToSql<diesel::sql_types::Text, diesel::pg::Pg>>::to_sql(s, out)
+    }
+}
+
+impl FromSql<crate::schemas::sql_types::FactoryKind, Pg> for FactoryKind {
+    fn from_sql(
+        bytes: <Pg as diesel::backend::Backend>::RawValue<'_>,
+    ) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        match FactoryKind::from_str(&s) {
+            Ok(kind) => Ok(kind),
+            Err(e) => Err(Box::new(Error::DeserializationError(e.into()))
+                as Box<dyn std::error::Error + Send + Sync>),
+        }
+    }
+}
 
 use super::pair::DBAddress;
 
@@ -79,16 +140,16 @@ use super::pair::DBAddress;
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Factory {
     /// The ID of the factory
-    id: i32,
+    pub id: i32,
     /// The address of the factory
-    address: DBAddress,
+    pub address: DBAddress,
     /// The last pair ID of the factory
-    last_pair_id: i32,
+    pub last_pair_id: i32,
     /// The status of the factory
     ///
     /// This is future functionality.
     #[allow(dead_code)]
-    status: FactoryStatus,
+    pub status: FactoryStatus,
 }
 
 impl Factory {
@@ -109,25 +170,6 @@ impl Factory {
     pub fn last_pair_id(&self) -> i32 {
         self.last_pair_id
     }
-
-    /// Update the status of the factory
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if the database update fails
-    pub async fn update_status(
-        &mut self,
-        conn: &mut AsyncPgConnection,
-        status: FactoryStatus,
-    ) -> Result<(), Error> {
-        diesel::update(factories::table)
-            .filter(factories::id.eq(self.id()))
-            .set(factories::status.eq(status))
-            .execute(conn)
-            .await?;
-
-        Ok(())
-    }
 }
 
 /// A new factory
@@ -162,3 +204,35 @@ impl NewFactory {
         self.last_pair_id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factory_status_from_str() {
+        assert_eq!(
+            FactoryStatus::from_str("Unvalidated"),
+            Ok(FactoryStatus::Unvalidated)
+        );
+        assert_eq!(FactoryStatus::from_str("Synced"), Ok(FactoryStatus::Synced));
+        assert!(FactoryStatus::from_str("Syncing...").is_err());
+    }
+
+    #[test]
+    fn test_factory_kind_from_str() {
+        assert_eq!(FactoryKind::from_str("Solidly"), Ok(FactoryKind::Solidly));
+        assert_eq!(
+            FactoryKind::from_str("UniswapV2"),
+            Ok(FactoryKind::UniswapV2)
+        );
+        assert!(FactoryKind::from_str("V2").is_err());
+    }
+
+    #[test]
+    fn test_factory_status_is_trusted() {
+        assert!(FactoryStatus::Unsynced.is_trusted());
+        assert!(FactoryStatus::Broken.is_trusted());
+        assert!(!FactoryStatus::Unvalidated.is_trusted());
+    }
+}
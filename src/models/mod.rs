@@ -1,6 +1,31 @@
+/// Block model
+pub mod block;
+/// Execution model
+///
+/// Only used by the `execution` module, which is not part of the binary yet.
+#[allow(dead_code)]
+pub mod execution;
 /// Factory model
 pub mod factory;
+/// Nonce model
+///
+/// Only used by the `execution` module, which is not part of the binary yet.
+#[allow(dead_code)]
+pub mod nonce;
+/// Opportunity model
+///
+/// The analysis queries are only run by hand, and most skip reasons come with execution, which
+/// is not part of the binary yet.
+#[allow(dead_code)]
+pub mod opportunity;
 /// Pair model
 pub mod pair;
+/// Reserve history model
+pub mod reserve_history;
+/// Sync state model
+pub mod sync_state;
 /// Token model
 pub mod token;
+
+/// Rows per insert statement, keeping well below the Postgres limit of 65535 bind parameters
+pub const ROWS_PER_INSERT: usize = 5_000;
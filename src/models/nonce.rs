@@ -0,0 +1,204 @@
+use alloy::primitives::Address;
+use diesel::dsl::sql;
+use diesel::result::Error;
+use diesel::sql_types::BigInt;
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use super::pair::DBAddress;
+use crate::schemas::nonces;
+
+/// The last nonce used by a sending address, `-1` if its first nonce was given back
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schemas::nonces)]
+pub struct Nonce {
+    /// The sending address
+    address: DBAddress,
+    /// The last nonce used
+    nonce: i64,
+}
+
+impl Nonce {
+    /// Create a new nonce record
+    #[must_use]
+    pub fn new(address: Address, nonce: i64) -> Self {
+        Self {
+            address: DBAddress::new(address),
+            nonce,
+        }
+    }
+}
+
+/// Reserve the nonce after the last used one of the address, but at least `floor`
+///
+/// The row is locked by the upsert, so concurrent reservations of several processes get
+/// distinct nonces.
+///
+/// # Errors
+///
+/// Returns an error if the database upsert fails
+pub async fn reserve(
+    conn: &mut AsyncPgConnection,
+    address: Address,
+    floor: i64,
+) -> Result<i64, Error> {
+    diesel::insert_into(nonces::table)
+        .values(Nonce::new(address, floor))
+        .on_conflict(nonces::address)
+        .do_update()
+        .set((
+            nonces::nonce.eq(sql::<BigInt>("GREATEST(nonces.nonce + 1, excluded.nonce)")),
+            nonces::updated_at.eq(diesel::dsl::now),
+        ))
+        .returning(nonces::nonce)
+        .get_result(conn)
+        .await
+}
+
+/// Give back the last used nonce of the address
+///
+/// # Returns
+///
+/// `true` if `nonce` was the last used one and will be reserved again, `false` if a later
+/// nonce was reserved in the meantime
+///
+/// # Errors
+///
+/// Returns an error if the database update fails
+pub async fn release(
+    conn: &mut AsyncPgConnection,
+    address: Address,
+    nonce: i64,
+) -> Result<bool, Error> {
+    let updated = diesel::update(
+        nonces::table
+            .filter(nonces::address.eq(address.to_string()))
+            .filter(nonces::nonce.eq(nonce)),
+    )
+    .set((
+        nonces::nonce.eq(nonce - 1),
+        nonces::updated_at.eq(diesel::dsl::now),
+    ))
+    .execute(conn)
+    .await?;
+
+    Ok(updated == 1)
+}
+
+/// Move the last used nonce of the address back below `next`, after the chain showed that
+/// nonces from `next` on never reached it
+///
+/// # Arguments
+///
+/// * `next` - The nonce to hand out again
+/// * `last` - The last nonce reserved by the caller, the rewind only happens if nobody reserved
+///   a nonce after it
+///
+/// # Returns
+///
+/// `true` if the last used nonce moved back
+///
+/// # Errors
+///
+/// Returns an error if the database update fails
+pub async fn rewind(
+    conn: &mut AsyncPgConnection,
+    address: Address,
+    next: i64,
+    last: i64,
+) -> Result<bool, Error> {
+    if next > last {
+        return Ok(false);
+    }
+
+    let updated = diesel::update(
+        nonces::table
+            .filter(nonces::address.eq(address.to_string()))
+            .filter(nonces::nonce.eq(last)),
+    )
+    .set((
+        nonces::nonce.eq(next - 1),
+        nonces::updated_at.eq(diesel::dsl::now),
+    ))
+    .execute(conn)
+    .await?;
+
+    Ok(updated == 1)
+}
+
+/// Load the last nonce used by an address
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+pub async fn last_nonce(
+    conn: &mut AsyncPgConnection,
+    address: Address,
+) -> Result<Option<i64>, Error> {
+    nonces::table
+        .filter(nonces::address.eq(address.to_string()))
+        .select(nonces::nonce)
+        .first(conn)
+        .await
+        .optional()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::utils::test_db;
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let address = Address::repeat_byte(0x11);
+
+        assert_eq!(reserve(&mut conn, address, 5).await.unwrap(), 5);
+        assert_eq!(reserve(&mut conn, address, 0).await.unwrap(), 6);
+        // The chain moved ahead of the last used nonce
+        assert_eq!(reserve(&mut conn, address, 10).await.unwrap(), 10);
+        assert_eq!(last_nonce(&mut conn, address).await.unwrap(), Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_release() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let address = Address::repeat_byte(0x12);
+
+        reserve(&mut conn, address, 0).await.unwrap();
+        reserve(&mut conn, address, 0).await.unwrap();
+
+        // Only the last used nonce can be given back
+        assert!(!release(&mut conn, address, 0).await.unwrap());
+        assert!(release(&mut conn, address, 1).await.unwrap());
+        assert_eq!(last_nonce(&mut conn, address).await.unwrap(), Some(0));
+        assert_eq!(reserve(&mut conn, address, 0).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rewind() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let address = Address::repeat_byte(0x13);
+
+        for _ in 0..3 {
+            reserve(&mut conn, address, 0).await.unwrap();
+        }
+
+        // Another holder reserved 3 after our last nonce 2
+        reserve(&mut conn, address, 0).await.unwrap();
+        assert!(!rewind(&mut conn, address, 1, 2).await.unwrap());
+        assert_eq!(last_nonce(&mut conn, address).await.unwrap(), Some(3));
+
+        // Nobody reserved after 3
+        assert!(rewind(&mut conn, address, 1, 3).await.unwrap());
+        assert_eq!(last_nonce(&mut conn, address).await.unwrap(), Some(0));
+        assert_eq!(reserve(&mut conn, address, 0).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,377 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::dsl::{self, count_star};
+use diesel::result::Error;
+use diesel::{
+    ExpressionMethods, Insertable, PgSortExpressionMethods, QueryDsl, Queryable, Selectable,
+    SelectableHelper,
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use super::pair::DBAddress;
+use super::ROWS_PER_INSERT;
+use crate::schemas::opportunities;
+
+/// Why a detected opportunity was not executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The profit is below the execution threshold
+    BelowThreshold,
+    /// Another opportunity of the same update was executed instead
+    Outranked,
+    /// The start token has no USD rate, so the opportunity can't be ranked
+    Unpriced,
+    /// A pool of the cycle is on cooldown after failing
+    PoolOnCooldown,
+    /// The simulation of the cycle failed
+    SimulationFailed,
+    /// Gas costs more than the cycle earns
+    GasExceedsProfit,
+    /// Execution is not part of the binary yet
+    ExecutionDisabled,
+}
+
+impl SkipReason {
+    /// The value stored in `opportunities.skip_reason`
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::BelowThreshold => "BelowThreshold",
+            Self::Outranked => "Outranked",
+            Self::Unpriced => "Unpriced",
+            Self::PoolOnCooldown => "PoolOnCooldown",
+            Self::SimulationFailed => "SimulationFailed",
+            Self::GasExceedsProfit => "GasExceedsProfit",
+            Self::ExecutionDisabled => "ExecutionDisabled",
+        }
+    }
+}
+
+/// A profitable cycle the bot detected
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::schemas::opportunities)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Opportunity {
+    /// The ID of the opportunity
+    pub id: i64,
+    /// The block of the reserves the cycle was quoted on
+    pub block_number: i64,
+    /// Pair addresses of the cycle in execution order, comma separated
+    pub cycle: String,
+    /// The token the cycle starts and ends with
+    pub start_token: DBAddress,
+    /// The quoted amount in
+    pub amount_in: BigDecimal,
+    /// The quoted amount out
+    pub amount_out: BigDecimal,
+    /// Quoted profit in the start token, before gas
+    pub gross_profit: BigDecimal,
+    /// Quoted profit in the start token after gas, if gas was estimated
+    pub net_profit: Option<BigDecimal>,
+    /// Quoted profit in USD, if the start token has a USD rate
+    pub profit_usd: Option<BigDecimal>,
+    /// Quoted profit margin in basis points
+    pub margin_bps: i32,
+    /// Whether the opportunity was executed
+    pub executed: bool,
+    /// Why the opportunity was not executed
+    pub skip_reason: Option<String>,
+    /// When the opportunity was recorded
+    pub created_at: NaiveDateTime,
+}
+
+/// A new opportunity, recorded when a world update is quoted
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = crate::schemas::opportunities)]
+pub struct NewOpportunity {
+    /// The block of the reserves the cycle was quoted on
+    pub block_number: i64,
+    /// Pair addresses of the cycle in execution order, comma separated
+    pub cycle: String,
+    /// The token the cycle starts and ends with
+    pub start_token: DBAddress,
+    /// The quoted amount in
+    pub amount_in: BigDecimal,
+    /// The quoted amount out
+    pub amount_out: BigDecimal,
+    /// Quoted profit in the start token, before gas
+    pub gross_profit: BigDecimal,
+    /// Quoted profit in the start token after gas, if gas was estimated
+    pub net_profit: Option<BigDecimal>,
+    /// Quoted profit in USD, if the start token has a USD rate
+    pub profit_usd: Option<BigDecimal>,
+    /// Quoted profit margin in basis points
+    pub margin_bps: i32,
+    /// Whether the opportunity was executed
+    pub executed: bool,
+    /// Why the opportunity was not executed
+    pub skip_reason: Option<String>,
+}
+
+/// Opportunities with the same skip reason
+#[derive(Queryable, Debug)]
+pub struct SkipSummary {
+    /// Why the opportunities were not executed, `None` for executed ones
+    pub skip_reason: Option<String>,
+    /// Number of opportunities
+    pub opportunities: i64,
+    /// Sum of their profits in USD
+    pub profit_usd: Option<BigDecimal>,
+}
+
+/// Opportunities of a cycle
+#[derive(Queryable, Debug)]
+pub struct CycleSummary {
+    /// Pair addresses of the cycle in execution order, comma separated
+    pub cycle: String,
+    /// Number of opportunities
+    pub opportunities: i64,
+    /// Highest profit margin in basis points
+    pub max_margin_bps: Option<i32>,
+    /// Sum of profits in USD
+    pub profit_usd: Option<BigDecimal>,
+}
+
+/// Append opportunities
+///
+/// # Errors
+///
+/// Returns an error if the database insert fails
+pub async fn insert(conn: &mut AsyncPgConnection, rows: &[NewOpportunity]) -> Result<usize, Error> {
+    let mut inserted = 0;
+
+    for chunk in rows.chunks(ROWS_PER_INSERT) {
+        inserted += diesel::insert_into(opportunities::table)
+            .values(chunk)
+            .execute(conn)
+            .await?;
+    }
+
+    Ok(inserted)
+}
+
+/// Opportunities since a block that were not executed although worth at least `min_profit_usd`,
+/// most profitable first
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+pub async fn missed(
+    conn: &mut AsyncPgConnection,
+    min_profit_usd: &BigDecimal,
+    since_block: i64,
+    limit: i64,
+) -> Result<Vec<Opportunity>, Error> {
+    opportunities::table
+        .filter(opportunities::executed.eq(false))
+        .filter(opportunities::block_number.ge(since_block))
+        .filter(opportunities::profit_usd.ge(min_profit_usd))
+        .order(opportunities::profit_usd.desc())
+        .limit(limit)
+        .select(Opportunity::as_select())
+        .load(conn)
+        .await
+}
+
+/// Number and USD profit of the opportunities since a block, per skip reason
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+pub async fn by_skip_reason(
+    conn: &mut AsyncPgConnection,
+    since_block: i64,
+) -> Result<Vec<SkipSummary>, Error> {
+    opportunities::table
+        .filter(opportunities::block_number.ge(since_block))
+        .group_by(opportunities::skip_reason)
+        .select((
+            opportunities::skip_reason,
+            count_star(),
+            dsl::sum(opportunities::profit_usd),
+        ))
+        .load::<SkipSummary>(conn)
+        .await
+}
+
+/// Number, best margin and USD profit of the opportunities since a block, per cycle, most
+/// profitable first
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+pub async fn by_cycle(
+    conn: &mut AsyncPgConnection,
+    since_block: i64,
+    limit: i64,
+) -> Result<Vec<CycleSummary>, Error> {
+    opportunities::table
+        .filter(opportunities::block_number.ge(since_block))
+        .group_by(opportunities::cycle)
+        .select((
+            opportunities::cycle,
+            count_star(),
+            dsl::max(opportunities::margin_bps),
+            dsl::sum(opportunities::profit_usd),
+        ))
+        .order(dsl::sum(opportunities::profit_usd).desc().nulls_last())
+        .limit(limit)
+        .load::<CycleSummary>(conn)
+        .await
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloy::primitives::Address;
+
+    use super::*;
+    use crate::utils::test_db;
+
+    fn opportunity(
+        block_number: i64,
+        cycle: &str,
+        profit_usd: Option<i32>,
+        margin_bps: i32,
+        skip_reason: Option<SkipReason>,
+    ) -> NewOpportunity {
+        NewOpportunity {
+            block_number,
+            cycle: cycle.to_string(),
+            start_token: DBAddress::new(Address::repeat_byte(0xa)),
+            amount_in: BigDecimal::from(1_000),
+            amount_out: BigDecimal::from(1_100),
+            gross_profit: BigDecimal::from(100),
+            net_profit: None,
+            profit_usd: profit_usd.map(BigDecimal::from),
+            margin_bps,
+            executed: skip_reason.is_none(),
+            skip_reason: skip_reason.map(|reason| reason.as_str().to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missed() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let below = Some(SkipReason::BelowThreshold);
+        insert(
+            &mut conn,
+            &[
+                // Before the window
+                opportunity(99, "a", Some(50), 10, below),
+                opportunity(100, "a", Some(20), 10, below),
+                opportunity(100, "b", Some(40), 10, Some(SkipReason::Outranked)),
+                // Executed
+                opportunity(101, "a", Some(90), 10, None),
+                // Worth too little
+                opportunity(101, "b", Some(5), 10, below),
+                // Unpriced
+                opportunity(102, "c", None, 10, Some(SkipReason::Unpriced)),
+                opportunity(102, "a", Some(30), 10, below),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let rows = missed(&mut conn, &BigDecimal::from(10), 100, 10)
+            .await
+            .unwrap();
+        let profits: Vec<_> = rows.iter().map(|row| row.profit_usd.clone()).collect();
+        assert_eq!(profits, [40, 30, 20].map(|usd| Some(BigDecimal::from(usd))));
+
+        let top = missed(&mut conn, &BigDecimal::from(10), 100, 1)
+            .await
+            .unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].skip_reason.as_deref(), Some("Outranked"));
+    }
+
+    #[tokio::test]
+    async fn test_by_skip_reason() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let below = Some(SkipReason::BelowThreshold);
+        insert(
+            &mut conn,
+            &[
+                opportunity(99, "a", Some(50), 10, below),
+                opportunity(100, "a", Some(20), 10, below),
+                opportunity(101, "b", Some(5), 10, below),
+                opportunity(101, "a", Some(90), 10, None),
+                opportunity(102, "c", None, 10, Some(SkipReason::Unpriced)),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let mut summaries = by_skip_reason(&mut conn, 100).await.unwrap();
+        summaries.sort_by(|a, b| a.skip_reason.cmp(&b.skip_reason));
+        let summaries: Vec<_> = summaries
+            .iter()
+            .map(|s| {
+                (
+                    s.skip_reason.as_deref(),
+                    s.opportunities,
+                    s.profit_usd.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                (None, 1, Some(BigDecimal::from(90))),
+                (Some("BelowThreshold"), 2, Some(BigDecimal::from(25))),
+                (Some("Unpriced"), 1, None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_by_cycle() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let below = Some(SkipReason::BelowThreshold);
+        insert(
+            &mut conn,
+            &[
+                opportunity(99, "d", Some(500), 90, below),
+                opportunity(100, "a", Some(20), 10, below),
+                opportunity(101, "a", Some(30), 40, None),
+                opportunity(100, "b", Some(40), 20, below),
+                opportunity(102, "c", None, 70, Some(SkipReason::Unpriced)),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let cycles = by_cycle(&mut conn, 100, 10).await.unwrap();
+        let cycles: Vec<_> = cycles
+            .iter()
+            .map(|c| {
+                (
+                    c.cycle.as_str(),
+                    c.opportunities,
+                    c.max_margin_bps,
+                    c.profit_usd.clone(),
+                )
+            })
+            .collect();
+        // Cycles without a USD profit come last
+        assert_eq!(
+            cycles,
+            [
+                ("a", 2, Some(40), Some(BigDecimal::from(50))),
+                ("b", 1, Some(20), Some(BigDecimal::from(40))),
+                ("c", 1, Some(70), None),
+            ]
+        );
+
+        let top = by_cycle(&mut conn, 100, 1).await.unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].cycle, "a");
+    }
+}
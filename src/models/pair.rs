@@ -22,8 +22,14 @@ pub struct Pair {
     /// The address of the pair
     pub address: DBAddress,
     /// The FK of the token0 - tokens.id
+    ///
+    /// This is future functionality.
+    #[allow(dead_code)]
     pub token0_id: Option<i32>,
     /// The FK of the token1 - tokens.id
+    ///
+    /// This is future functionality.
+    #[allow(dead_code)]
     pub token1_id: Option<i32>,
     /// The FK of the factory - factories.id
     ///
@@ -31,14 +37,20 @@ pub struct Pair {
     #[allow(dead_code)]
     pub factory_id: Option<i32>,
     /// The reserve of the token0
+    ///
+    /// This is future functionality.
+    #[allow(dead_code)]
     pub reserve0: Option<BigDecimal>,
     /// The reserve of the token1
+    ///
+    /// This is future functionality.
+    #[allow(dead_code)]
     pub reserve1: Option<BigDecimal>,
     /// The USD value of the pair
     ///
     /// This is future functionality.
     #[allow(dead_code)]
-    pub usd: Option<i32>,
+    pub usd: Option<BigDecimal>,
 }
 
 impl Pair {
@@ -113,7 +125,7 @@ pub struct NewPair {
     /// The reserve of the token1
     pub reserve1: BigDecimal,
     /// The USD value of the pair
-    pub usd: i32,
+    pub usd: BigDecimal,
 }
 
 impl NewPair {
@@ -127,7 +139,7 @@ impl NewPair {
             factory_id,
             reserve0: BigDecimal::from(0),
             reserve1: BigDecimal::from(0),
-            usd: 0,
+            usd: BigDecimal::from(0),
         }
     }
 
@@ -140,7 +152,7 @@ impl NewPair {
         factory_id: i32,
         reserve0: BigDecimal,
         reserve1: BigDecimal,
-        usd: i32,
+        usd: BigDecimal,
     ) -> Self {
         Self {
             address: DBAddress::new(address),
@@ -191,7 +203,7 @@ impl NewPair {
 
     /// The USD value of the pair
     #[must_use]
-    pub fn usd(&self) -> i32 {
-        self.usd
+    pub fn usd(&self) -> &BigDecimal {
+        &self.usd
     }
 }
@@ -0,0 +1,358 @@
+use bigdecimal::BigDecimal;
+use diesel::result::Error;
+use diesel::sql_types::Text;
+use diesel::{
+    ExpressionMethods, Insertable, QueryDsl, Queryable, QueryableByName, Selectable,
+    SelectableHelper,
+};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+
+use super::ROWS_PER_INSERT;
+use crate::schemas::reserve_history;
+
+/// Prefix of the names of block range partitions
+const PARTITION_PREFIX: &str = "reserve_history_p";
+
+/// The partition catching rows outside of every block range partition
+const DEFAULT_PARTITION: &str = "reserve_history_default";
+
+/// A Sync event applied to a pair
+#[derive(Insertable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = crate::schemas::reserve_history)]
+pub struct NewReserveHistory {
+    /// The ID of the pair
+    pub pair_id: i32,
+    /// The block of the Sync event
+    pub block_number: i64,
+    /// The index of the Sync event in its block
+    pub log_index: i32,
+    /// The transaction that emitted the Sync event
+    pub tx_hash: String,
+    /// The reserve of the token0 after the event
+    pub reserve0: BigDecimal,
+    /// The reserve of the token1 after the event
+    pub reserve1: BigDecimal,
+}
+
+/// Reserves of a pair as of a block
+///
+/// Only read by analytics and replay tooling, which is not part of the binary.
+#[allow(dead_code)]
+#[derive(Queryable, Selectable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = crate::schemas::reserve_history)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ReserveSnapshot {
+    /// The ID of the pair
+    pub pair_id: i32,
+    /// The block of the last Sync event at or before the requested block
+    pub block_number: i64,
+    /// The index of that Sync event in its block
+    pub log_index: i32,
+    /// The reserve of the token0
+    pub reserve0: BigDecimal,
+    /// The reserve of the token1
+    pub reserve1: BigDecimal,
+}
+
+/// Name of a partition, as listed by `pg_inherits`
+#[derive(QueryableByName, Debug)]
+struct PartitionName {
+    /// The table name of the partition
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+/// Append Sync events, ignoring those already recorded
+///
+/// # Errors
+///
+/// Returns an error if the database insert fails
+pub async fn insert(
+    conn: &mut AsyncPgConnection,
+    rows: &[NewReserveHistory],
+) -> Result<usize, Error> {
+    let mut inserted = 0;
+
+    for chunk in rows.chunks(ROWS_PER_INSERT) {
+        inserted += diesel::insert_into(reserve_history::table)
+            .values(chunk)
+            .on_conflict((reserve_history::block_number, reserve_history::log_index))
+            .do_nothing()
+            .execute(conn)
+            .await?;
+    }
+
+    Ok(inserted)
+}
+
+/// Reserves of the given pairs as of a block
+///
+/// Pairs without a Sync event at or before the block are left out.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+#[allow(dead_code)]
+pub async fn reserves_at(
+    conn: &mut AsyncPgConnection,
+    pair_ids: &[i32],
+    block_number: i64,
+) -> Result<Vec<ReserveSnapshot>, Error> {
+    reserve_history::table
+        .filter(reserve_history::pair_id.eq_any(pair_ids))
+        .filter(reserve_history::block_number.le(block_number))
+        .distinct_on(reserve_history::pair_id)
+        .order((
+            reserve_history::pair_id,
+            reserve_history::block_number.desc(),
+            reserve_history::log_index.desc(),
+        ))
+        .select(ReserveSnapshot::as_select())
+        .load(conn)
+        .await
+}
+
+/// Delete Sync events after a block, used when rolling back a reorg
+///
+/// # Errors
+///
+/// Returns an error if the database delete fails
+pub async fn delete_after(conn: &mut AsyncPgConnection, block_number: i64) -> Result<usize, Error> {
+    diesel::delete(reserve_history::table.filter(reserve_history::block_number.gt(block_number)))
+        .execute(conn)
+        .await
+}
+
+/// The first block of the partition holding a block
+#[must_use]
+pub const fn partition_start(block_number: i64, partition_blocks: i64) -> i64 {
+    block_number - block_number.rem_euclid(partition_blocks)
+}
+
+/// The table name of the partition starting at a block
+#[must_use]
+pub fn partition_name(start: i64) -> String {
+    format!("{PARTITION_PREFIX}{start}")
+}
+
+/// The first block of a partition from its table name
+fn parse_partition_name(name: &str) -> Option<i64> {
+    name.strip_prefix(PARTITION_PREFIX)?.parse().ok()
+}
+
+/// Partitions whose every block is older than the cutoff
+#[must_use]
+pub fn expired_partitions(starts: &[i64], partition_blocks: i64, cutoff: i64) -> Vec<i64> {
+    starts
+        .iter()
+        .copied()
+        .filter(|start| start + partition_blocks <= cutoff)
+        .collect()
+}
+
+/// First blocks of the existing block range partitions, sorted
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+pub async fn partitions(conn: &mut AsyncPgConnection) -> Result<Vec<i64>, Error> {
+    let names = diesel::sql_query(
+        "SELECT c.relname AS name FROM pg_inherits i \
+         JOIN pg_class c ON c.oid = i.inhrelid \
+         WHERE i.inhparent = 'reserve_history'::regclass",
+    )
+    .load::<PartitionName>(conn)
+    .await?;
+
+    let mut starts = names
+        .iter()
+        .filter_map(|partition| parse_partition_name(&partition.name))
+        .collect::<Vec<_>>();
+    starts.sort_unstable();
+
+    Ok(starts)
+}
+
+/// Create the partition starting at a block, if it does not exist
+///
+/// Postgres refuses to create a partition while the default partition holds rows of its range,
+/// and those are there whenever Sync events were written before their partition existed. The
+/// default partition is therefore detached, the partition created, the rows of its range moved
+/// over and the default partition attached again, all in one transaction. The partition size
+/// must not change between runs, overlapping partitions are rejected.
+///
+/// # Errors
+///
+/// Returns an error if the partition overlaps an existing one, or if a database statement fails
+pub async fn create_partition(
+    conn: &mut AsyncPgConnection,
+    start: i64,
+    partition_blocks: i64,
+) -> Result<(), Error> {
+    let name = partition_name(start);
+    let end = start + partition_blocks;
+
+    conn.transaction::<_, Error, _>(|conn| {
+        async move {
+            if partitions(conn).await?.contains(&start) {
+                return Ok(());
+            }
+
+            let statements = [
+                format!("ALTER TABLE reserve_history DETACH PARTITION {DEFAULT_PARTITION}"),
+                format!(
+                    "CREATE TABLE {name} PARTITION OF reserve_history \
+                     FOR VALUES FROM ({start}) TO ({end})"
+                ),
+                format!(
+                    "WITH moved AS (\
+                     DELETE FROM {DEFAULT_PARTITION} \
+                     WHERE block_number >= {start} AND block_number < {end} RETURNING *) \
+                     INSERT INTO {name} SELECT * FROM moved"
+                ),
+                format!("ALTER TABLE reserve_history ATTACH PARTITION {DEFAULT_PARTITION} DEFAULT"),
+            ];
+            for statement in statements {
+                diesel::sql_query(statement).execute(conn).await?;
+            }
+
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Drop the partition starting at a block with all its rows
+///
+/// # Errors
+///
+/// Returns an error if the database statement fails
+pub async fn drop_partition(conn: &mut AsyncPgConnection, start: i64) -> Result<(), Error> {
+    diesel::sql_query(format!("DROP TABLE IF EXISTS {}", partition_name(start)))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Delete rows older than the cutoff that ended up in the default partition
+///
+/// # Errors
+///
+/// Returns an error if the database delete fails
+pub async fn delete_default_before(
+    conn: &mut AsyncPgConnection,
+    cutoff: i64,
+) -> Result<usize, Error> {
+    diesel::sql_query(format!(
+        "DELETE FROM {DEFAULT_PARTITION} WHERE block_number < {cutoff}"
+    ))
+    .execute(conn)
+    .await
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use diesel::sql_types::BigInt;
+
+    use super::*;
+    use crate::schemas::pairs;
+    use crate::utils::test_db;
+
+    /// Number of rows of a table in a block range
+    #[derive(QueryableByName)]
+    struct Rows {
+        #[diesel(sql_type = BigInt)]
+        count: i64,
+    }
+
+    async fn rows(conn: &mut AsyncPgConnection, table: &str, start: i64, end: i64) -> i64 {
+        diesel::sql_query(format!(
+            "SELECT COUNT(*) AS count FROM {table} \
+             WHERE block_number >= {start} AND block_number < {end}"
+        ))
+        .get_result::<Rows>(conn)
+        .await
+        .unwrap()
+        .count
+    }
+
+    #[test]
+    fn test_partition_start() {
+        assert_eq!(partition_start(0, 100), 0);
+        assert_eq!(partition_start(99, 100), 0);
+        assert_eq!(partition_start(100, 100), 100);
+        assert_eq!(partition_start(27_123_456, 302_400), 26_913_600);
+    }
+
+    #[test]
+    fn test_partition_name() {
+        assert_eq!(partition_name(27_115_200), "reserve_history_p27115200");
+        assert_eq!(
+            parse_partition_name("reserve_history_p27115200"),
+            Some(27_115_200)
+        );
+        assert_eq!(parse_partition_name("reserve_history_default"), None);
+    }
+
+    #[test]
+    fn test_expired_partitions() {
+        let starts = [0, 100, 200, 300];
+
+        assert_eq!(expired_partitions(&starts, 100, 250), vec![0, 100]);
+        assert_eq!(expired_partitions(&starts, 100, 300), vec![0, 100, 200]);
+        assert!(expired_partitions(&starts, 100, 99).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_partition_moves_rows_out_of_default() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        // Far beyond any partition a run of the bot creates
+        let start = 9_000_000_000;
+        let pair_id = diesel::insert_into(pairs::table)
+            .values(pairs::address.eq("0x00000000000000000000000000000000000000f1"))
+            .returning(pairs::id)
+            .get_result::<i32>(&mut conn)
+            .await
+            .unwrap();
+        let row = |block_number: i64| NewReserveHistory {
+            pair_id,
+            block_number,
+            log_index: 0,
+            tx_hash: "0x01".to_string(),
+            reserve0: BigDecimal::from(1),
+            reserve1: BigDecimal::from(1),
+        };
+        insert(
+            &mut conn,
+            &[row(start + 5), row(start + 99), row(start + 100)],
+        )
+        .await
+        .unwrap();
+
+        create_partition(&mut conn, start, 100).await.unwrap();
+        // Creating it again is a no-op
+        create_partition(&mut conn, start, 100).await.unwrap();
+
+        assert!(partitions(&mut conn).await.unwrap().contains(&start));
+        let name = partition_name(start);
+        assert_eq!(rows(&mut conn, &name, start, start + 100).await, 2);
+        assert_eq!(
+            rows(&mut conn, DEFAULT_PARTITION, start, start + 100).await,
+            0
+        );
+        assert_eq!(
+            rows(&mut conn, DEFAULT_PARTITION, start + 100, start + 200).await,
+            1
+        );
+        assert_eq!(
+            rows(&mut conn, "reserve_history", start, start + 200).await,
+            3
+        );
+    }
+}
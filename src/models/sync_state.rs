@@ -0,0 +1,22 @@
+use diesel::Insertable;
+
+/// The last block processed by a backfilled event stream
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schemas::sync_state)]
+pub struct SyncState {
+    /// The name of the stream
+    stream: String,
+    /// The last block whose events have been processed
+    last_block: i64,
+}
+
+impl SyncState {
+    /// Create a new checkpoint
+    #[must_use]
+    pub fn new(stream: &str, last_block: i64) -> Self {
+        Self {
+            stream: stream.to_string(),
+            last_block,
+        }
+    }
+}
@@ -1,14 +1,141 @@
+use std::str::FromStr;
+
 use alloy::primitives::Address;
 use bigdecimal::BigDecimal;
 use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::Pg;
+use diesel::result::Error;
+use diesel::serialize::ToSql;
+use diesel::sql_types::Text;
 use diesel::{Insertable, Queryable, Selectable};
 
 use super::pair::DBAddress;
 
+/// How a token metadata field (`name`, `symbol` or `decimals`) was decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = crate::schemas::sql_types::MetadataStatus)]
+pub enum MetadataStatus {
+    /// The call returned a standard ABI value
+    Ok,
+    /// The call returned a `bytes32` instead of a `string` (e.g. MKR)
+    Bytes32,
+    /// The call reverted, or the contract does not implement it
+    Reverted,
+    /// The call returned data that decodes as neither
+    Malformed,
+}
+
+impl MetadataStatus {
+    /// Whether the value of the field is known
+    #[must_use]
+    pub const fn is_decoded(self) -> bool {
+        matches!(self, Self::Ok | Self::Bytes32)
+    }
+}
+
+impl FromStr for MetadataStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Ok" => Ok(MetadataStatus::Ok),
+            "Bytes32" => Ok(MetadataStatus::Bytes32),
+            "Reverted" => Ok(MetadataStatus::Reverted),
+            "Malformed" => Ok(MetadataStatus::Malformed),
+            _ => Err("Invalid metadata status".to_string()),
+        }
+    }
+}
+
+impl ToSql<crate::schemas::sql_types::MetadataStatus, Pg> for MetadataStatus {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let s = match self {
+            MetadataStatus::Ok => "Ok",
+            MetadataStatus::Bytes32 => "Bytes32",
+            MetadataStatus::Reverted => "Reverted",
+            MetadataStatus::Malformed => "Malformed",
+        };
+        <str as ToSql<Text, Pg>>::to_sql(s, out)
+    }
+}
+
+impl FromSql<crate::schemas::sql_types::MetadataStatus, Pg> for MetadataStatus {
+    fn from_sql(
+        bytes: <Pg as diesel::backend::Backend>::RawValue<'_>,
+    ) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        match MetadataStatus::from_str(&s) {
+            Ok(status) => Ok(status),
+            Err(e) => Err(Box::new(Error::DeserializationError(e.into()))
+                as Box<dyn std::error::Error + Send + Sync>),
+        }
+    }
+}
+
+/// Whether a token price passed the manipulation guards of `pricing::guard`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = crate::schemas::sql_types::PriceStatus)]
+pub enum PriceStatus {
+    /// The price passed every guard
+    Accepted,
+    /// The price tripped a guard and is stored with a lower confidence
+    Flagged,
+    /// The price tripped every guard and the previous price was kept
+    Rejected,
+}
+
+impl FromStr for PriceStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Accepted" => Ok(PriceStatus::Accepted),
+            "Flagged" => Ok(PriceStatus::Flagged),
+            "Rejected" => Ok(PriceStatus::Rejected),
+            _ => Err("Invalid price status".to_string()),
+        }
+    }
+}
+
+impl ToSql<crate::schemas::sql_types::PriceStatus, Pg> for PriceStatus {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, Pg>,
+    ) -> diesel::serialize::Result {
+        let s = match self {
+            PriceStatus::Accepted => "Accepted",
+            PriceStatus::Flagged => "Flagged",
+            PriceStatus::Rejected => "Rejected",
+        };
+        <str as ToSql<Text, Pg>>::to_sql(s, out)
+    }
+}
+
+impl FromSql<crate::schemas::sql_types::PriceStatus, Pg> for PriceStatus {
+    fn from_sql(
+        bytes: <Pg as diesel::backend::Backend>::RawValue<'_>,
+    ) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, Pg>>::from_sql(bytes)?;
+        match PriceStatus::from_str(&s) {
+            Ok(status) => Ok(status),
+            Err(e) => Err(Box::new(Error::DeserializationError(e.into()))
+                as Box<dyn std::error::Error + Send + Sync>),
+        }
+    }
+}
+
 /// Token model
+///
+/// This is future functionality.
 #[derive(Queryable, Selectable, Debug)]
 #[diesel(table_name = crate::schemas::tokens)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
+#[allow(dead_code)]
 pub struct Token {
     /// The ID of the token
     id: i32,
@@ -58,6 +185,7 @@ pub struct TokenParams {
     pub updated_last: Option<NaiveDateTime>,
 }
 
+#[allow(dead_code)]
 impl Token {
     /// Get the ID of the token
     #[must_use]
@@ -66,6 +194,25 @@ impl Token {
     }
 }
 
+/// Metadata of a token, as returned by its `name()`, `symbol()` and `decimals()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+    /// The address of the token
+    pub address: Address,
+    /// The name of the token, if decoded
+    pub name: Option<String>,
+    /// How the name was decoded
+    pub name_status: MetadataStatus,
+    /// The symbol of the token, if decoded
+    pub symbol: Option<String>,
+    /// How the symbol was decoded
+    pub symbol_status: MetadataStatus,
+    /// The decimals of the token, if decoded
+    pub decimals: Option<u8>,
+    /// How the decimals were decoded
+    pub decimals_status: MetadataStatus,
+}
+
 /// A new token
 #[derive(Insertable, Clone, Debug)]
 #[diesel(table_name = crate::schemas::tokens)]
@@ -178,7 +325,7 @@ impl NewToken {
 ///
 /// # Returns
 /// A new `String` with invalid UTF-8 replaced and null bytes removed.
-fn sanitize_string(value: &str) -> String {
+pub(crate) fn sanitize_string(value: &str) -> String {
     // First convert to lossy UTF-8 string to handle invalid sequences
     let sanitized = String::from_utf8_lossy(value.as_bytes()).to_string();
 
@@ -196,6 +343,29 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_metadata_status_from_str() {
+        assert_eq!(
+            MetadataStatus::from_str("Bytes32"),
+            Ok(MetadataStatus::Bytes32)
+        );
+        assert!(MetadataStatus::from_str("bytes32").is_err());
+    }
+
+    #[test]
+    fn test_metadata_status_is_decoded() {
+        assert!(MetadataStatus::Ok.is_decoded());
+        assert!(MetadataStatus::Bytes32.is_decoded());
+        assert!(!MetadataStatus::Reverted.is_decoded());
+        assert!(!MetadataStatus::Malformed.is_decoded());
+    }
+
+    #[test]
+    fn test_price_status_from_str() {
+        assert_eq!(PriceStatus::from_str("Flagged"), Ok(PriceStatus::Flagged));
+        assert!(PriceStatus::from_str("Suspicious").is_err());
+    }
+
     // Test sanitization function
     #[test]
     fn test_sanitize_string() {
@@ -0,0 +1,89 @@
+//! Exact conversions of token amounts.
+//!
+//! Amounts stay `BigDecimal`s from the reserves in the database to USD values, so tokens with
+//! any number of decimals and reserves up to `uint256` are valued without rounding. Only the
+//! final value is rounded, by the caller.
+
+use bigdecimal::BigDecimal;
+
+/// An amount in the smallest unit of a token, in whole tokens
+///
+/// Moves the decimal point rather than dividing, so it is exact for any decimals.
+#[must_use]
+pub fn whole(amount: &BigDecimal, decimals: u8) -> BigDecimal {
+    let (digits, scale) = amount.as_bigint_and_exponent();
+    BigDecimal::new(digits, scale + i64::from(decimals))
+}
+
+/// The USD value of an amount in the smallest unit of a token, given USD per whole token
+#[must_use]
+pub fn usd(rate: &BigDecimal, amount: &BigDecimal, decimals: u8) -> BigDecimal {
+    rate * whole(amount, decimals)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use alloy::primitives::U256;
+    use proptest::prelude::*;
+    use std::str::FromStr;
+
+    /// Any `uint256`, which bounds reserves
+    fn uint256() -> impl Strategy<Value = BigDecimal> {
+        prop::array::uniform4(any::<u64>())
+            .prop_map(|limbs| BigDecimal::from_str(&U256::from_limbs(limbs).to_string()).unwrap())
+    }
+
+    /// A USD rate with up to 18 decimal places
+    fn rate() -> impl Strategy<Value = BigDecimal> {
+        (any::<u64>(), 0i64..=18).prop_map(|(digits, scale)| BigDecimal::new(digits.into(), scale))
+    }
+
+    /// 10^decimals
+    fn base(decimals: u8) -> BigDecimal {
+        BigDecimal::new(1.into(), -i64::from(decimals))
+    }
+
+    #[test]
+    fn test_whole() {
+        let amount = BigDecimal::from(1_500_000);
+
+        assert_eq!(whole(&amount, 6), BigDecimal::from_str("1.5").unwrap());
+        assert_eq!(whole(&amount, 0), amount);
+        assert_eq!(usd(&BigDecimal::from(2), &amount, 6), BigDecimal::from(3));
+    }
+
+    proptest! {
+        #[test]
+        fn whole_is_exact(amount in uint256(), decimals in any::<u8>()) {
+            prop_assert_eq!(whole(&amount, decimals) * base(decimals), amount);
+        }
+
+        #[test]
+        fn whole_keeps_order(a in uint256(), b in uint256(), decimals in any::<u8>()) {
+            prop_assert_eq!(a.cmp(&b), whole(&a, decimals).cmp(&whole(&b, decimals)));
+        }
+
+        #[test]
+        fn usd_is_linear(
+            rate in rate(),
+            a in uint256(),
+            b in uint256(),
+            decimals in any::<u8>(),
+        ) {
+            prop_assert_eq!(
+                usd(&rate, &(&a + &b), decimals),
+                usd(&rate, &a, decimals) + usd(&rate, &b, decimals)
+            );
+        }
+
+        #[test]
+        fn usd_matches_division(rate in rate(), amount in uint256(), decimals in any::<u8>()) {
+            prop_assert_eq!(
+                usd(&rate, &amount, decimals) * base(decimals),
+                &rate * &amount
+            );
+        }
+    }
+}
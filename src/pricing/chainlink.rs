@@ -0,0 +1,169 @@
+//! Chainlink price feeds.
+//!
+//! Reads `latestRoundData` of an aggregator through multicall, so the price comes from the chain
+//! we trade on rather than from an API. Answers older than `MAX_AGE_SECS` are refused.
+
+use alloy::primitives::{address, Address};
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::BigDecimal;
+use chrono::Utc;
+use eyre::{bail, eyre, Result};
+
+use super::source::PriceSource;
+use crate::chain::{Call, Chain};
+
+sol! {
+    interface IAggregatorV3 {
+        function decimals() external view returns (uint8);
+        function latestRoundData() external view returns (
+            uint80 roundId,
+            int256 answer,
+            uint256 startedAt,
+            uint256 updatedAt,
+            uint80 answeredInRound
+        );
+    }
+}
+
+/// Chainlink ETH/USD aggregator on Base
+pub const ETH_USD_FEED: Address = address!("0x71041dddad3595F9CEd3DcCFBe3D1F4b0a16Bb70");
+
+/// The ETH/USD feed updates at least every 20 minutes, so an hour old answer is stale
+const MAX_AGE_SECS: u64 = 60 * 60;
+
+/// A Chainlink aggregator
+#[derive(Debug)]
+pub struct Chainlink<'a, C> {
+    /// Chain to read the aggregator from
+    chain: &'a C,
+    /// Address of the aggregator
+    feed: Address,
+}
+
+impl<'a, C: Chain + Sync> Chainlink<'a, C> {
+    /// The ETH/USD aggregator on Base
+    #[must_use]
+    pub const fn eth_usd(chain: &'a C) -> Self {
+        Self {
+            chain,
+            feed: ETH_USD_FEED,
+        }
+    }
+
+    /// The latest answer of the aggregator, in its quote currency
+    ///
+    /// # Errors
+    /// Returns an error if the calls fail, or if the answer is not positive or stale
+    pub async fn latest(&self) -> Result<BigDecimal> {
+        let calls = vec![
+            Call::new(self.feed, IAggregatorV3::decimalsCall {}.abi_encode()),
+            Call::new(
+                self.feed,
+                IAggregatorV3::latestRoundDataCall {}.abi_encode(),
+            ),
+        ];
+
+        let results = self.chain.aggregate3(calls, None).await?;
+        let [decimals, round] = results.as_slice() else {
+            bail!("Expected 2 results from {}", self.feed);
+        };
+        if !decimals.success || !round.success {
+            bail!("Aggregator {} reverted", self.feed);
+        }
+
+        let decimals =
+            IAggregatorV3::decimalsCall::abi_decode_returns(&decimals.return_data, true)?._0;
+        let round =
+            IAggregatorV3::latestRoundDataCall::abi_decode_returns(&round.return_data, true)?;
+
+        if !round.answer.is_positive() {
+            bail!("Aggregator {} answered {}", self.feed, round.answer);
+        }
+
+        let now = u64::try_from(Utc::now().timestamp())?;
+        let updated_at = u64::try_from(round.updatedAt)?;
+        if now.saturating_sub(updated_at) > MAX_AGE_SECS {
+            bail!(
+                "Aggregator {} answer is {}s old",
+                self.feed,
+                now.saturating_sub(updated_at)
+            );
+        }
+
+        let answer = round
+            .answer
+            .to_string()
+            .parse::<BigInt>()
+            .map_err(|e| eyre!("Invalid answer {}: {e}", round.answer))?;
+
+        Ok(BigDecimal::new(answer, i64::from(decimals)))
+    }
+}
+
+impl<C: Chain + Sync> PriceSource for Chainlink<'_, C> {
+    fn name(&self) -> &'static str {
+        "chainlink"
+    }
+
+    async fn weth_usd(&self) -> Result<BigDecimal> {
+        self.latest().await
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::chain::fake::FakeChain;
+    use alloy::primitives::{I256, U256};
+    use alloy::sol_types::SolValue;
+    use std::str::FromStr;
+
+    fn answer(chain: &FakeChain, price: i64, age: i64) {
+        let updated_at = U256::from(Utc::now().timestamp() - age);
+        chain.respond(
+            ETH_USD_FEED,
+            &IAggregatorV3::decimalsCall {},
+            U256::from(8).abi_encode(),
+        );
+        chain.respond(
+            ETH_USD_FEED,
+            &IAggregatorV3::latestRoundDataCall {},
+            (
+                U256::from(1),
+                I256::try_from(price).unwrap(),
+                updated_at,
+                updated_at,
+                U256::from(1),
+            )
+                .abi_encode_params(),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reads_answer_with_decimals() {
+        let chain = FakeChain::new();
+        answer(&chain, 251_234_567_890, 60);
+
+        let price = Chainlink::eth_usd(&chain).weth_usd().await.unwrap();
+
+        assert_eq!(price, BigDecimal::from_str("2512.3456789").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_refuses_stale_and_negative_answers() {
+        let chain = FakeChain::new();
+
+        answer(&chain, 250_000_000_000, 2 * 60 * 60);
+        assert!(Chainlink::eth_usd(&chain).weth_usd().await.is_err());
+
+        answer(&chain, -1, 60);
+        assert!(Chainlink::eth_usd(&chain).weth_usd().await.is_err());
+
+        // Not an aggregator
+        let chain = FakeChain::new();
+        assert!(Chainlink::eth_usd(&chain).weth_usd().await.is_err());
+    }
+}
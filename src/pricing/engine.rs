@@ -0,0 +1,389 @@
+//! Widest path pricing.
+//!
+//! The depth of a pool is its USD value, twice the value of the side whose price is known. The
+//! depth of a route is the depth of its shallowest pool. Every token is priced through the route
+//! to a reference asset with the greatest depth, found with Dijkstra's algorithm maximizing the
+//! bottleneck instead of minimizing a sum. Ties go to the route with fewer hops. Pools shallower
+//! than the engine's minimum depth are never used.
+//!
+//! Everything happens in memory on a snapshot of tokens and reserves, so callers can write all
+//! prices at once.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+
+use super::amount::whole;
+
+/// Decimal places of computed prices
+const PRICE_SCALE: i64 = 18;
+
+/// Decimal places of computed depths
+const DEPTH_SCALE: i64 = 6;
+
+/// A pool between two tokens with its raw reserves
+#[derive(Debug, Clone)]
+pub struct Pool {
+    /// The ID of the pair
+    pub id: i32,
+    /// The ID of token0
+    pub token0: i32,
+    /// The ID of token1
+    pub token1: i32,
+    /// Reserve of token0 in its smallest unit
+    pub reserve0: BigDecimal,
+    /// Reserve of token1 in its smallest unit
+    pub reserve1: BigDecimal,
+}
+
+/// The USD price of a token and the route it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Price {
+    /// USD per whole token
+    pub rate: BigDecimal,
+    /// Depth of the route in USD, `None` for reference assets
+    pub depth: Option<BigDecimal>,
+    /// Pools from the reference asset to the token, empty for reference assets
+    pub route: Vec<i32>,
+    /// Number of pools between the token and a reference asset
+    pub hops: u32,
+}
+
+/// One side of a pool, seen from the token being priced
+#[derive(Debug)]
+struct Edge {
+    /// The ID of the pair
+    pool: i32,
+    /// The token on the other side
+    other: i32,
+    /// Reserve of our token in whole tokens
+    reserve: BigDecimal,
+    /// Reserve of the other token in whole tokens
+    other_reserve: BigDecimal,
+}
+
+/// Prices tokens through the deepest routes to reference assets
+#[derive(Debug, Clone)]
+pub struct Engine {
+    /// Pools shallower than this, in USD, are ignored
+    min_depth: BigDecimal,
+}
+
+impl Engine {
+    /// Create an engine that ignores pools shallower than `min_depth` USD
+    #[must_use]
+    pub const fn new(min_depth: BigDecimal) -> Self {
+        Self { min_depth }
+    }
+
+    /// Price every token reachable from the reference assets
+    ///
+    /// # Arguments
+    /// * `decimals` - Decimals of each token, pools of tokens without decimals are skipped
+    /// * `pools` - Pools with their reserves
+    /// * `references` - Tokens with a known USD price per whole token
+    #[must_use]
+    pub fn price(
+        &self,
+        decimals: &HashMap<i32, u8>,
+        pools: &[Pool],
+        references: &[(i32, BigDecimal)],
+    ) -> HashMap<i32, Price> {
+        let edges = edges(decimals, pools);
+
+        let mut prices = HashMap::new();
+        let mut candidates = HashMap::new();
+        let mut queue = BinaryHeap::new();
+
+        for (token, rate) in references {
+            prices.insert(
+                *token,
+                Price {
+                    rate: rate.clone(),
+                    depth: None,
+                    route: Vec::new(),
+                    hops: 0,
+                },
+            );
+        }
+        for token in references.iter().map(|(token, _)| token) {
+            self.relax(&edges, *token, &prices, &mut candidates, &mut queue);
+        }
+
+        // Settle the deepest candidate, skipping entries that were outranked since
+        while let Some((key, Reverse(token))) = queue.pop() {
+            if prices.contains_key(&token) || candidates.get(&token).map(rank) != Some(key) {
+                continue;
+            }
+            if let Some(price) = candidates.remove(&token) {
+                prices.insert(token, price);
+                self.relax(&edges, token, &prices, &mut candidates, &mut queue);
+            }
+        }
+
+        prices
+    }
+
+    /// Offer the price of `token` to its unsettled neighbors
+    fn relax(
+        &self,
+        edges: &HashMap<i32, Vec<Edge>>,
+        token: i32,
+        prices: &HashMap<i32, Price>,
+        candidates: &mut HashMap<i32, Price>,
+        queue: &mut BinaryHeap<(Rank, Reverse<i32>)>,
+    ) {
+        let Some(from) = prices.get(&token) else {
+            return;
+        };
+
+        for edge in edges.get(&token).into_iter().flatten() {
+            if prices.contains_key(&edge.other) {
+                continue;
+            }
+            let Some(candidate) = self.through(from, edge) else {
+                continue;
+            };
+
+            let better = candidates
+                .get(&edge.other)
+                .is_none_or(|current| rank(&candidate) > rank(current));
+            if better {
+                queue.push((rank(&candidate), Reverse(edge.other)));
+                candidates.insert(edge.other, candidate);
+            }
+        }
+    }
+
+    /// The price of the other side of `edge`, given the price of our side
+    fn through(&self, from: &Price, edge: &Edge) -> Option<Price> {
+        let depth = (&edge.reserve * &from.rate * BigDecimal::from(2))
+            .with_scale_round(DEPTH_SCALE, RoundingMode::Down);
+        if depth < self.min_depth {
+            return None;
+        }
+
+        let rate = (&from.rate * &edge.reserve / &edge.other_reserve)
+            .with_scale_round(PRICE_SCALE, RoundingMode::HalfUp);
+        if rate.is_zero() {
+            return None;
+        }
+
+        Some(Price {
+            rate,
+            depth: Some(match &from.depth {
+                Some(bottleneck) if *bottleneck < depth => bottleneck.clone(),
+                _ => depth,
+            }),
+            route: from.route.iter().copied().chain([edge.pool]).collect(),
+            hops: from.hops + 1,
+        })
+    }
+}
+
+/// Depth of a route, then its number of hops
+type Rank = (Option<BigDecimal>, Reverse<u32>);
+
+/// Deeper routes rank higher, then shorter ones
+fn rank(price: &Price) -> Rank {
+    (price.depth.clone(), Reverse(price.hops))
+}
+
+/// Both sides of every pool with reserves in whole tokens, by token
+fn edges(decimals: &HashMap<i32, u8>, pools: &[Pool]) -> HashMap<i32, Vec<Edge>> {
+    let mut edges: HashMap<i32, Vec<Edge>> = HashMap::new();
+
+    for pool in pools {
+        if pool.token0 == pool.token1 {
+            continue;
+        }
+        let (Some(decimals0), Some(decimals1)) =
+            (decimals.get(&pool.token0), decimals.get(&pool.token1))
+        else {
+            continue;
+        };
+
+        let reserve0 = whole(&pool.reserve0, *decimals0);
+        let reserve1 = whole(&pool.reserve1, *decimals1);
+        if reserve0 <= BigDecimal::zero() || reserve1 <= BigDecimal::zero() {
+            continue;
+        }
+
+        edges.entry(pool.token0).or_default().push(Edge {
+            pool: pool.id,
+            other: pool.token1,
+            reserve: reserve0.clone(),
+            other_reserve: reserve1.clone(),
+        });
+        edges.entry(pool.token1).or_default().push(Edge {
+            pool: pool.id,
+            other: pool.token0,
+            reserve: reserve1,
+            other_reserve: reserve0,
+        });
+    }
+
+    edges
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use alloy::primitives::U256;
+    use proptest::prelude::*;
+    use std::str::FromStr;
+
+    const WETH: i32 = 1;
+    const USDC: i32 = 2;
+    const TOKEN: i32 = 3;
+    const DUST: i32 = 4;
+
+    fn decimals() -> HashMap<i32, u8> {
+        HashMap::from([(WETH, 18), (USDC, 6), (TOKEN, 18), (DUST, 18)])
+    }
+
+    fn pool(id: i32, token0: i32, token1: i32, reserve0: &str, reserve1: &str) -> Pool {
+        Pool {
+            id,
+            token0,
+            token1,
+            reserve0: BigDecimal::from_str(reserve0).unwrap(),
+            reserve1: BigDecimal::from_str(reserve1).unwrap(),
+        }
+    }
+
+    fn engine() -> Engine {
+        Engine::new(BigDecimal::from(1000))
+    }
+
+    fn references() -> Vec<(i32, BigDecimal)> {
+        vec![(WETH, BigDecimal::from(2000))]
+    }
+
+    #[test]
+    fn test_prices_through_one_pool() {
+        // 10 WETH and 20,000 USDC
+        let pools = [pool(1, WETH, USDC, "10e18", "20000e6")];
+
+        let prices = engine().price(&decimals(), &pools, &references());
+
+        assert_eq!(prices[&WETH].rate, BigDecimal::from(2000));
+        assert_eq!(prices[&USDC].rate, BigDecimal::from(1));
+        assert_eq!(prices[&USDC].depth, Some(BigDecimal::from(40_000)));
+        assert_eq!(prices[&USDC].route, vec![1]);
+        assert_eq!(prices[&USDC].hops, 1);
+    }
+
+    #[test]
+    fn test_deepest_pool_wins_over_dust() {
+        let pools = [
+            // A dust pool pricing TOKEN at 1000 USD, found first
+            pool(1, WETH, TOKEN, "1e18", "2e18"),
+            // A deep pool pricing TOKEN at 1 USD
+            pool(2, WETH, TOKEN, "100e18", "200000e18"),
+        ];
+
+        let prices = engine().price(&decimals(), &pools, &references());
+
+        assert_eq!(prices[&TOKEN].rate, BigDecimal::from(1));
+        assert_eq!(prices[&TOKEN].route, vec![2]);
+    }
+
+    #[test]
+    fn test_route_depth_is_its_shallowest_pool() {
+        let pools = [
+            // Direct, but only 4,000 USD deep
+            pool(1, WETH, TOKEN, "1e18", "1000e18"),
+            // Through USDC, 400,000 and 200,000 USD deep
+            pool(2, WETH, USDC, "100e18", "200000e6"),
+            pool(3, USDC, TOKEN, "100000e6", "50000e18"),
+        ];
+
+        let prices = engine().price(&decimals(), &pools, &references());
+
+        assert_eq!(prices[&TOKEN].rate, BigDecimal::from(2));
+        assert_eq!(prices[&TOKEN].depth, Some(BigDecimal::from(200_000)));
+        assert_eq!(prices[&TOKEN].hops, 2);
+        assert_eq!(prices[&TOKEN].route, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_shallow_pools_are_ignored() {
+        let pools = [
+            // 200 USD deep
+            pool(1, WETH, DUST, "5e16", "1e18"),
+            pool(2, DUST, TOKEN, "1e18", "1e18"),
+        ];
+
+        let prices = engine().price(&decimals(), &pools, &references());
+
+        assert!(!prices.contains_key(&DUST));
+        assert!(!prices.contains_key(&TOKEN));
+    }
+
+    #[test]
+    fn test_skips_tokens_without_decimals_and_empty_pools() {
+        let mut decimals = decimals();
+        decimals.remove(&TOKEN);
+        let pools = [
+            pool(1, WETH, TOKEN, "10e18", "10e18"),
+            pool(2, WETH, USDC, "10e18", "0"),
+        ];
+
+        let prices = engine().price(&decimals, &pools, &references());
+
+        assert_eq!(prices.len(), 1);
+    }
+
+    #[test]
+    fn test_references_keep_their_price() {
+        let pools = [pool(1, WETH, USDC, "10e18", "10000e6")];
+        let references = [(WETH, BigDecimal::from(2000)), (USDC, BigDecimal::from(1))];
+
+        let prices = engine().price(&decimals(), &pools, &references);
+
+        assert_eq!(prices[&WETH].rate, BigDecimal::from(2000));
+        assert_eq!(prices[&USDC].rate, BigDecimal::from(1));
+        assert_eq!(prices[&USDC].route, Vec::<i32>::new());
+    }
+
+    /// Reserves from 1 to `uint256` max
+    fn reserve() -> impl Strategy<Value = BigDecimal> {
+        prop::array::uniform4(any::<u64>()).prop_filter_map("empty reserve", |limbs| {
+            let reserve = U256::from_limbs(limbs);
+            (!reserve.is_zero()).then(|| BigDecimal::from_str(&reserve.to_string()).ok())?
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn price_is_the_reserve_ratio(
+            reserve0 in reserve(),
+            reserve1 in reserve(),
+            decimals0 in any::<u8>(),
+            decimals1 in any::<u8>(),
+        ) {
+            let decimals = HashMap::from([(WETH, decimals0), (TOKEN, decimals1)]);
+            let pools = [Pool {
+                id: 1,
+                token0: WETH,
+                token1: TOKEN,
+                reserve0: reserve0.clone(),
+                reserve1: reserve1.clone(),
+            }];
+
+            let prices = Engine::new(BigDecimal::zero()).price(&decimals, &pools, &references());
+
+            // Prices are rounded to 18 decimal places, and prices that round to zero are not set
+            let exact =
+                BigDecimal::from(2000) * whole(&reserve0, decimals0) / whole(&reserve1, decimals1);
+            let half_ulp = BigDecimal::new(5.into(), PRICE_SCALE + 1);
+            match prices.get(&TOKEN) {
+                Some(price) => prop_assert!((&price.rate - &exact).abs() <= half_ulp),
+                None => prop_assert!(exact < half_ulp),
+            }
+        }
+    }
+}
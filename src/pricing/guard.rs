@@ -0,0 +1,296 @@
+//! Manipulation guards for token prices.
+//!
+//! A price is only as good as the pools it went through. A pool can be created, or pushed, to
+//! say anything, and a token priced through it makes every cycle through the token look hugely
+//! profitable in USD. The guard compares each new price to the stored one and looks at the age
+//! of the pools of its route:
+//!
+//! - A price that moved more than `max_move_bps` since the last update, or that went through a
+//!   pool created less than `min_pool_age_blocks` ago, is flagged and kept with a lower
+//!   confidence.
+//! - A price that did both is rejected, and the previous price stays.
+//!
+//! Confidence is between 0 and 1. It grows with the depth of the route and shrinks with every
+//! hop and flag.
+
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+
+use super::engine::Price;
+use crate::models::token::PriceStatus;
+
+/// Confidence lost with every hop after the first
+const HOP_DECAY: f64 = 0.9;
+
+/// Confidence kept by a flagged price
+const FLAG_PENALTY: f64 = 0.5;
+
+/// Price guard configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuardConfig {
+    /// Largest move of a price between two updates, in basis points
+    pub max_move_bps: u32,
+    /// Pools created fewer blocks ago than this are new
+    pub min_pool_age_blocks: i64,
+    /// Route depth in USD at which depth gives half of the confidence
+    pub half_confidence_depth: BigDecimal,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            // Half or double the price
+            max_move_bps: 5_000,
+            // One hour of 2 second blocks
+            min_pool_age_blocks: 1_800,
+            half_confidence_depth: BigDecimal::from(100_000),
+        }
+    }
+}
+
+impl GuardConfig {
+    /// Load the configuration from environment variables, falling back to the defaults
+    ///
+    /// # Environment Variables:
+    /// - `FLY_PRICE_MAX_MOVE_BPS`: Largest move of a price between two updates, in basis points
+    /// - `FLY_PRICE_MIN_POOL_AGE_BLOCKS`: Pools created fewer blocks ago than this are new
+    /// - `FLY_PRICE_HALF_CONFIDENCE_DEPTH_USD`: Route depth at which depth gives half of the
+    ///   confidence
+    #[must_use]
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        Self {
+            max_move_bps: env::var("FLY_PRICE_MAX_MOVE_BPS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(defaults.max_move_bps),
+            min_pool_age_blocks: env::var("FLY_PRICE_MIN_POOL_AGE_BLOCKS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|value| *value >= 0)
+                .unwrap_or(defaults.min_pool_age_blocks),
+            half_confidence_depth: env::var("FLY_PRICE_HALF_CONFIDENCE_DEPTH_USD")
+                .ok()
+                .and_then(|value| BigDecimal::from_str(&value).ok())
+                .filter(|value| *value > BigDecimal::zero())
+                .unwrap_or(defaults.half_confidence_depth),
+        }
+    }
+}
+
+/// Blocks of a pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolBlocks {
+    /// Block the pool was created in, if known
+    pub created: Option<i64>,
+    /// Block of its reserves
+    pub updated: Option<i64>,
+}
+
+/// What the guard made of a price
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verdict {
+    /// Whether the price is kept, and how
+    pub status: PriceStatus,
+    /// The price moved more than `max_move_bps`
+    pub moved: bool,
+    /// The route goes through a new pool
+    pub new_pool: bool,
+    /// From 0 to 1
+    pub confidence: f64,
+    /// Latest reserves block of the route
+    pub block: Option<i64>,
+}
+
+/// Checks prices from the engine before they are stored
+#[derive(Debug, Clone)]
+pub struct Guard {
+    /// Limits of the guard
+    config: GuardConfig,
+}
+
+impl Guard {
+    /// Create a guard with limits
+    #[must_use]
+    pub const fn new(config: GuardConfig) -> Self {
+        Self { config }
+    }
+
+    /// Check a new price against the previous one and the pools of its route
+    ///
+    /// Pools with an unknown creation block are not new.
+    ///
+    /// # Arguments
+    /// * `previous` - The stored price of the token, if any
+    /// * `price` - The new price
+    /// * `pools` - Blocks of the pools, by pair ID
+    /// * `head` - The latest block
+    #[must_use]
+    pub fn check(
+        &self,
+        previous: Option<&BigDecimal>,
+        price: &Price,
+        pools: &HashMap<i32, PoolBlocks>,
+        head: i64,
+    ) -> Verdict {
+        let moved = previous.is_some_and(|previous| self.moved(previous, &price.rate));
+        let new_pool = price.route.iter().any(|pool| {
+            pools
+                .get(pool)
+                .and_then(|blocks| blocks.created)
+                .is_some_and(|created| head - created < self.config.min_pool_age_blocks)
+        });
+
+        let status = match (moved, new_pool) {
+            (true, true) => PriceStatus::Rejected,
+            (true, false) | (false, true) => PriceStatus::Flagged,
+            (false, false) => PriceStatus::Accepted,
+        };
+
+        let flags = i32::from(moved) + i32::from(new_pool);
+        let hops = i32::try_from(price.hops.saturating_sub(1)).unwrap_or(i32::MAX);
+        let confidence =
+            self.depth_confidence(price) * HOP_DECAY.powi(hops) * FLAG_PENALTY.powi(flags);
+
+        Verdict {
+            status,
+            moved,
+            new_pool,
+            confidence,
+            block: price
+                .route
+                .iter()
+                .filter_map(|pool| pools.get(pool)?.updated)
+                .max(),
+        }
+    }
+
+    /// Whether `rate` is more than `max_move_bps` away from a positive `previous`
+    fn moved(&self, previous: &BigDecimal, rate: &BigDecimal) -> bool {
+        if *previous <= BigDecimal::zero() {
+            return false;
+        }
+        (rate - previous).abs() * BigDecimal::from(10_000)
+            > previous * BigDecimal::from(self.config.max_move_bps)
+    }
+
+    /// `depth / (depth + half_confidence_depth)`, 1 for reference assets
+    fn depth_confidence(&self, price: &Price) -> f64 {
+        let Some(depth) = &price.depth else {
+            return 1.0;
+        };
+        (depth / (depth + &self.config.half_confidence_depth))
+            .to_f64()
+            .unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEAD: i64 = 10_000;
+
+    fn guard() -> Guard {
+        Guard::new(GuardConfig::default())
+    }
+
+    fn price(rate: i64, depth: i64, route: &[i32]) -> Price {
+        Price {
+            rate: BigDecimal::from(rate),
+            depth: Some(BigDecimal::from(depth)),
+            route: route.to_vec(),
+            hops: u32::try_from(route.len()).unwrap_or(u32::MAX),
+        }
+    }
+
+    /// Pool 1 is old, pool 2 was created 10 blocks ago
+    fn pools() -> HashMap<i32, PoolBlocks> {
+        HashMap::from([
+            (
+                1,
+                PoolBlocks {
+                    created: Some(100),
+                    updated: Some(HEAD - 5),
+                },
+            ),
+            (
+                2,
+                PoolBlocks {
+                    created: Some(HEAD - 10),
+                    updated: Some(HEAD),
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_accepts_steady_price_from_old_pool() {
+        let verdict = guard().check(
+            Some(&BigDecimal::from(100)),
+            &price(120, 100_000, &[1]),
+            &pools(),
+            HEAD,
+        );
+
+        assert_eq!(verdict.status, PriceStatus::Accepted);
+        assert!((verdict.confidence - 0.5).abs() < 1e-9);
+        assert_eq!(verdict.block, Some(HEAD - 5));
+    }
+
+    #[test]
+    fn test_flags_large_moves_and_new_pools() {
+        let moved = guard().check(
+            Some(&BigDecimal::from(100)),
+            &price(151, 100_000, &[1]),
+            &pools(),
+            HEAD,
+        );
+        assert_eq!(moved.status, PriceStatus::Flagged);
+        assert!(moved.moved && !moved.new_pool);
+        assert!((moved.confidence - 0.25).abs() < 1e-9);
+
+        let new_pool = guard().check(None, &price(100, 100_000, &[1, 2]), &pools(), HEAD);
+        assert_eq!(new_pool.status, PriceStatus::Flagged);
+        assert!(!new_pool.moved && new_pool.new_pool);
+        assert_eq!(new_pool.block, Some(HEAD));
+    }
+
+    #[test]
+    fn test_rejects_large_moves_through_new_pools() {
+        let verdict = guard().check(
+            Some(&BigDecimal::from(100)),
+            &price(10_000, 100_000, &[2]),
+            &pools(),
+            HEAD,
+        );
+
+        assert_eq!(verdict.status, PriceStatus::Rejected);
+    }
+
+    #[test]
+    fn test_first_price_and_unknown_pools_are_not_flagged() {
+        let verdict = guard().check(None, &price(10_000, 100_000, &[3]), &pools(), HEAD);
+
+        assert_eq!(verdict.status, PriceStatus::Accepted);
+        assert_eq!(verdict.block, None);
+    }
+
+    #[test]
+    fn test_confidence_grows_with_depth_and_shrinks_with_hops() {
+        let confidence = |depth, route: &[i32]| {
+            guard()
+                .check(None, &price(1, depth, route), &HashMap::new(), HEAD)
+                .confidence
+        };
+
+        assert!(confidence(1_000_000, &[1]) > confidence(10_000, &[1]));
+        assert!(confidence(10_000, &[1]) > confidence(10_000, &[1, 3]));
+        assert!(confidence(1_000_000_000, &[1]) < 1.0);
+    }
+}
@@ -0,0 +1,24 @@
+//! Token pricing.
+//!
+//! Prices spread out from reference assets with known USD prices through pools. A token takes
+//! its price from the route through the deepest liquidity, so a dust pool can't set the price of
+//! a token that also trades in a deep one. The price of WETH, the reference asset, comes from a
+//! `PriceSource`. Prices go through a `Guard` before they are stored, which flags or rejects
+//! sudden moves and prices read from new pools.
+
+/// Exact conversions of token amounts
+pub mod amount;
+/// Chainlink price feeds
+pub mod chainlink;
+/// Liquidity-weighted price routing
+pub mod engine;
+/// Manipulation guards and confidence of prices
+pub mod guard;
+/// Moralis token price API
+pub mod moralis;
+/// Sources of the WETH price
+pub mod source;
+
+pub use engine::{Engine, Pool};
+pub use guard::{Guard, GuardConfig};
+pub use source::{PriceSourceConfig, Source};
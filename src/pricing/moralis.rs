@@ -0,0 +1,79 @@
+//! Moralis token price API.
+
+use std::env;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use eyre::{bail, Result};
+use reqwest::header;
+use serde::Deserialize;
+
+use super::source::PriceSource;
+
+/// WETH token address on Base chain
+const WETH_ADDRESS: &str = "0x4200000000000000000000000000000000000006";
+
+/// Response structure for Moralis API token price endpoint
+#[derive(Debug, Deserialize)]
+struct MoralisTokenPriceResponse {
+    /// USD price of the token
+    #[serde(rename = "usdPrice")]
+    usd_price: f64,
+}
+
+/// The Moralis token price API
+#[derive(Debug)]
+pub struct Moralis {
+    /// HTTP client
+    client: reqwest::Client,
+    /// API key, the source fails without one
+    api_key: Option<String>,
+}
+
+impl Moralis {
+    /// Create the source with the API key of the environment
+    ///
+    /// # Environment Variables
+    /// * `MORALIS_API_KEY` - The Moralis API key
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key: env::var("MORALIS_API_KEY").ok(),
+        }
+    }
+}
+
+impl PriceSource for Moralis {
+    fn name(&self) -> &'static str {
+        "moralis"
+    }
+
+    async fn weth_usd(&self) -> Result<BigDecimal> {
+        let Some(api_key) = &self.api_key else {
+            bail!("MORALIS_API_KEY environment variable not set");
+        };
+
+        let url =
+            format!("https://deep-index.moralis.io/api/v2.2/erc20/{WETH_ADDRESS}/price?chain=base");
+
+        let response = self
+            .client
+            .get(&url)
+            .header(header::ACCEPT, "application/json")
+            .header("X-API-Key", api_key)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            bail!(
+                "Moralis API request failed with status: {}",
+                response.status()
+            );
+        }
+
+        let price_data: MoralisTokenPriceResponse = response.json().await?;
+
+        Ok(BigDecimal::from_str(&price_data.usd_price.to_string())?)
+    }
+}
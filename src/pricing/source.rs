@@ -0,0 +1,265 @@
+//! Sources of the WETH price.
+//!
+//! Every other price is derived from WETH's, so it comes from outside the pools. Sources are
+//! tried in the order set by `FLY_WETH_PRICE_SOURCES` until one answers.
+
+use std::env;
+use std::future::Future;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, Zero};
+use eyre::{bail, eyre, Result};
+use log::warn;
+
+use super::chainlink::Chainlink;
+use super::moralis::Moralis;
+use crate::chain::Chain;
+
+/// A source of the USD price of WETH
+pub trait PriceSource {
+    /// Name of the source, for logs
+    fn name(&self) -> &'static str;
+
+    /// USD per whole WETH
+    fn weth_usd(&self) -> impl Future<Output = Result<BigDecimal>> + Send;
+}
+
+/// A price that never changes, for tests and as a last resort
+#[derive(Debug, Clone)]
+pub struct Fixed(pub BigDecimal);
+
+impl PriceSource for Fixed {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    async fn weth_usd(&self) -> Result<BigDecimal> {
+        Ok(self.0.clone())
+    }
+}
+
+/// The kinds of sources that can be configured
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceKind {
+    /// The Chainlink ETH/USD aggregator on Base
+    Chainlink,
+    /// The Moralis token price API
+    Moralis,
+    /// A fixed price
+    Fixed(BigDecimal),
+}
+
+impl FromStr for SourceKind {
+    type Err = String;
+
+    /// Parse `chainlink`, `moralis` or `fixed:<price>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once(':') {
+            None if s.trim() == "chainlink" => Ok(SourceKind::Chainlink),
+            None if s.trim() == "moralis" => Ok(SourceKind::Moralis),
+            Some(("fixed", price)) => BigDecimal::from_str(price.trim())
+                .map(SourceKind::Fixed)
+                .map_err(|e| format!("Invalid fixed price {price}: {e}")),
+            _ => Err(format!("Invalid price source {s}")),
+        }
+    }
+}
+
+/// Which sources give the WETH price, and how often
+#[derive(Debug, Clone)]
+pub struct PriceSourceConfig {
+    /// Sources in the order they are tried
+    pub sources: Vec<SourceKind>,
+    /// Seconds between updates
+    pub interval_secs: u64,
+}
+
+impl Default for PriceSourceConfig {
+    fn default() -> Self {
+        Self {
+            sources: vec![SourceKind::Chainlink, SourceKind::Moralis],
+            interval_secs: 60,
+        }
+    }
+}
+
+impl PriceSourceConfig {
+    /// Load the configuration from environment variables, falling back to the defaults
+    ///
+    /// # Environment Variables:
+    /// - `FLY_WETH_PRICE_SOURCES`: Comma separated sources in fallback order, each one of
+    ///   `chainlink`, `moralis` or `fixed:<price>`
+    /// - `FLY_WETH_PRICE_INTERVAL_SECS`: Seconds between updates
+    #[must_use]
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let sources = env::var("FLY_WETH_PRICE_SOURCES")
+            .map(|sources| {
+                sources
+                    .split(',')
+                    .filter(|source| !source.trim().is_empty())
+                    .filter_map(|source| {
+                        source
+                            .parse()
+                            .inspect_err(|e| warn!("pricing::source: {e}, skipping"))
+                            .ok()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .ok()
+            .filter(|sources| !sources.is_empty())
+            .unwrap_or(defaults.sources);
+
+        Self {
+            sources,
+            interval_secs: env::var("FLY_WETH_PRICE_INTERVAL_SECS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(defaults.interval_secs),
+        }
+    }
+}
+
+/// Any of the configured sources
+#[derive(Debug)]
+pub enum Source<'a, C> {
+    /// See `Chainlink`
+    Chainlink(Chainlink<'a, C>),
+    /// See `Moralis`
+    Moralis(Moralis),
+    /// See `Fixed`
+    Fixed(Fixed),
+}
+
+impl<'a, C: Chain + Sync> Source<'a, C> {
+    /// Build the configured sources
+    #[must_use]
+    pub fn from_config(chain: &'a C, config: &PriceSourceConfig) -> Vec<Self> {
+        config
+            .sources
+            .iter()
+            .map(|kind| match kind {
+                SourceKind::Chainlink => Source::Chainlink(Chainlink::eth_usd(chain)),
+                SourceKind::Moralis => Source::Moralis(Moralis::from_env()),
+                SourceKind::Fixed(price) => Source::Fixed(Fixed(price.clone())),
+            })
+            .collect()
+    }
+}
+
+impl<C: Chain + Sync> PriceSource for Source<'_, C> {
+    fn name(&self) -> &'static str {
+        match self {
+            Source::Chainlink(source) => source.name(),
+            Source::Moralis(source) => source.name(),
+            Source::Fixed(source) => source.name(),
+        }
+    }
+
+    async fn weth_usd(&self) -> Result<BigDecimal> {
+        match self {
+            Source::Chainlink(source) => source.weth_usd().await,
+            Source::Moralis(source) => source.weth_usd().await,
+            Source::Fixed(source) => source.weth_usd().await,
+        }
+    }
+}
+
+/// The WETH price of the first source that answers with a positive price
+///
+/// # Returns
+/// The name of the source and the price
+///
+/// # Errors
+/// Returns an error if no source answers
+pub async fn weth_usd<S: PriceSource>(sources: &[S]) -> Result<(&'static str, BigDecimal)> {
+    if sources.is_empty() {
+        bail!("No WETH price sources configured");
+    }
+
+    for source in sources {
+        match source.weth_usd().await {
+            Ok(price) if price > BigDecimal::zero() => return Ok((source.name(), price)),
+            Ok(price) => warn!("pricing::source: {} returned {price}", source.name()),
+            Err(e) => warn!("pricing::source: {} failed: {e}", source.name()),
+        }
+    }
+
+    Err(eyre!("All WETH price sources failed"))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    /// A source that always fails
+    struct Down;
+
+    impl PriceSource for Down {
+        fn name(&self) -> &'static str {
+            "down"
+        }
+
+        async fn weth_usd(&self) -> Result<BigDecimal> {
+            Err(eyre!("unreachable"))
+        }
+    }
+
+    /// Either source, so both fit in one slice
+    enum Test {
+        Down(Down),
+        Fixed(Fixed),
+    }
+
+    impl PriceSource for Test {
+        fn name(&self) -> &'static str {
+            match self {
+                Test::Down(source) => source.name(),
+                Test::Fixed(source) => source.name(),
+            }
+        }
+
+        async fn weth_usd(&self) -> Result<BigDecimal> {
+            match self {
+                Test::Down(source) => source.weth_usd().await,
+                Test::Fixed(source) => source.weth_usd().await,
+            }
+        }
+    }
+
+    #[test]
+    fn test_source_kind_from_str() {
+        assert_eq!(SourceKind::from_str("chainlink"), Ok(SourceKind::Chainlink));
+        assert_eq!(SourceKind::from_str(" moralis"), Ok(SourceKind::Moralis));
+        assert_eq!(
+            SourceKind::from_str("fixed:2100.5"),
+            Ok(SourceKind::Fixed(BigDecimal::from_str("2100.5").unwrap()))
+        );
+        assert!(SourceKind::from_str("fixed:cheap").is_err());
+        assert!(SourceKind::from_str("coingecko").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_in_order() {
+        let sources = [
+            Test::Down(Down),
+            Test::Fixed(Fixed(BigDecimal::zero())),
+            Test::Fixed(Fixed(BigDecimal::from(2100))),
+            Test::Fixed(Fixed(BigDecimal::from(1))),
+        ];
+
+        let (name, price) = weth_usd(&sources).await.unwrap();
+
+        assert_eq!(name, "fixed");
+        assert_eq!(price, BigDecimal::from(2100));
+    }
+
+    #[tokio::test]
+    async fn test_fails_when_all_sources_fail() {
+        assert!(weth_usd(&[Down, Down]).await.is_err());
+        assert!(weth_usd::<Down>(&[]).await.is_err());
+    }
+}
@@ -0,0 +1,1156 @@
+//! In-memory repositories.
+//!
+//! Tests seed pairs, tokens and factories, hand the `MemoryRepo` to the code under test in place
+//! of `PgRepo`, then read the rows back. Rows follow the Postgres defaults: pairs and tokens are
+//! valid, factories are unsynced. Only the partitions of reserve history are kept, not its rows.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+use alloy::primitives::{Address, B256, U256};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use eyre::Result;
+
+use super::{
+    BlockRepo, CreatedPair, FactoryRepo, OpportunityRepo, PairRepo, PairReserves, PairScope,
+    PoolFilter, PriceChange, ReserveHistoryRepo, StoredBlock, SyncStateRepo, TokenPrice,
+    TokenRates, TokenRepo, TradablePair, UsdValue, END_OF_BLOCK,
+};
+use crate::models::block::BlockUpdate;
+use crate::models::factory::{Factory, FactoryKind, FactoryStatus};
+use crate::models::opportunity::NewOpportunity;
+use crate::models::pair::{DBAddress, Pair};
+use crate::models::token::{MetadataStatus, PriceStatus, TokenMetadata};
+use crate::pricing::guard::PoolBlocks;
+use crate::pricing::Pool;
+use crate::utils::numeric::u256_to_big_decimal;
+
+/// A row of `pairs`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairRow {
+    /// The address of the pair
+    pub address: Address,
+    /// The ID of token0
+    pub token0_id: Option<i32>,
+    /// The ID of token1
+    pub token1_id: Option<i32>,
+    /// The ID of the factory
+    pub factory_id: Option<i32>,
+    /// The reserve of token0
+    pub reserve0: Option<BigDecimal>,
+    /// The reserve of token1
+    pub reserve1: Option<BigDecimal>,
+    /// The USD value of the pair
+    pub usd: Option<BigDecimal>,
+    /// The block of the reserves the USD value was computed from
+    pub usd_block: Option<i64>,
+    /// When the USD value was computed
+    pub usd_updated_at: Option<NaiveDateTime>,
+    /// Whether the pair can be traded
+    pub is_valid: bool,
+    /// The block the pair was created in
+    pub created_block: Option<i64>,
+    /// The block of the reserves
+    pub block_number: Option<i64>,
+    /// The hash of the block of the reserves
+    pub block_hash: Option<B256>,
+    /// The index of the Sync log of the reserves in their block
+    pub log_index: Option<u64>,
+}
+
+impl PairRow {
+    /// A valid pair with nothing else known
+    #[must_use]
+    pub const fn new(address: Address) -> Self {
+        Self {
+            address,
+            token0_id: None,
+            token1_id: None,
+            factory_id: None,
+            reserve0: None,
+            reserve1: None,
+            usd: None,
+            usd_block: None,
+            usd_updated_at: None,
+            is_valid: true,
+            created_block: None,
+            block_number: None,
+            block_hash: None,
+            log_index: None,
+        }
+    }
+
+    /// Write reserves unless the stored ones are newer
+    ///
+    /// Like the upsert of `PgRepo`, a block replaced under the same number overwrites and a later
+    /// log of the same block moves reserves forward.
+    fn upsert_reserves(
+        &mut self,
+        number: i64,
+        hash: B256,
+        log_index: u64,
+        reserve0: U256,
+        reserve1: U256,
+    ) {
+        let replaced = self.block_hash != Some(hash);
+        let later = self.log_index.is_none_or(|index| index < log_index);
+        if self
+            .block_number
+            .is_some_and(|block| block > number || (block == number && !replaced && !later))
+        {
+            return;
+        }
+
+        self.reserve0 = Some(u256_to_big_decimal(reserve0));
+        self.reserve1 = Some(u256_to_big_decimal(reserve1));
+        self.block_number = Some(number);
+        self.block_hash = Some(hash);
+        self.log_index = Some(log_index);
+    }
+}
+
+/// A row of `tokens`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenRow {
+    /// The address of the token
+    pub address: Address,
+    /// The name of the token
+    pub name: Option<String>,
+    /// The symbol of the token
+    pub symbol: Option<String>,
+    /// The decimals of the token
+    pub decimals: Option<i32>,
+    /// How the decimals were decoded, `None` for tokens stored before statuses existed
+    pub decimals_status: Option<MetadataStatus>,
+    /// Whether the token can be priced
+    pub is_valid: bool,
+    /// USD per whole token
+    pub exchange_rate: Option<BigDecimal>,
+    /// Pools the exchange rate was read through
+    pub price_route: Option<Vec<i32>>,
+    /// Depth of the route in USD
+    pub price_depth: Option<BigDecimal>,
+    /// Confidence in the exchange rate
+    pub price_confidence: Option<f64>,
+    /// Latest reserves block of the route
+    pub price_block: Option<i64>,
+    /// Guard status of the exchange rate
+    pub price_status: Option<PriceStatus>,
+    /// When the exchange rate was last written
+    pub updated_last: Option<NaiveDateTime>,
+}
+
+impl TokenRow {
+    /// A valid token with nothing else known
+    #[must_use]
+    pub const fn new(address: Address) -> Self {
+        Self {
+            address,
+            name: None,
+            symbol: None,
+            decimals: None,
+            decimals_status: None,
+            is_valid: true,
+            exchange_rate: None,
+            price_route: None,
+            price_depth: None,
+            price_confidence: None,
+            price_block: None,
+            price_status: None,
+            updated_last: None,
+        }
+    }
+}
+
+/// A row of `factories`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactoryRow {
+    /// The address of the factory
+    pub address: Address,
+    /// The number of pairs listed so far
+    pub last_pair_id: i32,
+    /// The status of the factory
+    pub status: FactoryStatus,
+    /// The kind of DEX of the factory
+    pub kind: Option<FactoryKind>,
+    /// The swap fee of the factory in basis points
+    pub fee_bps: Option<i32>,
+}
+
+impl FactoryRow {
+    /// An unsynced factory
+    #[must_use]
+    pub const fn new(address: Address) -> Self {
+        Self {
+            address,
+            last_pair_id: 0,
+            status: FactoryStatus::Unsynced,
+            kind: None,
+            fee_bps: None,
+        }
+    }
+}
+
+/// Mutable state of a `MemoryRepo`
+#[derive(Debug, Default)]
+struct State {
+    /// Pairs by ID
+    pairs: BTreeMap<i32, PairRow>,
+    /// Tokens by ID
+    tokens: BTreeMap<i32, TokenRow>,
+    /// Factories by ID
+    factories: BTreeMap<i32, FactoryRow>,
+    /// Checkpoints by stream
+    checkpoints: HashMap<String, i64>,
+    /// Blocks by number
+    blocks: BTreeMap<u64, StoredBlock>,
+    /// First blocks of the reserve history partitions
+    partitions: BTreeSet<i64>,
+    /// Recorded opportunities, in insertion order
+    opportunities: Vec<NewOpportunity>,
+}
+
+impl State {
+    /// The ID of the pair with an address
+    fn pair_id(&self, address: Address) -> Option<i32> {
+        self.pairs
+            .iter()
+            .find(|(_, pair)| pair.address == address)
+            .map(|(id, _)| *id)
+    }
+
+    /// The ID of the token with an address
+    fn token_id(&self, address: Address) -> Option<i32> {
+        self.tokens
+            .iter()
+            .find(|(_, token)| token.address == address)
+            .map(|(id, _)| *id)
+    }
+
+    /// The ID of the factory with an address
+    fn factory_id(&self, address: Address) -> Option<i32> {
+        self.factories
+            .iter()
+            .find(|(_, factory)| factory.address == address)
+            .map(|(id, _)| *id)
+    }
+
+    /// Insert a pair, returning its ID
+    fn insert_pair(&mut self, pair: PairRow) -> i32 {
+        let id = next_id(&self.pairs);
+        self.pairs.insert(id, pair);
+        id
+    }
+}
+
+/// Repositories keeping their rows in memory, for tests
+#[derive(Debug, Default)]
+pub struct MemoryRepo {
+    /// The rows
+    state: Mutex<State>,
+}
+
+impl MemoryRepo {
+    /// Create empty repositories
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock the state, ignoring poisoning by a failed test
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Insert a pair, returning its ID
+    pub fn insert_pair(&self, pair: PairRow) -> i32 {
+        self.state().insert_pair(pair)
+    }
+
+    /// Insert a token, returning its ID
+    pub fn insert_token(&self, token: TokenRow) -> i32 {
+        let mut state = self.state();
+        let id = next_id(&state.tokens);
+        state.tokens.insert(id, token);
+        id
+    }
+
+    /// Insert a factory, returning its ID
+    pub fn insert_factory(&self, factory: FactoryRow) -> i32 {
+        let mut state = self.state();
+        let id = next_id(&state.factories);
+        state.factories.insert(id, factory);
+        id
+    }
+
+    /// The pair with an address
+    #[must_use]
+    pub fn pair(&self, address: Address) -> Option<PairRow> {
+        let state = self.state();
+        state
+            .pair_id(address)
+            .and_then(|id| state.pairs.get(&id).cloned())
+    }
+
+    /// The token with an address
+    #[must_use]
+    pub fn token(&self, address: Address) -> Option<TokenRow> {
+        let state = self.state();
+        state
+            .token_id(address)
+            .and_then(|id| state.tokens.get(&id).cloned())
+    }
+
+    /// The factory with an address
+    #[must_use]
+    pub fn factory(&self, address: Address) -> Option<FactoryRow> {
+        let state = self.state();
+        state
+            .factory_id(address)
+            .and_then(|id| state.factories.get(&id).cloned())
+    }
+
+    /// The recorded opportunities, in insertion order
+    #[must_use]
+    pub fn opportunities(&self) -> Vec<NewOpportunity> {
+        self.state().opportunities.clone()
+    }
+
+    /// Pairs matching a filter, in ID order
+    fn pairs_where(&self, limit: i64, filter: impl Fn(&PairRow) -> bool) -> Vec<Pair> {
+        self.state()
+            .pairs
+            .iter()
+            .filter(|(_, pair)| filter(pair))
+            .take(usize::try_from(limit).unwrap_or_default())
+            .map(|(id, pair)| Pair {
+                id: *id,
+                address: DBAddress::new(pair.address),
+                token0_id: pair.token0_id,
+                token1_id: pair.token1_id,
+                factory_id: pair.factory_id,
+                reserve0: pair.reserve0.clone(),
+                reserve1: pair.reserve1.clone(),
+                usd: pair.usd.clone(),
+            })
+            .collect()
+    }
+}
+
+/// The ID the next row of a table gets, like a serial column
+fn next_id<T>(rows: &BTreeMap<i32, T>) -> i32 {
+    rows.keys().next_back().map_or(1, |id| id + 1)
+}
+
+impl PairRepo for MemoryRepo {
+    async fn without_reserves(&self, limit: i64) -> Result<Vec<Pair>> {
+        Ok(self.pairs_where(limit, |pair| {
+            pair.reserve0.is_none() || pair.reserve1.is_none()
+        }))
+    }
+
+    async fn without_factory(&self, limit: i64) -> Result<Vec<Pair>> {
+        Ok(self.pairs_where(limit, |pair| pair.factory_id.is_none()))
+    }
+
+    async fn without_tokens(&self, limit: i64) -> Result<Vec<Pair>> {
+        Ok(self.pairs_where(limit, |pair| {
+            pair.token0_id.is_none() || pair.token1_id.is_none()
+        }))
+    }
+
+    async fn set_reserves(
+        &self,
+        number: u64,
+        hash: B256,
+        reserves: &[(Address, U256, U256)],
+    ) -> Result<()> {
+        let mut state = self.state();
+        let number = i64::try_from(number)?;
+
+        for &(address, reserve0, reserve1) in reserves {
+            if let Some(id) = state.pair_id(address) {
+                if let Some(pair) = state.pairs.get_mut(&id) {
+                    pair.upsert_reserves(number, hash, END_OF_BLOCK, reserve0, reserve1);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn set_factory(&self, id: i32, factory_id: i32) -> Result<()> {
+        if let Some(pair) = self.state().pairs.get_mut(&id) {
+            pair.factory_id = Some(factory_id);
+        }
+        Ok(())
+    }
+
+    async fn set_token(
+        &self,
+        id: i32,
+        is_token0: bool,
+        token_id: i32,
+        is_valid: bool,
+    ) -> Result<()> {
+        if let Some(pair) = self.state().pairs.get_mut(&id) {
+            if is_token0 {
+                pair.token0_id = Some(token_id);
+            } else {
+                pair.token1_id = Some(token_id);
+            }
+            pair.is_valid = is_valid;
+        }
+        Ok(())
+    }
+
+    async fn discover(&self, address: Address, factory_id: i32) -> Result<()> {
+        let mut state = self.state();
+        match state.pair_id(address) {
+            Some(id) => {
+                if let Some(pair) = state.pairs.get_mut(&id) {
+                    pair.factory_id = Some(factory_id);
+                }
+            }
+            None => {
+                state.insert_pair(PairRow {
+                    factory_id: Some(factory_id),
+                    ..PairRow::new(address)
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn store_created(&self, created: &CreatedPair, trusted: bool) -> Result<()> {
+        let mut state = self.state();
+        match state.pair_id(created.address) {
+            Some(id) if trusted => {
+                if let Some(pair) = state.pairs.get_mut(&id) {
+                    pair.factory_id = Some(created.factory_id);
+                    pair.token0_id = Some(created.token0_id);
+                    pair.token1_id = Some(created.token1_id);
+                    pair.created_block = created.created_block;
+                }
+            }
+            Some(_) => {}
+            None => {
+                state.insert_pair(PairRow {
+                    factory_id: Some(created.factory_id),
+                    token0_id: Some(created.token0_id),
+                    token1_id: Some(created.token1_id),
+                    created_block: created.created_block,
+                    is_valid: created.is_valid,
+                    ..PairRow::new(created.address)
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn store_reserves(&self, updates: &[BlockUpdate], _record_history: bool) -> Result<()> {
+        let mut state = self.state();
+
+        for update in updates {
+            let number = i64::try_from(update.number)?;
+            let stored = state.blocks.get(&update.number).copied();
+            // Like `PgRepo`, the same block keeps its parent hash when replayed without one
+            let parent_hash = update.parent_hash.or_else(|| {
+                stored
+                    .filter(|block| block.hash == update.hash)
+                    .and_then(|block| block.parent_hash)
+            });
+            state.blocks.insert(
+                update.number,
+                StoredBlock {
+                    number: update.number,
+                    hash: update.hash,
+                    parent_hash,
+                },
+            );
+
+            for pool in &update.pools {
+                let id = match state.pair_id(pool.address) {
+                    Some(id) => id,
+                    None => state.insert_pair(PairRow::new(pool.address)),
+                };
+                if let Some(pair) = state.pairs.get_mut(&id) {
+                    pair.upsert_reserves(
+                        number,
+                        update.hash,
+                        pool.log_index,
+                        pool.reserve0,
+                        pool.reserve1,
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn with_reserves(
+        &self,
+        scope: &PairScope,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<PairReserves>> {
+        Ok(self
+            .state()
+            .pairs
+            .range(after.saturating_add(1)..)
+            .filter(|(_, pair)| match scope {
+                PairScope::All => true,
+                PairScope::Pairs(addresses) => addresses.contains(&pair.address),
+                PairScope::Tokens(ids) => [pair.token0_id, pair.token1_id]
+                    .iter()
+                    .flatten()
+                    .any(|id| ids.contains(id)),
+            })
+            .filter_map(|(id, pair)| {
+                Some(PairReserves {
+                    id: *id,
+                    token0_id: pair.token0_id?,
+                    token1_id: pair.token1_id?,
+                    reserve0: pair.reserve0.clone()?,
+                    reserve1: pair.reserve1.clone()?,
+                    block_number: pair.block_number,
+                })
+            })
+            .take(usize::try_from(limit).unwrap_or_default())
+            .collect())
+    }
+
+    async fn tradable(
+        &self,
+        filter: &PoolFilter,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<TradablePair>> {
+        let state = self.state();
+        let address = |id: Option<i32>| {
+            let token = state.tokens.get(&id?)?;
+            Some(DBAddress::new(token.address))
+        };
+
+        Ok(state
+            .pairs
+            .range(after.saturating_add(1)..)
+            .filter(|(_, pair)| pair.is_valid)
+            .filter(|(_, pair)| {
+                // A pair without value is below any minimum, as in SQL
+                filter
+                    .min_usd
+                    .as_ref()
+                    .is_none_or(|min_usd| pair.usd.as_ref().is_some_and(|usd| usd >= min_usd))
+            })
+            .filter(|(_, pair)| {
+                filter.factories.as_ref().is_none_or(|factories| {
+                    pair.factory_id.is_some_and(|id| factories.contains(&id))
+                })
+            })
+            .filter_map(|(id, pair)| {
+                Some(TradablePair {
+                    id: *id,
+                    address: DBAddress::new(pair.address),
+                    token0: address(pair.token0_id)?,
+                    token1: address(pair.token1_id)?,
+                    reserve0: pair.reserve0.clone()?,
+                    reserve1: pair.reserve1.clone()?,
+                })
+            })
+            .take(usize::try_from(limit).unwrap_or_default())
+            .collect())
+    }
+
+    async fn store_usd(&self, values: &[UsdValue], updated_at: NaiveDateTime) -> Result<()> {
+        let mut state = self.state();
+        for value in values {
+            if let Some(pair) = state.pairs.get_mut(&value.pair_id) {
+                pair.usd.clone_from(&value.usd);
+                pair.usd_block = value.block;
+                pair.usd_updated_at = Some(updated_at);
+            }
+        }
+        Ok(())
+    }
+
+    async fn pools(&self) -> Result<(Vec<Pool>, HashMap<i32, PoolBlocks>)> {
+        let state = self.state();
+        let zero = BigDecimal::from(0);
+        let loaded = state.pairs.iter().filter(|(_, pair)| {
+            pair.is_valid
+                && pair
+                    .reserve0
+                    .as_ref()
+                    .is_some_and(|reserve| *reserve > zero)
+                && pair
+                    .reserve1
+                    .as_ref()
+                    .is_some_and(|reserve| *reserve > zero)
+        });
+
+        let blocks = loaded
+            .clone()
+            .map(|(id, pair)| {
+                (
+                    *id,
+                    PoolBlocks {
+                        created: pair.created_block,
+                        updated: pair.block_number,
+                    },
+                )
+            })
+            .collect();
+
+        let pools = loaded
+            .filter_map(|(id, pair)| {
+                Some(Pool {
+                    id: *id,
+                    token0: pair.token0_id?,
+                    token1: pair.token1_id?,
+                    reserve0: pair.reserve0.clone()?,
+                    reserve1: pair.reserve1.clone()?,
+                })
+            })
+            .collect();
+
+        Ok((pools, blocks))
+    }
+}
+
+impl TokenRepo for MemoryRepo {
+    async fn save_metadata(&self, metadata: &TokenMetadata) -> Result<i32> {
+        let mut state = self.state();
+        let id = state.token_id(metadata.address).unwrap_or_else(|| {
+            let id = next_id(&state.tokens);
+            state.tokens.insert(id, TokenRow::new(metadata.address));
+            id
+        });
+
+        if let Some(token) = state.tokens.get_mut(&id) {
+            token.name.clone_from(&metadata.name);
+            token.symbol.clone_from(&metadata.symbol);
+            token.decimals = metadata.decimals.map(i32::from);
+            token.decimals_status = Some(metadata.decimals_status);
+            token.is_valid = metadata.is_valid();
+        }
+        Ok(id)
+    }
+
+    async fn undecoded(&self, limit: i64) -> Result<Vec<Address>> {
+        Ok(self
+            .state()
+            .tokens
+            .values()
+            .filter(|token| token.decimals_status.is_none())
+            .take(usize::try_from(limit).unwrap_or_default())
+            .map(|token| token.address)
+            .collect())
+    }
+
+    async fn valid(&self) -> Result<Vec<Address>> {
+        Ok(self
+            .state()
+            .tokens
+            .values()
+            .filter(|token| token.is_valid)
+            .map(|token| token.address)
+            .collect())
+    }
+
+    async fn ids(&self, addresses: &[Address]) -> Result<Vec<i32>> {
+        Ok(self
+            .state()
+            .tokens
+            .iter()
+            .filter(|(_, token)| addresses.contains(&token.address))
+            .map(|(id, _)| *id)
+            .collect())
+    }
+
+    async fn rates(&self, ids: &[i32]) -> Result<TokenRates> {
+        let state = self.state();
+        Ok(ids
+            .iter()
+            .filter_map(|id| {
+                let token = state.tokens.get(id)?;
+                Some((*id, (token.exchange_rate.clone(), token.decimals)))
+            })
+            .collect())
+    }
+
+    async fn set_rate(
+        &self,
+        address: Address,
+        rate: &BigDecimal,
+        updated_at: NaiveDateTime,
+    ) -> Result<bool> {
+        let mut state = self.state();
+        let Some(token) = state
+            .token_id(address)
+            .and_then(|id| state.tokens.get_mut(&id))
+        else {
+            return Ok(false);
+        };
+        if token.exchange_rate.as_ref() == Some(rate) {
+            return Ok(false);
+        }
+
+        token.exchange_rate = Some(rate.clone());
+        token.updated_last = Some(updated_at);
+        Ok(true)
+    }
+
+    async fn prices(&self) -> Result<Vec<TokenPrice>> {
+        Ok(self
+            .state()
+            .tokens
+            .iter()
+            .map(|(id, token)| TokenPrice {
+                id: *id,
+                address: token.address.to_string(),
+                decimals: token.decimals,
+                exchange_rate: token.exchange_rate.clone(),
+                price_route: token
+                    .price_route
+                    .as_ref()
+                    .map(|route| route.iter().copied().map(Some).collect()),
+                price_status: token.price_status,
+                price_confidence: token.price_confidence,
+            })
+            .collect())
+    }
+
+    async fn update_prices(
+        &self,
+        changes: &[(i32, &PriceChange)],
+        updated_at: NaiveDateTime,
+    ) -> Result<()> {
+        let mut state = self.state();
+        for (id, change) in changes {
+            let Some(token) = state.tokens.get_mut(id) else {
+                continue;
+            };
+
+            match change {
+                PriceChange::Priced(price, verdict) => {
+                    token.exchange_rate = Some(price.rate.clone());
+                    token.price_route = Some(price.route.clone());
+                    token.price_depth.clone_from(&price.depth);
+                    token.price_confidence = Some(verdict.confidence);
+                    token.price_block = verdict.block;
+                    token.price_status = Some(verdict.status);
+                    token.updated_last = Some(updated_at);
+                }
+                PriceChange::Rejected(_) => token.price_status = Some(PriceStatus::Rejected),
+                PriceChange::Unpriced => {
+                    *token = TokenRow {
+                        updated_last: Some(updated_at),
+                        exchange_rate: None,
+                        price_route: None,
+                        price_depth: None,
+                        price_confidence: None,
+                        price_block: None,
+                        price_status: None,
+                        ..token.clone()
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FactoryRepo for MemoryRepo {
+    async fn resolve(
+        &self,
+        address: Address,
+        status: FactoryStatus,
+    ) -> Result<(i32, FactoryStatus)> {
+        let mut state = self.state();
+        if let Some((id, factory)) = state
+            .factories
+            .iter()
+            .find(|(_, factory)| factory.address == address)
+        {
+            return Ok((*id, factory.status));
+        }
+
+        let id = next_id(&state.factories);
+        state.factories.insert(
+            id,
+            FactoryRow {
+                status,
+                ..FactoryRow::new(address)
+            },
+        );
+        Ok((id, status))
+    }
+
+    async fn next_unsynced(&self) -> Result<Option<Factory>> {
+        Ok(self
+            .state()
+            .factories
+            .iter()
+            .find(|(_, factory)| factory.status == FactoryStatus::Unsynced)
+            .map(|(id, factory)| Factory {
+                id: *id,
+                address: DBAddress::new(factory.address),
+                last_pair_id: factory.last_pair_id,
+                status: factory.status,
+            }))
+    }
+
+    async fn set_status(&self, id: i32, status: FactoryStatus) -> Result<()> {
+        if let Some(factory) = self.state().factories.get_mut(&id) {
+            factory.status = status;
+        }
+        Ok(())
+    }
+
+    async fn set_last_pair_id(&self, id: i32, last_pair_id: i32) -> Result<()> {
+        if let Some(factory) = self.state().factories.get_mut(&id) {
+            factory.last_pair_id = last_pair_id;
+        }
+        Ok(())
+    }
+
+    async fn unclassified(&self, limit: i64) -> Result<Vec<(i32, String, String)>> {
+        let state = self.state();
+        Ok(state
+            .factories
+            .iter()
+            .filter(|(_, factory)| factory.kind.is_none())
+            .filter_map(|(id, factory)| {
+                let pair = state
+                    .pairs
+                    .values()
+                    .find(|pair| pair.factory_id == Some(*id))?;
+                Some((*id, factory.address.to_string(), pair.address.to_string()))
+            })
+            .take(usize::try_from(limit).unwrap_or_default())
+            .collect())
+    }
+
+    async fn classify(&self, id: i32, kind: FactoryKind, fee_bps: Option<u32>) -> Result<()> {
+        if let Some(factory) = self.state().factories.get_mut(&id) {
+            factory.kind = Some(kind);
+            factory.fee_bps = fee_bps.and_then(|fee| i32::try_from(fee).ok());
+        }
+        Ok(())
+    }
+
+    async fn validate(&self, id: i32) -> Result<bool> {
+        let mut state = self.state();
+        let Some(factory) = state
+            .factories
+            .get_mut(&id)
+            .filter(|factory| factory.status == FactoryStatus::Unvalidated)
+        else {
+            return Ok(false);
+        };
+        factory.status = FactoryStatus::Unsynced;
+
+        let valid_tokens = state
+            .tokens
+            .iter()
+            .filter(|(_, token)| token.is_valid)
+            .map(|(id, _)| *id)
+            .collect::<BTreeSet<_>>();
+        for pair in state.pairs.values_mut() {
+            let tokens_valid = [pair.token0_id, pair.token1_id]
+                .iter()
+                .all(|token| token.is_some_and(|token| valid_tokens.contains(&token)));
+            if pair.factory_id == Some(id) && tokens_valid {
+                pair.is_valid = true;
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl SyncStateRepo for MemoryRepo {
+    async fn last_block(&self, stream: &str) -> Result<Option<i64>> {
+        Ok(self.state().checkpoints.get(stream).copied())
+    }
+
+    async fn save_checkpoint(&self, stream: &str, last_block: i64) -> Result<()> {
+        self.state()
+            .checkpoints
+            .insert(stream.to_string(), last_block);
+        Ok(())
+    }
+}
+
+impl BlockRepo for MemoryRepo {
+    async fn blocks(&self, from: u64, to: u64) -> Result<Vec<StoredBlock>> {
+        Ok(self
+            .state()
+            .blocks
+            .range(from..=to)
+            .map(|(_, block)| *block)
+            .collect())
+    }
+
+    async fn latest(&self, limit: u64) -> Result<Vec<(u64, B256)>> {
+        Ok(self
+            .state()
+            .blocks
+            .iter()
+            .rev()
+            .take(usize::try_from(limit).unwrap_or(usize::MAX))
+            .map(|(number, block)| (*number, block.hash))
+            .collect())
+    }
+
+    async fn rollback(&self, ancestor: u64) -> Result<Vec<Address>> {
+        let mut state = self.state();
+        let ancestor_i64 = i64::try_from(ancestor)?;
+
+        let mut addresses = Vec::new();
+        for pair in state.pairs.values_mut() {
+            if pair.block_number.is_some_and(|block| block > ancestor_i64) {
+                pair.reserve0 = None;
+                pair.reserve1 = None;
+                pair.block_number = None;
+                pair.block_hash = None;
+                pair.log_index = None;
+                addresses.push(pair.address);
+            }
+        }
+
+        state.blocks.retain(|number, _| *number <= ancestor);
+        for last_block in state.checkpoints.values_mut() {
+            *last_block = (*last_block).min(ancestor_i64);
+        }
+
+        Ok(addresses)
+    }
+
+    async fn prune(&self, oldest: u64) -> Result<()> {
+        self.state().blocks.retain(|number, _| *number >= oldest);
+        Ok(())
+    }
+}
+
+impl ReserveHistoryRepo for MemoryRepo {
+    async fn partitions(&self) -> Result<Vec<i64>> {
+        Ok(self.state().partitions.iter().copied().collect())
+    }
+
+    async fn create_partition(&self, start: i64, _blocks: i64) -> Result<()> {
+        self.state().partitions.insert(start);
+        Ok(())
+    }
+
+    async fn drop_partition(&self, start: i64) -> Result<()> {
+        self.state().partitions.remove(&start);
+        Ok(())
+    }
+
+    async fn delete_default_before(&self, _cutoff: i64) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+impl OpportunityRepo for MemoryRepo {
+    async fn record(&self, opportunities: &[NewOpportunity]) -> Result<()> {
+        self.state().opportunities.extend_from_slice(opportunities);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloy::primitives::U256;
+
+    use super::*;
+    use crate::models::block::PoolUpdate;
+
+    fn block(number: u64, address: Address, reserve: u64) -> BlockUpdate {
+        BlockUpdate {
+            number,
+            hash: B256::repeat_byte(u8::try_from(number).unwrap()),
+            parent_hash: None,
+            pools: vec![PoolUpdate {
+                address,
+                reserve0: U256::from(reserve),
+                reserve1: U256::from(reserve),
+                log_index: 0,
+            }],
+            syncs: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_reserves_keeps_newest_block() {
+        let repo = MemoryRepo::new();
+        let pair = Address::repeat_byte(1);
+
+        repo.store_reserves(&[block(2, pair, 20)], false)
+            .await
+            .unwrap();
+        repo.store_reserves(&[block(1, pair, 10)], false)
+            .await
+            .unwrap();
+
+        let row = repo.pair(pair).unwrap();
+        assert_eq!(row.reserve0, Some(BigDecimal::from(20)));
+        assert_eq!(row.block_number, Some(2));
+        assert!(repo.without_reserves(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_store_reserves_merges_block_committed_in_parts() {
+        let repo = MemoryRepo::new();
+        let pair = Address::repeat_byte(1);
+
+        let mut first = block(2, pair, 20);
+        first.pools[0].log_index = 4;
+        repo.store_reserves(&[first], false).await.unwrap();
+
+        // Late logs of the same block, committed after the stream went quiet
+        let mut second = block(2, pair, 25);
+        second.pools[0].log_index = 9;
+        repo.store_reserves(&[second], false).await.unwrap();
+        assert_eq!(
+            repo.pair(pair).unwrap().reserve0,
+            Some(BigDecimal::from(25))
+        );
+
+        // A replay of the first part does not move reserves back
+        let mut replay = block(2, pair, 20);
+        replay.pools[0].log_index = 4;
+        repo.store_reserves(&[replay], false).await.unwrap();
+
+        let row = repo.pair(pair).unwrap();
+        assert_eq!(row.reserve0, Some(BigDecimal::from(25)));
+        assert_eq!(row.log_index, Some(9));
+    }
+
+    #[tokio::test]
+    async fn test_set_reserves_keeps_newer_sync_reserves() {
+        let repo = MemoryRepo::new();
+        let pair = Address::repeat_byte(1);
+        repo.store_reserves(&[block(2, pair, 20)], false)
+            .await
+            .unwrap();
+
+        // A snapshot read at an older block
+        let snapshot = [(pair, U256::from(10), U256::from(10))];
+        repo.set_reserves(1, B256::repeat_byte(1), &snapshot)
+            .await
+            .unwrap();
+        assert_eq!(
+            repo.pair(pair).unwrap().reserve0,
+            Some(BigDecimal::from(20))
+        );
+
+        // A snapshot read at the end of the same block follows every Sync log of it
+        let snapshot = [(pair, U256::from(21), U256::from(21))];
+        repo.set_reserves(2, B256::repeat_byte(2), &snapshot)
+            .await
+            .unwrap();
+        assert_eq!(
+            repo.pair(pair).unwrap().reserve0,
+            Some(BigDecimal::from(21))
+        );
+
+        let mut late = block(2, pair, 25);
+        late.pools[0].log_index = 9;
+        repo.store_reserves(&[late], false).await.unwrap();
+        assert_eq!(
+            repo.pair(pair).unwrap().reserve0,
+            Some(BigDecimal::from(21))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_created_only_overwrites_for_trusted_factories() {
+        let repo = MemoryRepo::new();
+        let address = Address::repeat_byte(1);
+        let created = |factory_id| CreatedPair {
+            address,
+            factory_id,
+            token0_id: 1,
+            token1_id: 2,
+            created_block: Some(5),
+            is_valid: false,
+        };
+
+        repo.store_created(&created(1), false).await.unwrap();
+        repo.store_created(&created(2), false).await.unwrap();
+        assert_eq!(repo.pair(address).unwrap().factory_id, Some(1));
+        assert!(!repo.pair(address).unwrap().is_valid);
+
+        repo.store_created(&created(3), true).await.unwrap();
+        assert_eq!(repo.pair(address).unwrap().factory_id, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_with_reserves_pages_a_scope() {
+        let repo = MemoryRepo::new();
+        let priced = |byte, token0_id| PairRow {
+            token0_id: Some(token0_id),
+            token1_id: Some(9),
+            reserve0: Some(BigDecimal::from(1)),
+            reserve1: Some(BigDecimal::from(1)),
+            ..PairRow::new(Address::repeat_byte(byte))
+        };
+        let first = repo.insert_pair(priced(1, 1));
+        repo.insert_pair(PairRow::new(Address::repeat_byte(2)));
+        let third = repo.insert_pair(priced(3, 1));
+        repo.insert_pair(priced(4, 2));
+
+        let scope = PairScope::Tokens(vec![1]);
+        let page = repo.with_reserves(&scope, 0, 1).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].id, first);
+
+        let page = repo.with_reserves(&scope, first, 10).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].id, third);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_keeps_known_factories() {
+        let repo = MemoryRepo::new();
+        let address = Address::repeat_byte(1);
+
+        let (id, status) = repo
+            .resolve(address, FactoryStatus::Unvalidated)
+            .await
+            .unwrap();
+        assert_eq!(status, FactoryStatus::Unvalidated);
+
+        let resolved = repo
+            .resolve(address, FactoryStatus::Unsynced)
+            .await
+            .unwrap();
+        assert_eq!(resolved, (id, FactoryStatus::Unvalidated));
+        assert!(repo.next_unsynced().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rollback_clears_pairs_after_ancestor() {
+        let repo = MemoryRepo::new();
+        let (kept, orphaned) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        repo.store_reserves(&[block(10, kept, 100), block(12, orphaned, 120)], false)
+            .await
+            .unwrap();
+        repo.save_checkpoint("events", 12).await.unwrap();
+
+        assert_eq!(repo.rollback(11).await.unwrap(), vec![orphaned]);
+
+        assert_eq!(repo.pair(kept).unwrap().block_number, Some(10));
+        assert_eq!(repo.pair(orphaned).unwrap().reserve0, None);
+        assert_eq!(
+            repo.latest(10).await.unwrap(),
+            vec![(10, B256::repeat_byte(10))]
+        );
+        assert_eq!(repo.last_block("events").await.unwrap(), Some(11));
+    }
+}
@@ -0,0 +1,427 @@
+//! The database reads and writes sync workers make, behind traits.
+//!
+//! Workers used to write their diesel queries inline against `crate::schemas`, so none of them
+//! could run without Postgres and the same queries were repeated across files. The repositories
+//! cover the pairs, tokens, factories, sync state, block window and reserve history partitions
+//! that workers read and write, and the opportunities the bot detected. `pg::PgRepo` implements
+//! them on the connection pool for production, and `memory::MemoryRepo` keeps the rows in memory
+//! for tests.
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use alloy::primitives::{Address, B256, U256};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use eyre::Result;
+
+pub use pg::PgRepo;
+
+use crate::models::block::BlockUpdate;
+use crate::models::factory::{Factory, FactoryKind, FactoryStatus};
+use crate::models::opportunity::NewOpportunity;
+use crate::models::pair::{DBAddress, Pair};
+use crate::models::token::{PriceStatus, TokenMetadata};
+use crate::pricing::engine::Price;
+use crate::pricing::guard::{PoolBlocks, Verdict};
+use crate::pricing::Pool;
+
+/// In-memory repositories for tests
+#[cfg(test)]
+pub mod memory;
+/// Repositories backed by Postgres
+pub mod pg;
+
+/// The log index of reserves read at the end of a block, after every Sync log of the block
+const END_OF_BLOCK: u64 = 0x7fff_ffff;
+
+/// Exchange rates and decimals of tokens by ID
+pub type TokenRates = HashMap<i32, (Option<BigDecimal>, Option<i32>)>;
+
+/// Which pairs to load
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairScope {
+    /// Every pair
+    All,
+    /// Pairs with these addresses
+    Pairs(Vec<Address>),
+    /// Pairs of these token IDs
+    Tokens(Vec<i32>),
+}
+
+/// A pair with both tokens and reserves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairReserves {
+    /// The ID of the pair
+    pub id: i32,
+    /// The ID of token0
+    pub token0_id: i32,
+    /// The ID of token1
+    pub token1_id: i32,
+    /// The reserve of token0
+    pub reserve0: BigDecimal,
+    /// The reserve of token1
+    pub reserve1: BigDecimal,
+    /// The block of the reserves, if known
+    pub block_number: Option<i64>,
+}
+
+/// Which tradable pairs to load, beyond being valid with both tokens and reserves
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolFilter {
+    /// Only pairs worth at least this in USD
+    pub min_usd: Option<BigDecimal>,
+    /// Only pairs of these factory IDs
+    pub factories: Option<Vec<i32>>,
+}
+
+/// A valid pair with the addresses of its tokens and its reserves
+#[derive(Debug, Clone)]
+pub struct TradablePair {
+    /// The ID of the pair
+    pub id: i32,
+    /// The address of the pair
+    pub address: DBAddress,
+    /// The address of token0
+    pub token0: DBAddress,
+    /// The address of token1
+    pub token1: DBAddress,
+    /// The reserve of token0
+    pub reserve0: BigDecimal,
+    /// The reserve of token1
+    pub reserve1: BigDecimal,
+}
+
+/// The USD value of a pair
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsdValue {
+    /// The ID of the pair
+    pub pair_id: i32,
+    /// The value, `None` if a token can't be valued
+    pub usd: Option<BigDecimal>,
+    /// The block of the reserves the value was computed from
+    pub block: Option<i64>,
+}
+
+/// A block of the reorg window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoredBlock {
+    /// The block number
+    pub number: u64,
+    /// The block hash
+    pub hash: B256,
+    /// The parent block hash, unknown for blocks only written by the backfill
+    pub parent_hash: Option<B256>,
+}
+
+/// A pair announced by a `PairCreated` event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedPair {
+    /// The address of the pair
+    pub address: Address,
+    /// The ID of the emitting factory
+    pub factory_id: i32,
+    /// The ID of token0
+    pub token0_id: i32,
+    /// The ID of token1
+    pub token1_id: i32,
+    /// The block of the event
+    pub created_block: Option<i64>,
+    /// Whether the pair can be traded
+    pub is_valid: bool,
+}
+
+/// A token with its stored price
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenPrice {
+    /// The ID of the token
+    pub id: i32,
+    /// The address of the token
+    pub address: String,
+    /// The decimals of the token
+    pub decimals: Option<i32>,
+    /// The stored exchange rate
+    pub exchange_rate: Option<BigDecimal>,
+    /// The stored route of the exchange rate
+    pub price_route: Option<Vec<Option<i32>>>,
+    /// The stored guard status of the exchange rate
+    pub price_status: Option<PriceStatus>,
+    /// The stored confidence in the exchange rate
+    pub price_confidence: Option<f64>,
+}
+
+impl TokenPrice {
+    /// The exchange rate, unless the guard flagged it or its confidence is below
+    /// `min_confidence`
+    ///
+    /// Rates stored before the guard existed have neither a status nor a confidence and are
+    /// trusted. A rejected price keeps the previous rate, which is trusted as before.
+    #[must_use]
+    pub fn trusted_rate(&self, min_confidence: f64) -> Option<&BigDecimal> {
+        if self.price_status == Some(PriceStatus::Flagged)
+            || self
+                .price_confidence
+                .is_some_and(|confidence| confidence < min_confidence)
+        {
+            return None;
+        }
+
+        self.exchange_rate.as_ref()
+    }
+
+    /// Whether the stored price is `price` with `verdict`
+    #[must_use]
+    pub fn has(&self, price: &Price, verdict: &Verdict) -> bool {
+        self.exchange_rate.as_ref() == Some(&price.rate)
+            && self.price_status == Some(verdict.status)
+            && self
+                .price_route
+                .iter()
+                .flatten()
+                .copied()
+                .eq(price.route.iter().copied().map(Some))
+    }
+}
+
+/// What happens to the stored price of a token
+#[derive(Debug, Clone)]
+pub enum PriceChange {
+    /// The token takes a new price, stored with where it came from
+    Priced(Price, Verdict),
+    /// The new price was rejected by the guard, the previous one stays
+    Rejected(Verdict),
+    /// The token can no longer be priced
+    Unpriced,
+}
+
+/// Pairs and their reserves
+pub trait PairRepo: Send + Sync {
+    /// Pairs missing a reserve
+    fn without_reserves(&self, limit: i64) -> impl Future<Output = Result<Vec<Pair>>> + Send;
+
+    /// Pairs missing their factory
+    fn without_factory(&self, limit: i64) -> impl Future<Output = Result<Vec<Pair>>> + Send;
+
+    /// Pairs missing a token
+    fn without_tokens(&self, limit: i64) -> impl Future<Output = Result<Vec<Pair>>> + Send;
+
+    /// Set the reserves of pairs, as `(address, reserve0, reserve1)`, read at the end of a block
+    ///
+    /// Goes through the upsert of `store_reserves` as if from after every Sync log of the block,
+    /// so reserves of an older block never overwrite newer Sync reserves and a reorg rolls them
+    /// back like the others.
+    fn set_reserves(
+        &self,
+        number: u64,
+        hash: B256,
+        reserves: &[(Address, U256, U256)],
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Set the factory of a pair
+    fn set_factory(&self, id: i32, factory_id: i32) -> impl Future<Output = Result<()>> + Send;
+
+    /// Set token0 or token1 of a pair, and whether the pair is valid
+    fn set_token(
+        &self,
+        id: i32,
+        is_token0: bool,
+        token_id: i32,
+        is_valid: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Insert a pair listed by a factory, or attach an existing pair to the factory
+    fn discover(
+        &self,
+        address: Address,
+        factory_id: i32,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Store the pair of a `PairCreated` event
+    ///
+    /// Pairs of trusted factories are upserted, so events can be replayed. Pairs of other
+    /// factories never overwrite an existing pair.
+    fn store_created(
+        &self,
+        pair: &CreatedPair,
+        trusted: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Write blocks and the reserves of their pairs in one transaction
+    ///
+    /// Updates must be ordered by block number. A pair that changed in several blocks is written
+    /// once with its latest reserves, and never overwrites reserves of a newer block, or of a
+    /// later Sync log of the same block, already stored. Replaying old blocks is harmless and a
+    /// block committed in several parts ends with its last reserves. With `record_history`, every
+    /// Sync log is also appended to `reserve_history`.
+    fn store_reserves(
+        &self,
+        updates: &[BlockUpdate],
+        record_history: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// The next pairs of a scope with both tokens and reserves, after the pair ID `after`, in ID
+    /// order
+    fn with_reserves(
+        &self,
+        scope: &PairScope,
+        after: i32,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<PairReserves>>> + Send;
+
+    /// The next valid pairs matching a filter with both tokens and reserves, after the pair ID
+    /// `after`, in ID order
+    fn tradable(
+        &self,
+        filter: &PoolFilter,
+        after: i32,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<TradablePair>>> + Send;
+
+    /// Store the USD values of pairs
+    fn store_usd(
+        &self,
+        values: &[UsdValue],
+        updated_at: NaiveDateTime,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Valid pairs with both tokens and positive reserves, and the blocks of every pair loaded
+    fn pools(&self) -> impl Future<Output = Result<(Vec<Pool>, HashMap<i32, PoolBlocks>)>> + Send;
+}
+
+/// Tokens, their metadata and their prices
+pub trait TokenRepo: Send + Sync {
+    /// Insert or update a token with its metadata, returning its ID
+    fn save_metadata(&self, metadata: &TokenMetadata) -> impl Future<Output = Result<i32>> + Send;
+
+    /// Tokens whose metadata was stored without status, oldest first
+    fn undecoded(&self, limit: i64) -> impl Future<Output = Result<Vec<Address>>> + Send;
+
+    /// Addresses of valid tokens
+    fn valid(&self) -> impl Future<Output = Result<Vec<Address>>> + Send;
+
+    /// IDs of tokens by address, unknown tokens are left out
+    fn ids(&self, addresses: &[Address]) -> impl Future<Output = Result<Vec<i32>>> + Send;
+
+    /// Exchange rates and decimals of tokens by ID
+    fn rates(&self, ids: &[i32]) -> impl Future<Output = Result<TokenRates>> + Send;
+
+    /// Set the exchange rate of a token, returning whether it changed
+    fn set_rate(
+        &self,
+        address: Address,
+        rate: &BigDecimal,
+        updated_at: NaiveDateTime,
+    ) -> impl Future<Output = Result<bool>> + Send;
+
+    /// Every token with its stored price
+    fn prices(&self) -> impl Future<Output = Result<Vec<TokenPrice>>> + Send;
+
+    /// Store changes to the prices of tokens by ID in one transaction
+    fn update_prices(
+        &self,
+        changes: &[(i32, &PriceChange)],
+        updated_at: NaiveDateTime,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Factories of pairs
+pub trait FactoryRepo: Send + Sync {
+    /// Get the ID and status of a factory, inserting it with `status` if it is unknown
+    fn resolve(
+        &self,
+        address: Address,
+        status: FactoryStatus,
+    ) -> impl Future<Output = Result<(i32, FactoryStatus)>> + Send;
+
+    /// The first factory whose pairs were not all listed
+    fn next_unsynced(&self) -> impl Future<Output = Result<Option<Factory>>> + Send;
+
+    /// Set the status of a factory
+    fn set_status(&self, id: i32, status: FactoryStatus)
+        -> impl Future<Output = Result<()>> + Send;
+
+    /// Set the number of pairs of a factory listed so far
+    fn set_last_pair_id(
+        &self,
+        id: i32,
+        last_pair_id: i32,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Factories that were not classified yet, each with the address of one of its pairs
+    fn unclassified(
+        &self,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<(i32, String, String)>>> + Send;
+
+    /// Store the kind and swap fee of a factory
+    fn classify(
+        &self,
+        id: i32,
+        kind: FactoryKind,
+        fee_bps: Option<u32>,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Trust a factory only known from `PairCreated` events, after it was classified
+    ///
+    /// The factory becomes `Unsynced`, so all its pairs get listed, and its pairs whose tokens
+    /// are both valid become valid. Factories that are not `Unvalidated` are left alone.
+    ///
+    /// # Returns
+    /// `true` if the factory was unvalidated
+    fn validate(&self, id: i32) -> impl Future<Output = Result<bool>> + Send;
+}
+
+/// Checkpoints of backfilled event streams
+pub trait SyncStateRepo: Send + Sync {
+    /// The last block processed by a stream
+    fn last_block(&self, stream: &str) -> impl Future<Output = Result<Option<i64>>> + Send;
+
+    /// Insert or update the checkpoint of a stream
+    fn save_checkpoint(
+        &self,
+        stream: &str,
+        last_block: i64,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// The hashes of recent blocks, kept by `sync::reorg` to find the common ancestor of a reorg
+pub trait BlockRepo: Send + Sync {
+    /// Stored blocks from `from` to `to`, in number order
+    fn blocks(&self, from: u64, to: u64) -> impl Future<Output = Result<Vec<StoredBlock>>> + Send;
+
+    /// Stored `(number, hash)` of the newest `limit` blocks, newest first
+    fn latest(&self, limit: u64) -> impl Future<Output = Result<Vec<(u64, B256)>>> + Send;
+
+    /// Roll back everything written after block `ancestor` in one transaction
+    ///
+    /// Pairs updated after the ancestor lose their reserves, later blocks and their reserve
+    /// history are deleted and checkpoints past the ancestor are rewound. Returns the addresses
+    /// of the rolled back pairs.
+    fn rollback(&self, ancestor: u64) -> impl Future<Output = Result<Vec<Address>>> + Send;
+
+    /// Delete the blocks before `oldest`
+    fn prune(&self, oldest: u64) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// The block range partitions of `reserve_history`
+pub trait ReserveHistoryRepo: Send + Sync {
+    /// The first block of every partition
+    fn partitions(&self) -> impl Future<Output = Result<Vec<i64>>> + Send;
+
+    /// Create the partition of `blocks` blocks from `start` unless it exists
+    ///
+    /// Rows of the range already in the default partition are moved into the new partition.
+    fn create_partition(&self, start: i64, blocks: i64) -> impl Future<Output = Result<()>> + Send;
+
+    /// Drop the partition starting at `start`
+    fn drop_partition(&self, start: i64) -> impl Future<Output = Result<()>> + Send;
+
+    /// Delete the rows of the default partition before `cutoff`, returning how many
+    fn delete_default_before(&self, cutoff: i64) -> impl Future<Output = Result<usize>> + Send;
+}
+
+/// Opportunities the bot detected
+pub trait OpportunityRepo: Send + Sync {
+    /// Append opportunities
+    fn record(&self, opportunities: &[NewOpportunity]) -> impl Future<Output = Result<()>> + Send;
+}
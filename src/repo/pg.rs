@@ -0,0 +1,1493 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use alloy::primitives::{Address, B256, U256};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use diesel::sql_types::{Array, BigInt, Int4, Int8, Nullable, Numeric, Text, Timestamp};
+use diesel::upsert::excluded;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgExpressionMethods, QueryDsl,
+    QueryableByName, SelectableHelper,
+};
+use diesel_async::pooled_connection::deadpool::Pool;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use eyre::Result;
+
+use super::{
+    BlockRepo, CreatedPair, FactoryRepo, OpportunityRepo, PairRepo, PairReserves, PairScope,
+    PoolFilter, PriceChange, ReserveHistoryRepo, StoredBlock, SyncStateRepo, TokenPrice,
+    TokenRates, TokenRepo, TradablePair, UsdValue, END_OF_BLOCK,
+};
+use crate::models::block::BlockUpdate;
+use crate::models::factory::{Factory, FactoryKind, FactoryStatus};
+use crate::models::opportunity::{self, NewOpportunity};
+use crate::models::pair::{DBAddress, Pair};
+use crate::models::reserve_history::{self, NewReserveHistory};
+use crate::models::sync_state::SyncState;
+use crate::models::token::{PriceStatus, TokenMetadata};
+use crate::models::ROWS_PER_INSERT;
+use crate::pricing::guard::PoolBlocks;
+use crate::pricing::Pool as PricedPool;
+use crate::schemas::{blocks, factories, pairs, sync_state, tokens};
+use crate::utils::numeric::u256_to_big_decimal;
+
+/// The parent hash of an upserted block. Replayed blocks come without one, so the same block
+/// keeps the parent hash it was stored with, while a block replacing it takes its own.
+const KEEP_PARENT_HASH: &str = "CASE WHEN blocks.hash = excluded.hash
+    THEN COALESCE(excluded.parent_hash, blocks.parent_hash)
+    ELSE excluded.parent_hash END";
+
+/// Write the reserves of pairs unless the stored ones are from a newer block, or from a later
+/// Sync log of the same block. A block that replaced the stored one under the same number
+/// overwrites it, and a block committed in several parts moves reserves forward.
+const UPSERT_RESERVES: &str = "
+    INSERT INTO pairs (address, reserve0, reserve1, block_number, block_hash, log_index)
+    SELECT * FROM UNNEST(
+        $1::varchar[], $2::numeric[], $3::numeric[], $4::bigint[], $5::varchar[], $6::int[]
+    )
+    ON CONFLICT (address) DO UPDATE SET
+        reserve0 = EXCLUDED.reserve0,
+        reserve1 = EXCLUDED.reserve1,
+        block_number = EXCLUDED.block_number,
+        block_hash = EXCLUDED.block_hash,
+        log_index = EXCLUDED.log_index
+    WHERE pairs.block_number IS NULL
+        OR pairs.block_number < EXCLUDED.block_number
+        OR (pairs.block_number = EXCLUDED.block_number
+            AND pairs.block_hash IS DISTINCT FROM EXCLUDED.block_hash)
+        OR (pairs.block_number = EXCLUDED.block_number
+            AND pairs.block_hash = EXCLUDED.block_hash
+            AND (pairs.log_index IS NULL OR pairs.log_index < EXCLUDED.log_index))
+";
+
+/// Repositories on the Postgres connection pool
+#[derive(Clone)]
+pub struct PgRepo {
+    /// The connection pool
+    pool: Pool<AsyncPgConnection>,
+}
+
+/// The reserves of a pair as written by `UPSERT_RESERVES`
+struct ReserveRow {
+    /// The address of the pair
+    address: Address,
+    /// The reserve of token0
+    reserve0: U256,
+    /// The reserve of token1
+    reserve1: U256,
+    /// The block of the reserves
+    block_number: i64,
+    /// The hash of the block of the reserves
+    block_hash: B256,
+    /// The index of the Sync log of the reserves in their block
+    log_index: i32,
+}
+
+/// A row of the tradable pairs query
+#[derive(QueryableByName)]
+struct TradableRow {
+    /// The ID of the pair
+    #[diesel(sql_type = Int4)]
+    id: i32,
+    /// The address of the pair
+    #[diesel(sql_type = Text)]
+    address: DBAddress,
+    /// The address of token0
+    #[diesel(sql_type = Text)]
+    token0: DBAddress,
+    /// The address of token1
+    #[diesel(sql_type = Text)]
+    token1: DBAddress,
+    /// The reserve of token0
+    #[diesel(sql_type = Numeric)]
+    reserve0: BigDecimal,
+    /// The reserve of token1
+    #[diesel(sql_type = Numeric)]
+    reserve1: BigDecimal,
+}
+
+impl PgRepo {
+    /// Create repositories on a connection pool
+    #[must_use]
+    pub const fn new(pool: Pool<AsyncPgConnection>) -> Self {
+        Self { pool }
+    }
+}
+
+impl PairRepo for PgRepo {
+    async fn without_reserves(&self, limit: i64) -> Result<Vec<Pair>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(pairs::table
+            .filter(pairs::reserve0.is_null().or(pairs::reserve1.is_null()))
+            .select(Pair::as_select())
+            .limit(limit)
+            .load(&mut conn)
+            .await?)
+    }
+
+    async fn without_factory(&self, limit: i64) -> Result<Vec<Pair>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(pairs::table
+            .filter(pairs::factory_id.is_null())
+            .select(Pair::as_select())
+            .limit(limit)
+            .load(&mut conn)
+            .await?)
+    }
+
+    async fn without_tokens(&self, limit: i64) -> Result<Vec<Pair>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(pairs::table
+            .filter(pairs::token0_id.is_null().or(pairs::token1_id.is_null()))
+            .select(Pair::as_select())
+            .limit(limit)
+            .load(&mut conn)
+            .await?)
+    }
+
+    async fn set_reserves(
+        &self,
+        number: u64,
+        hash: B256,
+        reserves: &[(Address, U256, U256)],
+    ) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        let block_number = to_i64(number)?;
+        let log_index = to_i32(END_OF_BLOCK)?;
+
+        let rows = reserves
+            .iter()
+            .map(|&(address, reserve0, reserve1)| ReserveRow {
+                address,
+                reserve0,
+                reserve1,
+                block_number,
+                block_hash: hash,
+                log_index,
+            })
+            .collect::<Vec<_>>();
+        upsert_reserves(&mut conn, &rows).await?;
+
+        Ok(())
+    }
+
+    async fn set_factory(&self, id: i32, factory_id: i32) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::update(pairs::table.find(id))
+            .set(pairs::factory_id.eq(factory_id))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_token(
+        &self,
+        id: i32,
+        is_token0: bool,
+        token_id: i32,
+        is_valid: bool,
+    ) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        let target = pairs::table.find(id);
+
+        if is_token0 {
+            diesel::update(target)
+                .set((pairs::token0_id.eq(token_id), pairs::is_valid.eq(is_valid)))
+                .execute(&mut conn)
+                .await?;
+        } else {
+            diesel::update(target)
+                .set((pairs::token1_id.eq(token_id), pairs::is_valid.eq(is_valid)))
+                .execute(&mut conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn discover(&self, address: Address, factory_id: i32) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::insert_into(pairs::table)
+            .values((
+                pairs::address.eq(address.to_string()),
+                pairs::factory_id.eq(factory_id),
+            ))
+            .on_conflict(pairs::address)
+            .do_update()
+            .set(pairs::factory_id.eq(factory_id))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn store_created(&self, pair: &CreatedPair, trusted: bool) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        let values = (
+            pairs::address.eq(pair.address.to_string()),
+            pairs::factory_id.eq(pair.factory_id),
+            pairs::token0_id.eq(pair.token0_id),
+            pairs::token1_id.eq(pair.token1_id),
+            pairs::created_block.eq(pair.created_block),
+            pairs::is_valid.eq(pair.is_valid),
+        );
+
+        if trusted {
+            diesel::insert_into(pairs::table)
+                .values(values)
+                .on_conflict(pairs::address)
+                .do_update()
+                .set((
+                    pairs::factory_id.eq(excluded(pairs::factory_id)),
+                    pairs::token0_id.eq(excluded(pairs::token0_id)),
+                    pairs::token1_id.eq(excluded(pairs::token1_id)),
+                    pairs::created_block.eq(excluded(pairs::created_block)),
+                ))
+                .execute(&mut conn)
+                .await?;
+        } else {
+            diesel::insert_into(pairs::table)
+                .values(values)
+                .on_conflict(pairs::address)
+                .do_nothing()
+                .execute(&mut conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn store_reserves(&self, updates: &[BlockUpdate], record_history: bool) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move { store_reserves(conn, updates, record_history).await }.scope_boxed()
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn with_reserves(
+        &self,
+        scope: &PairScope,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<PairReserves>> {
+        let mut conn = self.pool.get().await?;
+
+        let mut query = pairs::table
+            .filter(pairs::id.gt(after))
+            .filter(
+                pairs::token0_id
+                    .is_not_null()
+                    .and(pairs::token1_id.is_not_null())
+                    .and(pairs::reserve0.is_not_null())
+                    .and(pairs::reserve1.is_not_null()),
+            )
+            .select((
+                pairs::id,
+                pairs::token0_id,
+                pairs::token1_id,
+                pairs::reserve0,
+                pairs::reserve1,
+                pairs::block_number,
+            ))
+            .order(pairs::id)
+            .limit(limit)
+            .into_boxed();
+
+        query = match scope {
+            PairScope::All => query,
+            PairScope::Pairs(addresses) => query.filter(
+                pairs::address.eq_any(
+                    addresses
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>(),
+                ),
+            ),
+            PairScope::Tokens(ids) => query.filter(
+                pairs::token0_id
+                    .eq_any(ids)
+                    .or(pairs::token1_id.eq_any(ids)),
+            ),
+        };
+
+        let rows = query
+            .load::<(
+                i32,
+                Option<i32>,
+                Option<i32>,
+                Option<BigDecimal>,
+                Option<BigDecimal>,
+                Option<i64>,
+            )>(&mut conn)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(
+                |(id, token0_id, token1_id, reserve0, reserve1, block_number)| {
+                    Some(PairReserves {
+                        id,
+                        token0_id: token0_id?,
+                        token1_id: token1_id?,
+                        reserve0: reserve0?,
+                        reserve1: reserve1?,
+                        block_number,
+                    })
+                },
+            )
+            .collect())
+    }
+
+    async fn tradable(
+        &self,
+        filter: &PoolFilter,
+        after: i32,
+        limit: i64,
+    ) -> Result<Vec<TradablePair>> {
+        let mut conn = self.pool.get().await?;
+
+        // Both tokens are joined for their addresses, and the pages walk the primary key
+        let rows = diesel::sql_query(
+            "SELECT p.id, p.address, t0.address AS token0, t1.address AS token1, \
+             p.reserve0, p.reserve1 \
+             FROM pairs p \
+             JOIN tokens t0 ON t0.id = p.token0_id \
+             JOIN tokens t1 ON t1.id = p.token1_id \
+             WHERE p.id > $1 AND p.is_valid \
+             AND p.reserve0 IS NOT NULL AND p.reserve1 IS NOT NULL \
+             AND ($2::numeric IS NULL OR p.usd >= $2) \
+             AND ($3::int4[] IS NULL OR p.factory_id = ANY($3)) \
+             ORDER BY p.id LIMIT $4",
+        )
+        .bind::<Int4, _>(after)
+        .bind::<Nullable<Numeric>, _>(filter.min_usd.clone())
+        .bind::<Nullable<Array<Int4>>, _>(filter.factories.clone())
+        .bind::<Int8, _>(limit)
+        .load::<TradableRow>(&mut conn)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TradablePair {
+                id: row.id,
+                address: row.address,
+                token0: row.token0,
+                token1: row.token1,
+                reserve0: row.reserve0,
+                reserve1: row.reserve1,
+            })
+            .collect())
+    }
+
+    async fn store_usd(&self, values: &[UsdValue], updated_at: NaiveDateTime) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        let ids = values.iter().map(|value| value.pair_id).collect::<Vec<_>>();
+        let usd = values
+            .iter()
+            .map(|value| value.usd.clone())
+            .collect::<Vec<_>>();
+        let blocks = values.iter().map(|value| value.block).collect::<Vec<_>>();
+
+        // One statement for the whole batch
+        diesel::sql_query(
+            "UPDATE pairs SET usd = v.usd, usd_block = v.block, usd_updated_at = $4 \
+             FROM unnest($1::int4[], $2::numeric[], $3::int8[]) AS v(id, usd, block) \
+             WHERE pairs.id = v.id",
+        )
+        .bind::<Array<Int4>, _>(ids)
+        .bind::<Array<Nullable<Numeric>>, _>(usd)
+        .bind::<Array<Nullable<Int8>>, _>(blocks)
+        .bind::<Timestamp, _>(updated_at)
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn pools(&self) -> Result<(Vec<PricedPool>, HashMap<i32, PoolBlocks>)> {
+        let mut conn = self.pool.get().await?;
+
+        let pairs = pairs::table
+            .filter(pairs::is_valid.eq(true))
+            .filter(pairs::reserve0.gt(BigDecimal::from(0)))
+            .filter(pairs::reserve1.gt(BigDecimal::from(0)))
+            .select((
+                pairs::id,
+                pairs::token0_id,
+                pairs::token1_id,
+                pairs::reserve0,
+                pairs::reserve1,
+                pairs::created_block,
+                pairs::block_number,
+            ))
+            .load::<(
+                i32,
+                Option<i32>,
+                Option<i32>,
+                Option<BigDecimal>,
+                Option<BigDecimal>,
+                Option<i64>,
+                Option<i64>,
+            )>(&mut conn)
+            .await?;
+
+        let blocks = pairs
+            .iter()
+            .map(|(id, _, _, _, _, created, updated)| {
+                (
+                    *id,
+                    PoolBlocks {
+                        created: *created,
+                        updated: *updated,
+                    },
+                )
+            })
+            .collect();
+
+        let pools = pairs
+            .into_iter()
+            .filter_map(|(id, token0, token1, reserve0, reserve1, _, _)| {
+                Some(PricedPool {
+                    id,
+                    token0: token0?,
+                    token1: token1?,
+                    reserve0: reserve0?,
+                    reserve1: reserve1?,
+                })
+            })
+            .collect();
+
+        Ok((pools, blocks))
+    }
+}
+
+impl TokenRepo for PgRepo {
+    async fn save_metadata(&self, metadata: &TokenMetadata) -> Result<i32> {
+        let mut conn = self.pool.get().await?;
+
+        let values = (
+            tokens::name.eq(metadata.name.as_deref()),
+            tokens::name_status.eq(metadata.name_status),
+            tokens::symbol.eq(metadata.symbol.as_deref()),
+            tokens::symbol_status.eq(metadata.symbol_status),
+            tokens::decimals.eq(metadata.decimals.map(i32::from)),
+            tokens::decimals_status.eq(metadata.decimals_status),
+            tokens::is_valid.eq(metadata.is_valid()),
+        );
+
+        Ok(diesel::insert_into(tokens::table)
+            .values((
+                tokens::address.eq(metadata.address.to_string()),
+                values.clone(),
+            ))
+            .on_conflict(tokens::address)
+            .do_update()
+            .set(values)
+            .returning(tokens::id)
+            .get_result(&mut conn)
+            .await?)
+    }
+
+    async fn undecoded(&self, limit: i64) -> Result<Vec<Address>> {
+        let mut conn = self.pool.get().await?;
+
+        let addresses: Vec<String> = tokens::table
+            .filter(tokens::decimals_status.is_null())
+            .order(tokens::id)
+            .select(tokens::address)
+            .limit(limit)
+            .load(&mut conn)
+            .await?;
+
+        Ok(parse_addresses(&addresses))
+    }
+
+    async fn valid(&self) -> Result<Vec<Address>> {
+        let mut conn = self.pool.get().await?;
+
+        let addresses: Vec<String> = tokens::table
+            .filter(tokens::is_valid.eq(true))
+            .select(tokens::address)
+            .load(&mut conn)
+            .await?;
+
+        Ok(parse_addresses(&addresses))
+    }
+
+    async fn ids(&self, addresses: &[Address]) -> Result<Vec<i32>> {
+        let mut conn = self.pool.get().await?;
+
+        let addresses = addresses
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        Ok(tokens::table
+            .filter(tokens::address.eq_any(addresses))
+            .select(tokens::id)
+            .load(&mut conn)
+            .await?)
+    }
+
+    async fn rates(&self, ids: &[i32]) -> Result<TokenRates> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(tokens::table
+            .filter(tokens::id.eq_any(ids))
+            .select((tokens::id, tokens::exchange_rate, tokens::decimals))
+            .load::<(i32, Option<BigDecimal>, Option<i32>)>(&mut conn)
+            .await?
+            .into_iter()
+            .map(|(id, exchange_rate, decimals)| (id, (exchange_rate, decimals)))
+            .collect())
+    }
+
+    async fn set_rate(
+        &self,
+        address: Address,
+        rate: &BigDecimal,
+        updated_at: NaiveDateTime,
+    ) -> Result<bool> {
+        let mut conn = self.pool.get().await?;
+
+        let updated_rows = diesel::update(
+            tokens::table
+                .filter(tokens::address.eq(address.to_string()))
+                .filter(tokens::exchange_rate.is_distinct_from(rate)),
+        )
+        .set((
+            tokens::exchange_rate.eq(rate),
+            tokens::updated_last.eq(updated_at),
+        ))
+        .execute(&mut conn)
+        .await?;
+
+        Ok(updated_rows > 0)
+    }
+
+    async fn prices(&self) -> Result<Vec<TokenPrice>> {
+        let mut conn = self.pool.get().await?;
+
+        let rows = tokens::table
+            .select((
+                tokens::id,
+                tokens::address,
+                tokens::decimals,
+                tokens::exchange_rate,
+                tokens::price_route,
+                tokens::price_status,
+                tokens::price_confidence,
+            ))
+            .load::<(
+                i32,
+                String,
+                Option<i32>,
+                Option<BigDecimal>,
+                Option<Vec<Option<i32>>>,
+                Option<PriceStatus>,
+                Option<f64>,
+            )>(&mut conn)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(
+                    id,
+                    address,
+                    decimals,
+                    exchange_rate,
+                    price_route,
+                    price_status,
+                    price_confidence,
+                )| TokenPrice {
+                    id,
+                    address,
+                    decimals,
+                    exchange_rate,
+                    price_route,
+                    price_status,
+                    price_confidence,
+                },
+            )
+            .collect())
+    }
+
+    async fn update_prices(
+        &self,
+        changes: &[(i32, &PriceChange)],
+        updated_at: NaiveDateTime,
+    ) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                for (token_id, change) in changes {
+                    update_price(conn, *token_id, change, updated_at).await?;
+                }
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl FactoryRepo for PgRepo {
+    async fn resolve(
+        &self,
+        address: Address,
+        status: FactoryStatus,
+    ) -> Result<(i32, FactoryStatus)> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(diesel::insert_into(factories::table)
+            .values((
+                factories::address.eq(address.to_string()),
+                factories::status.eq(status),
+            ))
+            .on_conflict(factories::address)
+            // A no-op update, so the existing row is returned
+            .do_update()
+            .set(factories::address.eq(excluded(factories::address)))
+            .returning((factories::id, factories::status))
+            .get_result(&mut conn)
+            .await?)
+    }
+
+    async fn next_unsynced(&self) -> Result<Option<Factory>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(factories::table
+            .filter(factories::status.eq(FactoryStatus::Unsynced))
+            .select(Factory::as_select())
+            .first(&mut conn)
+            .await
+            .optional()?)
+    }
+
+    async fn set_status(&self, id: i32, status: FactoryStatus) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::update(factories::table.find(id))
+            .set(factories::status.eq(status))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_last_pair_id(&self, id: i32, last_pair_id: i32) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::update(factories::table.find(id))
+            .set(factories::last_pair_id.eq(last_pair_id))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn unclassified(&self, limit: i64) -> Result<Vec<(i32, String, String)>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(factories::table
+            .inner_join(pairs::table)
+            .filter(factories::kind.is_null())
+            .distinct_on(factories::id)
+            .order((factories::id, pairs::id))
+            .select((factories::id, factories::address, pairs::address))
+            .limit(limit)
+            .load(&mut conn)
+            .await?)
+    }
+
+    async fn classify(&self, id: i32, kind: FactoryKind, fee_bps: Option<u32>) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::update(factories::table.find(id))
+            .set((
+                factories::kind.eq(kind),
+                factories::fee_bps.eq(fee_bps.and_then(|fee| i32::try_from(fee).ok())),
+            ))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn validate(&self, id: i32) -> Result<bool> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(conn
+            .transaction(|conn| async move { validate(conn, id).await }.scope_boxed())
+            .await?)
+    }
+}
+
+impl SyncStateRepo for PgRepo {
+    async fn last_block(&self, stream: &str) -> Result<Option<i64>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(sync_state::table
+            .filter(sync_state::stream.eq(stream))
+            .select(sync_state::last_block)
+            .first(&mut conn)
+            .await
+            .optional()?)
+    }
+
+    async fn save_checkpoint(&self, stream: &str, last_block: i64) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        diesel::insert_into(sync_state::table)
+            .values(SyncState::new(stream, last_block))
+            .on_conflict(sync_state::stream)
+            .do_update()
+            .set((
+                sync_state::last_block.eq(excluded(sync_state::last_block)),
+                sync_state::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl BlockRepo for PgRepo {
+    async fn blocks(&self, from: u64, to: u64) -> Result<Vec<StoredBlock>> {
+        let mut conn = self.pool.get().await?;
+
+        let rows = blocks::table
+            .filter(blocks::number.between(i64::try_from(from)?, i64::try_from(to)?))
+            .order(blocks::number)
+            .select((blocks::number, blocks::hash, blocks::parent_hash))
+            .load::<(i64, String, Option<String>)>(&mut conn)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(number, hash, parent_hash)| {
+                Some(StoredBlock {
+                    number: u64::try_from(number).ok()?,
+                    hash: B256::from_str(&hash).ok()?,
+                    parent_hash: parent_hash.and_then(|hash| B256::from_str(&hash).ok()),
+                })
+            })
+            .collect())
+    }
+
+    async fn latest(&self, limit: u64) -> Result<Vec<(u64, B256)>> {
+        let mut conn = self.pool.get().await?;
+
+        let rows = blocks::table
+            .order(blocks::number.desc())
+            .limit(i64::try_from(limit)?)
+            .select((blocks::number, blocks::hash))
+            .load::<(i64, String)>(&mut conn)
+            .await?;
+
+        Ok(parse_blocks(rows))
+    }
+
+    async fn rollback(&self, ancestor: u64) -> Result<Vec<Address>> {
+        let mut conn = self.pool.get().await?;
+        let ancestor = i64::try_from(ancestor)?;
+
+        let addresses = conn
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                async move { rollback(conn, ancestor).await }.scope_boxed()
+            })
+            .await?;
+
+        Ok(parse_addresses(&addresses))
+    }
+
+    async fn prune(&self, oldest: u64) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        let oldest = i64::try_from(oldest).unwrap_or(i64::MAX);
+
+        diesel::delete(blocks::table.filter(blocks::number.lt(oldest)))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl ReserveHistoryRepo for PgRepo {
+    async fn partitions(&self) -> Result<Vec<i64>> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(reserve_history::partitions(&mut conn).await?)
+    }
+
+    async fn create_partition(&self, start: i64, blocks: i64) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(reserve_history::create_partition(&mut conn, start, blocks).await?)
+    }
+
+    async fn drop_partition(&self, start: i64) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(reserve_history::drop_partition(&mut conn, start).await?)
+    }
+
+    async fn delete_default_before(&self, cutoff: i64) -> Result<usize> {
+        let mut conn = self.pool.get().await?;
+
+        Ok(reserve_history::delete_default_before(&mut conn, cutoff).await?)
+    }
+}
+
+impl OpportunityRepo for PgRepo {
+    async fn record(&self, opportunities: &[NewOpportunity]) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+
+        opportunity::insert(&mut conn, opportunities).await?;
+
+        Ok(())
+    }
+}
+
+/// Parse stored `(number, hash)` rows, skipping malformed ones
+fn parse_blocks(rows: Vec<(i64, String)>) -> Vec<(u64, B256)> {
+    rows.into_iter()
+        .filter_map(|(number, hash)| {
+            Some((u64::try_from(number).ok()?, B256::from_str(&hash).ok()?))
+        })
+        .collect()
+}
+
+/// Parse stored addresses, skipping malformed ones
+fn parse_addresses(addresses: &[String]) -> Vec<Address> {
+    addresses
+        .iter()
+        .filter_map(|address| Address::from_str(address).ok())
+        .collect()
+}
+
+/// Write blocks, the reserves of their pairs and optionally their Sync logs
+///
+/// # Errors
+/// Returns an error if a database query fails
+async fn store_reserves(
+    conn: &mut AsyncPgConnection,
+    updates: &[BlockUpdate],
+    record_history: bool,
+) -> Result<(), diesel::result::Error> {
+    let mut block_rows = Vec::with_capacity(updates.len());
+    let mut latest: HashMap<Address, ReserveRow> = HashMap::new();
+
+    for update in updates {
+        let number = to_i64(update.number)?;
+
+        block_rows.push((
+            blocks::number.eq(number),
+            blocks::hash.eq(update.hash.to_string()),
+            blocks::parent_hash.eq(update.parent_hash.map(|hash| hash.to_string())),
+        ));
+
+        for pool in &update.pools {
+            latest.insert(
+                pool.address,
+                ReserveRow {
+                    address: pool.address,
+                    reserve0: pool.reserve0,
+                    reserve1: pool.reserve1,
+                    block_number: number,
+                    block_hash: update.hash,
+                    log_index: to_i32(pool.log_index)?,
+                },
+            );
+        }
+    }
+
+    for chunk in block_rows.chunks(ROWS_PER_INSERT) {
+        diesel::insert_into(blocks::table)
+            .values(chunk)
+            .on_conflict(blocks::number)
+            .do_update()
+            .set((
+                blocks::hash.eq(excluded(blocks::hash)),
+                blocks::parent_hash.eq(diesel::dsl::sql::<Nullable<Text>>(KEEP_PARENT_HASH)),
+            ))
+            .execute(conn)
+            .await?;
+    }
+
+    upsert_reserves(conn, &latest.into_values().collect::<Vec<_>>()).await?;
+
+    if record_history {
+        store_history(conn, updates).await?;
+    }
+
+    Ok(())
+}
+
+/// Write the reserves of pairs through `UPSERT_RESERVES`
+///
+/// # Errors
+/// Returns an error if the database query fails
+async fn upsert_reserves(
+    conn: &mut AsyncPgConnection,
+    rows: &[ReserveRow],
+) -> Result<(), diesel::result::Error> {
+    let mut addresses = Vec::with_capacity(rows.len());
+    let mut reserves0 = Vec::with_capacity(rows.len());
+    let mut reserves1 = Vec::with_capacity(rows.len());
+    let mut numbers = Vec::with_capacity(rows.len());
+    let mut hashes = Vec::with_capacity(rows.len());
+    let mut log_indexes = Vec::with_capacity(rows.len());
+    for row in rows {
+        addresses.push(row.address.to_string());
+        reserves0.push(u256_to_big_decimal(row.reserve0));
+        reserves1.push(u256_to_big_decimal(row.reserve1));
+        numbers.push(row.block_number);
+        hashes.push(row.block_hash.to_string());
+        log_indexes.push(row.log_index);
+    }
+
+    // Rows are passed as arrays, so a single statement has six bind parameters
+    diesel::sql_query(UPSERT_RESERVES)
+        .bind::<Array<Text>, _>(&addresses)
+        .bind::<Array<Numeric>, _>(&reserves0)
+        .bind::<Array<Numeric>, _>(&reserves1)
+        .bind::<Array<BigInt>, _>(&numbers)
+        .bind::<Array<Text>, _>(&hashes)
+        .bind::<Array<Int4>, _>(&log_indexes)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Clear the reserves of pairs updated after a block, delete later blocks and their reserve
+/// history and rewind checkpoints past it
+///
+/// # Returns
+/// The addresses of the rolled back pairs
+///
+/// # Errors
+/// Returns an error if a database query fails
+async fn rollback(
+    conn: &mut AsyncPgConnection,
+    ancestor: i64,
+) -> Result<Vec<String>, diesel::result::Error> {
+    let addresses = diesel::update(pairs::table)
+        .filter(pairs::block_number.gt(ancestor))
+        .set((
+            pairs::reserve0.eq(None::<BigDecimal>),
+            pairs::reserve1.eq(None::<BigDecimal>),
+            pairs::block_number.eq(None::<i64>),
+            pairs::block_hash.eq(None::<String>),
+            pairs::log_index.eq(None::<i32>),
+        ))
+        .returning(pairs::address)
+        .get_results::<String>(conn)
+        .await?;
+
+    diesel::delete(blocks::table.filter(blocks::number.gt(ancestor)))
+        .execute(conn)
+        .await?;
+
+    reserve_history::delete_after(conn, ancestor).await?;
+
+    diesel::update(sync_state::table)
+        .filter(sync_state::last_block.gt(ancestor))
+        .set((
+            sync_state::last_block.eq(ancestor),
+            sync_state::updated_at.eq(diesel::dsl::now),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(addresses)
+}
+
+/// Promote an unvalidated factory to `Unsynced` and validate its pairs of valid tokens
+///
+/// # Returns
+/// `true` if the factory was unvalidated
+///
+/// # Errors
+/// Returns an error if a database query fails
+async fn validate(conn: &mut AsyncPgConnection, id: i32) -> Result<bool, diesel::result::Error> {
+    let promoted = diesel::update(
+        factories::table
+            .find(id)
+            .filter(factories::status.eq(FactoryStatus::Unvalidated)),
+    )
+    .set(factories::status.eq(FactoryStatus::Unsynced))
+    .execute(conn)
+    .await?;
+    if promoted == 0 {
+        return Ok(false);
+    }
+
+    let valid_tokens = tokens::table
+        .filter(tokens::is_valid.eq(true))
+        .select(tokens::id);
+    diesel::update(pairs::table)
+        .filter(pairs::factory_id.eq(id))
+        .filter(pairs::token0_id.eq_any(valid_tokens.nullable()))
+        .filter(pairs::token1_id.eq_any(valid_tokens.nullable()))
+        .set(pairs::is_valid.eq(true))
+        .execute(conn)
+        .await?;
+
+    Ok(true)
+}
+
+/// Append every Sync log of the updates to `reserve_history`
+///
+/// # Errors
+/// Returns an error if a database query fails
+async fn store_history(
+    conn: &mut AsyncPgConnection,
+    updates: &[BlockUpdate],
+) -> Result<(), diesel::result::Error> {
+    let addresses = updates
+        .iter()
+        .flat_map(|update| update.syncs.iter().map(|sync| sync.address.to_string()))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    // Every pair exists by now, the reserves upsert inserted the unknown ones
+    let pair_ids = pairs::table
+        .filter(pairs::address.eq_any(&addresses))
+        .select((pairs::address, pairs::id))
+        .load::<(String, i32)>(conn)
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let mut rows = Vec::new();
+    for update in updates {
+        let block_number = to_i64(update.number)?;
+
+        for sync in &update.syncs {
+            let Some(&pair_id) = pair_ids.get(&sync.address.to_string()) else {
+                continue;
+            };
+
+            rows.push(NewReserveHistory {
+                pair_id,
+                block_number,
+                log_index: to_i32(sync.log_index)?,
+                tx_hash: sync.tx_hash.to_string(),
+                reserve0: u256_to_big_decimal(sync.reserve0),
+                reserve1: u256_to_big_decimal(sync.reserve1),
+            });
+        }
+    }
+
+    reserve_history::insert(conn, &rows).await?;
+
+    Ok(())
+}
+
+/// Store a change to the price of a token
+async fn update_price(
+    conn: &mut AsyncPgConnection,
+    token_id: i32,
+    change: &PriceChange,
+    timestamp: NaiveDateTime,
+) -> Result<(), diesel::result::Error> {
+    let target = tokens::table.filter(tokens::id.eq(token_id));
+
+    match change {
+        PriceChange::Priced(price, verdict) => {
+            diesel::update(target)
+                .set((
+                    tokens::exchange_rate.eq(&price.rate),
+                    tokens::price_route.eq(price
+                        .route
+                        .iter()
+                        .copied()
+                        .map(Some)
+                        .collect::<Vec<_>>()),
+                    tokens::price_depth.eq(price.depth.as_ref()),
+                    tokens::price_confidence.eq(verdict.confidence),
+                    tokens::price_block.eq(verdict.block),
+                    tokens::price_status.eq(verdict.status),
+                    tokens::updated_last.eq(timestamp),
+                ))
+                .execute(conn)
+                .await?
+        }
+        // The previous price and its provenance stay
+        PriceChange::Rejected(_) => {
+            diesel::update(target)
+                .set(tokens::price_status.eq(PriceStatus::Rejected))
+                .execute(conn)
+                .await?
+        }
+        PriceChange::Unpriced => {
+            diesel::update(target)
+                .set((
+                    tokens::exchange_rate.eq(None::<BigDecimal>),
+                    tokens::price_route.eq(None::<Vec<Option<i32>>>),
+                    tokens::price_depth.eq(None::<BigDecimal>),
+                    tokens::price_confidence.eq(None::<f64>),
+                    tokens::price_block.eq(None::<i64>),
+                    tokens::price_status.eq(None::<PriceStatus>),
+                    tokens::updated_last.eq(timestamp),
+                ))
+                .execute(conn)
+                .await?
+        }
+    };
+
+    Ok(())
+}
+
+/// Convert a block number to its database type
+fn to_i64(number: u64) -> Result<i64, diesel::result::Error> {
+    i64::try_from(number).map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))
+}
+
+/// Convert a log index to its database type
+fn to_i32(index: u64) -> Result<i32, diesel::result::Error> {
+    i32::try_from(index).map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloy::primitives::U256;
+
+    use super::*;
+    use crate::models::block::PoolUpdate;
+    use crate::utils::test_db;
+
+    fn block(number: u64, address: Address, reserve: u64) -> BlockUpdate {
+        BlockUpdate {
+            number,
+            hash: B256::repeat_byte(u8::try_from(number).unwrap()),
+            parent_hash: None,
+            pools: vec![PoolUpdate {
+                address,
+                reserve0: U256::from(reserve),
+                reserve1: U256::from(reserve),
+                log_index: 0,
+            }],
+            syncs: Vec::new(),
+        }
+    }
+
+    async fn reserves(
+        conn: &mut AsyncPgConnection,
+        address: Address,
+    ) -> (Option<BigDecimal>, Option<i64>) {
+        pairs::table
+            .filter(pairs::address.eq(address.to_string()))
+            .select((pairs::reserve0, pairs::block_number))
+            .first(conn)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_store_reserves_keeps_newer_reserves() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let pair = Address::repeat_byte(1);
+
+        store_reserves(&mut conn, &[block(10, pair, 100)], false)
+            .await
+            .unwrap();
+        // A replay of an older block
+        store_reserves(&mut conn, &[block(9, pair, 90)], false)
+            .await
+            .unwrap();
+        assert_eq!(
+            reserves(&mut conn, pair).await,
+            (Some(BigDecimal::from(100)), Some(10))
+        );
+
+        // A block that replaced the stored one under the same number
+        let mut replacement = block(10, pair, 101);
+        replacement.hash = B256::repeat_byte(0xaa);
+        store_reserves(&mut conn, &[replacement], false)
+            .await
+            .unwrap();
+        assert_eq!(
+            reserves(&mut conn, pair).await,
+            (Some(BigDecimal::from(101)), Some(10))
+        );
+
+        store_reserves(&mut conn, &[block(11, pair, 110)], false)
+            .await
+            .unwrap();
+        assert_eq!(
+            reserves(&mut conn, pair).await,
+            (Some(BigDecimal::from(110)), Some(11))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_reserves_replaces_block() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let pair = Address::repeat_byte(4);
+
+        let mut orphaned = block(10, pair, 100);
+        orphaned.parent_hash = Some(B256::repeat_byte(0xa9));
+        store_reserves(&mut conn, &[orphaned], false).await.unwrap();
+
+        // The block that replaced it after a reorg, on a different parent
+        let mut replacement = block(10, pair, 101);
+        replacement.hash = B256::repeat_byte(0xaa);
+        replacement.parent_hash = Some(B256::repeat_byte(0xab));
+        store_reserves(&mut conn, &[replacement], false)
+            .await
+            .unwrap();
+
+        let stored = blocks::table
+            .filter(blocks::number.eq(10))
+            .select((blocks::hash, blocks::parent_hash))
+            .first::<(String, Option<String>)>(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(
+            stored,
+            (
+                B256::repeat_byte(0xaa).to_string(),
+                Some(B256::repeat_byte(0xab).to_string())
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_reserves_keeps_parent_hash_of_replayed_block() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let pair = Address::repeat_byte(5);
+
+        let mut live = block(10, pair, 100);
+        live.parent_hash = Some(B256::repeat_byte(0xa9));
+        store_reserves(&mut conn, &[live], false).await.unwrap();
+
+        // The backfill replays the same block without its parent hash
+        store_reserves(&mut conn, &[block(10, pair, 100)], false)
+            .await
+            .unwrap();
+
+        let parent_hash = blocks::table
+            .filter(blocks::number.eq(10))
+            .select(blocks::parent_hash)
+            .first::<Option<String>>(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(parent_hash, Some(B256::repeat_byte(0xa9).to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_store_reserves_merges_block_committed_in_parts() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let pair = Address::repeat_byte(3);
+
+        let mut first = block(10, pair, 100);
+        first.pools[0].log_index = 4;
+        store_reserves(&mut conn, &[first], false).await.unwrap();
+
+        // Late logs of the same block, committed after the stream went quiet
+        let mut second = block(10, pair, 105);
+        second.pools[0].log_index = 9;
+        store_reserves(&mut conn, &[second], false).await.unwrap();
+        assert_eq!(
+            reserves(&mut conn, pair).await,
+            (Some(BigDecimal::from(105)), Some(10))
+        );
+
+        // A replay of the first part does not move reserves back
+        let mut replay = block(10, pair, 100);
+        replay.pools[0].log_index = 4;
+        store_reserves(&mut conn, &[replay], false).await.unwrap();
+        assert_eq!(
+            reserves(&mut conn, pair).await,
+            (Some(BigDecimal::from(105)), Some(10))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upsert_reserves_keeps_newer_sync_reserves() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let pair = Address::repeat_byte(5);
+        let snapshot = |number: i64, reserve: u64| ReserveRow {
+            address: pair,
+            reserve0: U256::from(reserve),
+            reserve1: U256::from(reserve),
+            block_number: number,
+            block_hash: B256::repeat_byte(u8::try_from(number).unwrap()),
+            log_index: to_i32(END_OF_BLOCK).unwrap(),
+        };
+
+        store_reserves(&mut conn, &[block(10, pair, 100)], false)
+            .await
+            .unwrap();
+        // A snapshot read at an older block
+        upsert_reserves(&mut conn, &[snapshot(9, 90)])
+            .await
+            .unwrap();
+        assert_eq!(
+            reserves(&mut conn, pair).await,
+            (Some(BigDecimal::from(100)), Some(10))
+        );
+
+        // A snapshot read at the end of the same block follows every Sync log of it
+        upsert_reserves(&mut conn, &[snapshot(10, 101)])
+            .await
+            .unwrap();
+        assert_eq!(
+            reserves(&mut conn, pair).await,
+            (Some(BigDecimal::from(101)), Some(10))
+        );
+
+        store_reserves(&mut conn, &[block(11, pair, 110)], false)
+            .await
+            .unwrap();
+        assert_eq!(
+            reserves(&mut conn, pair).await,
+            (Some(BigDecimal::from(110)), Some(11))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_trusts_pairs_of_valid_tokens() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+
+        let factory_id = diesel::insert_into(factories::table)
+            .values((
+                factories::address.eq(Address::repeat_byte(1).to_string()),
+                factories::status.eq(FactoryStatus::Unvalidated),
+            ))
+            .returning(factories::id)
+            .get_result::<i32>(&mut conn)
+            .await
+            .unwrap();
+        let mut token_ids = Vec::new();
+        for (byte, is_valid) in [(0xa, true), (0xb, true), (0xc, false)] {
+            let id = diesel::insert_into(tokens::table)
+                .values((
+                    tokens::address.eq(Address::repeat_byte(byte).to_string()),
+                    tokens::is_valid.eq(is_valid),
+                ))
+                .returning(tokens::id)
+                .get_result::<i32>(&mut conn)
+                .await
+                .unwrap();
+            token_ids.push(id);
+        }
+        let (valid, invalid) = (Address::repeat_byte(2), Address::repeat_byte(3));
+        for (pair, token1_id) in [(valid, token_ids[1]), (invalid, token_ids[2])] {
+            diesel::insert_into(pairs::table)
+                .values((
+                    pairs::address.eq(pair.to_string()),
+                    pairs::factory_id.eq(factory_id),
+                    pairs::token0_id.eq(token_ids[0]),
+                    pairs::token1_id.eq(token1_id),
+                    pairs::is_valid.eq(false),
+                ))
+                .execute(&mut conn)
+                .await
+                .unwrap();
+        }
+
+        assert!(validate(&mut conn, factory_id).await.unwrap());
+        // Already validated
+        assert!(!validate(&mut conn, factory_id).await.unwrap());
+
+        let status = factories::table
+            .find(factory_id)
+            .select(factories::status)
+            .first::<FactoryStatus>(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(status, FactoryStatus::Unsynced);
+        for (pair, expected) in [(valid, true), (invalid, false)] {
+            let is_valid = pairs::table
+                .filter(pairs::address.eq(pair.to_string()))
+                .select(pairs::is_valid)
+                .first::<bool>(&mut conn)
+                .await
+                .unwrap();
+            assert_eq!(is_valid, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rollback_clears_orphaned_blocks() {
+        let Some(mut conn) = test_db::connection().await else {
+            return;
+        };
+        let (kept, orphaned) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let stream = "test_rollback";
+
+        store_reserves(
+            &mut conn,
+            &[block(10, kept, 100), block(12, orphaned, 120)],
+            false,
+        )
+        .await
+        .unwrap();
+        diesel::insert_into(sync_state::table)
+            .values(SyncState::new(stream, 12))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let addresses = rollback(&mut conn, 11).await.unwrap();
+        assert_eq!(addresses, vec![orphaned.to_string()]);
+
+        assert_eq!(
+            reserves(&mut conn, kept).await,
+            (Some(BigDecimal::from(100)), Some(10))
+        );
+        assert_eq!(reserves(&mut conn, orphaned).await, (None, None));
+
+        let numbers = blocks::table
+            .select(blocks::number)
+            .load::<i64>(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(numbers, vec![10]);
+
+        let last_block = sync_state::table
+            .filter(sync_state::stream.eq(stream))
+            .select(sync_state::last_block)
+            .first::<i64>(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(last_block, 11);
+    }
+}
@@ -4,17 +4,189 @@
 ///
 /// (Automatically generated by Diesel.)
 pub mod sql_types {
+    /// The `execution_status` SQL type
+    ///
+    /// (Automatically generated by Diesel.)
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "execution_status"))]
+    pub struct ExecutionStatus;
+
+    /// The `factory_kind` SQL type
+    ///
+    /// (Automatically generated by Diesel.)
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "factory_kind"))]
+    pub struct FactoryKind;
+
     /// The `factory_status` SQL type
     ///
     /// (Automatically generated by Diesel.)
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "factory_status"))]
     pub struct FactoryStatus;
+
+    /// The `metadata_status` SQL type
+    ///
+    /// (Automatically generated by Diesel.)
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "metadata_status"))]
+    pub struct MetadataStatus;
+
+    /// The `price_status` SQL type
+    ///
+    /// (Automatically generated by Diesel.)
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "price_status"))]
+    pub struct PriceStatus;
+}
+
+diesel::table! {
+    /// Representation of the `blocks` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    blocks (number) {
+        /// The `number` column of the `blocks` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        number -> Int8,
+        /// The `hash` column of the `blocks` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        hash -> Varchar,
+        /// The `created_at` column of the `blocks` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `parent_hash` column of the `blocks` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        parent_hash -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ExecutionStatus;
+
+    /// Representation of the `executions` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    executions (id) {
+        /// The `id` column of the `executions` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int4,
+        /// Pair addresses of the cycle in execution order, comma separated
+        cycle -> Varchar,
+        /// The `start_token` column of the `executions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        start_token -> Varchar,
+        /// The `amount_in` column of the `executions` table.
+        ///
+        /// Its SQL type is `Numeric`.
+        ///
+        /// (Automatically generated by Diesel.)
+        amount_in -> Numeric,
+        /// The `amount_out` column of the `executions` table.
+        ///
+        /// Its SQL type is `Numeric`.
+        ///
+        /// (Automatically generated by Diesel.)
+        amount_out -> Numeric,
+        /// The `quoted_profit` column of the `executions` table.
+        ///
+        /// Its SQL type is `Numeric`.
+        ///
+        /// (Automatically generated by Diesel.)
+        quoted_profit -> Numeric,
+        /// The `tx_hash` column of the `executions` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tx_hash -> Varchar,
+        /// The `nonce` column of the `executions` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        nonce -> Int8,
+        /// The `gas_limit` column of the `executions` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        gas_limit -> Int8,
+        /// The `max_fee_per_gas` column of the `executions` table.
+        ///
+        /// Its SQL type is `Numeric`.
+        ///
+        /// (Automatically generated by Diesel.)
+        max_fee_per_gas -> Numeric,
+        /// The `max_priority_fee_per_gas` column of the `executions` table.
+        ///
+        /// Its SQL type is `Numeric`.
+        ///
+        /// (Automatically generated by Diesel.)
+        max_priority_fee_per_gas -> Numeric,
+        /// The `status` column of the `executions` table.
+        ///
+        /// Its SQL type is `ExecutionStatus`.
+        ///
+        /// (Automatically generated by Diesel.)
+        status -> ExecutionStatus,
+        /// The `block_number` column of the `executions` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        block_number -> Nullable<Int8>,
+        /// The `gas_used` column of the `executions` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        gas_used -> Nullable<Int8>,
+        /// The `effective_gas_price` column of the `executions` table.
+        ///
+        /// Its SQL type is `Nullable<Numeric>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        effective_gas_price -> Nullable<Numeric>,
+        /// Profit in the start token computed from the receipt Transfer and Sync logs
+        realized_profit -> Nullable<Numeric>,
+        /// The `created_at` column of the `executions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+        /// The `updated_at` column of the `executions` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
 }
 
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::FactoryStatus;
+    use super::sql_types::FactoryKind;
 
     /// Representation of the `factories` table.
     ///
@@ -44,6 +216,110 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         status -> FactoryStatus,
+        /// The `kind` column of the `factories` table.
+        ///
+        /// Its SQL type is `Nullable<FactoryKind>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        kind -> Nullable<FactoryKind>,
+        /// The `fee_bps` column of the `factories` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        fee_bps -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `nonces` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    nonces (address) {
+        /// The `address` column of the `nonces` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        address -> Varchar,
+        /// The `nonce` column of the `nonces` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        nonce -> Int8,
+        /// The `updated_at` column of the `nonces` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `opportunities` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    opportunities (id) {
+        /// The `id` column of the `opportunities` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        id -> Int8,
+        /// The `block_number` column of the `opportunities` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        block_number -> Int8,
+        /// Pair addresses of the cycle in execution order, comma separated
+        cycle -> Varchar,
+        /// The `start_token` column of the `opportunities` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        start_token -> Varchar,
+        /// The `amount_in` column of the `opportunities` table.
+        ///
+        /// Its SQL type is `Numeric`.
+        ///
+        /// (Automatically generated by Diesel.)
+        amount_in -> Numeric,
+        /// The `amount_out` column of the `opportunities` table.
+        ///
+        /// Its SQL type is `Numeric`.
+        ///
+        /// (Automatically generated by Diesel.)
+        amount_out -> Numeric,
+        /// Quoted profit in the start token, before gas
+        gross_profit -> Numeric,
+        /// Quoted profit in the start token after gas, if gas was estimated
+        net_profit -> Nullable<Numeric>,
+        /// Quoted profit in USD, if the start token has a USD rate
+        profit_usd -> Nullable<Numeric>,
+        /// The `margin_bps` column of the `opportunities` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        margin_bps -> Int4,
+        /// The `executed` column of the `opportunities` table.
+        ///
+        /// Its SQL type is `Bool`.
+        ///
+        /// (Automatically generated by Diesel.)
+        executed -> Bool,
+        /// Why the opportunity was not executed
+        skip_reason -> Nullable<Varchar>,
+        /// The `created_at` column of the `opportunities` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
     }
 }
 
@@ -96,20 +372,136 @@ diesel::table! {
         reserve1 -> Nullable<Numeric>,
         /// The `usd` column of the `pairs` table.
         ///
-        /// Its SQL type is `Nullable<Int4>`.
+        /// Its SQL type is `Nullable<Numeric>`.
         ///
         /// (Automatically generated by Diesel.)
-        usd -> Nullable<Int4>,
+        usd -> Nullable<Numeric>,
         /// The `is_valid` column of the `pairs` table.
         ///
         /// Its SQL type is `Bool`.
         ///
         /// (Automatically generated by Diesel.)
         is_valid -> Bool,
+        /// The `block_number` column of the `pairs` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        block_number -> Nullable<Int8>,
+        /// The `block_hash` column of the `pairs` table.
+        ///
+        /// Its SQL type is `Nullable<Varchar>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        block_hash -> Nullable<Varchar>,
+        /// The `created_block` column of the `pairs` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_block -> Nullable<Int8>,
+        /// The `usd_updated_at` column of the `pairs` table.
+        ///
+        /// Its SQL type is `Nullable<Timestamp>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        usd_updated_at -> Nullable<Timestamp>,
+        /// The `usd_block` column of the `pairs` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        usd_block -> Nullable<Int8>,
+        /// The `log_index` column of the `pairs` table.
+        ///
+        /// Its SQL type is `Nullable<Int4>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        log_index -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `reserve_history` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    reserve_history (block_number, log_index) {
+        /// The `pair_id` column of the `reserve_history` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        pair_id -> Int4,
+        /// The `block_number` column of the `reserve_history` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        block_number -> Int8,
+        /// The `log_index` column of the `reserve_history` table.
+        ///
+        /// Its SQL type is `Int4`.
+        ///
+        /// (Automatically generated by Diesel.)
+        log_index -> Int4,
+        /// The `tx_hash` column of the `reserve_history` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        tx_hash -> Varchar,
+        /// The `reserve0` column of the `reserve_history` table.
+        ///
+        /// Its SQL type is `Numeric`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reserve0 -> Numeric,
+        /// The `reserve1` column of the `reserve_history` table.
+        ///
+        /// Its SQL type is `Numeric`.
+        ///
+        /// (Automatically generated by Diesel.)
+        reserve1 -> Numeric,
+        /// The `created_at` column of the `reserve_history` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    /// Representation of the `sync_state` table.
+    ///
+    /// (Automatically generated by Diesel.)
+    sync_state (stream) {
+        /// The `stream` column of the `sync_state` table.
+        ///
+        /// Its SQL type is `Varchar`.
+        ///
+        /// (Automatically generated by Diesel.)
+        stream -> Varchar,
+        /// The `last_block` column of the `sync_state` table.
+        ///
+        /// Its SQL type is `Int8`.
+        ///
+        /// (Automatically generated by Diesel.)
+        last_block -> Int8,
+        /// The `updated_at` column of the `sync_state` table.
+        ///
+        /// Its SQL type is `Timestamp`.
+        ///
+        /// (Automatically generated by Diesel.)
+        updated_at -> Timestamp,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::MetadataStatus;
+    use super::sql_types::PriceStatus;
+
     /// Representation of the `tokens` table.
     ///
     /// (Automatically generated by Diesel.)
@@ -154,9 +546,68 @@ diesel::table! {
         ///
         /// (Automatically generated by Diesel.)
         is_valid -> Bool,
+        /// The `name_status` column of the `tokens` table.
+        ///
+        /// Its SQL type is `Nullable<MetadataStatus>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        name_status -> Nullable<MetadataStatus>,
+        /// The `symbol_status` column of the `tokens` table.
+        ///
+        /// Its SQL type is `Nullable<MetadataStatus>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        symbol_status -> Nullable<MetadataStatus>,
+        /// The `decimals_status` column of the `tokens` table.
+        ///
+        /// Its SQL type is `Nullable<MetadataStatus>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        decimals_status -> Nullable<MetadataStatus>,
+        /// The `price_route` column of the `tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Array<Nullable<Int4>>>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        price_route -> Nullable<Array<Nullable<Int4>>>,
+        /// The `price_depth` column of the `tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Numeric>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        price_depth -> Nullable<Numeric>,
+        /// The `price_confidence` column of the `tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Float8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        price_confidence -> Nullable<Float8>,
+        /// The `price_block` column of the `tokens` table.
+        ///
+        /// Its SQL type is `Nullable<Int8>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        price_block -> Nullable<Int8>,
+        /// The `price_status` column of the `tokens` table.
+        ///
+        /// Its SQL type is `Nullable<PriceStatus>`.
+        ///
+        /// (Automatically generated by Diesel.)
+        price_status -> Nullable<PriceStatus>,
     }
 }
 
 diesel::joinable!(pairs -> factories (factory_id));
+diesel::joinable!(reserve_history -> pairs (pair_id));
 
-diesel::allow_tables_to_appear_in_same_query!(factories, pairs, tokens,);
+diesel::allow_tables_to_appear_in_same_query!(
+    blocks,
+    executions,
+    factories,
+    nonces,
+    opportunities,
+    pairs,
+    reserve_history,
+    sync_state,
+    tokens,
+);
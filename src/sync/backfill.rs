@@ -0,0 +1,440 @@
+use alloy::{rpc::types::Filter, rpc::types::Log, sol_types::SolEvent};
+use eyre::{bail, Result};
+use log::{error, info};
+
+use super::bus::{Bus, SyncEvent};
+use super::pair_created_events::{self, PairCreated};
+use super::reserve_history::ReserveHistoryConfig;
+use super::sync_events::{self, Sync};
+use crate::chain::Chain;
+use crate::repo::{FactoryRepo, PairRepo, SyncStateRepo, TokenRepo};
+use crate::utils::app_context::AppContext;
+
+/// Blocks behind the head that are left to the live subscriptions
+const CONFIRMATIONS: u64 = 5;
+
+/// How long to wait between two catch-ups once the streams reached the head
+const POLL_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(30);
+
+/// Smallest number of blocks requested at once
+const MIN_RANGE: u64 = 1;
+
+/// Number of blocks requested at once on startup
+const INITIAL_RANGE: u64 = 2_000;
+
+/// Largest number of blocks requested at once
+const MAX_RANGE: u64 = 10_000;
+
+/// Event streams replayed by the backfill
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stream {
+    /// Sync events of Uniswap V2 pairs
+    Sync,
+    /// `PairCreated` events of Uniswap V2 factories
+    PairCreated,
+}
+
+impl Stream {
+    /// All streams, in the order they are caught up
+    const ALL: [Self; 2] = [Self::PairCreated, Self::Sync];
+
+    /// The name of the stream in the `sync_state` table
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Sync => sync_events::STREAM,
+            Self::PairCreated => "pair_created_events",
+        }
+    }
+
+    /// The log filter of the stream, without a block range
+    fn filter(self) -> Filter {
+        match self {
+            Self::Sync => Filter::new().event(Sync::SIGNATURE),
+            Self::PairCreated => Filter::new().event(PairCreated::SIGNATURE),
+        }
+    }
+}
+
+/// Number of blocks requested per `eth_getLogs` call
+///
+/// Halves whenever the provider refuses a range, and doubles again after each successful page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RangeSize {
+    /// The current number of blocks per request
+    size: u64,
+    /// The smallest number of blocks per request
+    min: u64,
+    /// The largest number of blocks per request
+    max: u64,
+}
+
+impl Default for RangeSize {
+    fn default() -> Self {
+        Self {
+            size: INITIAL_RANGE,
+            min: MIN_RANGE,
+            max: MAX_RANGE,
+        }
+    }
+}
+
+impl RangeSize {
+    /// The last block of the range starting at `from`, capped at `head`
+    fn end(self, from: u64, head: u64) -> u64 {
+        from.saturating_add(self.size - 1).min(head)
+    }
+
+    /// Halve the range, returning false if it is already at its minimum
+    fn shrink(&mut self) -> bool {
+        if self.size <= self.min {
+            return false;
+        }
+
+        self.size = (self.size / 2).max(self.min);
+        true
+    }
+
+    /// Double the range, up to its maximum
+    fn grow(&mut self) {
+        self.size = self.size.saturating_mul(2).min(self.max);
+    }
+}
+
+/// Whether an `eth_getLogs` error means the range must be smaller
+///
+/// Providers word this differently, e.g. "query returned more than 10000 results",
+/// "Log response size exceeded" or "block range is too large".
+fn is_too_many_results(message: &str) -> bool {
+    let message = message.to_lowercase();
+
+    [
+        "more than",
+        "too many",
+        "response size exceeded",
+        "range is too large",
+        "range too large",
+        "limit exceeded",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Replay Sync and `PairCreated` events missed while the bot was down
+///
+/// The live subscriptions start at the latest block, so each stream keeps a checkpoint of the
+/// last block it processed in `sync_state`. On startup, and then periodically, the logs from the
+/// checkpoint up to a few blocks behind the head are paged through `eth_getLogs` and stored. A
+/// stream without a checkpoint starts at the current head. Once caught up, `sync::events` moves
+/// the checkpoint of Sync events along with the blocks it commits, so later catch-ups only
+/// replay real gaps, like a gap the subscription skipped after a long disconnect.
+///
+/// Replayed reserves are written to the database only, they are not published on
+/// `AppContext::blocks`.
+///
+/// # Errors
+/// Never returns an error, failures are logged and retried on the next catch-up
+pub async fn backfill(ctx: &AppContext) -> Result<()> {
+    info!("sync::backfill: Starting backfill...");
+
+    let record_history = ReserveHistoryConfig::from_env().enabled;
+
+    loop {
+        for stream in Stream::ALL {
+            let caught_up = catch_up(
+                &ctx.base_provider,
+                &ctx.repo,
+                &ctx.bus,
+                stream,
+                record_history,
+            );
+            if let Err(e) = caught_up.await {
+                error!("sync::backfill: Failed to backfill {}: {e}", stream.name());
+            }
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Process the logs of a stream from its checkpoint up to the confirmed head
+async fn catch_up(
+    chain: &impl Chain,
+    repo: &(impl FactoryRepo + TokenRepo + PairRepo + SyncStateRepo),
+    bus: &Bus,
+    stream: Stream,
+    record_history: bool,
+) -> Result<()> {
+    let head = chain.block_number().await?.saturating_sub(CONFIRMATIONS);
+
+    let Some(last_block) = repo.last_block(stream.name()).await? else {
+        repo.save_checkpoint(stream.name(), i64::try_from(head)?)
+            .await?;
+        info!(
+            "sync::backfill: No checkpoint for {}, starting at block {head}",
+            stream.name()
+        );
+        return Ok(());
+    };
+
+    let mut from = u64::try_from(last_block)? + 1;
+    let mut range = RangeSize::default();
+
+    while from <= head {
+        let (to, logs) = fetch_page(chain, &stream.filter(), from, head, &mut range).await?;
+
+        process(chain, repo, bus, stream, &logs, to, record_history).await?;
+        info!(
+            "sync::backfill: Processed {} {} logs of blocks {from}..={to}",
+            logs.len(),
+            stream.name()
+        );
+
+        from = to + 1;
+    }
+
+    Ok(())
+}
+
+/// Fetch the logs of the next page starting at `from`, returning its last block
+///
+/// The range shrinks until the provider accepts it, and grows again once it does.
+async fn fetch_page(
+    chain: &impl Chain,
+    filter: &Filter,
+    from: u64,
+    head: u64,
+    range: &mut RangeSize,
+) -> Result<(u64, Vec<Log>)> {
+    loop {
+        let to = range.end(from, head);
+        let page = filter.clone().from_block(from).to_block(to);
+
+        match chain.logs(&page).await {
+            Ok(logs) => {
+                range.grow();
+                return Ok((to, logs));
+            }
+            Err(e) if is_too_many_results(&e.to_string()) => {
+                if !range.shrink() {
+                    bail!("Provider refused the logs of block {from}: {e}");
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Store the logs of a page and move the checkpoint of the stream to its last block
+async fn process(
+    chain: &impl Chain,
+    repo: &(impl FactoryRepo + TokenRepo + PairRepo + SyncStateRepo),
+    bus: &Bus,
+    stream: Stream,
+    logs: &[Log],
+    to: u64,
+    record_history: bool,
+) -> Result<()> {
+    let checkpoint = i64::try_from(to)?;
+
+    match stream {
+        Stream::Sync => {
+            let updates = sync_events::block_updates(logs);
+            let mut pools = updates
+                .iter()
+                .flat_map(|update| update.pools.iter().map(|pool| pool.address))
+                .collect::<Vec<_>>();
+            pools.sort();
+            pools.dedup();
+
+            // The checkpoint only moves once the reserves are stored, so a crash replays the
+            // whole page, and replaying reserves is harmless
+            repo.store_reserves(&updates, record_history).await?;
+            repo.save_checkpoint(stream.name(), checkpoint).await?;
+
+            bus.publish(SyncEvent::ReservesUpdated(pools));
+        }
+        Stream::PairCreated => {
+            for log in logs {
+                match PairCreated::decode_log(&log.inner, true) {
+                    Ok(event) => {
+                        pair_created_events::store(chain, repo, bus, log, &event).await?;
+                    }
+                    Err(e) => error!("sync::backfill: Failed to decode event: {e}"),
+                }
+            }
+
+            // Storing a pair is idempotent, so the checkpoint can follow the page
+            repo.save_checkpoint(stream.name(), checkpoint).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::chain::fake::FakeChain;
+    use crate::repo::memory::MemoryRepo;
+    use alloy::primitives::{aliases::U112, Address, LogData};
+
+    #[test]
+    fn test_range_end() {
+        let range = RangeSize::default();
+
+        assert_eq!(range.end(100, 10_000), 2_099);
+        assert_eq!(range.end(100, 500), 500);
+        assert_eq!(range.end(500, 500), 500);
+    }
+
+    #[test]
+    fn test_range_shrink() {
+        let mut range = RangeSize {
+            size: 5,
+            min: 1,
+            max: 10,
+        };
+
+        assert!(range.shrink());
+        assert_eq!(range.size, 2);
+        assert!(range.shrink());
+        assert_eq!(range.size, 1);
+        assert!(!range.shrink());
+        assert_eq!(range.end(7, 100), 7);
+    }
+
+    #[test]
+    fn test_range_grow() {
+        let mut range = RangeSize {
+            size: 3,
+            min: 1,
+            max: 10,
+        };
+
+        range.grow();
+        assert_eq!(range.size, 6);
+        range.grow();
+        assert_eq!(range.size, 10);
+        range.grow();
+        assert_eq!(range.size, 10);
+    }
+
+    #[test]
+    fn test_is_too_many_results() {
+        assert!(is_too_many_results(
+            "server returned an error response: error code -32005: query returned more than 10000 results"
+        ));
+        assert!(is_too_many_results(
+            "server returned an error response: error code -32602: Log response size exceeded."
+        ));
+        assert!(is_too_many_results("block range is too large"));
+        assert!(!is_too_many_results("connection reset by peer"));
+        assert!(!is_too_many_results("header not found"));
+    }
+
+    fn sync(reserve: u64) -> LogData {
+        Sync {
+            reserve0: U112::from(reserve),
+            reserve1: U112::from(reserve),
+        }
+        .encode_log_data()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_page_shrinks_refused_ranges() {
+        let chain = FakeChain::new();
+        let pair = Address::repeat_byte(1);
+        for reserve in 1..=4 {
+            chain.emit(pair, sync(reserve));
+            chain.mine();
+        }
+        chain.limit_logs(1);
+
+        let mut range = RangeSize {
+            size: 4,
+            min: 1,
+            max: 4,
+        };
+        let filter = Stream::Sync.filter();
+
+        let (to, logs) = fetch_page(&chain, &filter, 1, 4, &mut range).await.unwrap();
+        assert_eq!(to, 1);
+        assert_eq!(logs.len(), 1);
+        assert_eq!(range.size, 2);
+
+        let (to, logs) = fetch_page(&chain, &filter, 2, 4, &mut range).await.unwrap();
+        assert_eq!(to, 2);
+        assert_eq!(logs.len(), 1);
+
+        let updates = sync_events::block_updates(&logs);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].number, 2);
+        assert_eq!(updates[0].pools[0].address, pair);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_page_gives_up_at_minimum_range() {
+        let chain = FakeChain::new();
+        let pair = Address::repeat_byte(1);
+        chain.emit(pair, sync(1));
+        chain.emit(pair, sync(2));
+        chain.mine();
+        chain.limit_logs(1);
+
+        let mut range = RangeSize::default();
+        let result = fetch_page(&chain, &Stream::Sync.filter(), 1, 1, &mut range).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_catch_up_resumes_from_checkpoint() {
+        let chain = FakeChain::new();
+        let repo = MemoryRepo::new();
+        let bus = Bus::default();
+        let (a, b) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb));
+
+        chain.mine_to(2);
+        chain.emit(a, sync(1));
+        chain.mine();
+        chain.mine_to(5);
+        chain.emit(b, sync(2));
+        chain.mine();
+        chain.mine_to(20);
+
+        repo.save_checkpoint(Stream::Sync.name(), 4).await.unwrap();
+        catch_up(&chain, &repo, &bus, Stream::Sync, false)
+            .await
+            .unwrap();
+
+        // Block 3 is before the checkpoint
+        assert!(repo.pair(a).is_none());
+        assert_eq!(repo.pair(b).unwrap().block_number, Some(6));
+        assert_eq!(
+            repo.last_block(Stream::Sync.name()).await.unwrap(),
+            Some(20 - i64::try_from(CONFIRMATIONS).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_catch_up_starts_at_head_without_checkpoint() {
+        let chain = FakeChain::new();
+        let repo = MemoryRepo::new();
+        let pair = Address::repeat_byte(0xa);
+
+        chain.emit(pair, sync(1));
+        chain.mine();
+        chain.mine_to(20);
+
+        catch_up(&chain, &repo, &Bus::default(), Stream::Sync, false)
+            .await
+            .unwrap();
+
+        assert!(repo.pair(pair).is_none());
+        assert_eq!(
+            repo.last_block(Stream::Sync.name()).await.unwrap(),
+            Some(20 - i64::try_from(CONFIRMATIONS).unwrap())
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::env;
+use std::str::FromStr;
+
+use alloy::{
+    primitives::{Address, U256},
+    rpc::types::Filter,
+    sol,
+    sol_types::{SolCall, SolEvent, SolValue},
+};
+use eyre::Result;
+use log::{error, info, warn};
+
+use crate::arb::portfolio::Portfolio;
+use crate::arb::token::TokenId;
+use crate::chain::{Call, Chain, Subscription};
+use crate::repo::TokenRepo;
+use crate::utils::app_context::AppContext;
+
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 value);
+}
+
+sol! {
+    #[sol(rpc)]
+    "contracts/src/interfaces/IERC20.sol"
+}
+
+/// Number of `balanceOf` calls per multicall
+const BATCH_SIZE: usize = 500;
+
+/// Balances drift from events for tokens that take fees on transfer or rebase,
+/// so they are reloaded from chain periodically
+const RELOAD_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_mins(30);
+
+/// Track token balances of our wallet and executor and publish them as a `Portfolio`.
+///
+/// Balances of every known token are loaded in bulk through multicall, then kept current
+/// from `Transfer` events sent from or to our addresses, reversing those a reorg removed. The
+/// combined holdings of all our addresses are published on `AppContext::portfolio`.
+///
+/// # Environment Variables
+/// * `FLY_BASE_WALLET_ADDRESS` - The wallet address
+/// * `FLY_BASE_EXECUTOR_ADDRESS` - The executor contract address (optional)
+///
+/// # Errors
+/// * If the wallet address is missing or invalid
+/// * If the database connection fails
+pub async fn balances(ctx: &AppContext) -> Result<()> {
+    let owners = owners()?;
+    info!("sync::balances: Tracking balances of {owners:?}");
+
+    loop {
+        if let Err(e) = track(ctx, &owners).await {
+            error!("sync::balances: {e}");
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        }
+    }
+}
+
+/// Addresses whose balances we track
+fn owners() -> Result<Vec<Address>> {
+    let mut owners = vec![Address::from_str(&env::var("FLY_BASE_WALLET_ADDRESS")?)?];
+
+    if let Ok(executor) = env::var("FLY_BASE_EXECUTOR_ADDRESS") {
+        owners.push(Address::from_str(&executor)?);
+    }
+
+    Ok(owners)
+}
+
+/// Load balances, then follow transfers until the next reload
+async fn track(ctx: &AppContext, owners: &[Address]) -> Result<()> {
+    let chain = &ctx.base_provider;
+    let topics = owners.iter().map(Address::into_word).collect::<Vec<_>>();
+
+    let outgoing = Filter::new()
+        .event(Transfer::SIGNATURE)
+        .topic1(topics.clone());
+    let incoming = Filter::new().event(Transfer::SIGNATURE).topic2(topics);
+
+    let block = chain.block_number().await?;
+    let known = ctx.portfolio.borrow().holdings.keys().copied().collect();
+    let portfolio = load(ctx, owners, block, known).await?;
+    info!(
+        "sync::balances: Loaded {} token balances at block {block}",
+        portfolio.holdings.len()
+    );
+    ctx.portfolio.send_replace(portfolio);
+
+    // Replays from the snapshot block, so no transfer falls between the snapshot and the stream
+    let mut subscription =
+        Subscription::new(chain, "balances", vec![outgoing, incoming]).since(block);
+
+    let deadline = tokio::time::Instant::now() + RELOAD_INTERVAL;
+    while let Ok(log) = tokio::time::timeout_at(deadline, subscription.next()).await {
+        let snapshotted = log.block_number.is_some_and(|number| number <= block);
+
+        if log.removed && snapshotted {
+            warn!("sync::balances: Snapshot block {block} was reorged out, reloading");
+            return Ok(());
+        }
+
+        // Already part of the snapshot
+        if snapshotted {
+            continue;
+        }
+
+        let transfer = match Transfer::decode_log(&log.inner, true) {
+            Ok(transfer) => transfer,
+            Err(e) => {
+                warn!("sync::balances: Failed to decode transfer event: {e}");
+                continue;
+            }
+        };
+
+        let token = TokenId::from(log.address());
+        ctx.portfolio.send_modify(|portfolio| {
+            apply(portfolio, owners, token, &transfer, log.removed);
+        });
+
+        info!(
+            "sync::balances: {} {} of {token} ({:?})",
+            match (owners.contains(&transfer.to), log.removed) {
+                (true, false) => "Received",
+                (false, false) => "Sent",
+                (true, true) => "Reverted receiving",
+                (false, true) => "Reverted sending",
+            },
+            transfer.value,
+            log.transaction_hash
+        );
+    }
+
+    Ok(())
+}
+
+/// Apply a transfer from or to our addresses, or reverse it if a reorg removed its log
+fn apply(
+    portfolio: &mut Portfolio,
+    owners: &[Address],
+    token: TokenId,
+    transfer: &Transfer,
+    removed: bool,
+) {
+    let outgoing = owners.contains(&transfer.from);
+    let incoming = owners.contains(&transfer.to);
+
+    match (outgoing, incoming, removed) {
+        (true, false, false) | (false, true, true) => portfolio.debit(token, transfer.value),
+        (false, true, false) | (true, false, true) => portfolio.credit(token, transfer.value),
+        // Transfers between our own addresses do not change the total
+        _ => {}
+    }
+}
+
+/// Load balances of all valid tokens in the database (and of tokens we already hold)
+///
+/// # Arguments
+/// * `ctx` - Application context
+/// * `owners` - Addresses whose balances are summed up
+/// * `block` - Block to load the balances at
+/// * `known` - Tokens we hold that may not be in the database
+async fn load(
+    ctx: &AppContext,
+    owners: &[Address],
+    block: u64,
+    known: HashSet<TokenId>,
+) -> Result<Portfolio> {
+    let mut token_ids = known;
+    token_ids.extend(ctx.repo.valid().await?.into_iter().map(TokenId::from));
+    let token_ids = token_ids.into_iter().collect::<Vec<_>>();
+
+    balances_of(&ctx.base_provider, owners, &token_ids, block).await
+}
+
+/// Sum up the balances of `owners` in each token at `block`, skipping empty balances
+async fn balances_of(
+    chain: &impl Chain,
+    owners: &[Address],
+    token_ids: &[TokenId],
+    block: u64,
+) -> Result<Portfolio> {
+    let mut portfolio = Portfolio::default();
+
+    // Each token gets one call per owner
+    for chunk in token_ids.chunks(BATCH_SIZE / owners.len().max(1)) {
+        let calls = chunk
+            .iter()
+            .flat_map(|token| {
+                owners.iter().map(|owner| {
+                    Call::new(token.0, IERC20::balanceOfCall::new((*owner,)).abi_encode())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let results = chain.aggregate3(calls, Some(block)).await?;
+
+        for (token, results) in chunk.iter().zip(results.chunks(owners.len())) {
+            for result in results.iter().filter(|result| result.success) {
+                if let Ok(balance) = U256::abi_decode(&result.return_data, true) {
+                    if !balance.is_zero() {
+                        portfolio.credit(*token, balance);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(portfolio)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::chain::fake::FakeChain;
+
+    #[tokio::test]
+    async fn test_balances_of_sums_owners() {
+        let chain = FakeChain::new();
+        let (wallet, executor) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let (weth, usdc, empty) = (
+            TokenId(Address::repeat_byte(0xa)),
+            TokenId(Address::repeat_byte(0xb)),
+            TokenId(Address::repeat_byte(0xc)),
+        );
+        chain.set_balance(weth.0, wallet, U256::from(3));
+        chain.set_balance(weth.0, executor, U256::from(4));
+        chain.set_balance(usdc.0, executor, U256::from(5));
+        chain.set_balance(empty.0, wallet, U256::ZERO);
+
+        let portfolio = balances_of(&chain, &[wallet, executor], &[weth, usdc, empty], 0)
+            .await
+            .unwrap();
+
+        assert_eq!(portfolio.holdings.len(), 2);
+        assert_eq!(portfolio.holdings[&weth], U256::from(7));
+        assert_eq!(portfolio.holdings[&usdc], U256::from(5));
+    }
+
+    #[test]
+    fn test_apply_reverses_removed_transfers() {
+        let (wallet, other) = (Address::repeat_byte(1), Address::repeat_byte(9));
+        let weth = TokenId(Address::repeat_byte(0xa));
+        let mut portfolio = Portfolio::default();
+        portfolio.credit(weth, U256::from(10));
+
+        let received = Transfer {
+            from: other,
+            to: wallet,
+            value: U256::from(5),
+        };
+        apply(&mut portfolio, &[wallet], weth, &received, false);
+        assert_eq!(portfolio.holdings[&weth], U256::from(15));
+        apply(&mut portfolio, &[wallet], weth, &received, true);
+        assert_eq!(portfolio.holdings[&weth], U256::from(10));
+
+        let sent = Transfer {
+            from: wallet,
+            to: other,
+            value: U256::from(4),
+        };
+        apply(&mut portfolio, &[wallet], weth, &sent, false);
+        assert_eq!(portfolio.holdings[&weth], U256::from(6));
+        apply(&mut portfolio, &[wallet], weth, &sent, true);
+        assert_eq!(portfolio.holdings[&weth], U256::from(10));
+    }
+}
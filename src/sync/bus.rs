@@ -0,0 +1,188 @@
+//! In-process events between sync workers.
+//!
+//! Workers used to find their work by polling Postgres every second for NULL columns. Now the
+//! worker that writes something publishes an event on `AppContext::bus`, and the workers
+//! downstream of it wake up right away. Events are hints, not the source of truth: a worker
+//! that wakes up still reads its work from the database, and still polls it every
+//! `CATCH_UP_INTERVAL` to pick up rows written before it started or while it lagged behind.
+
+use std::sync::Arc;
+
+use alloy::primitives::Address;
+use tokio::sync::broadcast;
+
+/// How long a worker waits for an event before polling the database anyway
+pub const CATCH_UP_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(30);
+
+/// Number of events kept for slow subscribers before they start lagging
+const CAPACITY: usize = 1024;
+
+/// Something a sync worker wrote to the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncEvent {
+    /// New pairs were stored, possibly without tokens, factory or reserves
+    PairDiscovered(Vec<Address>),
+    /// Tokens of these pairs were stored
+    TokensResolved(Vec<Address>),
+    /// Reserves of these pairs changed
+    ReservesUpdated(Vec<Address>),
+    /// Exchange rates of these tokens changed
+    ExchangeRateUpdated(Vec<Address>),
+}
+
+/// The kind of a `SyncEvent`, used to subscribe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    /// `SyncEvent::PairDiscovered`
+    PairDiscovered,
+    /// `SyncEvent::TokensResolved`
+    TokensResolved,
+    /// `SyncEvent::ReservesUpdated`
+    ReservesUpdated,
+    /// `SyncEvent::ExchangeRateUpdated`
+    ExchangeRateUpdated,
+}
+
+impl SyncEvent {
+    /// The topic of the event
+    #[must_use]
+    pub const fn topic(&self) -> Topic {
+        match self {
+            Self::PairDiscovered(_) => Topic::PairDiscovered,
+            Self::TokensResolved(_) => Topic::TokensResolved,
+            Self::ReservesUpdated(_) => Topic::ReservesUpdated,
+            Self::ExchangeRateUpdated(_) => Topic::ExchangeRateUpdated,
+        }
+    }
+
+    /// The pairs or tokens the event is about
+    #[must_use]
+    pub fn addresses(&self) -> &[Address] {
+        match self {
+            Self::PairDiscovered(addresses)
+            | Self::TokensResolved(addresses)
+            | Self::ReservesUpdated(addresses)
+            | Self::ExchangeRateUpdated(addresses) => addresses,
+        }
+    }
+}
+
+/// Broadcasts `SyncEvent`s to every subscribed worker
+#[derive(Debug, Clone)]
+pub struct Bus {
+    /// The sending half of the broadcast channel
+    sender: broadcast::Sender<Arc<SyncEvent>>,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl Bus {
+    /// Publish an event, ignoring events about nothing
+    pub fn publish(&self, event: SyncEvent) {
+        if event.addresses().is_empty() {
+            return;
+        }
+
+        // Nobody may be listening, which is fine
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    /// Subscribe to events of the given topics
+    #[must_use]
+    pub fn subscribe(&self, topics: &[Topic]) -> Subscriber {
+        Subscriber {
+            receiver: self.sender.subscribe(),
+            topics: topics.to_vec(),
+        }
+    }
+}
+
+/// Receives the events of some topics
+#[derive(Debug)]
+pub struct Subscriber {
+    /// The receiving half of the broadcast channel
+    receiver: broadcast::Receiver<Arc<SyncEvent>>,
+    /// The topics to wake up for
+    topics: Vec<Topic>,
+}
+
+impl Subscriber {
+    /// Wait for the next event of a subscribed topic
+    ///
+    /// Returns `None` once `timeout` elapses, or if events were missed because the subscriber
+    /// lagged behind. Either way the caller should poll the database.
+    pub async fn next(&mut self, timeout: tokio::time::Duration) -> Option<Arc<SyncEvent>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            match tokio::time::timeout_at(deadline, self.receiver.recv()).await {
+                Ok(Ok(event)) if self.topics.contains(&event.topic()) => return Some(event),
+                Ok(Ok(_)) => {}
+                Ok(Err(broadcast::error::RecvError::Lagged(missed))) => {
+                    log::warn!("sync::bus: Subscriber missed {missed} events");
+                    return None;
+                }
+                Ok(Err(broadcast::error::RecvError::Closed)) => {
+                    tokio::time::sleep_until(deadline).await;
+                    return None;
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// Wait for an event of a subscribed topic or `CATCH_UP_INTERVAL`, whichever comes first
+    pub async fn wait(&mut self) {
+        let _ = self.next(CATCH_UP_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_receives_subscribed_topics() {
+        let bus = Bus::default();
+        let mut subscriber = bus.subscribe(&[Topic::PairDiscovered]);
+
+        bus.publish(SyncEvent::ReservesUpdated(vec![Address::ZERO]));
+        bus.publish(SyncEvent::PairDiscovered(vec![Address::ZERO]));
+
+        let event = subscriber.next(TIMEOUT).await.unwrap();
+        assert_eq!(*event, SyncEvent::PairDiscovered(vec![Address::ZERO]));
+        assert!(subscriber.next(TIMEOUT).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ignores_empty_events() {
+        let bus = Bus::default();
+        let mut subscriber = bus.subscribe(&[Topic::TokensResolved]);
+
+        bus.publish(SyncEvent::TokensResolved(Vec::new()));
+
+        assert!(subscriber.next(TIMEOUT).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_polls() {
+        let bus = Bus::default();
+        let mut subscriber = bus.subscribe(&[Topic::ReservesUpdated]);
+
+        for _ in 0..=CAPACITY {
+            bus.publish(SyncEvent::ReservesUpdated(vec![Address::ZERO]));
+        }
+
+        assert!(subscriber.next(TIMEOUT).await.is_none());
+        assert!(subscriber.next(TIMEOUT).await.is_some());
+    }
+}
@@ -1,27 +1,19 @@
-use crate::schemas::{pairs, tokens};
+use crate::models::token::PriceStatus;
+use crate::pricing::{Engine, Guard, GuardConfig};
+use crate::repo::{PairRepo, PriceChange, TokenRepo};
+use crate::sync::bus::{Bus, SyncEvent, Topic};
 use crate::utils::app_context::AppContext;
-use bigdecimal::{BigDecimal, RoundingMode};
+use crate::utils::constants::WETH;
+use alloy::primitives::Address;
+use bigdecimal::BigDecimal;
 use chrono::Utc;
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
 use eyre::Result;
 use log;
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::str::FromStr;
 
-/// Type alias for the actual connection type used in the project
-type DbConn = diesel_async::AsyncPgConnection;
-
-/// Number of tokens to process in each batch
-const BATCH_SIZE: i64 = 100;
-/// ID of WETH token in our database
-const WETH_TOKEN_ID: i32 = 3;
-/// WETH token address
-const WETH_ADDRESS: &str = "0x4200000000000000000000000000000000000006";
-/// Hardcoded WETH price in USD for now
-const WETH_USD_PRICE: f64 = 2100.0;
-/// Maximum decimal places to allow for tokens
-const MAX_DECIMALS: i32 = 30;
+/// Pools worth less than this in USD don't price tokens
+const MIN_POOL_DEPTH_USD: u32 = 1_000;
 
 /// Synchronizes exchange rates between tokens.
 ///
@@ -33,385 +25,124 @@ const MAX_DECIMALS: i32 = 30;
 /// Returns an error if database operations fail, if there are issues with rate calculations,
 /// or if the sync process encounters any other problem.
 pub async fn exchange_rates(ctx: &AppContext) -> Result<()> {
-    log::info!("sync::exchange_rates: Starting exchange rates sync service");
+    let guard = Guard::new(GuardConfig::from_env());
+    log::info!("sync::exchange_rates: Starting exchange rates sync service with {guard:?}");
+
+    let mut events = ctx.bus.subscribe(&[Topic::TokensResolved]);
 
     loop {
-        let updated_count = sync(ctx, BATCH_SIZE).await?;
+        let updated_count = sync(&ctx.repo, &ctx.bus, &guard).await?;
         log::info!(
             "sync::exchange_rates: Completed sync iteration. Updated exchange rates for {} tokens",
             updated_count
         );
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+        // Rates drift with reserves, so they are recomputed even without new tokens
+        let _ = events.next(tokio::time::Duration::from_secs(10)).await;
     }
 }
 
-/// Inner sync function that does the actual work
-async fn sync(ctx: &AppContext, _limit: i64) -> Result<usize> {
-    let mut conn = ctx.db.get().await?;
+/// Price every token reachable from WETH through pools of at least `MIN_POOL_DEPTH_USD`
+///
+/// The WETH price itself is owned by `sync::weth`.
+/// Tokens and reserves are loaded at once, priced in memory, checked by the guard and written in
+/// one transaction. Tokens that can no longer be priced lose their exchange rate.
+async fn sync(repo: &(impl PairRepo + TokenRepo), bus: &Bus, guard: &Guard) -> Result<usize> {
     let now_timestamp = Utc::now().naive_utc();
 
-    // Update WETH price
-    update_weth_price(&mut conn, now_timestamp).await?;
-
-    // Load all tokens with known exchange rates
-    let mut known_tokens = load_known_tokens(&mut conn).await?;
-
-    // Track processed tokens and update counts
-    let mut processed_token_ids = HashSet::new();
-
-    // Get WETH token ID by address
-    let weth_id = get_weth_token_id(&mut conn).await?;
-
-    processed_token_ids.insert(weth_id); // Consider WETH already processed
-
-    let mut updated_count = 0;
-    let mut iteration = 0;
-    let mut new_tokens_discovered = true;
-
-    // Process tokens in waves until no new exchange rates can be discovered
-    while new_tokens_discovered {
-        iteration += 1;
-        new_tokens_discovered = false;
-
-        // Find pairs where exactly one token has a known exchange rate
-        let candidate_pairs = find_candidate_pairs(&mut conn, &known_tokens).await?;
-
-        if candidate_pairs.is_empty() {
-            log::info!("sync::exchange_rates: No more pairs to process. Updated 0 tokens.");
-            break;
-        }
-
-        let mut iteration_updated_count = 0;
-
-        // Process each candidate pair
-        for (pair_id, token0_id, token1_id, reserve0, reserve1) in candidate_pairs {
-            // Skip if both tokens already have known exchange rates
-            if both_tokens_known(token0_id, token1_id, &known_tokens) {
-                continue;
-            }
-
-            // Determine which token already has a price and which needs a price
-            let (known_token_id, unknown_token_id) =
-                identify_token_roles(token0_id, token1_id, &known_tokens);
+    let weth_address = WETH.to_string();
+    let tokens = repo.prices().await?;
+    let Some((weth_id, weth_rate)) = tokens
+        .iter()
+        .find(|token| token.address == weth_address)
+        .and_then(|token| Some((token.id, token.exchange_rate.clone()?)))
+    else {
+        log::warn!("sync::exchange_rates: WETH has no exchange rate, skipping");
+        return Ok(0);
+    };
 
-            // Skip if we can't determine both tokens
-            let (Some(known_token_id), Some(unknown_token_id)) = (known_token_id, unknown_token_id)
-            else {
-                log::debug!(
-                    "sync::exchange_rates: Pair {} - Skipping due to None token ID",
-                    pair_id
-                );
-                continue;
+    let decimals = tokens
+        .iter()
+        .filter_map(|token| Some((token.id, u8::try_from(token.decimals?).ok()?)))
+        .collect::<HashMap<_, _>>();
+    let (pools, blocks) = repo.pools().await?;
+    let head = blocks
+        .values()
+        .filter_map(|blocks| blocks.updated)
+        .max()
+        .unwrap_or_default();
+
+    let engine = Engine::new(BigDecimal::from(MIN_POOL_DEPTH_USD));
+    let prices = engine.price(&decimals, &pools, &[(weth_id, weth_rate)]);
+
+    let changes = tokens
+        .into_iter()
+        .filter(|token| token.id != weth_id)
+        .filter_map(|token| {
+            let Some(price) = prices.get(&token.id) else {
+                let had_price = token.exchange_rate.is_some() || token.price_status.is_some();
+                return had_price.then_some((token, PriceChange::Unpriced));
             };
 
-            // Skip if we've already processed this token in this sync
-            if processed_token_ids.contains(&unknown_token_id) {
-                continue;
-            }
-
-            // Mark token as processed to avoid checking it again
-            processed_token_ids.insert(unknown_token_id);
-
-            // Calculate and update the exchange rate
-            if let Some(token_price) = calculate_exchange_rate(
-                &mut conn,
-                known_token_id,
-                unknown_token_id,
-                token0_id,
-                reserve0,
-                reserve1,
-            )
-            .await?
-            {
-                // Update the token's exchange rate in the database
-                if update_token_exchange_rate(
-                    &mut conn,
-                    unknown_token_id,
-                    &token_price,
-                    now_timestamp,
-                )
-                .await?
-                {
-                    // Get token address for logging
-                    let token_address = get_token_address(&mut conn, unknown_token_id).await?;
-
-                    log::info!(
-                        "sync::exchange_rates: Updated exchange rate for token {} (ID: {}) based on {} (ID: {}): ${}",
-                        token_address,
-                        unknown_token_id,
-                        if known_token_id == get_weth_token_id(&mut conn).await? { "WETH".to_string() } else { format!("token {known_token_id}") },
-                        known_token_id,
-                        token_price
-                    );
-
-                    updated_count += 1;
-                    iteration_updated_count += 1;
-
-                    // Add this token to known tokens for future iterations
-                    known_tokens.insert(unknown_token_id);
-                    new_tokens_discovered = true;
-                }
+            let verdict = guard.check(token.exchange_rate.as_ref(), price, &blocks, head);
+            match verdict.status {
+                PriceStatus::Rejected => (token.price_status != Some(PriceStatus::Rejected))
+                    .then_some((token, PriceChange::Rejected(verdict))),
+                _ => (!token.has(price, &verdict))
+                    .then(|| (token, PriceChange::Priced(price.clone(), verdict))),
             }
-        }
-
-        log::info!(
-            "sync::exchange_rates: Iteration {} - Updated {} tokens",
-            iteration,
-            iteration_updated_count
-        );
-
-        // Stop if no new tokens were updated in this iteration
-        if iteration_updated_count == 0 {
-            log::info!("sync::exchange_rates: No tokens updated in this iteration. Stopping.");
-            break;
+        })
+        .collect::<Vec<_>>();
+
+    for (token, change) in &changes {
+        match change {
+            PriceChange::Priced(price, verdict) => log::debug!(
+                "sync::exchange_rates: Token {} (ID: {}) is worth ${} through pairs {:?}, ${:?} deep, {:?} with confidence {:.3}",
+                token.address,
+                token.id,
+                price.rate,
+                price.route,
+                price.depth,
+                verdict.status,
+                verdict.confidence
+            ),
+            PriceChange::Rejected(verdict) => log::warn!(
+                "sync::exchange_rates: Rejected new price of token {} (ID: {}), moved: {}, new pool: {}, keeping ${:?}",
+                token.address,
+                token.id,
+                verdict.moved,
+                verdict.new_pool,
+                token.exchange_rate
+            ),
+            PriceChange::Unpriced => {}
         }
     }
 
-    Ok(updated_count)
-}
-
-/// Update the WETH token's price
-async fn update_weth_price(conn: &mut DbConn, timestamp: chrono::NaiveDateTime) -> Result<()> {
-    let weth_price = BigDecimal::from_str(&WETH_USD_PRICE.to_string())?;
-
-    diesel::update(tokens::table.filter(tokens::address.eq(WETH_ADDRESS)))
-        .set((
-            tokens::exchange_rate.eq(&weth_price),
-            tokens::updated_last.eq(timestamp),
-        ))
-        .execute(conn)
-        .await?;
+    let updates = changes
+        .iter()
+        .map(|(token, change)| (token.id, change))
+        .collect::<Vec<_>>();
+    repo.update_prices(&updates, now_timestamp).await?;
 
     log::info!(
-        "sync::exchange_rates: Updated WETH price to ${}",
-        WETH_USD_PRICE
+        "sync::exchange_rates: Priced {} tokens from {} pools, {} prices changed",
+        prices.len(),
+        pools.len(),
+        changes.len()
     );
 
-    Ok(())
-}
-
-/// Load all tokens that already have exchange rates
-async fn load_known_tokens(conn: &mut DbConn) -> Result<HashSet<i32>> {
-    let mut known_tokens = HashSet::new();
-
-    let tokens_with_rates = tokens::table
-        .filter(tokens::exchange_rate.is_not_null())
-        .filter(tokens::decimals.is_not_null())
-        .select(tokens::id)
-        .load::<i32>(conn)
-        .await?;
-
-    for token_id in tokens_with_rates {
-        known_tokens.insert(token_id);
-    }
-
-    log::debug!(
-        "sync::exchange_rates: Starting with {} tokens that already have exchange rates",
-        known_tokens.len()
-    );
-
-    Ok(known_tokens)
-}
-
-/// Find pairs where at least one token has a known exchange rate
-async fn find_candidate_pairs(
-    conn: &mut DbConn,
-    known_tokens: &HashSet<i32>,
-) -> Result<
-    Vec<(
-        i32,
-        Option<i32>,
-        Option<i32>,
-        Option<BigDecimal>,
-        Option<BigDecimal>,
-    )>,
-> {
-    let pairs = pairs::table
-        .filter(
-            pairs::token0_id
-                .eq_any(known_tokens)
-                .or(pairs::token1_id.eq_any(known_tokens)),
-        )
-        .filter(pairs::reserve0.gt(BigDecimal::from(0)))
-        .filter(pairs::reserve1.gt(BigDecimal::from(0)))
-        .select((
-            pairs::id,
-            pairs::token0_id,
-            pairs::token1_id,
-            pairs::reserve0,
-            pairs::reserve1,
-        ))
-        .load::<(
-            i32,
-            Option<i32>,
-            Option<i32>,
-            Option<BigDecimal>,
-            Option<BigDecimal>,
-        )>(conn)
-        .await?;
+    // Only a new rate changes the value of pairs
+    let updated_tokens = changes
+        .iter()
+        .filter(|(token, change)| match change {
+            PriceChange::Priced(price, _) => token.exchange_rate.as_ref() != Some(&price.rate),
+            PriceChange::Rejected(_) => false,
+            PriceChange::Unpriced => token.exchange_rate.is_some(),
+        })
+        .filter_map(|(token, _)| Address::from_str(&token.address).ok())
+        .collect::<Vec<_>>();
+    let updated_count = updated_tokens.len();
+    bus.publish(SyncEvent::ExchangeRateUpdated(updated_tokens));
 
-    log::debug!(
-        "sync::exchange_rates: Found {} potential pairs to process",
-        pairs.len()
-    );
-
-    Ok(pairs)
-}
-
-/// Check if both tokens in a pair already have known exchange rates
-fn both_tokens_known(
-    token0_id: Option<i32>,
-    token1_id: Option<i32>,
-    known_tokens: &HashSet<i32>,
-) -> bool {
-    token0_id.is_some_and(|id| known_tokens.contains(&id))
-        && token1_id.is_some_and(|id| known_tokens.contains(&id))
-}
-
-/// Identify which token has a known rate and which needs a rate
-fn identify_token_roles(
-    token0_id: Option<i32>,
-    token1_id: Option<i32>,
-    known_tokens: &HashSet<i32>,
-) -> (Option<i32>, Option<i32>) {
-    if token0_id.is_some_and(|id| known_tokens.contains(&id)) {
-        (token0_id, token1_id)
-    } else {
-        (token1_id, token0_id)
-    }
-}
-
-/// Calculate a token's exchange rate based on a known token's price
-async fn calculate_exchange_rate(
-    conn: &mut DbConn,
-    known_token_id: i32,
-    unknown_token_id: i32,
-    token0_id: Option<i32>,
-    reserve0: Option<BigDecimal>,
-    reserve1: Option<BigDecimal>,
-) -> Result<Option<BigDecimal>> {
-    // Get information about the unknown token
-    let Ok(Some(unknown_token_info)) = tokens::table
-        .filter(tokens::id.eq(unknown_token_id))
-        .select(tokens::decimals)
-        .first::<Option<i32>>(conn)
-        .await
-    else {
-        log::debug!(
-            "sync::exchange_rates: Token {} has no decimals, skipping",
-            unknown_token_id
-        );
-        return Ok(None);
-    };
-
-    // Get exchange rate and decimals of the known token
-    let Ok((Some(known_token_exchange_rate), Some(known_token_decimals))) = tokens::table
-        .filter(tokens::id.eq(known_token_id))
-        .select((tokens::exchange_rate, tokens::decimals))
-        .first::<(Option<BigDecimal>, Option<i32>)>(conn)
-        .await
-    else {
-        log::debug!(
-            "sync::exchange_rates: Known token {} missing exchange rate or decimals",
-            known_token_id
-        );
-        return Ok(None);
-    };
-
-    // Skip tokens with extremely large decimals to prevent numeric overflow
-    if unknown_token_info > MAX_DECIMALS || known_token_decimals > MAX_DECIMALS {
-        log::warn!(
-            "sync::exchange_rates: Token {} or known token {} has too many decimals ({} or {}), skipping to prevent overflow",
-            unknown_token_id,
-            known_token_id,
-            unknown_token_info,
-            known_token_decimals
-        );
-        return Ok(None);
-    }
-
-    // Unwrap reserves which should be non-null due to our filters
-    let reserve0 = reserve0.unwrap_or_else(|| BigDecimal::from(0));
-    let reserve1 = reserve1.unwrap_or_else(|| BigDecimal::from(0));
-
-    // Determine known token and unknown token reserves
-    let (known_reserve, unknown_reserve) = if token0_id == Some(known_token_id) {
-        (reserve0, reserve1)
-    } else {
-        (reserve1, reserve0)
-    };
-
-    // Calculate decimal bases the safe way
-    let known_decimal_base = calculate_decimal_base(known_token_decimals);
-    let unknown_decimal_base = calculate_decimal_base(unknown_token_info);
-
-    // Normalize reserves by dividing by their decimal bases
-    let known_reserve_normalized = known_reserve / &known_decimal_base;
-    let unknown_reserve_normalized = unknown_reserve / &unknown_decimal_base;
-
-    // Calculate the token price (Formula 2)
-    let price = if unknown_reserve_normalized == BigDecimal::from(0) {
-        return Ok(None); // Avoid division by zero
-    } else {
-        known_token_exchange_rate * (known_reserve_normalized / unknown_reserve_normalized)
-    };
-
-    // Round to 18 decimal places for consistency
-    let price_rounded = price.with_scale_round(18, RoundingMode::HalfUp);
-
-    Ok(Some(price_rounded))
-}
-
-/// Calculate 10^decimals safely using `BigDecimal`
-fn calculate_decimal_base(decimals: i32) -> BigDecimal {
-    match decimals {
-        0 => BigDecimal::from(1),
-        d => {
-            let mut result = BigDecimal::from(1);
-            for _ in 0..d {
-                result *= BigDecimal::from(10);
-            }
-            result
-        }
-    }
-}
-
-/// Update a token's exchange rate in the database
-async fn update_token_exchange_rate(
-    conn: &mut DbConn,
-    token_id: i32,
-    price: &BigDecimal,
-    timestamp: chrono::NaiveDateTime,
-) -> Result<bool> {
-    let updated = diesel::update(tokens::table.filter(tokens::id.eq(token_id)))
-        .set((
-            tokens::exchange_rate.eq(price),
-            tokens::updated_last.eq(timestamp),
-        ))
-        .execute(conn)
-        .await?;
-
-    Ok(updated > 0)
-}
-
-/// Get a token's address for logging purposes
-async fn get_token_address(conn: &mut DbConn, token_id: i32) -> Result<String> {
-    let address = tokens::table
-        .filter(tokens::id.eq(token_id))
-        .select(tokens::address)
-        .first::<String>(conn)
-        .await?;
-
-    Ok(address)
-}
-
-/// Helper function to get the WETH token ID by its address
-async fn get_weth_token_id(conn: &mut DbConn) -> Result<i32> {
-    tokens::table
-        .filter(tokens::address.eq(WETH_ADDRESS))
-        .select(tokens::id)
-        .first::<i32>(conn)
-        .await
-        .or_else(|_| Ok(WETH_TOKEN_ID)) // Fallback to constant if query fails
+    Ok(updated_count)
 }
@@ -1,38 +1,40 @@
-use crate::models::pair::Pair;
-use crate::schemas::{factories, pairs};
+use crate::chain::{Call, Chain};
+use crate::models::factory::{FactoryKind, FactoryStatus};
+use crate::repo::{FactoryRepo, PairRepo};
+use crate::sync::bus::Topic;
+use crate::sync::factory_classifier;
 use crate::utils::app_context::AppContext;
-use alloy::primitives::{Address, Bytes};
-use alloy::providers::MULTICALL3_ADDRESS;
+use std::str::FromStr;
+
+use alloy::primitives::Address;
 use alloy::sol;
 use alloy::sol_types::{SolCall, SolValue};
-use diesel::{ExpressionMethods, QueryDsl, SelectableHelper};
-use diesel_async::RunQueryDsl;
 use eyre::Result;
 
-sol! {
-    #[sol(rpc)]
-    "contracts/src/interfaces/IMulticall3.sol"
-}
-
 sol! {
     #[sol(abi)]
     "contracts/src/interfaces/IUniswapV2Pair.sol"
 }
 
-/// Sync factories for pairs
+/// Sync factories for pairs, then classify them
 /// # Errors
-/// Returns an error if the database connection fails
+/// Returns an error if the database connection fails or `FLY_BASE_ROUTERS` is invalid
 ///
 /// # Returns
 /// Returns the number of pairs synced
 pub async fn factories(ctx: &AppContext) -> Result<()> {
     log::info!("sync::factories: Starting factories sync...");
 
+    let routers = factory_classifier::routers()?;
+    let mut events = ctx.bus.subscribe(&[Topic::PairDiscovered]);
+
     loop {
-        let synced_tokens_count = sync(ctx, 100).await?;
+        let chain = &ctx.base_provider;
+        let synced_tokens_count =
+            sync(chain, &ctx.repo, 100).await? + classify(chain, &ctx.repo, &routers, 100).await?;
 
         if synced_tokens_count == 0 {
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            events.wait().await;
         }
     }
 }
@@ -43,57 +45,157 @@ pub async fn factories(ctx: &AppContext) -> Result<()> {
 ///
 /// # Returns
 /// Returns the number of pairs synced
-async fn sync(ctx: &AppContext, limit: i64) -> Result<usize> {
-    let mut conn = ctx.db.get().await?;
-
-    // Pairs missing factory_id
-    let pairs: Vec<Pair> = pairs::table
-        .filter(pairs::factory_id.is_null())
-        .select(Pair::as_select())
-        .limit(limit)
-        .load(&mut conn)
-        .await?;
-
-    // Multicall3 instance
-    let multicall = IMulticall3::new(MULTICALL3_ADDRESS, &ctx.base_provider);
+async fn sync(
+    chain: &impl Chain,
+    repo: &(impl PairRepo + FactoryRepo),
+    limit: i64,
+) -> Result<usize> {
+    let pairs = repo.without_factory(limit).await?;
 
     // Create calls for each pair
-    let calls: Vec<IMulticall3::Call3> = pairs
+    let calls: Vec<Call> = pairs
         .iter()
-        .map(|p| IMulticall3::Call3 {
-            target: p.address(),
-            allowFailure: true,
-            callData: Bytes::from(IUniswapV2Pair::factoryCall::new(()).abi_encode()),
+        .map(|p| {
+            Call::new(
+                p.address(),
+                IUniswapV2Pair::factoryCall::new(()).abi_encode(),
+            )
         })
         .collect();
 
     // Execute calls
-    let result = multicall.aggregate3(calls).call().await?;
+    let result = chain.aggregate3(calls, None).await?;
 
     // Update pairs with factory_id
     for (index, pair) in pairs.iter().enumerate() {
-        if result.returnData[index].success {
-            if let Ok(factory_address) =
-                Address::abi_decode(&result.returnData[index].returnData, true)
-            {
-                // Upsert factory_address to factories table
-                let factory_id = diesel::insert_into(factories::table)
-                    .values(factories::address.eq(factory_address.to_string()))
-                    .on_conflict(factories::address)
-                    .do_update()
-                    .set(factories::address.eq(factory_address.to_string()))
-                    .returning(factories::id)
-                    .get_result::<i32>(&mut conn)
-                    .await?;
-
-                // Update pair with factory_id
-                diesel::update(pairs::table.find(pair.id()))
-                    .set(pairs::factory_id.eq(factory_id))
-                    .execute(&mut conn)
+        if result[index].success {
+            if let Ok(factory_address) = Address::abi_decode(&result[index].return_data, true) {
+                let (factory_id, _) = repo
+                    .resolve(factory_address, FactoryStatus::Unsynced)
                     .await?;
+                repo.set_factory(pair.id(), factory_id).await?;
             }
         }
     }
 
     Ok(pairs.len())
 }
+
+/// Classify a batch of factories by probing one of their pairs
+///
+/// Factories only known from `PairCreated` events are validated once they turn out to be a
+/// known kind of DEX.
+/// # Errors
+/// Returns an error if the database connection or a multicall fails
+///
+/// # Returns
+/// Returns the number of factories classified
+async fn classify(
+    chain: &impl Chain,
+    repo: &impl FactoryRepo,
+    routers: &[Address],
+    limit: i64,
+) -> Result<usize> {
+    let factories = repo.unclassified(limit).await?;
+
+    for (factory_id, factory, pair) in &factories {
+        let classification = match (Address::from_str(factory), Address::from_str(pair)) {
+            (Ok(factory), Ok(pair)) => {
+                factory_classifier::classify(chain, factory, pair, routers).await?
+            }
+            _ => factory_classifier::Classification::UNKNOWN,
+        };
+
+        log::info!(
+            "sync::factories: Factory {factory} is {:?} with a fee of {:?} bps",
+            classification.kind,
+            classification.fee_bps
+        );
+
+        repo.classify(*factory_id, classification.kind, classification.fee_bps)
+            .await?;
+
+        // A factory that implements a known interface is a DEX, its pairs can be trusted
+        if classification.kind != FactoryKind::Unknown && repo.validate(*factory_id).await? {
+            log::info!("sync::factories: Validated factory {factory}, its pairs are trusted");
+        }
+    }
+
+    Ok(factories.len())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloy::primitives::U256;
+
+    use super::*;
+    use crate::chain::fake::FakeChain;
+    use crate::repo::memory::{FactoryRow, MemoryRepo, PairRow, TokenRow};
+    use crate::sync::factory_classifier::ISolidlyFactory;
+
+    /// An unvalidated factory with one invalid pair of two valid tokens
+    fn unvalidated(repo: &MemoryRepo, factory: Address, pair: Address) -> i32 {
+        let mut row = FactoryRow::new(factory);
+        row.status = FactoryStatus::Unvalidated;
+        let factory_id = repo.insert_factory(row);
+
+        let token0_id = repo.insert_token(TokenRow::new(Address::repeat_byte(0xa)));
+        let token1_id = repo.insert_token(TokenRow::new(Address::repeat_byte(0xb)));
+        repo.insert_pair(PairRow {
+            factory_id: Some(factory_id),
+            token0_id: Some(token0_id),
+            token1_id: Some(token1_id),
+            is_valid: false,
+            ..PairRow::new(pair)
+        });
+
+        factory_id
+    }
+
+    #[tokio::test]
+    async fn test_classify_validates_dex_factories() {
+        let (factory, pair) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let chain = FakeChain::new();
+        chain.add_pair(
+            pair,
+            factory,
+            Address::repeat_byte(0xa),
+            Address::repeat_byte(0xb),
+        );
+        chain.respond(
+            pair,
+            &IUniswapV2Pair::getReservesCall {},
+            (U256::ZERO, U256::ZERO, U256::ZERO).abi_encode(),
+        );
+        chain.respond(
+            factory,
+            &ISolidlyFactory::isPairCall { pair },
+            true.abi_encode(),
+        );
+        let repo = MemoryRepo::new();
+        unvalidated(&repo, factory, pair);
+
+        assert_eq!(classify(&chain, &repo, &[], 10).await.unwrap(), 1);
+
+        let row = repo.factory(factory).unwrap();
+        assert_eq!(row.kind, Some(FactoryKind::Solidly));
+        assert_eq!(row.status, FactoryStatus::Unsynced);
+        assert!(repo.pair(pair).unwrap().is_valid);
+    }
+
+    #[tokio::test]
+    async fn test_classify_keeps_unknown_factories_unvalidated() {
+        let (factory, pair) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let chain = FakeChain::new();
+        let repo = MemoryRepo::new();
+        unvalidated(&repo, factory, pair);
+
+        assert_eq!(classify(&chain, &repo, &[], 10).await.unwrap(), 1);
+
+        let row = repo.factory(factory).unwrap();
+        assert_eq!(row.kind, Some(FactoryKind::Unknown));
+        assert_eq!(row.status, FactoryStatus::Unvalidated);
+        assert!(!repo.pair(pair).unwrap().is_valid);
+    }
+}
@@ -0,0 +1,513 @@
+//! Factory fingerprinting.
+//!
+//! `sync::factories` records whatever `pair.factory()` returns, so a factory can be any contract.
+//! Each factory is probed with one of its pairs: a Uniswap V2 fork implements `allPairsLength`
+//! and returns the pair from `getPair`, a Solidly fork returns true from `isPair`. The swap fee
+//! is read from Solidly's `getFee(bool)`, or `getFee(address,bool)` on forks with per pair fees
+//! like Aerodrome, or inferred from the amount a router or pair quotes for a swap against known
+//! reserves. Uniswap V2 pairs don't quote, so their fee is only known when a
+//! router of the factory is listed in `FLY_BASE_ROUTERS`.
+
+use std::env;
+use std::str::FromStr;
+
+use alloy::primitives::{Address, U256};
+use alloy::sol;
+use alloy::sol_types::{SolCall, SolType, SolValue};
+use eyre::Result;
+
+use crate::chain::{Call, CallResult, Chain};
+use crate::models::factory::FactoryKind;
+
+sol! {
+    #[sol(abi)]
+    "contracts/src/interfaces/IUniswapV2Factory.sol"
+}
+
+sol! {
+    #[sol(abi)]
+    "contracts/src/interfaces/IUniswapV2Pair.sol"
+}
+
+sol! {
+    interface ISolidlyFactory {
+        function isPair(address pair) external view returns (bool);
+        function getFee(bool stable) external view returns (uint256);
+    }
+
+    interface IAerodromeFactory {
+        function getFee(address pool, bool stable) external view returns (uint256);
+    }
+
+    interface ISolidlyPair {
+        function stable() external view returns (bool);
+        function getAmountOut(uint256 amountIn, address tokenIn) external view returns (uint256);
+    }
+
+    interface IUniswapV2Router {
+        function factory() external view returns (address);
+        function getAmountOut(uint256 amountIn, uint256 reserveIn, uint256 reserveOut)
+            external pure returns (uint256 amountOut);
+    }
+}
+
+/// Fees above 10% are not swap fees, but a quote we can't read
+const MAX_FEE_BPS: u32 = 1000;
+
+/// Quotes of smaller amounts round too much to infer a fee
+const MIN_PROBE_AMOUNT: u64 = 1_000_000;
+
+/// Amount routers are asked to quote
+const ROUTER_PROBE_AMOUNT: u128 = 10u128.pow(18);
+
+/// Reserves routers quote against
+const ROUTER_PROBE_RESERVE: u128 = 10u128.pow(24);
+
+/// What a factory turned out to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Classification {
+    /// The kind of DEX of the factory
+    pub kind: FactoryKind,
+    /// The swap fee of the factory's volatile pairs, if it could be detected
+    pub fee_bps: Option<u32>,
+}
+
+impl Classification {
+    /// A factory that implements none of the interfaces we know
+    pub const UNKNOWN: Self = Self {
+        kind: FactoryKind::Unknown,
+        fee_bps: None,
+    };
+}
+
+/// Routers used to detect the fee of Uniswap V2 forks
+///
+/// # Environment Variables
+/// * `FLY_BASE_ROUTERS` - Comma separated router addresses (optional)
+///
+/// # Errors
+/// Returns an error if an address is invalid
+pub fn routers() -> Result<Vec<Address>> {
+    let Ok(routers) = env::var("FLY_BASE_ROUTERS") else {
+        return Ok(Vec::new());
+    };
+
+    routers
+        .split(',')
+        .map(str::trim)
+        .filter(|router| !router.is_empty())
+        .map(|router| Ok(Address::from_str(router)?))
+        .collect()
+}
+
+/// Probe a factory through one of its pairs
+///
+/// # Arguments
+/// * `chain` - Chain to call
+/// * `factory` - The factory to classify
+/// * `pair` - A pair whose `factory()` returned the factory
+/// * `routers` - Routers that may belong to the factory
+///
+/// # Errors
+/// Returns an error if a multicall fails
+pub async fn classify(
+    chain: &impl Chain,
+    factory: Address,
+    pair: Address,
+    routers: &[Address],
+) -> Result<Classification> {
+    let mut calls = vec![
+        Call::new(pair, IUniswapV2Pair::token0Call {}.abi_encode()),
+        Call::new(pair, IUniswapV2Pair::token1Call {}.abi_encode()),
+        Call::new(pair, IUniswapV2Pair::getReservesCall {}.abi_encode()),
+        Call::new(pair, ISolidlyPair::stableCall {}.abi_encode()),
+        Call::new(
+            factory,
+            IUniswapV2Factory::allPairsLengthCall {}.abi_encode(),
+        ),
+        Call::new(factory, ISolidlyFactory::isPairCall { pair }.abi_encode()),
+        Call::new(
+            factory,
+            ISolidlyFactory::getFeeCall { stable: false }.abi_encode(),
+        ),
+        Call::new(
+            factory,
+            IAerodromeFactory::getFeeCall {
+                pool: pair,
+                stable: false,
+            }
+            .abi_encode(),
+        ),
+    ];
+    calls.extend(
+        routers
+            .iter()
+            .map(|router| Call::new(*router, IUniswapV2Router::factoryCall {}.abi_encode())),
+    );
+
+    let results = chain.aggregate3(calls, None).await?;
+    let [token0, token1, reserves, stable, pairs_length, is_pair, fee, pair_fee, router_factories @ ..] =
+        results.as_slice()
+    else {
+        return Ok(Classification::UNKNOWN);
+    };
+
+    let (Some(token0), Some(token1)) = (decode::<Address>(token0), decode::<Address>(token1))
+    else {
+        return Ok(Classification::UNKNOWN);
+    };
+    let reserves = decode::<(U256, U256, U256)>(reserves);
+    let routers = routers
+        .iter()
+        .zip(router_factories)
+        .filter(|(_, result)| decode::<Address>(result) == Some(factory))
+        .map(|(router, _)| *router)
+        .collect::<Vec<_>>();
+
+    // Pairs quote a tenth of a percent of their reserves
+    let probe_amount = reserves
+        .map(|(reserve0, _, _)| reserve0 / U256::from(1000))
+        .filter(|amount| *amount >= U256::from(MIN_PROBE_AMOUNT));
+
+    let calls = probe_calls(factory, pair, (token0, token1), probe_amount, &routers);
+    let results = chain.aggregate3(calls, None).await?;
+    let (get_pair, quotes) = results.split_first().unzip();
+    let (pair_quote, router_quotes) = match (probe_amount, quotes) {
+        (Some(_), Some([pair_quote, router_quotes @ ..])) => (Some(pair_quote), router_quotes),
+        (_, quotes) => (None, quotes.unwrap_or_default()),
+    };
+
+    if decode::<bool>(is_pair) == Some(true) {
+        let fee_bps = solidly_fee_bps([fee, pair_fee], || {
+            // The quotes of stable pairs follow a different curve
+            if decode::<bool>(stable) == Some(true) {
+                return None;
+            }
+            let (reserve_in, reserve_out, _) = reserves?;
+            infer_fee_bps(probe_amount?, reserve_in, reserve_out, decode(pair_quote?)?)
+        });
+
+        return Ok(Classification {
+            kind: FactoryKind::Solidly,
+            fee_bps,
+        });
+    }
+
+    if decode::<U256>(pairs_length).is_some() && get_pair.and_then(decode::<Address>) == Some(pair)
+    {
+        let fee_bps = router_quotes.iter().find_map(|quote| {
+            infer_fee_bps(
+                U256::from(ROUTER_PROBE_AMOUNT),
+                U256::from(ROUTER_PROBE_RESERVE),
+                U256::from(ROUTER_PROBE_RESERVE),
+                decode(quote)?,
+            )
+        });
+
+        return Ok(Classification {
+            kind: FactoryKind::UniswapV2,
+            fee_bps,
+        });
+    }
+
+    Ok(Classification::UNKNOWN)
+}
+
+/// Calls of the second probe: `getPair` on the factory, then the quote of the pair if its
+/// reserves are large enough, then the quotes of the routers
+fn probe_calls(
+    factory: Address,
+    pair: Address,
+    (token0, token1): (Address, Address),
+    probe_amount: Option<U256>,
+    routers: &[Address],
+) -> Vec<Call> {
+    let mut calls = vec![Call::new(
+        factory,
+        IUniswapV2Factory::getPairCall {
+            tokenA: token0,
+            tokenB: token1,
+        }
+        .abi_encode(),
+    )];
+    calls.extend(probe_amount.map(|amount| {
+        Call::new(
+            pair,
+            ISolidlyPair::getAmountOutCall {
+                amountIn: amount,
+                tokenIn: token0,
+            }
+            .abi_encode(),
+        )
+    }));
+    calls.extend(routers.iter().map(|router| {
+        Call::new(
+            *router,
+            IUniswapV2Router::getAmountOutCall {
+                amountIn: U256::from(ROUTER_PROBE_AMOUNT),
+                reserveIn: U256::from(ROUTER_PROBE_RESERVE),
+                reserveOut: U256::from(ROUTER_PROBE_RESERVE),
+            }
+            .abi_encode(),
+        )
+    }));
+
+    calls
+}
+
+/// The fee of a Solidly factory's volatile pairs, as read from the factory or else inferred
+fn solidly_fee_bps(
+    factory_fees: [&CallResult; 2],
+    inferred: impl FnOnce() -> Option<u32>,
+) -> Option<u32> {
+    factory_fees
+        .into_iter()
+        .find_map(|fee| {
+            decode::<U256>(fee)
+                .and_then(|fee| u32::try_from(fee).ok())
+                .filter(|fee| *fee <= MAX_FEE_BPS)
+        })
+        .or_else(inferred)
+}
+
+/// Infer the fee of a constant product swap from its quote
+///
+/// The fee is taken from the amount in, so `amount_out = a * reserve_out / (reserve_in + a)`
+/// where `a` is the amount in after the fee. Solving for `a` gives the share of the amount in
+/// that was kept, rounded to a basis point.
+///
+/// # Returns
+/// The fee in basis points, or `None` if the quote is not a constant product swap with a fee
+/// up to 10%
+#[must_use]
+pub fn infer_fee_bps(
+    amount_in: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    amount_out: U256,
+) -> Option<u32> {
+    if amount_in.is_zero() || amount_out >= reserve_out {
+        return None;
+    }
+
+    let bps = U256::from(10_000);
+    let amount_in_after_fee = amount_out.checked_mul(reserve_in)? / (reserve_out - amount_out);
+    let kept_bps = (amount_in_after_fee.checked_mul(bps)? + amount_in / U256::from(2)) / amount_in;
+
+    let fee_bps = u32::try_from(bps.checked_sub(kept_bps)?).ok()?;
+    (fee_bps <= MAX_FEE_BPS).then_some(fee_bps)
+}
+
+/// Decode the return data of a successful call
+fn decode<T: SolValue + From<<T::SolType as SolType>::RustType>>(result: &CallResult) -> Option<T> {
+    if !result.success {
+        return None;
+    }
+    T::abi_decode(&result.return_data, false).ok()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::chain::fake::FakeChain;
+
+    /// Uniswap V2 `getAmountOut` with a fee in basis points
+    fn amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256, fee_bps: u32) -> U256 {
+        let amount_in_with_fee = amount_in * U256::from(10_000 - fee_bps);
+        amount_in_with_fee * reserve_out / (reserve_in * U256::from(10_000) + amount_in_with_fee)
+    }
+
+    #[test]
+    fn test_infer_fee_bps() {
+        let (amount_in, reserve_in, reserve_out) = (
+            U256::from(10u128.pow(18)),
+            U256::from(10u128.pow(24)),
+            U256::from(3 * 10u128.pow(27)),
+        );
+
+        for fee_bps in [0, 5, 25, 30, 100, 1000] {
+            let amount_out = amount_out(amount_in, reserve_in, reserve_out, fee_bps);
+            assert_eq!(
+                infer_fee_bps(amount_in, reserve_in, reserve_out, amount_out),
+                Some(fee_bps)
+            );
+        }
+    }
+
+    #[test]
+    fn test_infer_fee_bps_rejects_non_quotes() {
+        let (amount_in, reserve) = (U256::from(1000), U256::from(10u128.pow(24)));
+
+        // Above 10%
+        let amount_out = amount_out(amount_in, reserve, reserve, 2000);
+        assert_eq!(infer_fee_bps(amount_in, reserve, reserve, amount_out), None);
+        // More out than in, after the price impact
+        assert_eq!(
+            infer_fee_bps(amount_in, reserve, reserve, amount_in + U256::from(1)),
+            None
+        );
+        assert_eq!(infer_fee_bps(amount_in, reserve, reserve, reserve), None);
+        assert_eq!(
+            infer_fee_bps(U256::ZERO, reserve, reserve, U256::ZERO),
+            None
+        );
+    }
+
+    /// A chain with a pair of `factory`, with 1e24 of both tokens in reserve
+    fn chain_with_pair(factory: Address, pair: Address) -> (FakeChain, Address, Address) {
+        let chain = FakeChain::new();
+        let (token0, token1) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb));
+        chain.add_pair(pair, factory, token0, token1);
+
+        let reserve = U256::from(ROUTER_PROBE_RESERVE);
+        chain.respond(
+            pair,
+            &IUniswapV2Pair::getReservesCall {},
+            (reserve, reserve, U256::ZERO).abi_encode(),
+        );
+
+        (chain, token0, token1)
+    }
+
+    #[tokio::test]
+    async fn test_classify_uniswap_v2_with_router() {
+        let (factory, pair, router) = (
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        );
+        let (chain, token0, token1) = chain_with_pair(factory, pair);
+        chain.respond(
+            factory,
+            &IUniswapV2Factory::allPairsLengthCall {},
+            U256::from(1).abi_encode(),
+        );
+        chain.respond(
+            factory,
+            &IUniswapV2Factory::getPairCall {
+                tokenA: token0,
+                tokenB: token1,
+            },
+            pair.abi_encode(),
+        );
+        chain.respond(
+            router,
+            &IUniswapV2Router::factoryCall {},
+            factory.abi_encode(),
+        );
+        let reserve = U256::from(ROUTER_PROBE_RESERVE);
+        chain.respond(
+            router,
+            &IUniswapV2Router::getAmountOutCall {
+                amountIn: U256::from(ROUTER_PROBE_AMOUNT),
+                reserveIn: reserve,
+                reserveOut: reserve,
+            },
+            amount_out(U256::from(ROUTER_PROBE_AMOUNT), reserve, reserve, 25).abi_encode(),
+        );
+
+        let classification = classify(&chain, factory, pair, &[router]).await.unwrap();
+        assert_eq!(
+            classification,
+            Classification {
+                kind: FactoryKind::UniswapV2,
+                fee_bps: Some(25),
+            }
+        );
+
+        // Without a router the fee is unknown
+        let classification = classify(&chain, factory, pair, &[]).await.unwrap();
+        assert_eq!(classification.kind, FactoryKind::UniswapV2);
+        assert_eq!(classification.fee_bps, None);
+    }
+
+    #[tokio::test]
+    async fn test_classify_solidly() {
+        let (factory, pair) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let (chain, token0, _) = chain_with_pair(factory, pair);
+        chain.respond(
+            factory,
+            &ISolidlyFactory::isPairCall { pair },
+            true.abi_encode(),
+        );
+        chain.respond(pair, &ISolidlyPair::stableCall {}, false.abi_encode());
+
+        // Inferred from the pair's quote
+        let reserve = U256::from(ROUTER_PROBE_RESERVE);
+        let amount_in = reserve / U256::from(1000);
+        chain.respond(
+            pair,
+            &ISolidlyPair::getAmountOutCall {
+                amountIn: amount_in,
+                tokenIn: token0,
+            },
+            amount_out(amount_in, reserve, reserve, 30).abi_encode(),
+        );
+        let classification = classify(&chain, factory, pair, &[]).await.unwrap();
+        assert_eq!(
+            classification,
+            Classification {
+                kind: FactoryKind::Solidly,
+                fee_bps: Some(30),
+            }
+        );
+
+        // Forks with per pair fees read the fee of the pair
+        chain.respond(
+            factory,
+            &IAerodromeFactory::getFeeCall {
+                pool: pair,
+                stable: false,
+            },
+            U256::from(5).abi_encode(),
+        );
+        let classification = classify(&chain, factory, pair, &[]).await.unwrap();
+        assert_eq!(classification.fee_bps, Some(5));
+
+        // The factory's fee takes precedence
+        chain.respond(
+            factory,
+            &ISolidlyFactory::getFeeCall { stable: false },
+            U256::from(20).abi_encode(),
+        );
+        let classification = classify(&chain, factory, pair, &[]).await.unwrap();
+        assert_eq!(classification.fee_bps, Some(20));
+    }
+
+    #[tokio::test]
+    async fn test_classify_unknown() {
+        let (factory, pair) = (Address::repeat_byte(1), Address::repeat_byte(2));
+
+        // Not a pair at all
+        let chain = FakeChain::new();
+        let classification = classify(&chain, factory, pair, &[]).await.unwrap();
+        assert_eq!(classification, Classification::UNKNOWN);
+
+        // A pair whose factory doesn't know it
+        let (chain, _, _) = chain_with_pair(factory, pair);
+        chain.respond(
+            factory,
+            &IUniswapV2Factory::allPairsLengthCall {},
+            U256::from(1).abi_encode(),
+        );
+        let classification = classify(&chain, factory, pair, &[]).await.unwrap();
+        assert_eq!(classification, Classification::UNKNOWN);
+    }
+
+    #[test]
+    fn test_routers() {
+        std::env::set_var(
+            "FLY_BASE_ROUTERS",
+            "0x0101010101010101010101010101010101010101, 0x0202020202020202020202020202020202020202",
+        );
+        assert_eq!(
+            routers().unwrap(),
+            vec![Address::repeat_byte(1), Address::repeat_byte(2)]
+        );
+
+        std::env::set_var("FLY_BASE_ROUTERS", "not an address");
+        assert!(routers().is_err());
+        std::env::remove_var("FLY_BASE_ROUTERS");
+    }
+}
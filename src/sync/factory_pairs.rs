@@ -1,20 +1,13 @@
-use crate::models::factory::{Factory, FactoryStatus};
-use crate::schemas::{factories, pairs};
+use crate::chain::{Call, Chain};
+use crate::models::factory::FactoryStatus;
+use crate::repo::{FactoryRepo, PairRepo};
+use crate::sync::bus::{Bus, SyncEvent};
 use crate::utils::app_context::AppContext;
-use alloy::primitives::{Address, Bytes, U256};
-use alloy::providers::MULTICALL3_ADDRESS;
+use alloy::primitives::{Address, U256};
 use alloy::sol;
 use alloy::sol_types::{SolCall, SolValue};
-use diesel::QueryDsl;
-use diesel::{ExpressionMethods, SelectableHelper};
-use diesel_async::RunQueryDsl;
 use eyre::Result;
 
-sol! {
-    #[sol(rpc)]
-    "contracts/src/interfaces/IMulticall3.sol"
-}
-
 sol! {
     #[sol(rpc)]
     "contracts/src/interfaces/IUniswapV2Factory.sol"
@@ -30,7 +23,7 @@ pub async fn factory_pairs(ctx: &AppContext) -> Result<()> {
     log::info!("sync::factory_pairs: Starting factory pairs sync...");
 
     loop {
-        let synced_pairs_count = sync(ctx).await?;
+        let synced_pairs_count = sync(&ctx.base_provider, &ctx.repo, &ctx.bus).await?;
 
         if synced_pairs_count == 0 {
             tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
@@ -47,35 +40,22 @@ pub async fn factory_pairs(ctx: &AppContext) -> Result<()> {
 ///
 /// # Returns
 /// Returns the number of pairs synced
-#[allow(clippy::too_many_lines)]
-async fn sync(ctx: &AppContext) -> Result<usize> {
-    let mut conn = ctx.db.get().await?;
-
+async fn sync(
+    chain: &impl Chain,
+    repo: &(impl FactoryRepo + PairRepo),
+    bus: &Bus,
+) -> Result<usize> {
     // First unsynced factory
-    let mut results: Vec<Factory> = factories::table
-        .filter(factories::status.eq(FactoryStatus::Unsynced))
-        .limit(1)
-        .select(Factory::as_select())
-        .load(&mut conn)
-        .await?;
-
-    if results.is_empty() {
+    let Some(factory) = repo.next_unsynced().await? else {
         return Ok(0);
-    }
-
-    let factory = &mut results[0];
-
-    // Create factory contract instance
-    let factory_contract = IUniswapV2Factory::new(factory.address(), &ctx.base_provider);
+    };
 
     // Get total number of pairs
-    let pairs_length = match factory_contract.allPairsLength().call().await {
-        Ok(length) => length._0.to::<i32>(),
+    let pairs_length = match all_pairs_length(chain, factory.address()).await {
+        Ok(length) => length,
         Err(e) => {
             log::error!("sync::factory_pairs: Failed to get pairs length: {}", e);
-            factory
-                .update_status(&mut conn, FactoryStatus::Broken)
-                .await?;
+            repo.set_status(factory.id(), FactoryStatus::Broken).await?;
             return Ok(0);
         }
     };
@@ -89,17 +69,10 @@ async fn sync(ctx: &AppContext) -> Result<usize> {
 
     // All pairs already processed, mark factory as synced
     if factory.last_pair_id() >= pairs_length {
-        diesel::update(factories::table)
-            .filter(factories::id.eq(factory.id()))
-            .set(factories::status.eq(FactoryStatus::Synced))
-            .execute(&mut conn)
-            .await?;
+        repo.set_status(factory.id(), FactoryStatus::Synced).await?;
         return Ok(0);
     }
 
-    // Multicall3 instance
-    let multicall = IMulticall3::new(MULTICALL3_ADDRESS, &ctx.base_provider);
-
     // Arbitrary number, can be changed
     let multicall_batch_size = 100;
 
@@ -112,19 +85,18 @@ async fn sync(ctx: &AppContext) -> Result<usize> {
     let pair_indexes = (start_id..end_id).collect::<Vec<usize>>();
 
     // Prepare multicall calls
-    let calls: Vec<IMulticall3::Call3> = pair_indexes
+    let calls: Vec<Call> = pair_indexes
         .iter()
-        .map(|pair| IMulticall3::Call3 {
-            target: *factory_contract.address(),
-            allowFailure: true,
-            callData: Bytes::from(
+        .map(|pair| {
+            Call::new(
+                factory.address(),
                 IUniswapV2Factory::allPairsCall::new((U256::from(*pair),)).abi_encode(),
-            ),
+            )
         })
         .collect();
 
     // Execute multicall
-    let multicall_result = match multicall.aggregate3(calls).call().await {
+    let multicall_result = match chain.aggregate3(calls, None).await {
         Ok(result) => result,
         Err(e) => {
             log::error!("sync::factory_pairs: Multicall failed: {}", e);
@@ -133,8 +105,9 @@ async fn sync(ctx: &AppContext) -> Result<usize> {
     };
 
     // Process results
+    let mut discovered = Vec::with_capacity(pair_indexes.len());
     for (return_index, pair_index) in pair_indexes.iter().enumerate() {
-        let result = &multicall_result.returnData[return_index];
+        let result = &multicall_result[return_index];
         if !result.success {
             log::warn!(
                 "sync::factory_pairs: Failed to get pair at index {} for factory {}",
@@ -143,27 +116,18 @@ async fn sync(ctx: &AppContext) -> Result<usize> {
             );
             continue;
         }
-        let pair_address = Address::abi_decode(&result.returnData, true);
+        let pair_address = Address::abi_decode(&result.return_data, true);
 
         if let Ok(pair_address) = pair_address {
             // Upsert pair into database
-            diesel::insert_into(pairs::table)
-                .values((
-                    pairs::address.eq(pair_address.to_string()),
-                    pairs::factory_id.eq(factory.id()),
-                ))
-                .on_conflict(pairs::address)
-                .do_update()
-                .set(pairs::factory_id.eq(factory.id()))
-                .execute(&mut conn)
-                .await?;
+            repo.discover(pair_address, factory.id()).await?;
+            discovered.push(pair_address);
         } else {
             log::warn!(
                 "sync::factory_pairs: Failed to decode pair address at index {} for factory {}",
                 pair_index,
                 factory.address()
             );
-            continue;
         }
     }
 
@@ -171,11 +135,9 @@ async fn sync(ctx: &AppContext) -> Result<usize> {
     // SAFETY: database ids are unsigned
     #[allow(clippy::cast_possible_wrap)]
     #[allow(clippy::cast_possible_truncation)]
-    diesel::update(factories::table)
-        .filter(factories::id.eq(factory.id()))
-        .set(factories::last_pair_id.eq(end_id as i32))
-        .execute(&mut conn)
-        .await?;
+    repo.set_last_pair_id(factory.id(), end_id as i32).await?;
+
+    bus.publish(SyncEvent::PairDiscovered(discovered));
 
     log::info!(
         "sync::factory_pairs: Synced {} pairs from factory {}",
@@ -185,3 +147,89 @@ async fn sync(ctx: &AppContext) -> Result<usize> {
 
     Ok(pair_indexes.len())
 }
+
+/// Number of pairs created by a factory
+///
+/// # Errors
+/// Returns an error if the call fails or the factory returns something else than a length
+async fn all_pairs_length(chain: &impl Chain, factory: Address) -> Result<i32> {
+    let call = Call::new(
+        factory,
+        IUniswapV2Factory::allPairsLengthCall::new(()).abi_encode(),
+    );
+    let results = chain.aggregate3(vec![call], None).await?;
+
+    // A slice pattern, `first` would resolve to `RunQueryDsl::first`
+    match results.as_slice() {
+        [result, ..] if result.success => {
+            Ok(i32::try_from(U256::abi_decode(&result.return_data, true)?)?)
+        }
+        _ => eyre::bail!("allPairsLength() reverted"),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::chain::fake::FakeChain;
+    use crate::repo::memory::{FactoryRow, MemoryRepo};
+    use crate::sync::bus::Topic;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sync_lists_pairs_then_marks_factory_synced() {
+        let factory = Address::repeat_byte(1);
+        let pairs = [Address::repeat_byte(2), Address::repeat_byte(3)];
+        let chain = FakeChain::new();
+        chain.respond(
+            factory,
+            &IUniswapV2Factory::allPairsLengthCall {},
+            U256::from(pairs.len()).abi_encode(),
+        );
+        for (index, pair) in pairs.iter().enumerate() {
+            chain.respond(
+                factory,
+                &IUniswapV2Factory::allPairsCall::new((U256::from(index),)),
+                pair.abi_encode(),
+            );
+        }
+        let repo = MemoryRepo::new();
+        let factory_id = repo.insert_factory(FactoryRow::new(factory));
+        let bus = Bus::default();
+        let mut subscriber = bus.subscribe(&[Topic::PairDiscovered]);
+
+        assert_eq!(sync(&chain, &repo, &bus).await.unwrap(), 2);
+
+        for pair in pairs {
+            assert_eq!(repo.pair(pair).unwrap().factory_id, Some(factory_id));
+        }
+        assert_eq!(repo.factory(factory).unwrap().last_pair_id, 2);
+        let event = subscriber.next(Duration::from_millis(10)).await;
+        assert_eq!(
+            event.as_deref(),
+            Some(&SyncEvent::PairDiscovered(pairs.to_vec()))
+        );
+
+        assert_eq!(sync(&chain, &repo, &bus).await.unwrap(), 0);
+        assert_eq!(repo.factory(factory).unwrap().status, FactoryStatus::Synced);
+    }
+
+    #[tokio::test]
+    async fn test_sync_marks_reverting_factory_broken() {
+        let factory = Address::repeat_byte(1);
+        let repo = MemoryRepo::new();
+        repo.insert_factory(FactoryRow::new(factory));
+
+        assert_eq!(
+            sync(&FakeChain::new(), &repo, &Bus::default())
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(repo.factory(factory).unwrap().status, FactoryStatus::Broken);
+    }
+}
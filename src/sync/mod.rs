@@ -1,3 +1,21 @@
+/// Backfill missed events
+///
+/// This module replays Sync and `PairCreated` events emitted while the bot was down.
+///
+/// # Errors
+/// Returns an error if the database connection fails
+pub mod backfill;
+/// Sync wallet and executor token balances
+///
+/// This module keeps the live `Portfolio` of our wallet and executor current.
+///
+/// # Errors
+/// Returns an error if the wallet address is missing or invalid
+pub mod balances;
+/// Events between sync workers
+///
+/// This module wakes up sync workers when the data they depend on changes.
+pub mod bus;
 /// Sync module
 ///
 /// This module contains all the functions for syncing the database.
@@ -16,6 +34,13 @@ pub mod exchange_rates;
 /// Returns an error if the database connection fails
 ///
 pub mod factories;
+/// Factory fingerprinting
+///
+/// This module detects the kind of DEX and the swap fee of factories.
+///
+/// # Errors
+/// Returns an error if a multicall fails
+pub mod factory_classifier;
 /// Sync factory pairs
 ///
 /// This module contains all the functions for syncing the factory pairs.
@@ -37,6 +62,20 @@ pub mod pair_created_events;
 /// # Errors
 /// Returns an error if the database connection fails
 pub mod pair_tokens;
+/// Chain reorganization handling
+///
+/// This module rolls back reserves written by orphaned blocks.
+///
+/// # Errors
+/// Returns an error if a provider call or the database transaction fails
+pub mod reorg;
+/// Reserve history partitions
+///
+/// This module creates and expires the block range partitions of `reserve_history`.
+///
+/// # Errors
+/// Returns an error if the database connection fails
+pub mod reserve_history;
 /// Sync reserves
 ///
 /// This module contains all the functions for syncing the reserves.
@@ -51,6 +90,13 @@ pub mod reserves;
 /// # Errors
 /// Returns an error if the database connection fails
 pub mod sync_events;
+/// ERC20 metadata decoding
+///
+/// This module decodes token names, symbols and decimals, including non-standard ones.
+///
+/// # Errors
+/// Returns an error if the multicall fails
+pub mod token_metadata;
 /// Sync USD
 ///
 /// This module contains all the functions for syncing the USD.
@@ -58,20 +104,22 @@ pub mod sync_events;
 /// # Errors
 /// Returns an error if the database connection fails
 pub mod usd;
-/// Sync WETH price from Moralis API
+/// Sync WETH price from the configured price sources
 ///
-/// This module contains functionality for fetching and updating the WETH price
-/// from the Moralis API.
+/// This module owns the WETH price, read from Chainlink, Moralis or a fixed value.
 ///
 /// # Errors
-/// Returns an error if the API call fails or the database connection fails
+/// Returns an error if the database connection fails
 pub mod weth;
 
+pub use backfill::backfill;
+pub use balances::balances;
 pub use exchange_rates::exchange_rates;
 pub use factories::factories;
 pub use factory_pairs::factory_pairs;
 pub use pair_created_events::pair_created_events;
 pub use pair_tokens::pair_tokens;
+pub use reserve_history::reserve_history;
 pub use reserves::reserves;
 pub use sync_events::events;
 pub use usd::usd;
@@ -1,19 +1,17 @@
 use alloy::{
-    primitives::{Address, Bytes, U256},
-    providers::{Provider, MULTICALL3_ADDRESS},
-    rpc::types::{BlockNumberOrTag, Filter},
+    rpc::types::{Filter, Log},
     sol,
-    sol_types::{SolCall, SolEvent, SolValue},
+    sol_types::SolEvent,
 };
-use diesel::ExpressionMethods;
-use diesel_async::RunQueryDsl;
 use eyre::Result;
-use futures::StreamExt;
 use log::{error, info};
 
-use crate::models::token::NewToken;
-use crate::schemas::tokens::{self};
-use crate::{schemas::pairs, utils::app_context::AppContext};
+use crate::chain::{Chain, Subscription};
+use crate::models::factory::FactoryStatus;
+use crate::repo::{CreatedPair, FactoryRepo, PairRepo, TokenRepo};
+use crate::sync::bus::{Bus, SyncEvent};
+use crate::sync::token_metadata;
+use crate::utils::app_context::AppContext;
 
 // Event emitted by UniswapV2Factory when a new trading pair is created.
 // Contains information about the two tokens in the pair and the pair contract address.
@@ -26,44 +24,22 @@ sol! {
     );
 }
 
-sol! {
-    #[sol(rpc)]
-    "contracts/src/interfaces/IMulticall3.sol"
-}
-
-sol! {
-    #[sol(rpc)]
-    "contracts/src/interfaces/IERC20.sol"
-}
-
 /// Sync pair created events.
 /// These are emitted by `UniswapV2Factory` contracts.
 /// # Errors
-/// Returns an error if the database connection fails
+/// Never returns an error, events that fail to be stored are logged and left to the backfill
 pub async fn pair_created_events(ctx: &AppContext) -> Result<()> {
     info!("sync::pair_created_events: Starting event sync...");
 
-    let mut conn = ctx.db.get().await?;
-    let provider = &ctx.base_provider;
-
-    // Create a filter for PairCreated events starting from the latest block
-    let filter = Filter::new()
-        .event(PairCreated::SIGNATURE)
-        .from_block(BlockNumberOrTag::Latest);
-
-    // Subscribe to logs with retry logic
-    let mut stream = loop {
-        match provider.subscribe_logs(&filter).await {
-            Ok(sub) => break sub.into_stream(),
-            Err(e) => {
-                error!("sync::events: Failed to subscribe to logs: {e}");
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            }
-        }
-    };
+    // Reconnects on its own and replays the events of missed blocks
+    let filter = Filter::new().event(PairCreated::SIGNATURE);
+    let mut subscription =
+        Subscription::new(&ctx.base_provider, "pair_created_events", vec![filter]);
 
     // Process events as they arrive
-    while let Some(log) = stream.next().await {
+    loop {
+        let log = subscription.next().await;
+
         // Decode the event from the log
         let event = match PairCreated::decode_log(&log.inner, true) {
             Ok(event) => event,
@@ -73,124 +49,137 @@ pub async fn pair_created_events(ctx: &AppContext) -> Result<()> {
             }
         };
 
-        // Get or create token records for both tokens in the pair
-        let token0_id = token_id_by_address(ctx, event.token0).await?;
-        let token1_id = token_id_by_address(ctx, event.token1).await?;
-
-        // Create a new pair record in the database
-        diesel::insert_into(pairs::table)
-            .values((
-                pairs::address.eq(event.pair.to_string()),
-                pairs::token0_id.eq(token0_id),
-                pairs::token1_id.eq(token1_id),
-                pairs::is_valid.eq(true), // New pairs are valid by default
-            ))
-            .execute(&mut conn)
-            .await?;
+        // `sync::backfill` replays the events of confirmed blocks, including this one
+        if let Err(e) = store(&ctx.base_provider, &ctx.repo, &ctx.bus, &log, &event).await {
+            error!(
+                "sync::pair_created_events: Failed to store pair {} of block {:?}, leaving it to the backfill: {e}",
+                event.pair, log.block_number
+            );
+        }
     }
-
-    Ok(())
 }
 
-/// Get the token ID for a given address, creating a new token record if it doesn't exist.
+/// Store the pair of a `PairCreated` event together with its factory and tokens
 ///
-/// This function:
-/// 1. Uses multicall to efficiently fetch token information (name, symbol, decimals)
-/// 2. Validates the token by checking if all calls succeed
-/// 3. Creates or updates the token record in the database
-/// 4. Returns the token's database ID
+/// The emitting factory is looked up in `factories`. A factory we don't know is inserted as
+/// `Unvalidated` and its pairs are stored with `is_valid = false`, so anyone can emit a
+/// `PairCreated` event without us trusting it. `sync::factories` validates the factory and its
+/// pairs once it is classified as a known kind of DEX. Pairs of tokens with unknown decimals are
+/// invalid as well. Events of trusted factories upsert the pair, so
+/// events can be replayed; events of unvalidated factories never overwrite an existing pair.
 ///
-/// # Arguments
-/// * `ctx` - Application context containing database and provider
-/// * `token_address` - Ethereum address of the token
-///
-/// # Returns
-/// * `Result<i32>` - Database ID of the token
-async fn token_id_by_address(ctx: &AppContext, token_address: Address) -> Result<i32> {
-    let mut conn = ctx.db.get().await?;
-    info!("token_id_by_address: {}", token_address);
-
-    // Create multicall instance for batch calling
-    let multicall = IMulticall3::new(MULTICALL3_ADDRESS, &ctx.base_provider);
-
-    // Prepare calls to fetch token information
-    let calls = vec![
-        IMulticall3::Call3 {
-            target: token_address,
-            allowFailure: true,
-            callData: Bytes::from(IERC20::nameCall::new(()).abi_encode()),
-        },
-        IMulticall3::Call3 {
-            target: token_address,
-            allowFailure: true,
-            callData: Bytes::from(IERC20::symbolCall::new(()).abi_encode()),
-        },
-        IMulticall3::Call3 {
-            target: token_address,
-            allowFailure: true,
-            callData: Bytes::from(IERC20::decimalsCall::new(()).abi_encode()),
-        },
-    ];
-
-    // Execute all calls in a single transaction
-    let results = multicall.aggregate3(calls).call().await?;
-
-    // Process results with fallbacks for failed calls
-    let name = if results.returnData[0].success {
-        String::abi_decode(&results.returnData[0].returnData, true).unwrap_or_default()
-    } else {
-        String::new()
+/// # Errors
+/// Returns an error if fetching token information or a database query fails
+pub(crate) async fn store(
+    chain: &impl Chain,
+    repo: &(impl FactoryRepo + TokenRepo + PairRepo),
+    bus: &Bus,
+    log: &Log,
+    event: &PairCreated,
+) -> Result<()> {
+    let factory = log.address();
+    let (factory_id, status) = repo.resolve(factory, FactoryStatus::Unvalidated).await?;
+    let created_block = log.block_number.map(i64::try_from).transpose()?;
+
+    // Get or create token records for both tokens in the pair
+    let metadata = token_metadata::fetch(chain, &[event.token0, event.token1]).await?;
+    let [token0, token1] = metadata.as_slice() else {
+        eyre::bail!("Expected metadata of 2 tokens, got {}", metadata.len());
     };
+    let token0_id = repo.save_metadata(token0).await?;
+    let token1_id = repo.save_metadata(token1).await?;
+
+    if !status.is_trusted() {
+        info!(
+            "sync::pair_created_events: Pair {} of unvalidated factory {factory} stays invalid until the factory is classified",
+            event.pair
+        );
+    }
 
-    let symbol = if results.returnData[1].success {
-        String::abi_decode(&results.returnData[1].returnData, true).unwrap_or_default()
-    } else {
-        String::new()
+    let pair = CreatedPair {
+        address: event.pair,
+        factory_id,
+        token0_id,
+        token1_id,
+        created_block,
+        is_valid: status.is_trusted() && token0.is_valid() && token1.is_valid(),
     };
+    repo.store_created(&pair, status.is_trusted()).await?;
 
-    let decimals = if results.returnData[2].success {
-        i32::from(
-            U256::abi_decode(&results.returnData[2].returnData, true)
-                .unwrap_or_default()
-                .to::<u8>(),
-        )
-    } else {
-        0
-    };
+    bus.publish(SyncEvent::PairDiscovered(vec![event.pair]));
+    bus.publish(SyncEvent::TokensResolved(vec![event.pair]));
 
-    // Token is considered valid only if all calls succeed
-    let is_valid = results.returnData.iter().all(|r| r.success);
-
-    // Create new token record with fetched information
-    let new_token = NewToken::new(
-        token_address,
-        Some(symbol),
-        Some(name),
-        decimals,
-        is_valid,
-        None,
-        None,
-    );
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloy::primitives::{Address, U256};
+
+    use crate::chain::fake::FakeChain;
+    use crate::repo::memory::{FactoryRow, MemoryRepo};
+
+    use super::*;
+
+    /// Emit a `PairCreated` event from `factory` and read back its log
+    async fn created(chain: &FakeChain, factory: Address, event: &PairCreated) -> Log {
+        chain.emit(factory, event.encode_log_data());
+        chain.mine();
 
-    // Insert or update token record in database
-    let id = diesel::insert_into(tokens::table)
-        .values((
-            tokens::address.eq(token_address.to_string()),
-            tokens::name.eq(new_token.name()),
-            tokens::symbol.eq(new_token.symbol()),
-            tokens::decimals.eq(new_token.decimals()),
-            tokens::is_valid.eq(new_token.is_valid()),
-        ))
-        .on_conflict(tokens::address)
-        .do_update()
-        .set((
-            tokens::name.eq(new_token.name()),
-            tokens::symbol.eq(new_token.symbol()),
-            tokens::decimals.eq(new_token.decimals()),
-            tokens::is_valid.eq(new_token.is_valid()),
-        ))
-        .returning(tokens::id)
-        .get_result::<i32>(&mut conn)
-        .await?;
-    Ok(id)
+        let filter = Filter::new().event(PairCreated::SIGNATURE);
+        chain.logs(&filter).await.unwrap().pop().unwrap()
+    }
+
+    fn event(pair: Address) -> PairCreated {
+        PairCreated {
+            token0: Address::repeat_byte(0xa),
+            token1: Address::repeat_byte(0xb),
+            pair,
+            _3: U256::from(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_trusts_known_factories_only() {
+        let (known, unknown) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let (pair, spoofed) = (Address::repeat_byte(3), Address::repeat_byte(4));
+        let chain = FakeChain::new();
+        chain.add_token(Address::repeat_byte(0xa), "Token A", "A", 18);
+        chain.add_token(Address::repeat_byte(0xb), "Token B", "B", 6);
+        let repo = MemoryRepo::new();
+        let mut factory = FactoryRow::new(known);
+        factory.status = FactoryStatus::Synced;
+        let factory_id = repo.insert_factory(factory);
+        let bus = Bus::default();
+
+        let log = created(&chain, known, &event(pair)).await;
+        store(&chain, &repo, &bus, &log, &event(pair))
+            .await
+            .unwrap();
+
+        let stored = repo.pair(pair).unwrap();
+        assert_eq!(stored.factory_id, Some(factory_id));
+        assert_eq!(stored.created_block, Some(1));
+        assert!(stored.is_valid);
+        assert_eq!(
+            repo.token(Address::repeat_byte(0xb)).unwrap().decimals,
+            Some(6)
+        );
+
+        // An unknown factory can't overwrite a pair, and its own pairs are invalid
+        for pair in [pair, spoofed] {
+            let log = created(&chain, unknown, &event(pair)).await;
+            store(&chain, &repo, &bus, &log, &event(pair))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(repo.pair(pair).unwrap().factory_id, Some(factory_id));
+        assert!(!repo.pair(spoofed).unwrap().is_valid);
+        assert_eq!(
+            repo.factory(unknown).unwrap().status,
+            FactoryStatus::Unvalidated
+        );
+    }
 }
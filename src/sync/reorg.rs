@@ -0,0 +1,230 @@
+//! Chain reorganization handling for reserves.
+//!
+//! `sync::events` tags every reserve write with its block and keeps the hashes of the last
+//! `REORG_WINDOW` blocks, with their parent hashes, in the `blocks` table. A reorg shows up
+//! either as logs with `removed: true` or as a block that disagrees with its stored neighbours.
+//! The stored window is then walked back against the canonical chain to find the common
+//! ancestor, and every pair updated after it is rolled back: its reserves are cleared for
+//! `sync::reserves` to re-fetch, and the pools are published on `AppContext::reorgs`.
+
+use std::sync::Arc;
+
+use alloy::primitives::{Address, B256};
+use eyre::Result;
+
+use tokio::sync::broadcast;
+
+use crate::chain::Chain;
+use crate::repo::{BlockRepo, StoredBlock};
+
+/// Number of recent blocks whose hashes are kept to find the common ancestor of a reorg
+pub const REORG_WINDOW: u64 = 128;
+
+/// Pools rolled back by a reorg
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reorg {
+    /// The last block that is still canonical
+    pub ancestor: u64,
+    /// Pools whose reserves came from orphaned blocks, sorted by address
+    pub pools: Vec<Address>,
+}
+
+/// Whether a new block conflicts with the stored window
+///
+/// It conflicts if its number is stored under another hash, if the stored block before it is
+/// not its parent, or if the stored block after it has another parent.
+///
+/// # Arguments
+/// * `stored` - Stored blocks around the new block
+/// * `number` - The number of the new block
+/// * `hash` - The hash of the new block
+/// * `parent_hash` - The parent hash of the new block
+pub(crate) fn conflicts(
+    stored: &[StoredBlock],
+    number: u64,
+    hash: B256,
+    parent_hash: B256,
+) -> bool {
+    stored.iter().any(|block| {
+        (block.number == number && block.hash != hash)
+            || (block.number + 1 == number && block.hash != parent_hash)
+            || (block.number == number + 1
+                && block.parent_hash.is_some_and(|parent| parent != hash))
+    })
+}
+
+/// The highest block whose stored hash matches the canonical chain
+///
+/// # Arguments
+/// * `stored` - Stored `(number, hash)` pairs
+/// * `canonical` - Canonical `(number, hash)` pairs of (some of) the same blocks
+pub(crate) fn common_ancestor(stored: &[(u64, B256)], canonical: &[(u64, B256)]) -> Option<u64> {
+    stored
+        .iter()
+        .filter(|stored| canonical.contains(stored))
+        .map(|&(number, _)| number)
+        .max()
+}
+
+/// Check whether a new block reorganizes the stored window
+///
+/// # Errors
+/// Returns an error if the database query fails
+pub(crate) async fn is_reorg(
+    repo: &impl BlockRepo,
+    number: u64,
+    hash: B256,
+    parent_hash: B256,
+) -> Result<bool> {
+    let stored = repo.blocks(number.saturating_sub(1), number + 1).await?;
+
+    Ok(conflicts(&stored, number, hash, parent_hash))
+}
+
+/// Roll back the pairs updated by orphaned blocks
+///
+/// Walks the stored window back from the newest block until its hash matches the canonical
+/// chain. A reorg deeper than the window rolls back the whole window. Pairs updated after the
+/// ancestor lose their reserves, orphaned blocks and their reserve history are deleted and
+/// backfill checkpoints past the ancestor are rewound, all in one transaction (see
+/// `BlockRepo::rollback`). The rolled back pools are then published on `reorgs`.
+///
+/// # Errors
+/// Returns an error if a provider call or the database transaction fails
+pub(crate) async fn rollback(
+    chain: &impl Chain,
+    repo: &impl BlockRepo,
+    reorgs: &broadcast::Sender<Arc<Reorg>>,
+) -> Result<Reorg> {
+    let stored = repo.latest(REORG_WINDOW).await?;
+
+    let Some(&(oldest, _)) = stored.last() else {
+        log::warn!("sync::reorg: No stored blocks, nothing to roll back");
+        return Ok(Reorg {
+            ancestor: 0,
+            pools: Vec::new(),
+        });
+    };
+
+    // Deeper than the window, everything we know of may be orphaned
+    let ancestor = find_ancestor(chain, &stored)
+        .await?
+        .unwrap_or_else(|| oldest.saturating_sub(1));
+
+    let mut pools = repo.rollback(ancestor).await?;
+    pools.sort();
+
+    let reorg = Reorg { ancestor, pools };
+    log::warn!(
+        "sync::reorg: Rolled back to block {} with {} orphaned pairs",
+        reorg.ancestor,
+        reorg.pools.len()
+    );
+
+    // Nobody may be listening, which is fine
+    let _ = reorgs.send(Arc::new(reorg.clone()));
+
+    Ok(reorg)
+}
+
+/// Delete blocks that fell out of the window
+///
+/// # Errors
+/// Returns an error if the database query fails
+pub(crate) async fn prune(repo: &impl BlockRepo, head: u64) -> Result<()> {
+    repo.prune(head.saturating_sub(REORG_WINDOW)).await
+}
+
+/// Walk the stored blocks, newest first, against the canonical chain until a hash matches
+///
+/// Returns `None` if no stored block is canonical anymore.
+async fn find_ancestor(chain: &impl Chain, stored: &[(u64, B256)]) -> Result<Option<u64>> {
+    let mut canonical = Vec::with_capacity(stored.len());
+    for &(number, stored_hash) in stored {
+        if let Some(header) = chain.header_by_number(number).await? {
+            canonical.push((number, header.hash));
+            if header.hash == stored_hash {
+                break;
+            }
+        }
+    }
+
+    Ok(common_ancestor(stored, &canonical))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::chain::fake::FakeChain;
+
+    fn hash(byte: u8) -> B256 {
+        B256::repeat_byte(byte)
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let block = |number: u8, parent_hash| StoredBlock {
+            number: number.into(),
+            hash: hash(number),
+            parent_hash,
+        };
+        let stored = [block(9, None), block(10, Some(hash(9)))];
+
+        // Extends the stored chain
+        assert!(!conflicts(&stored[1..], 11, hash(11), hash(10)));
+        // Same block again
+        assert!(!conflicts(&stored, 10, hash(10), hash(9)));
+        // Replaces block 10
+        assert!(conflicts(&stored, 10, hash(0xaa), hash(9)));
+        // Builds on another block 10
+        assert!(conflicts(&stored[1..], 11, hash(11), hash(0xaa)));
+        // Replaces block 9, which block 10 built on
+        assert!(conflicts(&stored[1..], 9, hash(0xaa), hash(8)));
+        // Block 10 came from the backfill, without parent hash
+        assert!(!conflicts(&[block(10, None)], 9, hash(0xaa), hash(8)));
+        // Nothing stored to compare with
+        assert!(!conflicts(&[], 11, hash(11), hash(0xaa)));
+    }
+
+    #[test]
+    fn test_common_ancestor() {
+        let stored = [(12, hash(12)), (11, hash(11)), (10, hash(10))];
+
+        // Blocks 11 and 12 were replaced
+        let canonical = [(12, hash(0xcc)), (11, hash(0xbb)), (10, hash(10))];
+        assert_eq!(common_ancestor(&stored, &canonical), Some(10));
+
+        // Only the newest block was replaced
+        let canonical = [(12, hash(0xcc)), (11, hash(11))];
+        assert_eq!(common_ancestor(&stored, &canonical), Some(11));
+
+        // Deeper than the window
+        let canonical = [(12, hash(0xcc)), (11, hash(0xbb)), (10, hash(0xaa))];
+        assert_eq!(common_ancestor(&stored, &canonical), None);
+    }
+
+    #[tokio::test]
+    async fn test_find_ancestor() {
+        let chain = FakeChain::new();
+        chain.mine_to(10);
+
+        let mut stored = Vec::new();
+        for number in (6..=10).rev() {
+            let header = chain.header_by_number(number).await.unwrap().unwrap();
+            stored.push((number, header.hash));
+        }
+
+        // Nothing replaced
+        assert_eq!(find_ancestor(&chain, &stored).await.unwrap(), Some(10));
+
+        chain.reorg(3);
+        chain.mine_to(10);
+        assert_eq!(find_ancestor(&chain, &stored).await.unwrap(), Some(7));
+
+        // Deeper than the stored window
+        chain.reorg(6);
+        chain.mine_to(10);
+        assert_eq!(find_ancestor(&chain, &stored).await.unwrap(), None);
+    }
+}
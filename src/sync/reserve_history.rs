@@ -0,0 +1,124 @@
+use std::env;
+
+use eyre::Result;
+use log::{error, info};
+
+use crate::chain::Chain;
+use crate::models::reserve_history::{self, partition_start};
+use crate::repo::ReserveHistoryRepo;
+use crate::utils::app_context::AppContext;
+
+/// How often partitions are created and expired
+const MAINTENANCE_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_hours(1);
+
+/// Reserve history configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReserveHistoryConfig {
+    /// Whether Sync events are recorded at all
+    pub enabled: bool,
+    /// Number of blocks per partition, must not change once partitions exist
+    pub partition_blocks: i64,
+    /// Number of blocks kept behind the head
+    pub retention_blocks: i64,
+}
+
+impl Default for ReserveHistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            // One week of 2 second blocks
+            partition_blocks: 302_400,
+            // Four weeks
+            retention_blocks: 1_209_600,
+        }
+    }
+}
+
+impl ReserveHistoryConfig {
+    /// Load the configuration from environment variables, falling back to the defaults
+    ///
+    /// # Environment Variables:
+    /// - `FLY_RESERVE_HISTORY`: Set to `false` to stop recording Sync events
+    /// - `FLY_RESERVE_HISTORY_PARTITION_BLOCKS`: Number of blocks per partition
+    /// - `FLY_RESERVE_HISTORY_RETENTION_BLOCKS`: Number of blocks kept behind the head
+    #[must_use]
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let var = |name: &str, default: i64| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(default)
+        };
+
+        Self {
+            enabled: env::var("FLY_RESERVE_HISTORY")
+                .map_or(defaults.enabled, |value| value != "false" && value != "0"),
+            partition_blocks: var(
+                "FLY_RESERVE_HISTORY_PARTITION_BLOCKS",
+                defaults.partition_blocks,
+            ),
+            retention_blocks: var(
+                "FLY_RESERVE_HISTORY_RETENTION_BLOCKS",
+                defaults.retention_blocks,
+            ),
+        }
+    }
+}
+
+/// Maintain the partitions of `reserve_history`
+///
+/// Sync events are written to `reserve_history` by `sync::events` and `sync::backfill`. This
+/// worker creates the partitions of the current and next block ranges ahead of them, and drops
+/// partitions older than the retention.
+///
+/// # Errors
+/// Never returns an error, failures are logged and retried on the next run
+pub async fn reserve_history(ctx: &AppContext) -> Result<()> {
+    let config = ReserveHistoryConfig::from_env();
+
+    if !config.enabled {
+        info!("sync::reserve_history: Disabled");
+        return Ok(());
+    }
+
+    info!("sync::reserve_history: Starting with {config:?}");
+
+    loop {
+        if let Err(e) = maintain(&ctx.base_provider, &ctx.repo, &config).await {
+            error!("sync::reserve_history: {e}");
+        }
+
+        tokio::time::sleep(MAINTENANCE_INTERVAL).await;
+    }
+}
+
+/// Create upcoming partitions and drop expired ones
+async fn maintain(
+    chain: &impl Chain,
+    repo: &impl ReserveHistoryRepo,
+    config: &ReserveHistoryConfig,
+) -> Result<()> {
+    let head = i64::try_from(chain.block_number().await?)?;
+
+    let current = partition_start(head, config.partition_blocks);
+    for start in [current, current + config.partition_blocks] {
+        repo.create_partition(start, config.partition_blocks)
+            .await?;
+    }
+
+    let cutoff = head - config.retention_blocks;
+    let starts = repo.partitions().await?;
+    for start in reserve_history::expired_partitions(&starts, config.partition_blocks, cutoff) {
+        repo.drop_partition(start).await?;
+        info!("sync::reserve_history: Dropped partition starting at block {start}");
+    }
+
+    let deleted = repo.delete_default_before(cutoff).await?;
+    if deleted > 0 {
+        info!("sync::reserve_history: Deleted {deleted} expired rows of the default partition");
+    }
+
+    Ok(())
+}
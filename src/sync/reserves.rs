@@ -1,18 +1,9 @@
-use crate::bootstrap::fetch_reserves_by_range;
-use crate::models::pair::Pair;
-use crate::schemas::pairs;
+use crate::chain::Chain;
+use crate::repo::PairRepo;
+use crate::sync::bus::{Bus, SyncEvent, Topic};
 use crate::utils::app_context::AppContext;
 use alloy::primitives::Address;
-use bigdecimal::BigDecimal;
-use diesel::dsl::sql;
-use diesel::sql_types::{Nullable, Numeric};
-use diesel::BoolExpressionMethods;
-use diesel::ExpressionMethods;
-use diesel::QueryDsl;
-use diesel::SelectableHelper;
-use diesel_async::RunQueryDsl;
 use eyre::Result;
-use std::str::FromStr;
 
 /// Update pairs with missing reserves.
 /// This runs as a worker thread to continuously update pairs.
@@ -28,11 +19,13 @@ use std::str::FromStr;
 /// * If contract calls fail
 /// * If database operations fail
 pub async fn reserves(ctx: &AppContext) -> Result<()> {
+    let mut events = ctx.bus.subscribe(&[Topic::PairDiscovered]);
+
     loop {
-        let pairs_updated = sync(ctx, 50).await?;
+        let pairs_updated = sync(&ctx.base_provider, &ctx.repo, &ctx.bus, 50).await?;
 
         if pairs_updated == 0 {
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            events.wait().await;
         }
     }
 }
@@ -47,16 +40,13 @@ pub async fn reserves(ctx: &AppContext) -> Result<()> {
 ///
 /// # Returns
 /// Returns the number of pairs synced
-async fn sync(ctx: &AppContext, batch_size: i16) -> Result<usize> {
-    let mut conn = ctx.db.get().await?;
-
-    // Query for pairs with missing reserves using Diesel
-    let pairs_missing_reserves: Vec<Pair> = pairs::table
-        .filter(pairs::reserve0.is_null().or(pairs::reserve1.is_null()))
-        .select(Pair::as_select())
-        .limit(i64::from(batch_size))
-        .load::<Pair>(&mut conn)
-        .await?;
+async fn sync(
+    chain: &impl Chain,
+    repo: &impl PairRepo,
+    bus: &Bus,
+    batch_size: i16,
+) -> Result<usize> {
+    let pairs_missing_reserves = repo.without_reserves(i64::from(batch_size)).await?;
 
     log::info!(
         "sync::reserves: Found {} pairs with missing reserves",
@@ -69,7 +59,7 @@ async fn sync(ctx: &AppContext, batch_size: i16) -> Result<usize> {
     }
 
     // Get addresses of pairs with missing reserves
-    let pair_addresses: Vec<Address> = pairs_missing_reserves
+    let mut pair_addresses: Vec<Address> = pairs_missing_reserves
         .iter()
         .map(crate::models::pair::Pair::address)
         .collect();
@@ -79,12 +69,22 @@ async fn sync(ctx: &AppContext, batch_size: i16) -> Result<usize> {
         pair_addresses.iter().take(5).collect::<Vec<_>>()
     );
 
+    // Read the reserves at a known block, so they never overwrite those of a newer Sync
+    let number = chain.block_number().await?;
+    let Some(block) = chain.header_by_number(number).await? else {
+        eyre::bail!("sync::reserves: Block {number} not found");
+    };
+
     // Fetch reserves for these pairs
     log::info!(
-        "sync::reserves: Fetching reserves for {} pairs...",
-        pair_addresses.len()
+        "sync::reserves: Fetching reserves for {} pairs at block {}...",
+        pair_addresses.len(),
+        block.number
     );
-    let reserves = match fetch_reserves_by_range(ctx, pair_addresses.clone()).await {
+    let reserves = match chain
+        .reserves(pair_addresses.clone(), Some(block.number))
+        .await
+    {
         Ok(reserves) => {
             log::info!(
                 "sync::reserves: Successfully fetched reserves for {} pairs",
@@ -99,7 +99,7 @@ async fn sync(ctx: &AppContext, batch_size: i16) -> Result<usize> {
     };
 
     // Update pairs with reserves
-    let mut updated_count = 0;
+    let mut updates = Vec::with_capacity(reserves.len());
     for (index, pair) in pairs_missing_reserves.iter().enumerate() {
         if index >= reserves.len() {
             log::error!(
@@ -111,32 +111,61 @@ async fn sync(ctx: &AppContext, batch_size: i16) -> Result<usize> {
         }
 
         let reserve = &reserves[index];
-        let reserve0_val = BigDecimal::from_str(&reserve.reserve0.to_string())
-            .unwrap_or_else(|_| BigDecimal::from(0));
-        let reserve1_val = BigDecimal::from_str(&reserve.reserve1.to_string())
-            .unwrap_or_else(|_| BigDecimal::from(0));
-
-        // Update pair in database using Diesel
-        diesel::update(pairs::table.find(pair.id()))
-            .set((
-                pairs::reserve0.eq(sql::<Nullable<Numeric>>(&reserve0_val.to_string())),
-                pairs::reserve1.eq(sql::<Nullable<Numeric>>(&reserve1_val.to_string())),
-            ))
-            .execute(&mut conn)
-            .await?;
-
         log::info!(
-            "sync::reserves: Updated pair {} with reserve0: {}, reserve1: {}",
+            "sync::reserves: Updating pair {} with reserve0: {}, reserve1: {}",
             pair.address(),
-            reserve0_val,
-            reserve1_val,
+            reserve.reserve0,
+            reserve.reserve1,
         );
-        updated_count += 1;
+        updates.push((pair.address(), reserve.reserve0, reserve.reserve1));
     }
 
+    repo.set_reserves(block.number, block.hash, &updates)
+        .await?;
+    let updated_count = updates.len();
+
     log::info!(
         "sync::reserves: Updated {} pairs with reserves",
         updated_count
     );
+
+    pair_addresses.truncate(updated_count);
+    bus.publish(SyncEvent::ReservesUpdated(pair_addresses));
+
     Ok(updated_count)
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::chain::fake::FakeChain;
+    use crate::repo::memory::{MemoryRepo, PairRow};
+    use alloy::primitives::U256;
+    use bigdecimal::BigDecimal;
+
+    #[tokio::test]
+    async fn test_sync_fills_missing_reserves() {
+        let chain = FakeChain::new();
+        let repo = MemoryRepo::new();
+        let bus = Bus::default();
+        let mut events = bus.subscribe(&[Topic::ReservesUpdated]);
+        let pair = Address::repeat_byte(1);
+        repo.insert_pair(PairRow::new(pair));
+        chain.set_reserves(pair, U256::from(3), U256::from(4));
+        chain.mine_to(7);
+
+        assert_eq!(sync(&chain, &repo, &bus, 50).await.unwrap(), 1);
+        assert_eq!(sync(&chain, &repo, &bus, 50).await.unwrap(), 0);
+
+        let row = repo.pair(pair).unwrap();
+        assert_eq!(row.reserve0, Some(BigDecimal::from(3)));
+        assert_eq!(row.reserve1, Some(BigDecimal::from(4)));
+        assert_eq!(row.block_number, Some(7));
+        let event = events.next(tokio::time::Duration::from_millis(10)).await;
+        assert_eq!(
+            event.as_deref(),
+            Some(&SyncEvent::ReservesUpdated(vec![pair]))
+        );
+    }
+}
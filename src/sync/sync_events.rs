@@ -1,15 +1,23 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
 use alloy::{
-    eips::BlockNumberOrTag, providers::Provider, rpc::types::Filter, sol, sol_types::SolEvent,
+    primitives::{B256, U256},
+    rpc::types::{Filter, Log},
+    sol,
+    sol_types::SolEvent,
 };
 
-use diesel::dsl::{exists, sql};
-use diesel::sql_types::{Nullable, Numeric};
-use diesel::{ExpressionMethods, QueryDsl};
-use diesel_async::RunQueryDsl;
 use eyre::Result;
-use futures::StreamExt;
+use tokio::sync::broadcast;
 
-use crate::schemas::pairs;
+use super::bus::{Bus, SyncEvent};
+use super::reorg::{self, Reorg};
+use super::reserve_history::ReserveHistoryConfig;
+use crate::chain::subscription::MAX_GAP_BLOCKS;
+use crate::chain::{Chain, Subscription};
+use crate::models::block::{BlockUpdate, PoolUpdate, SyncLog};
+use crate::repo::{BlockRepo, PairRepo, SyncStateRepo};
 use crate::utils::app_context::AppContext;
 
 sol! {
@@ -19,103 +27,667 @@ sol! {
     );
 }
 
-/// Subscribes to sync events from the network
+/// The name of the stream in the `sync_state` table and of its subscription
+pub(crate) const STREAM: &str = "sync_events";
+
+/// How long to wait for more logs of the current block before committing it.
+/// Logs of a block arrive in a burst, so the block is usually complete once the stream goes quiet.
+/// Late logs, such as those replayed after a reconnect, are committed as another part of the
+/// block and only move reserves forward.
+const FLUSH_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_millis(500);
+
+/// How long to wait before retrying a failed commit or rollback
+const RETRY_DELAY: tokio::time::Duration = tokio::time::Duration::from_secs(1);
+
+/// Where committed blocks and rollbacks are published
+#[derive(Debug, Clone, Copy)]
+struct Channels<'a> {
+    /// Pools updated by each committed block
+    blocks: &'a broadcast::Sender<Arc<BlockUpdate>>,
+    /// Pools rolled back by each reorg
+    reorgs: &'a broadcast::Sender<Arc<Reorg>>,
+    /// Events between sync workers
+    bus: &'a Bus,
+}
+
+impl<'a> Channels<'a> {
+    /// The channels of the application
+    const fn new(ctx: &'a AppContext) -> Self {
+        Self {
+            blocks: &ctx.blocks,
+            reorgs: &ctx.reorgs,
+            bus: &ctx.bus,
+        }
+    }
+}
+
+/// Decode a Sync log with its block number and hash
 ///
-/// Listens for Sync events from Uniswap V2 pairs and processes reserve updates
+/// # Errors
+/// Returns an error if the log is pending or is not a Sync event
+fn decode(log: &Log) -> Result<(u64, B256, SyncLog)> {
+    let (Some(number), Some(hash), Some(log_index), Some(tx_hash)) = (
+        log.block_number,
+        log.block_hash,
+        log.log_index,
+        log.transaction_hash,
+    ) else {
+        eyre::bail!("Sync event is pending");
+    };
+
+    let sync = Sync::decode_log(&log.inner, true)?;
+
+    Ok((
+        number,
+        hash,
+        SyncLog {
+            address: log.address(),
+            log_index,
+            tx_hash,
+            reserve0: U256::from(sync.reserve0),
+            reserve1: U256::from(sync.reserve1),
+        },
+    ))
+}
+
+/// Sync logs of one block, of which only the last Sync of each pair is committed
+#[derive(Debug)]
+struct BlockBuffer {
+    /// The block number
+    number: u64,
+    /// The block hash
+    hash: B256,
+    /// Every Sync log of the block, in arrival order
+    syncs: Vec<SyncLog>,
+}
+
+impl BlockBuffer {
+    /// Create an empty buffer for a block
+    fn new(number: u64, hash: B256) -> Self {
+        Self {
+            number,
+            hash,
+            syncs: Vec::new(),
+        }
+    }
+
+    /// Record a Sync log
+    fn push(&mut self, sync: SyncLog) {
+        self.syncs.push(sync);
+    }
+
+    /// The block update to publish once the buffer is committed
+    ///
+    /// Logs may arrive out of order, so the reserves of a pair are taken from its Sync log with
+    /// the highest index in the block.
+    fn update(&self) -> BlockUpdate {
+        let mut syncs = self.syncs.clone();
+        syncs.sort_by_key(|sync| sync.log_index);
+
+        let latest = syncs
+            .iter()
+            .map(|sync| {
+                let pool = PoolUpdate {
+                    address: sync.address,
+                    reserve0: sync.reserve0,
+                    reserve1: sync.reserve1,
+                    log_index: sync.log_index,
+                };
+                (sync.address, pool)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut pools = latest.into_values().collect::<Vec<_>>();
+        pools.sort_by_key(|pool| pool.address);
+
+        BlockUpdate {
+            number: self.number,
+            hash: self.hash,
+            parent_hash: None,
+            pools,
+            syncs,
+        }
+    }
+}
+
+/// Switch the buffer to the block of a new log
 ///
 /// # Returns
-/// * `Result<()>` - Ok(()) on successful subscription
+///
+/// The buffered block if the log belongs to another block number, it is complete and has to be
+/// committed. A buffered block with the same number but another hash was reorged out before it
+/// was committed, so its logs are dropped.
+fn advance(buffer: &mut Option<BlockBuffer>, number: u64, hash: B256) -> Option<BlockBuffer> {
+    let block = buffer.take_if(|block| block.number != number || block.hash != hash)?;
+
+    if block.number == number {
+        log::warn!(
+            "sync::events: Block {number} was replaced by {hash}, dropping {} logs of {}",
+            block.syncs.len(),
+            block.hash
+        );
+        return None;
+    }
+
+    Some(block)
+}
+
+/// Subscribes to sync events from the network
+///
+/// Listens for Sync events from Uniswap V2 pairs and buffers them per block. Once a block is
+/// complete, the last reserves of every pair are written in a single transaction together with
+/// the block, then the updated pools are published on `AppContext::blocks`.
+///
+/// Removed logs, or a block that does not build on the stored one, trigger a rollback of the
+/// pairs updated by orphaned blocks (see `sync::reorg`).
+///
+/// The subscription reconnects on its own when the WebSocket drops or stalls, replaying the
+/// Sync events of the blocks it missed (see `chain::subscription`). A commit or rollback that
+/// fails is retried after `RETRY_DELAY` before reading on, so the block is not lost.
 ///
 /// # Errors
-/// * If WebSocket connection cannot be established
-/// * If subscription request fails
-/// * If message parsing fails
-/// * If network connection is lost
-/// * If received message format is invalid
-/// * If WebSocket stream terminates unexpectedly
-/// * If message sending fails
+/// Never returns an error, failures are logged and retried
 pub async fn events(ctx: &AppContext) -> Result<()> {
-    let provider = &ctx.base_provider;
-    let filter = Filter::new()
-        .event(Sync::SIGNATURE)
-        .from_block(BlockNumberOrTag::Latest);
-
-    // Get a database connection
-    let mut conn = loop {
-        match ctx.db.get().await {
-            Ok(conn) => break conn,
-            Err(e) => {
-                log::error!("sync::events: Failed to get database connection: {e}");
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    let chain = &ctx.base_provider;
+    let repo = &ctx.repo;
+    let channels = Channels::new(ctx);
+    let filter = Filter::new().event(Sync::SIGNATURE);
+
+    // Subscribe to sync events
+    let mut subscription = Subscription::new(chain, STREAM, vec![filter]);
+
+    let record_history = ReserveHistoryConfig::from_env().enabled;
+    let mut buffer: Option<BlockBuffer> = None;
+    // A complete block, committed before the next log is read
+    let mut complete: Option<BlockBuffer> = None;
+    // The last block committed from the stream
+    let mut committed: Option<u64> = None;
+    // Removed logs of a reorg arrive in a burst, one rollback handles them all
+    let mut rolled_back = false;
+    let mut needs_rollback = false;
+
+    // Process sync events
+    loop {
+        if needs_rollback {
+            if let Err(e) = reorg::rollback(chain, repo, channels.reorgs).await {
+                log::error!("sync::events: Failed to roll back: {e}");
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
             }
+            needs_rollback = false;
         }
-    };
 
-    // Subscribe to sync events
-    let mut stream = loop {
-        match provider.subscribe_logs(&filter).await {
-            Ok(sub) => break sub.into_stream(),
-            Err(e) => {
-                log::error!("sync::events: Failed to subscribe to logs: {e}");
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        if let Some(block) = complete.take() {
+            let result = commit(chain, repo, channels, &block, committed, record_history).await;
+            if let Err(e) = result {
+                log::error!("sync::events: Failed to commit block {}: {e}", block.number);
+                complete = Some(block);
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
             }
+            committed = Some(block.number);
         }
-    };
 
-    // Process sync events
-    while let Some(log) = stream.next().await {
-        // Process sync event
-        let sync = match Sync::decode_log(&log.inner, true) {
-            Ok(sync) => sync,
+        let Ok(log) = tokio::time::timeout(FLUSH_TIMEOUT, subscription.next()).await else {
+            // The stream went quiet, the block is complete
+            complete = buffer.take();
+            continue;
+        };
+
+        let (Some(number), Some(hash)) = (log.block_number, log.block_hash) else {
+            log::warn!("sync::events: Skipping pending sync event without block");
+            continue;
+        };
+
+        if log.removed {
+            log::warn!("sync::events: Sync event of block {number} ({hash}) was removed");
+
+            // The buffered block may be the orphaned one
+            if buffer.as_ref().is_some_and(|block| block.number >= number) {
+                buffer = None;
+            }
+
+            needs_rollback |= !rolled_back;
+            rolled_back = true;
+            continue;
+        }
+        rolled_back = false;
+
+        // A log of a new block means the previous block is complete
+        complete = advance(&mut buffer, number, hash);
+
+        let sync = match decode(&log) {
+            Ok((_, _, sync)) => sync,
             Err(e) => {
                 log::error!("sync::events: Failed to decode sync event: {e}");
                 continue;
             }
         };
 
-        let address = log.address();
-
-        // Check if pair exists
-        let pair_exists = diesel::select(exists(
-            pairs::table.filter(pairs::address.eq(address.to_string())),
-        ))
-        .get_result::<bool>(&mut conn)
-        .await?;
-
-        if pair_exists {
-            // Update pair reserves
-            diesel::update(pairs::table.filter(pairs::address.eq(address.to_string())))
-                .set((
-                    pairs::reserve0.eq(sql::<Nullable<Numeric>>(&sync.reserve0.to_string())),
-                    pairs::reserve1.eq(sql::<Nullable<Numeric>>(&sync.reserve1.to_string())),
-                ))
-                .execute(&mut conn)
-                .await?;
-            log::info!(
-                "sync::events: Updated {} pair with {}/{} reserves",
-                address,
-                sync.reserve0,
-                sync.reserve1
-            );
-        } else {
-            // Insert new pair with reserves
-            diesel::insert_into(pairs::table)
-                .values((
-                    pairs::address.eq(address.to_string()),
-                    pairs::reserve0.eq(sql::<Nullable<Numeric>>(&sync.reserve0.to_string())),
-                    pairs::reserve1.eq(sql::<Nullable<Numeric>>(&sync.reserve1.to_string())),
-                ))
-                .execute(&mut conn)
-                .await?;
-
-            log::info!(
-                "sync::events: Inserted new {} pair with {}/{} reserves",
-                address,
-                sync.reserve0,
-                sync.reserve1
-            );
-        }
+        buffer
+            .get_or_insert_with(|| BlockBuffer::new(number, hash))
+            .push(sync);
+    }
+}
+
+/// Write a block and the reserves of its pairs, then publish it
+///
+/// With `record_history`, the Sync logs of the block are also appended to `reserve_history`.
+/// `previous` is the last block committed from the same stream, see `save_checkpoint`.
+///
+/// # Errors
+/// Returns an error if a provider call or a database query fails
+async fn commit(
+    chain: &impl Chain,
+    repo: &(impl BlockRepo + PairRepo + SyncStateRepo),
+    channels: Channels<'_>,
+    block: &BlockBuffer,
+    previous: Option<u64>,
+    record_history: bool,
+) -> Result<()> {
+    let mut update = block.update();
+
+    let Some(header) = chain.header_by_hash(update.hash).await? else {
+        log::warn!(
+            "sync::events: Block {} ({}) is no longer canonical, dropping it",
+            update.number,
+            update.hash
+        );
+        reorg::rollback(chain, repo, channels.reorgs).await?;
+        return Ok(());
+    };
+    let parent_hash = header.parent_hash;
+    update.parent_hash = Some(parent_hash);
+
+    if reorg::is_reorg(repo, update.number, update.hash, parent_hash).await? {
+        log::warn!(
+            "sync::events: Block {} ({}) does not build on the stored chain",
+            update.number,
+            update.hash
+        );
+        reorg::rollback(chain, repo, channels.reorgs).await?;
     }
 
+    let updates = std::slice::from_ref(&update);
+    let number = update.number;
+
+    repo.store_reserves(updates, record_history).await?;
+    // Blocks past the reorg window are no longer needed, pruning them again is harmless
+    reorg::prune(repo, number).await?;
+    save_checkpoint(repo, previous, number).await?;
+
+    log::info!(
+        "sync::events: Committed block {} with {} updated pairs",
+        update.number,
+        update.pools.len()
+    );
+
+    channels.bus.publish(SyncEvent::ReservesUpdated(
+        update.pools.iter().map(|pool| pool.address).collect(),
+    ));
+
+    // Nobody may be listening, which is fine
+    let _ = channels.blocks.send(Arc::new(update));
+
     Ok(())
 }
+
+/// Move the checkpoint of the stream to a committed block
+///
+/// The stream delivered every log between `previous` and `number`, unless it skipped a gap of
+/// more than `MAX_GAP_BLOCKS` after a disconnect. So the checkpoint only follows if it already
+/// reached `previous`, and `sync::backfill` replays whatever lies before, e.g. the blocks
+/// missed while the bot was down.
+///
+/// # Errors
+/// Returns an error if a database query fails
+async fn save_checkpoint(
+    repo: &impl SyncStateRepo,
+    previous: Option<u64>,
+    number: u64,
+) -> Result<()> {
+    let Some(previous) =
+        previous.filter(|&previous| previous < number && number - previous <= MAX_GAP_BLOCKS)
+    else {
+        return Ok(());
+    };
+
+    let previous = i64::try_from(previous)?;
+    let checkpoint = repo.last_block(STREAM).await?;
+    if checkpoint.is_some_and(|checkpoint| checkpoint >= previous) {
+        repo.save_checkpoint(STREAM, i64::try_from(number)?).await?;
+    }
+
+    Ok(())
+}
+
+/// Group Sync logs into block updates, ordered by block number
+///
+/// Logs without a block (pending) or that fail to decode are skipped. When logs of a block
+/// number come with different hashes, only the logs of the last hash seen are kept.
+pub(crate) fn block_updates(logs: &[Log]) -> Vec<BlockUpdate> {
+    let mut blocks: BTreeMap<u64, BlockBuffer> = BTreeMap::new();
+
+    for log in logs {
+        let Ok((number, hash, sync)) = decode(log) else {
+            continue;
+        };
+
+        let block = blocks
+            .entry(number)
+            .or_insert_with(|| BlockBuffer::new(number, hash));
+        if block.hash != hash {
+            *block = BlockBuffer::new(number, hash);
+        }
+        block.push(sync);
+    }
+
+    blocks.values().map(BlockBuffer::update).collect()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use alloy::primitives::aliases::U112;
+    use alloy::primitives::Address;
+
+    use super::*;
+    use crate::arb::test_helpers::address_from_str;
+    use crate::chain::fake::FakeChain;
+    use crate::chain::BlockHeader;
+    use crate::repo::memory::MemoryRepo;
+    use crate::repo::StoredBlock;
+
+    fn sync(pair: &str, log_index: u64, reserve: u64) -> SyncLog {
+        SyncLog {
+            address: address_from_str(pair),
+            log_index,
+            tx_hash: B256::repeat_byte(0xee),
+            reserve0: U256::from(reserve),
+            reserve1: U256::from(reserve),
+        }
+    }
+
+    #[test]
+    fn test_latest_sync_by_log_index() {
+        let mut block = BlockBuffer::new(7, B256::repeat_byte(1));
+        block.push(sync("F1", 5, 30));
+        block.push(sync("F2", 2, 20));
+        block.push(sync("F1", 1, 10));
+
+        let update = block.update();
+
+        assert_eq!(update.number, 7);
+        assert_eq!(update.pools.len(), 2);
+        assert_eq!(
+            update
+                .syncs
+                .iter()
+                .map(|sync| sync.log_index)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 5]
+        );
+        assert!(update.pools.contains(&PoolUpdate {
+            address: address_from_str("F1"),
+            reserve0: U256::from(30),
+            reserve1: U256::from(30),
+            log_index: 5,
+        }));
+    }
+
+    #[test]
+    fn test_advance_commits_previous_block() {
+        let mut buffer = Some(BlockBuffer::new(7, B256::repeat_byte(1)));
+
+        assert!(advance(&mut buffer, 7, B256::repeat_byte(1)).is_none());
+        assert!(buffer.is_some());
+
+        let block = advance(&mut buffer, 8, B256::repeat_byte(2)).unwrap();
+        assert_eq!(block.number, 7);
+        assert!(buffer.is_none());
+    }
+
+    #[test]
+    fn test_advance_drops_replaced_block() {
+        let mut buffer = Some(BlockBuffer::new(7, B256::repeat_byte(1)));
+        buffer.as_mut().unwrap().push(sync("F1", 0, 10));
+
+        assert!(advance(&mut buffer, 7, B256::repeat_byte(2)).is_none());
+        assert!(buffer.is_none());
+    }
+
+    fn sync_data(reserve0: u64, reserve1: u64) -> alloy::primitives::LogData {
+        Sync {
+            reserve0: U112::from(reserve0),
+            reserve1: U112::from(reserve1),
+        }
+        .encode_log_data()
+    }
+
+    #[tokio::test]
+    async fn test_block_updates_keep_last_sync_per_pair() {
+        let chain = FakeChain::new();
+        let (a, b) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb));
+
+        chain.emit(b, sync_data(1, 1));
+        chain.emit(a, sync_data(2, 2));
+        chain.emit(b, sync_data(3, 4));
+        let first = chain.mine();
+        chain.emit(a, sync_data(5, 6));
+        let second = chain.mine();
+
+        let filter = Filter::new().event(Sync::SIGNATURE);
+        let updates = block_updates(&chain.logs(&filter).await.unwrap());
+
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].number, first.number);
+        assert_eq!(updates[0].hash, first.hash);
+        assert_eq!(updates[0].syncs.len(), 3);
+        assert_eq!(
+            updates[0].pools,
+            vec![
+                PoolUpdate {
+                    address: a,
+                    reserve0: U256::from(2),
+                    reserve1: U256::from(2),
+                    log_index: 1,
+                },
+                PoolUpdate {
+                    address: b,
+                    reserve0: U256::from(3),
+                    reserve1: U256::from(4),
+                    log_index: 2,
+                },
+            ]
+        );
+        assert_eq!(updates[1].number, second.number);
+        assert_eq!(updates[1].pools[0].reserve1, U256::from(6));
+    }
+
+    #[tokio::test]
+    async fn test_save_checkpoint_follows_contiguous_blocks() {
+        let repo = MemoryRepo::new();
+
+        // Nothing to follow before the backfill saved a checkpoint
+        save_checkpoint(&repo, Some(9), 10).await.unwrap();
+        assert_eq!(repo.last_block(STREAM).await.unwrap(), None);
+
+        // The backfill has not reached the previous block yet
+        repo.save_checkpoint(STREAM, 8).await.unwrap();
+        save_checkpoint(&repo, Some(9), 10).await.unwrap();
+        assert_eq!(repo.last_block(STREAM).await.unwrap(), Some(8));
+
+        // The first block of the stream
+        repo.save_checkpoint(STREAM, 9).await.unwrap();
+        save_checkpoint(&repo, None, 10).await.unwrap();
+        assert_eq!(repo.last_block(STREAM).await.unwrap(), Some(9));
+
+        save_checkpoint(&repo, Some(9), 10).await.unwrap();
+        assert_eq!(repo.last_block(STREAM).await.unwrap(), Some(10));
+
+        // The subscription may have skipped blocks
+        save_checkpoint(&repo, Some(10), 11 + MAX_GAP_BLOCKS)
+            .await
+            .unwrap();
+        assert_eq!(repo.last_block(STREAM).await.unwrap(), Some(10));
+    }
+
+    /// The channels a test commits to
+    struct TestChannels {
+        blocks: broadcast::Sender<Arc<BlockUpdate>>,
+        reorgs: broadcast::Sender<Arc<Reorg>>,
+        bus: Bus,
+    }
+
+    impl TestChannels {
+        fn new() -> Self {
+            Self {
+                blocks: broadcast::channel(16).0,
+                reorgs: broadcast::channel(16).0,
+                bus: Bus::default(),
+            }
+        }
+
+        const fn channels(&self) -> Channels<'_> {
+            Channels {
+                blocks: &self.blocks,
+                reorgs: &self.reorgs,
+                bus: &self.bus,
+            }
+        }
+    }
+
+    /// Buffer the Sync logs of a mined block, like the stream does
+    async fn buffered(chain: &FakeChain, header: BlockHeader) -> BlockBuffer {
+        let filter = Filter::new()
+            .event(Sync::SIGNATURE)
+            .from_block(header.number)
+            .to_block(header.number);
+
+        let mut block = BlockBuffer::new(header.number, header.hash);
+        for log in chain.logs(&filter).await.unwrap() {
+            block.push(decode(&log).unwrap().2);
+        }
+        block
+    }
+
+    #[tokio::test]
+    async fn test_commit_stores_and_publishes_block() {
+        let chain = FakeChain::new();
+        let repo = MemoryRepo::new();
+        let test = TestChannels::new();
+        let mut blocks = test.blocks.subscribe();
+        let pair = Address::repeat_byte(0xa);
+
+        chain.mine_to(9);
+        repo.save_checkpoint(STREAM, 9).await.unwrap();
+        chain.emit(pair, sync_data(10, 20));
+        let header = chain.mine();
+
+        let block = buffered(&chain, header).await;
+        commit(&chain, &repo, test.channels(), &block, Some(9), false)
+            .await
+            .unwrap();
+
+        let row = repo.pair(pair).unwrap();
+        assert_eq!(row.reserve1, Some(20.into()));
+        assert_eq!(row.block_number, Some(10));
+        assert_eq!(
+            repo.blocks(10, 10).await.unwrap(),
+            vec![StoredBlock {
+                number: 10,
+                hash: header.hash,
+                parent_hash: Some(header.parent_hash),
+            }]
+        );
+        assert_eq!(repo.last_block(STREAM).await.unwrap(), Some(10));
+
+        let update = blocks.try_recv().unwrap();
+        assert_eq!(update.number, 10);
+        assert_eq!(update.pools[0].address, pair);
+    }
+
+    #[tokio::test]
+    async fn test_commit_rolls_back_replaced_block() {
+        let chain = FakeChain::new();
+        let repo = MemoryRepo::new();
+        let test = TestChannels::new();
+        let mut reorgs = test.reorgs.subscribe();
+        let (a, b) = (Address::repeat_byte(0xa), Address::repeat_byte(0xb));
+
+        chain.mine_to(5);
+        chain.emit(a, sync_data(1, 1));
+        let first = chain.mine();
+        commit(
+            &chain,
+            &repo,
+            test.channels(),
+            &buffered(&chain, first).await,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        // Block 6 is replaced by an empty one, block 7 builds on the replacement
+        chain.reorg(1);
+        chain.mine();
+        chain.emit(b, sync_data(2, 2));
+        let second = chain.mine();
+        commit(
+            &chain,
+            &repo,
+            test.channels(),
+            &buffered(&chain, second).await,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let reorg = reorgs.try_recv().unwrap();
+        assert_eq!(reorg.ancestor, 5);
+        assert_eq!(reorg.pools, vec![a]);
+        assert_eq!(repo.pair(a).unwrap().reserve0, None);
+        assert_eq!(repo.pair(b).unwrap().block_number, Some(7));
+        assert_eq!(repo.latest(10).await.unwrap(), vec![(7, second.hash)]);
+    }
+
+    #[tokio::test]
+    async fn test_commit_rolls_back_orphaned_block() {
+        let chain = FakeChain::new();
+        let repo = MemoryRepo::new();
+        let test = TestChannels::new();
+        let mut reorgs = test.reorgs.subscribe();
+        let pair = Address::repeat_byte(0xa);
+
+        chain.mine_to(5);
+        chain.emit(pair, sync_data(1, 1));
+        let first = chain.mine();
+        commit(
+            &chain,
+            &repo,
+            test.channels(),
+            &buffered(&chain, first).await,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+        // Blocks 6 and 7 are orphaned while block 7 is buffered
+        chain.emit(pair, sync_data(2, 2));
+        let orphaned = chain.mine();
+        let block = buffered(&chain, orphaned).await;
+        chain.reorg(2);
+        chain.mine_to(7);
+
+        commit(&chain, &repo, test.channels(), &block, Some(6), false)
+            .await
+            .unwrap();
+
+        assert_eq!(reorgs.try_recv().unwrap().pools, vec![pair]);
+        assert_eq!(repo.pair(pair).unwrap().reserve0, None);
+        assert!(repo.latest(10).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+//! ERC20 metadata decoding.
+//!
+//! `name()` and `symbol()` return a `string` in the standard, but early tokens like MKR return
+//! a `bytes32`, and some contracts revert or return garbage. Each field is decoded on its own and
+//! stored with a `MetadataStatus`, so a broken `name()` does not cost a token its `decimals`.
+//! Unknown decimals are stored as NULL rather than 0, which `sync::usd` and
+//! `sync::exchange_rates` skip.
+
+use alloy::primitives::{Address, Bytes, U256};
+use alloy::sol;
+use alloy::sol_types::{SolCall, SolValue};
+use eyre::Result;
+
+use crate::chain::{Call, CallResult, Chain};
+use crate::models::token::{sanitize_string, MetadataStatus, TokenMetadata};
+
+sol! {
+    #[sol(rpc)]
+    "contracts/src/interfaces/IERC20.sol"
+}
+
+impl TokenMetadata {
+    /// The `name()`, `symbol()` and `decimals()` calls of a token, in the order `decode` expects
+    #[must_use]
+    pub fn calls(address: Address) -> [Call; 3] {
+        [
+            Call::new(address, IERC20::nameCall::new(()).abi_encode()),
+            Call::new(address, IERC20::symbolCall::new(()).abi_encode()),
+            Call::new(address, IERC20::decimalsCall::new(()).abi_encode()),
+        ]
+    }
+
+    /// Decode the results of `calls`, missing results count as reverted
+    #[must_use]
+    pub fn decode(address: Address, results: &[CallResult]) -> Self {
+        let (name, name_status) = decode_text(results.first());
+        let (symbol, symbol_status) = decode_text(results.get(1));
+        let (decimals, decimals_status) = decode_decimals(results.get(2));
+
+        Self {
+            address,
+            name,
+            name_status,
+            symbol,
+            symbol_status,
+            decimals,
+            decimals_status,
+        }
+    }
+
+    /// Whether the token can be priced, which takes known decimals
+    #[must_use]
+    pub const fn is_valid(&self) -> bool {
+        self.decimals.is_some()
+    }
+}
+
+/// Fetch the metadata of tokens in one multicall
+///
+/// # Errors
+/// Returns an error if the multicall fails
+pub async fn fetch(chain: &impl Chain, tokens: &[Address]) -> Result<Vec<TokenMetadata>> {
+    let calls = tokens
+        .iter()
+        .flat_map(|token| TokenMetadata::calls(*token))
+        .collect();
+    let results = chain.aggregate3(calls, None).await?;
+
+    Ok(tokens
+        .iter()
+        .zip(results.chunks(3))
+        .map(|(token, results)| TokenMetadata::decode(*token, results))
+        .collect())
+}
+
+/// Decode the result of `name()` or `symbol()`
+fn decode_text(result: Option<&CallResult>) -> (Option<String>, MetadataStatus) {
+    let Some(data) = returned(result) else {
+        return (None, MetadataStatus::Reverted);
+    };
+
+    // A dynamic string takes at least an offset and a length, so one word is a bytes32
+    if data.len() == 32 {
+        let end = data
+            .iter()
+            .rposition(|byte| *byte != 0)
+            .map_or(0, |i| i + 1);
+        return match std::str::from_utf8(&data[..end]) {
+            Ok(text) if !text.is_empty() => (Some(sanitize_string(text)), MetadataStatus::Bytes32),
+            _ => (None, MetadataStatus::Malformed),
+        };
+    }
+
+    match Bytes::abi_decode(data, false) {
+        Ok(bytes) => {
+            let text = String::from_utf8_lossy(&bytes);
+            (Some(sanitize_string(&text)), MetadataStatus::Ok)
+        }
+        Err(_) => (None, MetadataStatus::Malformed),
+    }
+}
+
+/// Decode the result of `decimals()`, which must fit in a `uint8`
+fn decode_decimals(result: Option<&CallResult>) -> (Option<u8>, MetadataStatus) {
+    let Some(data) = returned(result) else {
+        return (None, MetadataStatus::Reverted);
+    };
+
+    let Some(word) = data.get(..32) else {
+        return (None, MetadataStatus::Malformed);
+    };
+
+    match u8::try_from(U256::from_be_slice(word)) {
+        Ok(decimals) => (Some(decimals), MetadataStatus::Ok),
+        Err(_) => (None, MetadataStatus::Malformed),
+    }
+}
+
+/// The return data of a successful call
+///
+/// Calls to addresses without code succeed with no data, which counts as not implemented.
+fn returned(result: Option<&CallResult>) -> Option<&Bytes> {
+    result
+        .filter(|result| result.success && !result.return_data.is_empty())
+        .map(|result| &result.return_data)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::chain::fake::FakeChain;
+    use alloy::primitives::B256;
+
+    fn ok(data: impl Into<Bytes>) -> CallResult {
+        CallResult {
+            success: true,
+            return_data: data.into(),
+        }
+    }
+
+    fn bytes32(text: &str) -> B256 {
+        let mut word = [0; 32];
+        word[..text.len()].copy_from_slice(text.as_bytes());
+        B256::from(word)
+    }
+
+    #[test]
+    fn test_decode_string() {
+        let result = ok("Wrapped Ether".to_string().abi_encode());
+
+        assert_eq!(
+            decode_text(Some(&result)),
+            (Some("Wrapped Ether".to_string()), MetadataStatus::Ok)
+        );
+    }
+
+    #[test]
+    fn test_decode_bytes32() {
+        let result = ok(bytes32("MKR").to_vec());
+
+        assert_eq!(
+            decode_text(Some(&result)),
+            (Some("MKR".to_string()), MetadataStatus::Bytes32)
+        );
+    }
+
+    #[test]
+    fn test_decode_malformed_text() {
+        assert_eq!(
+            decode_text(Some(&ok(B256::ZERO.to_vec()))),
+            (None, MetadataStatus::Malformed)
+        );
+        assert_eq!(
+            decode_text(Some(&ok(vec![1; 40]))),
+            (None, MetadataStatus::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_decode_reverted() {
+        let reverted = CallResult {
+            success: false,
+            return_data: Bytes::new(),
+        };
+
+        assert_eq!(
+            decode_text(Some(&reverted)),
+            (None, MetadataStatus::Reverted)
+        );
+        assert_eq!(
+            decode_text(Some(&ok(Bytes::new()))),
+            (None, MetadataStatus::Reverted)
+        );
+        assert_eq!(decode_decimals(None), (None, MetadataStatus::Reverted));
+    }
+
+    #[test]
+    fn test_decode_decimals() {
+        assert_eq!(
+            decode_decimals(Some(&ok(U256::from(6).abi_encode()))),
+            (Some(6), MetadataStatus::Ok)
+        );
+        assert_eq!(
+            decode_decimals(Some(&ok(U256::from(256).abi_encode()))),
+            (None, MetadataStatus::Malformed)
+        );
+        assert_eq!(
+            decode_decimals(Some(&ok(vec![18]))),
+            (None, MetadataStatus::Malformed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let chain = FakeChain::new();
+        let (weth, mkr, eoa) = (
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        );
+        chain.add_token(weth, "Wrapped Ether", "WETH", 18);
+        chain.respond(mkr, &IERC20::nameCall {}, bytes32("Maker").to_vec());
+        chain.respond(mkr, &IERC20::symbolCall {}, bytes32("MKR").to_vec());
+        chain.respond(mkr, &IERC20::decimalsCall {}, U256::from(18).abi_encode());
+
+        let metadata = fetch(&chain, &[weth, mkr, eoa]).await.unwrap();
+
+        assert_eq!(metadata[0].symbol.as_deref(), Some("WETH"));
+        assert_eq!(metadata[0].decimals, Some(18));
+        assert!(metadata[0].is_valid());
+
+        assert_eq!(metadata[1].name.as_deref(), Some("Maker"));
+        assert_eq!(metadata[1].symbol_status, MetadataStatus::Bytes32);
+        assert!(metadata[1].is_valid());
+
+        assert_eq!(metadata[2].decimals, None);
+        assert_eq!(metadata[2].decimals_status, MetadataStatus::Reverted);
+        assert!(!metadata[2].is_valid());
+    }
+}
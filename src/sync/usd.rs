@@ -1,248 +1,205 @@
-use crate::models::pair::Pair;
-use crate::models::token::Token;
-use crate::schemas::pairs;
-use crate::schemas::tokens;
+use crate::pricing::amount;
+use crate::repo::{PairRepo, PairReserves, PairScope, TokenRepo, UsdValue};
+use crate::sync::bus::{SyncEvent, Topic};
 use crate::utils::app_context::AppContext;
-use bigdecimal::BigDecimal;
+use bigdecimal::{BigDecimal, RoundingMode};
 use chrono::Utc;
-use diesel::SelectableHelper;
-use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl};
-use diesel_async::RunQueryDsl;
 use eyre::Result;
 use log;
 use std::collections::HashMap;
-use std::str::FromStr;
+use tokio::time::{Duration, Instant};
 
 /// The number of pairs to process in each batch to balance memory usage and performance
 const BATCH_SIZE: i64 = 750;
 
+/// Decimal places of stored USD values
+const USD_SCALE: i64 = 6;
+
+/// How often every pair is valued, to catch changes that were not announced on the bus
+const SWEEP_INTERVAL: Duration = Duration::from_hours(1);
+
 /// Sync USD values for pairs
-/// This function calculates and updates USD values for ALL pairs
-/// based on token reserves and exchange rates, then sleeps for 24 hours
+///
+/// Pairs are valued again as soon as their reserves or the exchange rate of one of their tokens
+/// change, and all pairs are swept every `SWEEP_INTERVAL`. The time of the valuation and the
+/// block of the reserves it used are stored with `usd`.
+///
 /// # Errors
 /// Returns an error if the database connection fails
 pub async fn usd(ctx: &AppContext) -> Result<()> {
+    let mut events = ctx
+        .bus
+        .subscribe(&[Topic::ReservesUpdated, Topic::ExchangeRateUpdated]);
+    let mut next_sweep = Instant::now();
+
     loop {
-        let start_time = Utc::now();
-        let updated_pairs_count = sync(ctx).await?;
-        let end_time = Utc::now();
-        let duration = end_time.signed_duration_since(start_time);
-
-        log::info!(
-            "sync::usd: Completed updating {} pairs in {} minutes. Sleeping for 24 hours.",
-            updated_pairs_count,
-            duration.num_minutes()
-        );
+        if Instant::now() >= next_sweep {
+            let start_time = Utc::now();
+            let updated_pairs_count = sync(&ctx.repo, &PairScope::All).await?;
+            let duration = Utc::now().signed_duration_since(start_time);
 
-        // Sleep for 5 days
-        tokio::time::sleep(tokio::time::Duration::from_secs(5 * 24 * 60 * 60)).await;
+            log::info!(
+                "sync::usd: Completed updating {} pairs in {} seconds",
+                updated_pairs_count,
+                duration.num_seconds()
+            );
+            next_sweep = Instant::now() + SWEEP_INTERVAL;
+        }
+
+        let timeout = next_sweep.saturating_duration_since(Instant::now());
+        let Some(event) = events.next(timeout).await else {
+            // Either it is time to sweep, or events were missed and a sweep catches up
+            next_sweep = Instant::now();
+            continue;
+        };
+
+        let scope = match &*event {
+            SyncEvent::ReservesUpdated(pairs) => PairScope::Pairs(pairs.clone()),
+            SyncEvent::ExchangeRateUpdated(tokens) => {
+                PairScope::Tokens(ctx.repo.ids(tokens).await?)
+            }
+            _ => continue,
+        };
+
+        let updated_pairs_count = sync(&ctx.repo, &scope).await?;
+        log::debug!(
+            "sync::usd: Updated {updated_pairs_count} pairs after {:?}",
+            event.topic()
+        );
     }
 }
 
-/// Sync ALL pairs' USD values
-async fn sync(ctx: &AppContext) -> Result<usize> {
+/// Value the pairs of a scope, walking them in ID order
+async fn sync(repo: &(impl PairRepo + TokenRepo), scope: &PairScope) -> Result<usize> {
     let mut total_updated_count = 0;
-    let mut total_processed_count = 0;
-    let mut offset = 0;
+    let mut after = 0;
 
-    // Count total pairs to process for progress reporting
-    let total_pairs_count = count_total_pairs(ctx).await?;
-    log::info!(
-        "sync::usd: Found {} total pairs to process",
-        total_pairs_count
-    );
+    if matches!(scope, PairScope::Tokens(ids) if ids.is_empty()) {
+        return Ok(0);
+    }
 
-    // Process pairs in batches
     loop {
-        let (batch_count, updated_count) = process_batch(ctx, offset, BATCH_SIZE).await?;
-
-        // Update counters
-        total_processed_count += batch_count;
-        total_updated_count += updated_count;
-
-        // Log progress
-        if batch_count > 0 {
-            #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
-            let percentage =
-                (total_processed_count as f64 / total_pairs_count as f64 * 100.0) as i32;
+        let pairs = repo.with_reserves(scope, after, BATCH_SIZE).await?;
+        let Some(last) = pairs.last() else {
+            break;
+        };
+        after = last.id;
 
-            log::info!(
-                "sync::usd: Progress: {}/{} pairs processed ({}%), Updated {} pairs in this batch",
-                total_processed_count,
-                total_pairs_count,
-                percentage,
-                updated_count
-            );
-        }
+        total_updated_count += process_batch(repo, &pairs).await?;
 
         // If we got fewer pairs than the batch size, it means we've processed all pairs
-        // (Note: this check comes AFTER processing the current batch, so partial batches ARE processed)
-        if batch_count < BATCH_SIZE {
+        if pairs.len() < usize::try_from(BATCH_SIZE)? {
             break;
         }
 
-        // Move to next batch
-        offset += BATCH_SIZE;
-
         // Small pause between batches to avoid overwhelming the database
-        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
     }
 
-    log::info!(
-        "sync::usd: Completed - Updated {}/{} pairs total",
-        total_updated_count,
-        total_processed_count
-    );
-
     Ok(total_updated_count)
 }
 
-/// Count total pairs that need to be processed
-async fn count_total_pairs(ctx: &AppContext) -> Result<i64> {
-    let mut conn = ctx.db.get().await?;
-
-    let count: i64 = pairs::table
-        .filter(
-            pairs::token0_id
-                .is_not_null()
-                .and(pairs::token1_id.is_not_null())
-                .and(pairs::reserve0.is_not_null())
-                .and(pairs::reserve1.is_not_null()),
-        )
-        .count()
-        .get_result(&mut conn)
-        .await?;
-
-    Ok(count)
-}
-
-/// Process a batch of pairs
-async fn process_batch(ctx: &AppContext, offset: i64, limit: i64) -> Result<(i64, usize)> {
-    let mut conn = ctx.db.get().await?;
-    let mut updated_count = 0;
-
-    // Query for this batch of pairs
-    let pairs: Vec<Pair> = diesel::QueryDsl::filter(
-        pairs::table,
-        pairs::token0_id
-            .is_not_null()
-            .and(pairs::token1_id.is_not_null())
-            .and(pairs::reserve0.is_not_null())
-            .and(pairs::reserve1.is_not_null()),
-    )
-    .select(Pair::as_select())
-    .offset(offset)
-    .limit(limit)
-    .load::<Pair>(&mut conn)
-    .await?;
-
-    #[allow(clippy::cast_possible_wrap)]
-    let batch_count = pairs.len() as i64;
-
-    if pairs.is_empty() {
-        return Ok((0, 0));
-    }
-
+/// Value a batch of pairs and store the values
+///
+/// Pairs with a token without exchange rate or decimals lose their value.
+async fn process_batch(
+    repo: &(impl PairRepo + TokenRepo),
+    pairs: &[PairReserves],
+) -> Result<usize> {
     // Get all required token IDs for this batch
     let token_ids: Vec<i32> = pairs
         .iter()
         .flat_map(|pair| [pair.token0_id, pair.token1_id])
-        .flatten()
         .collect();
 
-    // Fetch tokens with exchange rates and decimals for this batch
-    let tokens: Vec<(Token, Option<BigDecimal>, Option<i32>)> =
-        diesel::QueryDsl::filter(tokens::table, tokens::id.eq_any(&token_ids))
-            .select((Token::as_select(), tokens::exchange_rate, tokens::decimals))
-            .load::<(Token, Option<BigDecimal>, Option<i32>)>(&mut conn)
-            .await?;
-
-    // Create token lookup map
-    let token_map: HashMap<i32, (Token, Option<BigDecimal>, Option<i32>)> = tokens
-        .into_iter()
-        .map(|(token, exchange_rate, decimals)| (token.id(), (token, exchange_rate, decimals)))
-        .collect();
+    // Fetch exchange rates and decimals for this batch
+    let token_map = repo.rates(&token_ids).await?;
 
-    // Prepare updates with necessary information for logging
-    let mut updates = Vec::new();
-
-    // Process each pair in this batch
-    for pair in &pairs {
-        if let (Some(token0_id), Some(token1_id), Some(reserve0), Some(reserve1)) = (
-            pair.token0_id,
-            pair.token1_id,
-            pair.reserve0.clone(),
-            pair.reserve1.clone(),
-        ) {
-            // Get token data
-            let token0_data = token_map.get(&token0_id);
-            let token1_data = token_map.get(&token1_id);
-
-            if let (
-                Some((_token0, exchange_rate0, decimals0)),
-                Some((_token1, exchange_rate1, decimals1)),
-            ) = (token0_data, token1_data)
-            {
-                // Calculate USD value if both tokens have exchange rates and decimals
-                if let (
-                    Some(exchange_rate0),
-                    Some(exchange_rate1),
-                    Some(decimals0),
-                    Some(decimals1),
-                ) = (exchange_rate0, exchange_rate1, decimals0, decimals1)
-                {
-                    // Convert exchange rates and calculate USD value
-                    if let (Ok(rate0), Ok(rate1)) = (
-                        f64::from_str(&exchange_rate0.to_string()),
-                        f64::from_str(&exchange_rate1.to_string()),
-                    ) {
-                        let reserve0_normalized = convert_reserve_to_float(&reserve0, *decimals0);
-                        let reserve1_normalized = convert_reserve_to_float(&reserve1, *decimals1);
-
-                        let usd_value = rate0 * reserve0_normalized + rate1 * reserve1_normalized;
-
-                        // Add to updates with pair address for logging
-                        #[allow(clippy::cast_possible_truncation)]
-                        updates.push((pair.id(), pair.address(), usd_value, usd_value as i32));
-                        updated_count += 1;
-                    }
-                }
-            }
-        }
-    }
-
-    // Execute updates if any
-    if !updates.is_empty() {
-        // Process updates in smaller sub-batches to avoid too large transactions
-        for sub_batch in updates.chunks(100) {
-            for (pair_id, pair_address, usd_value_f64, usd_value_i32) in sub_batch {
-                // Update the database
-                diesel::update(pairs::table.find(pair_id))
-                    .set(pairs::usd.eq(usd_value_i32))
-                    .execute(&mut conn)
-                    .await?;
-
-                // Log each update with details including USD value
-                log::info!(
-                    "sync::usd: Updated pair {} combined reserves in USD: ${:.2}",
-                    pair_address,
-                    usd_value_f64
-                );
-            }
-        }
-    }
+    let values = pairs
+        .iter()
+        .map(|pair| UsdValue {
+            pair_id: pair.id,
+            usd: side(&token_map, pair.token0_id, &pair.reserve0)
+                .zip(side(&token_map, pair.token1_id, &pair.reserve1))
+                .map(|(value0, value1)| {
+                    (value0 + value1).with_scale_round(USD_SCALE, RoundingMode::HalfUp)
+                }),
+            block: pair.block_number,
+        })
+        .collect::<Vec<_>>();
+
+    repo.store_usd(&values, Utc::now().naive_utc()).await?;
+
+    Ok(values.len())
+}
 
-    Ok((batch_count, updated_count))
+/// USD value of one side of a pair, if its exchange rate and decimals are known
+fn side(
+    token_map: &HashMap<i32, (Option<BigDecimal>, Option<i32>)>,
+    token_id: i32,
+    reserve: &BigDecimal,
+) -> Option<BigDecimal> {
+    let (Some(rate), Some(decimals)) = token_map.get(&token_id)? else {
+        return None;
+    };
+    let decimals = u8::try_from(*decimals).ok()?;
+    Some(amount::usd(rate, reserve, decimals))
 }
 
-/// Convert token reserve to float value considering decimals
-fn convert_reserve_to_float(reserve: &BigDecimal, decimals: i32) -> f64 {
-    let divisor = 10.0_f64.powi(decimals);
-    let reserve_str = reserve.to_string();
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::str::FromStr;
+
+    use alloy::primitives::Address;
+
+    use crate::repo::memory::{MemoryRepo, PairRow, TokenRow};
+
+    use super::*;
 
-    // Parse reserve string to f64 and divide by the appropriate power of 10
-    match f64::from_str(&reserve_str) {
-        Ok(reserve_float) => reserve_float / divisor,
-        Err(_) => 0.0,
+    /// A token worth `rate` USD with `decimals` decimals
+    fn token(repo: &MemoryRepo, byte: u8, rate: Option<&str>, decimals: i32) -> i32 {
+        let mut token = TokenRow::new(Address::repeat_byte(byte));
+        token.exchange_rate = rate.map(|rate| BigDecimal::from_str(rate).unwrap());
+        token.decimals = Some(decimals);
+        repo.insert_token(token)
+    }
+
+    /// A pair of two tokens with reserves at block 7
+    fn pair(repo: &MemoryRepo, byte: u8, token0_id: i32, token1_id: i32) -> Address {
+        let address = Address::repeat_byte(byte);
+        let mut pair = PairRow::new(address);
+        pair.token0_id = Some(token0_id);
+        pair.token1_id = Some(token1_id);
+        pair.reserve0 = Some(BigDecimal::from(2_000_000));
+        pair.reserve1 = Some(BigDecimal::from(3 * 10_i64.pow(18)));
+        pair.block_number = Some(7);
+        repo.insert_pair(pair);
+        address
+    }
+
+    #[tokio::test]
+    async fn test_sync_values_pairs_of_scope() {
+        let repo = MemoryRepo::new();
+        let usdc = token(&repo, 1, Some("1"), 6);
+        let weth = token(&repo, 2, Some("2000.5"), 18);
+        let unpriced = token(&repo, 3, None, 18);
+        let priced = pair(&repo, 0xa, usdc, weth);
+        let partial = pair(&repo, 0xb, usdc, unpriced);
+        let outside = pair(&repo, 0xc, usdc, weth);
+
+        let scope = PairScope::Pairs(vec![priced, partial]);
+        assert_eq!(sync(&repo, &scope).await.unwrap(), 2);
+
+        let priced = repo.pair(priced).unwrap();
+        assert_eq!(
+            priced.usd,
+            Some(BigDecimal::from_str("6003.500000").unwrap())
+        );
+        assert_eq!(priced.usd_block, Some(7));
+        assert!(priced.usd_updated_at.is_some());
+        assert_eq!(repo.pair(partial).unwrap().usd, None);
+        assert!(repo.pair(partial).unwrap().usd_updated_at.is_some());
+        assert!(repo.pair(outside).unwrap().usd_updated_at.is_none());
     }
 }
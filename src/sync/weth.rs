@@ -1,108 +1,82 @@
-use crate::schemas::tokens;
+use crate::pricing::{source, PriceSourceConfig, Source};
+use crate::repo::TokenRepo;
+use crate::sync::bus::{Bus, SyncEvent};
 use crate::utils::app_context::AppContext;
+use crate::utils::constants::WETH;
 use bigdecimal::BigDecimal;
 use chrono::Utc;
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
 use eyre::Result;
 use log;
-use reqwest::header;
-use serde::Deserialize;
-use std::env;
-use std::str::FromStr;
 use std::time::Duration;
 
-/// WETH token address on Base chain
-const WETH_ADDRESS: &str = "0x4200000000000000000000000000000000000006";
-
-/// Response structure for Moralis API token price endpoint
-#[derive(Debug, Deserialize)]
-struct MoralisTokenPriceResponse {
-    /// USD price of the token
-    #[serde(rename = "usdPrice")]
-    usd_price: f64,
-}
-
-/// Synchronizes WETH price from Moralis API.
+/// Synchronizes the WETH price from the configured price sources.
 ///
-/// This function is the entry point for the WETH price sync microprocess.
-/// It fetches the current price of WETH from Moralis API and updates the database.
+/// This worker is the only writer of WETH's exchange rate, which every other token is priced
+/// from. Sources are tried in the order of `FLY_WETH_PRICE_SOURCES`, and the price is kept
+/// when all of them fail.
 ///
 /// # Errors
 ///
-/// Returns an error if API calls fail, database operations fail, or environment variables are missing.
+/// Returns an error if database operations fail.
 pub async fn weth(ctx: &AppContext) -> Result<()> {
-    log::info!("sync::weth: Starting WETH price sync service");
+    let config = PriceSourceConfig::from_env();
+    log::info!("sync::weth: Starting WETH price sync service with {config:?}");
 
-    loop {
-        let updated = sync_weth_price(ctx).await?;
+    let sources = Source::from_config(&ctx.base_provider, &config);
 
-        if updated {
-            log::info!("sync::weth: Updated WETH price from Moralis API");
-        } else {
-            log::warn!("sync::weth: Failed to update WETH price");
+    loop {
+        match source::weth_usd(&sources).await {
+            Ok((name, price)) => {
+                if update_weth_price(&ctx.repo, &ctx.bus, &price).await? {
+                    log::info!("sync::weth: Updated WETH price to ${price} from {name}");
+                }
+            }
+            Err(e) => log::error!("sync::weth: Keeping the last WETH price: {e}"),
         }
 
-        // Sleep for 24 hours before next update
-        log::info!("sync::weth: Sleeping for 24 hours before next update");
-        tokio::time::sleep(Duration::from_secs(24 * 60 * 60)).await;
+        tokio::time::sleep(Duration::from_secs(config.interval_secs)).await;
     }
 }
 
-/// Fetches WETH price from Moralis API and updates the database
+/// Write the WETH price if it changed and announce it
+///
+/// # Returns
+/// Whether the price changed
 ///
 /// # Errors
 ///
-/// Returns an error if API calls fail, database operations fail, or environment variables are missing.
-async fn sync_weth_price(ctx: &AppContext) -> Result<bool> {
-    // Get API key from environment variable
-    let api_key = match env::var("MORALIS_API_KEY") {
-        Ok(key) => key,
-        Err(_) => {
-            log::error!("sync::weth: MORALIS_API_KEY environment variable not set");
-            return Ok(false);
-        }
-    };
-
-    // Build request to Moralis API
-    let client = reqwest::Client::new();
-    let url = format!(
-        "https://deep-index.moralis.io/api/v2.2/erc20/{}/price?chain=base",
-        WETH_ADDRESS
-    );
-
-    let response = client
-        .get(&url)
-        .header(header::ACCEPT, "application/json")
-        .header("X-API-Key", api_key)
-        .send()
-        .await?;
+/// Returns an error if database operations fail.
+async fn update_weth_price(repo: &impl TokenRepo, bus: &Bus, price: &BigDecimal) -> Result<bool> {
+    let updated = repo.set_rate(WETH, price, Utc::now().naive_utc()).await?;
 
-    // Process response
-    if !response.status().is_success() {
-        log::error!(
-            "sync::weth: Moralis API request failed with status: {}",
-            response.status()
-        );
-        return Ok(false);
+    if updated {
+        bus.publish(SyncEvent::ExchangeRateUpdated(vec![WETH]));
     }
 
-    let price_data: MoralisTokenPriceResponse = response.json().await?;
-    log::info!("sync::weth: Got WETH price: ${}", price_data.usd_price);
+    Ok(updated)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::repo::memory::{MemoryRepo, TokenRow};
+    use crate::sync::bus::Topic;
 
-    // Update database with new price
-    let mut conn = ctx.db.get().await?;
-    let now_timestamp = Utc::now().naive_utc();
-    let weth_price = BigDecimal::from_str(&price_data.usd_price.to_string())?;
+    #[tokio::test]
+    async fn test_update_weth_price_announces_changes_only() {
+        let repo = MemoryRepo::new();
+        repo.insert_token(TokenRow::new(WETH));
+        let bus = Bus::default();
+        let mut events = bus.subscribe(&[Topic::ExchangeRateUpdated]);
+        let price = BigDecimal::from(2_000);
 
-    // Update token table with new price
-    let updated_rows = diesel::update(tokens::table.filter(tokens::address.eq(WETH_ADDRESS)))
-        .set((
-            tokens::exchange_rate.eq(&weth_price),
-            tokens::updated_last.eq(now_timestamp),
-        ))
-        .execute(&mut conn)
-        .await?;
+        assert!(update_weth_price(&repo, &bus, &price).await.unwrap());
+        assert!(!update_weth_price(&repo, &bus, &price).await.unwrap());
 
-    Ok(updated_rows > 0)
+        assert_eq!(repo.token(WETH).unwrap().exchange_rate, Some(price));
+        let event = events.next(Duration::from_millis(10)).await.unwrap();
+        assert_eq!(*event, SyncEvent::ExchangeRateUpdated(vec![WETH]));
+        assert!(events.next(Duration::from_millis(10)).await.is_none());
+    }
 }
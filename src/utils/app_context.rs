@@ -6,6 +6,11 @@
 //! - Ethereum Mainnet (local via IPC and remote via Infura)
 //! - Base Network (local via WebSocket and remote via Alchemy)
 
+use crate::arb::portfolio::Portfolio;
+use crate::models::block::BlockUpdate;
+use crate::repo::PgRepo;
+use crate::sync::bus::Bus;
+use crate::sync::reorg::Reorg;
 use crate::utils::signer::Signer;
 use alloy::providers::fillers::{
     BlobGasFiller, ChainIdFiller, FillProvider, GasFiller, JoinFill, NonceFiller,
@@ -17,6 +22,8 @@ use diesel_async::AsyncPgConnection;
 use eyre::{Error, Result};
 use log::info;
 use std::env;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
 
 use alloy::{
     network::Ethereum,
@@ -24,7 +31,7 @@ use alloy::{
 };
 
 /// There has to be a better way to do this
-type EthereumProvider = FillProvider<
+pub type EthereumProvider = FillProvider<
     JoinFill<
         Identity,
         JoinFill<GasFiller, JoinFill<BlobGasFiller, JoinFill<NonceFiller, ChainIdFiller>>>,
@@ -33,6 +40,12 @@ type EthereumProvider = FillProvider<
     Ethereum,
 >;
 
+/// Number of block updates kept for slow subscribers before they start lagging
+const BLOCK_UPDATES_CAPACITY: usize = 64;
+
+/// Number of reorgs kept for slow subscribers before they start lagging
+const REORGS_CAPACITY: usize = 16;
+
 /// Application context holding shared network providers and connections.
 pub struct AppContext {
     /// Base network provider (local or remote)
@@ -44,7 +57,20 @@ pub struct AppContext {
     #[allow(dead_code)]
     pub signer: Signer,
     /// Diesel async connection pool
+    ///
+    /// Only used by the `execution` module, which is not part of the binary yet.
+    #[allow(dead_code)]
     pub db: diesel_async::pooled_connection::deadpool::Pool<AsyncPgConnection>,
+    /// Repositories on `db`, which sync workers read and write through
+    pub repo: PgRepo,
+    /// Live token balances of our wallet and executor, published by `sync::balances`
+    pub portfolio: watch::Sender<Portfolio>,
+    /// Pools updated by each committed block, published by `sync::events`
+    pub blocks: broadcast::Sender<Arc<BlockUpdate>>,
+    /// Pools rolled back by each reorg, published by `sync::reorg`
+    pub reorgs: broadcast::Sender<Arc<Reorg>>,
+    /// Events between sync workers
+    pub bus: Bus,
 }
 
 impl AppContext {
@@ -70,7 +96,12 @@ impl AppContext {
             base_provider,
             base_provider_websocket_url: Self::base_provider_websocket_url(),
             signer: Signer::new("/tmp/fly.sock"),
+            repo: PgRepo::new(pool.clone()),
             db: pool,
+            portfolio: watch::channel(Portfolio::default()).0,
+            blocks: broadcast::channel(BLOCK_UPDATES_CAPACITY).0,
+            reorgs: broadcast::channel(REORGS_CAPACITY).0,
+            bus: Bus::default(),
         })
     }
 
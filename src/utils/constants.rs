@@ -1,8 +1,10 @@
 use alloy::primitives::{address, Address};
 
 /// WETH address
-#[allow(dead_code)]
 pub const WETH: Address = address!("0x4200000000000000000000000000000000000006");
 /// Uniswap V2 batch query address
 pub const UNISWAP_V2_BATCH_QUERY_ADDRESS: Address =
     address!("0x72D6545d3F45F20754F66a2B99fc1A4D75BFEf5c");
+/// OP Stack `GasPriceOracle` predeploy, quotes the L1 data fee of a transaction
+#[allow(dead_code)]
+pub const GAS_PRICE_ORACLE: Address = address!("0x420000000000000000000000000000000000000F");
@@ -14,9 +14,9 @@ pub fn setup_logger() -> Result<()> {
     Dispatch::new()
         // Set logging level from RUST_LOG env var or default to Info
         .level(
-            std::env::var("RUST_LOG")
-                .map(|level| level.parse().unwrap_or(log::LevelFilter::Info))
-                .unwrap_or(log::LevelFilter::Info),
+            std::env::var("RUST_LOG").map_or(log::LevelFilter::Info, |level| {
+                level.parse().unwrap_or(log::LevelFilter::Info)
+            }),
         )
         // Configure logging to console
         .chain(std::io::stdout())
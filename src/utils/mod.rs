@@ -4,7 +4,12 @@ pub mod app_context;
 pub mod constants;
 /// Logger
 pub mod logger;
+/// Numeric conversions
+pub mod numeric;
 /// Signer
 pub mod signer;
+/// Database for testing
+#[cfg(test)]
+pub(crate) mod test_db;
 /// Wallet
 pub mod wallet;
@@ -0,0 +1,101 @@
+//! Conversions between on-chain integers and database numerics.
+
+use std::str::FromStr;
+
+use alloy::primitives::{I256, U256};
+use bigdecimal::num_bigint::Sign;
+use bigdecimal::BigDecimal;
+
+/// Convert a `U256` to a `BigDecimal` without losing precision
+///
+/// # Panics
+///
+/// Never, the decimal representation of a `U256` is a valid `BigDecimal`
+#[must_use]
+pub fn u256_to_big_decimal(value: U256) -> BigDecimal {
+    // SAFETY: the decimal representation of a U256 is always a valid BigDecimal
+    #[allow(clippy::unwrap_used)]
+    BigDecimal::from_str(&value.to_string()).unwrap()
+}
+
+/// Convert a `BigDecimal` back to a `U256`
+///
+/// Returns `None` if the value is negative, has a fractional part or does not fit.
+#[must_use]
+pub fn big_decimal_to_u256(value: &BigDecimal) -> Option<U256> {
+    if !value.is_integer() || value.sign() == Sign::Minus {
+        return None;
+    }
+
+    let (digits, _) = value.with_scale(0).into_bigint_and_exponent();
+    U256::from_str(&digits.to_string()).ok()
+}
+
+/// Convert an `I256` to a `BigDecimal` without losing precision
+///
+/// # Panics
+///
+/// Never, the decimal representation of an `I256` is a valid `BigDecimal`
+#[must_use]
+#[allow(dead_code)]
+pub fn i256_to_big_decimal(value: I256) -> BigDecimal {
+    // SAFETY: the decimal representation of an I256 is always a valid BigDecimal
+    #[allow(clippy::unwrap_used)]
+    BigDecimal::from_str(&value.to_string()).unwrap()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_u256_to_big_decimal() {
+        assert_eq!(u256_to_big_decimal(U256::ZERO), BigDecimal::from(0));
+        assert_eq!(
+            u256_to_big_decimal(U256::from(1_000_000_000_000_000_000u128)),
+            BigDecimal::from(1_000_000_000_000_000_000u128)
+        );
+        assert_eq!(
+            u256_to_big_decimal(U256::MAX).to_string(),
+            U256::MAX.to_string()
+        );
+    }
+
+    #[test]
+    fn test_big_decimal_to_u256() {
+        assert_eq!(big_decimal_to_u256(&BigDecimal::from(0)), Some(U256::ZERO));
+        assert_eq!(
+            big_decimal_to_u256(&u256_to_big_decimal(U256::MAX)),
+            Some(U256::MAX)
+        );
+        // Postgres may hand back integers with a scale, e.g. 1000.00
+        assert_eq!(
+            big_decimal_to_u256(&BigDecimal::new(100_000.into(), 2)),
+            Some(U256::from(1000))
+        );
+        assert_eq!(
+            big_decimal_to_u256(&BigDecimal::new(1.into(), -3)),
+            Some(U256::from(1000))
+        );
+
+        assert_eq!(big_decimal_to_u256(&BigDecimal::from(-1)), None);
+        assert_eq!(big_decimal_to_u256(&BigDecimal::new(15.into(), 1)), None);
+        assert_eq!(
+            big_decimal_to_u256(&(u256_to_big_decimal(U256::MAX) + BigDecimal::from(1))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_i256_to_big_decimal() {
+        assert_eq!(
+            i256_to_big_decimal(I256::try_from(-134_621_970).unwrap()),
+            BigDecimal::from(-134_621_970)
+        );
+        assert_eq!(
+            i256_to_big_decimal(I256::MIN).to_string(),
+            I256::MIN.to_string()
+        );
+    }
+}
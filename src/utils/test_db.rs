@@ -0,0 +1,48 @@
+#![allow(dead_code)]
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+//! Postgres for tests of queries.
+//!
+//! Tests that need a database connect to `TEST_DATABASE_URL` and are skipped when it is not
+//! set, unless `CI` is set, so CI can't pass without running them. The migrations run once per
+//! test binary, and every connection is in a test transaction
+//! that is never committed, so tests don't see each other's rows.
+
+use std::env;
+use std::sync::Once;
+
+use diesel::{Connection, PgConnection};
+use diesel_async::{AsyncConnection, AsyncPgConnection};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+/// The migrations of the crate
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+/// Guards running the migrations
+static MIGRATED: Once = Once::new();
+
+/// A connection to the test database in a test transaction, `None` if no test database is set
+///
+/// # Panics
+/// If no test database is set in CI
+pub async fn connection() -> Option<AsyncPgConnection> {
+    let Ok(url) = env::var("TEST_DATABASE_URL") else {
+        assert!(
+            env::var_os("CI").is_none(),
+            "TEST_DATABASE_URL must be set in CI"
+        );
+        eprintln!("TEST_DATABASE_URL is not set, skipping database test");
+        return None;
+    };
+
+    MIGRATED.call_once(|| {
+        let mut conn = PgConnection::establish(&url).expect("test database is reachable");
+        conn.run_pending_migrations(MIGRATIONS)
+            .expect("migrations apply to the test database");
+    });
+
+    let mut conn = AsyncPgConnection::establish(&url).await.unwrap();
+    conn.begin_test_transaction().await.unwrap();
+    Some(conn)
+}